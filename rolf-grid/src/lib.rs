@@ -5,6 +5,7 @@ use crossterm::{
     terminal::{self, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use std::{
+    fmt,
     io::{self, Write},
     ops::{BitAnd, BitOr, BitOrAssign},
 };
@@ -15,6 +16,7 @@ where
 {
     output: T,
     output_buf: Vec<u8>,
+    raw_writes: Vec<RawWrite>,
     grid: Grid<Cell>,
     prev_grid: Grid<Cell>,
     cursor_display: (u16, u16),
@@ -32,6 +34,7 @@ where
         Ok(Self {
             output: screen_output,
             output_buf: vec![],
+            raw_writes: vec![],
             grid: Grid::new(width, height),
             prev_grid: Grid::new(width, height),
             cursor_display: (0, 0),
@@ -54,9 +57,63 @@ where
     }
 
     pub fn set_cell_style(&mut self, x: u16, y: u16, ch: char, style: Style) {
-        let mut cell = self.grid.get_mut(x, y);
-        cell.ch = ch;
-        cell.style = style;
+        self.set_cell_marks(x, y, ch, [None; 2], style);
+    }
+
+    /// Like set_cell_style, but does nothing if the cell is outside of the screen.
+    pub fn set_cell_style_clipped(&mut self, x: u16, y: u16, ch: char, style: Style) {
+        self.set_cell_marks_clipped(x, y, ch, [None; 2], style);
+    }
+
+    /// Sets a cell to a double-width character, which also takes up the cell to its right.
+    pub fn set_wide_cell_style(&mut self, x: u16, y: u16, ch: char, style: Style) {
+        self.set_cell_style(x, y, ch, style);
+        self.set_wide_continuation(x, y, style);
+    }
+
+    /// Like set_wide_cell_style, but does nothing if the cell is outside of the screen.
+    pub fn set_wide_cell_style_clipped(&mut self, x: u16, y: u16, ch: char, style: Style) {
+        self.set_cell_style_clipped(x, y, ch, style);
+        self.set_wide_continuation(x, y, style);
+    }
+
+    fn set_wide_continuation(&mut self, x: u16, y: u16, style: Style) {
+        if let Some(cell) = x
+            .checked_add(1)
+            .and_then(|next_x| self.grid.try_get_mut(next_x, y))
+        {
+            cell.ch = ' ';
+            cell.marks = [None; 2];
+            cell.style = style;
+            cell.is_wide_continuation = true;
+        }
+    }
+
+    /// Sets a cell whose character is followed by up to two combining marks, which are printed
+    /// immediately after the character in the same cell.
+    pub fn set_cell_marks(
+        &mut self,
+        x: u16,
+        y: u16,
+        ch: char,
+        marks: [Option<char>; 2],
+        style: Style,
+    ) {
+        set_cell_contents(self.grid.get_mut(x, y), ch, marks, style);
+    }
+
+    /// Like set_cell_marks, but does nothing if the cell is outside of the screen.
+    pub fn set_cell_marks_clipped(
+        &mut self,
+        x: u16,
+        y: u16,
+        ch: char,
+        marks: [Option<char>; 2],
+        style: Style,
+    ) {
+        if let Some(cell) = self.grid.try_get_mut(x, y) {
+            set_cell_contents(cell, ch, marks, style);
+        }
     }
 
     pub fn activate_direct(output: &mut T) -> io::Result<()> {
@@ -89,12 +146,29 @@ where
         Self::clear_grid(&mut self.grid);
     }
 
+    pub fn clear_logical_rect(&mut self, left_x: u16, top_y: u16, width: u16, height: u16) {
+        let right_x = left_x.saturating_add(width).min(self.grid.width);
+        let bot_y = top_y.saturating_add(height).min(self.grid.height);
+
+        for x in left_x..right_x {
+            for y in top_y..bot_y {
+                let cell = self.grid.get_mut(x, y);
+                cell.ch = ' ';
+                cell.marks = [None; 2];
+                cell.style = Style::default();
+                cell.is_wide_continuation = false;
+            }
+        }
+    }
+
     fn clear_grid(grid: &mut Grid<Cell>) {
         for x in 0..grid.width {
             for y in 0..grid.height {
-                let mut cell = grid.get_mut(x, y);
+                let cell = grid.get_mut(x, y);
                 cell.ch = ' ';
+                cell.marks = [None; 2];
                 cell.style = Style::default();
+                cell.is_wide_continuation = false;
             }
         }
     }
@@ -117,23 +191,50 @@ where
         Ok(())
     }
 
+    /// Draws a line built by a LineBuilder, dropping any cells which are outside of the screen.
     pub fn build_line(&mut self, x: u16, y: u16, builder: &LineBuilder) {
-        let mut curr_x = x;
-
-        for cell in &builder.cells {
-            if curr_x >= self.grid.width {
-                break;
+        for (curr_x, cell) in (x..=u16::MAX).zip(builder.cells.iter()) {
+            match self.grid.try_get_mut(curr_x, y) {
+                Some(grid_cell) => *grid_cell = *cell,
+                None => break,
             }
+        }
+    }
 
-            let grid_cell = self.grid.get_mut(curr_x, y);
-            *grid_cell = *cell;
-
-            curr_x += 1;
+    /// Copies the cells of part of a line, so that they can be drawn again later with build_line.
+    pub fn copy_line(&self, x: u16, y: u16, width: u16) -> LineBuilder {
+        let end_x = if y < self.grid.height {
+            x.saturating_add(width).min(self.grid.width)
+        } else {
+            x
+        };
+
+        LineBuilder {
+            cells: (x..end_x)
+                .map(|curr_x| Cell {
+                    is_dead: false,
+                    ..*self.grid.get(curr_x, y)
+                })
+                .collect(),
+            last_style: Style::default(),
         }
     }
 
+    /// Copies the logical screen (what will be shown on the next call to show()) as text, for
+    /// debugging rendering problems.
+    pub fn dump(&self) -> ScreenDump {
+        dump_grid(&self.grid)
+    }
+
+    /// Queues a raw byte sequence (such as an image escape code) to be written with the cursor at
+    /// the given cell. Raw writes are sent after the cells in the next call to show(), within the
+    /// same flush.
+    pub fn write_raw(&mut self, x: u16, y: u16, bytes: Vec<u8>) {
+        self.raw_writes.push(RawWrite { x, y, bytes });
+    }
+
     pub fn set_dead(&mut self, x: u16, y: u16, is_dead: bool) {
-        let mut cell = self.grid.get_mut(x, y);
+        let cell = self.grid.get_mut(x, y);
         cell.is_dead = is_dead;
     }
 }
@@ -147,7 +248,9 @@ impl Screen<Stdout> {
                 let cell = self.grid.get(x, y);
                 let prev_cell = self.prev_grid.get(x, y);
 
-                if cell != prev_cell && !cell.is_dead {
+                // NOTE(Chris): The right half of a double-width character was already drawn along
+                // with the character itself
+                if cell != prev_cell && !cell.is_dead && !cell.is_wide_continuation {
                     if cell.style != self.last_style {
                         queue!(
                             &mut self.output_buf,
@@ -185,6 +288,10 @@ impl Screen<Stdout> {
                         cursor::MoveTo(x, y),
                         style::Print(cell.ch)
                     )?;
+
+                    for mark in cell.marks.iter().flatten() {
+                        queue!(&mut self.output_buf, style::Print(mark))?;
+                    }
                 }
 
                 // Update the previous buffer
@@ -196,6 +303,26 @@ impl Screen<Stdout> {
             }
         }
 
+        if !self.raw_writes.is_empty() {
+            for raw_write in self.raw_writes.drain(..) {
+                queue!(
+                    &mut self.output_buf,
+                    style::SetAttribute(style::Attribute::Reset),
+                    cursor::MoveTo(raw_write.x, raw_write.y),
+                )?;
+
+                self.output_buf.extend_from_slice(&raw_write.bytes);
+            }
+
+            // NOTE(Chris): We can't know what a raw write did to the terminal's style, so we reset
+            // it afterwards.
+            queue!(
+                &mut self.output_buf,
+                style::SetAttribute(style::Attribute::Reset),
+            )?;
+            self.last_style = Style::default();
+        }
+
         if self.should_show_cursor {
             let move_to_cmd = cursor::MoveTo(self.cursor_display.0, self.cursor_display.1);
 
@@ -213,6 +340,12 @@ impl Screen<Stdout> {
     }
 }
 
+struct RawWrite {
+    x: u16,
+    y: u16,
+    bytes: Vec<u8>,
+}
+
 /// Grid implements a two-dimensional array with a single contiguous buffer
 #[derive(Clone)]
 pub struct Grid<T> {
@@ -230,14 +363,15 @@ where
         Self {
             width,
             height,
-            buffer: vec![T::default(); (width * height).into()],
+            buffer: vec![T::default(); usize::from(width) * usize::from(height)],
         }
     }
 
     fn resize_blunt(&mut self, width: u16, height: u16, value: T) {
         self.width = width;
         self.height = height;
-        self.buffer.resize((width * height).into(), value);
+        self.buffer
+            .resize(usize::from(width) * usize::from(height), value);
     }
 }
 
@@ -254,20 +388,110 @@ impl<T> Grid<T> {
     }
 
     fn get_mut(&mut self, x: u16, y: u16) -> &mut T {
-        if x > self.width - 1 {
-            panic!("Width is too large: {}", self.width);
+        let (width, height) = (self.width, self.height);
+
+        match self.try_get_mut(x, y) {
+            Some(value) => value,
+            None => panic!("({}, {}) is outside of the {}x{} grid", x, y, width, height),
         }
+    }
 
-        if y > self.height - 1 {
-            panic!("Height is too large: {}", self.height);
+    fn try_get_mut(&mut self, x: u16, y: u16) -> Option<&mut T> {
+        if x >= self.width || y >= self.height {
+            return None;
         }
 
-        &mut self.buffer[coords_to_index(self.width, x, y)]
+        Some(&mut self.buffer[coords_to_index(self.width, x, y)])
     }
 }
 
 fn coords_to_index(width: u16, x: u16, y: u16) -> usize {
-    (y * width + x).into()
+    // NOTE(Chris): The index is computed as a usize, since it can overflow a u16 on large screens
+    usize::from(y) * usize::from(width) + usize::from(x)
+}
+
+/// A copy of the logical screen, as returned by Screen::dump()
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenDump {
+    // The text of each line, without trailing spaces
+    pub lines: Vec<String>,
+    // The text of each line split into runs of the same style, where each run that isn't in the
+    // default style is preceded by its style in brackets, like "[bold blue/default]src[]"
+    pub styled_lines: Vec<String>,
+}
+
+impl fmt::Display for ScreenDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        writeln!(f)?;
+
+        for line in &self.styled_lines {
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn dump_grid(grid: &Grid<Cell>) -> ScreenDump {
+    let mut lines = vec![];
+    let mut styled_lines = vec![];
+
+    for y in 0..grid.height {
+        let mut line = String::new();
+        let mut styled_line = String::new();
+        let mut run_style = Style::default();
+
+        for x in 0..grid.width {
+            let cell = grid.get(x, y);
+
+            if cell.is_wide_continuation {
+                continue;
+            }
+
+            if cell.style != run_style {
+                if run_style != Style::default() {
+                    styled_line.push_str("[]");
+                }
+
+                if cell.style != Style::default() {
+                    styled_line.push_str(&format!("[{}]", cell.style));
+                }
+
+                run_style = cell.style;
+            }
+
+            // NOTE(Chris): Cells which have never been drawn to hold '\0'
+            let ch = if cell.ch == '\0' { ' ' } else { cell.ch };
+
+            for ch in std::iter::once(ch).chain(cell.marks.iter().flatten().copied()) {
+                line.push(ch);
+                styled_line.push(ch);
+            }
+        }
+
+        if run_style != Style::default() {
+            styled_line.push_str("[]");
+        }
+
+        lines.push(line.trim_end_matches(' ').to_string());
+        styled_lines.push(styled_line);
+    }
+
+    ScreenDump {
+        lines,
+        styled_lines,
+    }
+}
+
+fn set_cell_contents(cell: &mut Cell, ch: char, marks: [Option<char>; 2], style: Style) {
+    cell.ch = ch;
+    cell.marks = marks;
+    cell.style = style;
+    cell.is_wide_continuation = false;
 }
 
 #[derive(Default)]
@@ -286,7 +510,7 @@ impl LineBuilder {
         self.cells.push(Cell {
             ch,
             style,
-            is_dead: false,
+            ..Default::default()
         });
         self
     }
@@ -295,7 +519,7 @@ impl LineBuilder {
         self.cells.push(Cell {
             ch,
             style: self.last_style,
-            is_dead: false,
+            ..Default::default()
         });
         self
     }
@@ -305,7 +529,7 @@ impl LineBuilder {
             self.cells.push(Cell {
                 ch,
                 style: self.last_style,
-                is_dead: false,
+                ..Default::default()
             });
         }
         self
@@ -335,9 +559,14 @@ impl LineBuilder {
 #[derive(Clone, Copy, Default, PartialEq)]
 pub struct Cell {
     ch: char,
+    // Combining characters drawn after ch, such as the diacritics used by kitty's unicode
+    // placeholders
+    marks: [Option<char>; 2],
     style: Style,
     // A dead cell won't be updated until it's made alive
     is_dead: bool,
+    // Whether this cell is covered by the double-width character to its left
+    is_wide_continuation: bool,
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -368,6 +597,16 @@ impl Style {
     }
 }
 
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in self.attribute.names() {
+            write!(f, "{} ", name)?;
+        }
+
+        write!(f, "{}/{}", self.fg, self.bg)
+    }
+}
+
 impl Default for Style {
     fn default() -> Self {
         Self {
@@ -378,7 +617,7 @@ impl Default for Style {
     }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Color {
     Black,
     Red,
@@ -396,10 +635,21 @@ pub enum Color {
     BrightMagenta,
     BrightCyan,
     BrightWhite,
+    Ansi(u8),   // An index into the terminal's 256-color palette
     Foreground, // Default foreground color
     Background, // Default background color
 }
 
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ansi(value) => write!(f, "{}", value),
+            Self::Foreground | Self::Background => write!(f, "default"),
+            _ => write!(f, "{}", format!("{:?}", self).to_lowercase()),
+        }
+    }
+}
+
 impl Color {
     fn to_crossterm(self) -> style::Color {
         match self {
@@ -419,6 +669,7 @@ impl Color {
             Self::BrightMagenta => style::Color::Magenta,
             Self::BrightCyan => style::Color::Cyan,
             Self::BrightWhite => style::Color::White,
+            Self::Ansi(value) => style::Color::AnsiValue(value),
             Self::Foreground => unreachable!("Foreground not convertible to a crossterm color!"),
             Self::Background => unreachable!("Background not convertible to a crossterm color!"),
         }
@@ -490,6 +741,19 @@ impl Attribute {
     fn contains(self, other: Self) -> bool {
         self.0 & other.0 == other.0
     }
+
+    fn names(self) -> impl Iterator<Item = &'static str> {
+        [
+            (Self::Bold, "bold"),
+            (Self::Dim, "dim"),
+            (Self::Underlined, "underlined"),
+            (Self::Reverse, "reverse"),
+            (Self::Hidden, "hidden"),
+        ]
+        .into_iter()
+        .filter(move |(attribute, _)| self.contains(*attribute))
+        .map(|(_, name)| name)
+    }
 }
 
 #[cfg(test)]
@@ -506,6 +770,54 @@ mod tests {
         assert_eq!(grid.get(2, 3), &'a');
     }
 
+    #[test]
+    fn test_grid_try_get_mut() {
+        let mut grid: Grid<char> = Grid::new(4, 5);
+
+        assert!(grid.try_get_mut(3, 4).is_some());
+        assert_eq!(grid.try_get_mut(4, 4), None);
+        assert_eq!(grid.try_get_mut(3, 5), None);
+
+        let mut empty_grid: Grid<char> = Grid::new(0, 0);
+        assert_eq!(empty_grid.try_get_mut(0, 0), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "(4, 0) is outside of the 4x5 grid")]
+    fn test_grid_get_mut_out_of_bounds() {
+        let mut grid: Grid<char> = Grid::new(4, 5);
+
+        grid.get_mut(4, 0);
+    }
+
+    #[test]
+    fn test_dump_grid() {
+        let mut grid: Grid<Cell> = Grid::new(6, 2);
+        for x in 0..6 {
+            for y in 0..2 {
+                grid.get_mut(x, y).ch = ' ';
+            }
+        }
+
+        for (x, ch) in (1..).zip("src".chars()) {
+            let cell = grid.get_mut(x, 0);
+            cell.ch = ch;
+            cell.style = Style::new(Attribute::Bold, Color::Blue, Color::Background);
+        }
+        grid.get_mut(5, 0).ch = 'x';
+
+        assert_eq!(
+            dump_grid(&grid),
+            ScreenDump {
+                lines: vec![" src x".to_string(), "".to_string()],
+                styled_lines: vec![
+                    " [bold blue/default]src[] x".to_string(),
+                    "      ".to_string()
+                ],
+            }
+        );
+    }
+
     #[test]
     fn test_attribute_contains() {
         let attr1 = Attribute::Bold | Attribute::Underlined;
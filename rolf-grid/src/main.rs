@@ -133,35 +133,23 @@ fn main() -> io::Result<()> {
 
         match event {
             Event::Key(key_event) => match key_event.code {
-                KeyCode::Char('j') => {
-                    if y < height - 1 {
-                        y += 1;
-                    }
+                KeyCode::Char('j') if y < height - 1 => {
+                    y += 1;
                 }
                 KeyCode::Char('k') => {
-                    if y > 0 {
-                        y -= 1;
-                    }
+                    y = y.saturating_sub(1);
                 }
-                KeyCode::Char('l') => {
-                    if x < width - 1 {
-                        x += 1;
-                    }
+                KeyCode::Char('l') if x < width - 1 => {
+                    x += 1;
                 }
                 KeyCode::Char('h') => {
-                    if x > 0 {
-                        x -= 1;
-                    }
+                    x = x.saturating_sub(1);
                 }
-                KeyCode::Char('J') => {
-                    if (file_curr_ind as usize) < items.len() - 1 {
-                        file_curr_ind += 1;
-                    }
+                KeyCode::Char('J') if (file_curr_ind as usize) < items.len() - 1 => {
+                    file_curr_ind += 1;
                 }
                 KeyCode::Char('K') => {
-                    if file_curr_ind > 0 {
-                        file_curr_ind -= 1;
-                    }
+                    file_curr_ind = file_curr_ind.saturating_sub(1);
                 }
                 KeyCode::Char('s') => {
                     is_cursor_visible = !is_cursor_visible;
@@ -170,9 +158,7 @@ fn main() -> io::Result<()> {
                     file_top_ind += 1;
                 }
                 KeyCode::Char('-') => {
-                    if file_top_ind > 0 {
-                        file_top_ind -= 1;
-                    }
+                    file_top_ind = file_top_ind.saturating_sub(1);
                 }
                 KeyCode::Char('q') => break,
                 _ => (),
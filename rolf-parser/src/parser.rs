@@ -3,6 +3,7 @@ use std::{error::Error, mem, ops};
 
 type LexResult<T> = std::result::Result<T, LexError>;
 type ParseResult<T> = std::result::Result<T, ParseError>;
+type Lexer = dyn Fn(&mut Scanner) -> LexResult<Token>;
 
 pub fn parse(input: &str) -> ParseResult<Program> {
     parse_rule_from(input, parse_overall)
@@ -38,9 +39,18 @@ pub fn lex_overall(scanner: &mut Scanner) -> LexResult<Vec<Token>> {
     let lex_plus = lex_phrase("+");
 
     // NOTE(Chris): The order matters here, in case one lexing rule conflicts with another.
-    let mut lexers: Vec<&dyn Fn(&mut Scanner) -> LexResult<Token>> =
-        vec![&lex_mod, &lex_newline, &lex_whitespace, &lex_map, &lex_plus];
-
+    let mut lexers: Vec<&Lexer> = vec![
+        &lex_mod,
+        &lex_newline,
+        &lex_whitespace,
+        &lex_comment,
+        &lex_map,
+        &lex_plus,
+    ];
+
+    // NOTE(Chris): lex_word only succeeds for text that couldn't be an id, so it needs to come
+    // before lex_id.
+    lexers.push(&lex_word);
     lexers.push(&lex_id);
 
     let mut tokens = vec![];
@@ -55,8 +65,13 @@ pub fn lex_overall(scanner: &mut Scanner) -> LexResult<Vec<Token>> {
                 mem::swap(&mut token.line, &mut prev_line);
                 mem::swap(&mut token.col, &mut prev_col);
 
-                // Ignore whitespace
-                if token.kind != TokenKind::Whitespace {
+                let is_blank_line = token.kind == TokenKind::Newline
+                    && tokens
+                        .last()
+                        .is_none_or(|prev: &Token| prev.kind == TokenKind::Newline);
+
+                // Ignore whitespace (including comments) and blank lines
+                if token.kind != TokenKind::Whitespace && !is_blank_line {
                     tokens.push(token);
                 }
 
@@ -103,6 +118,27 @@ fn lex_id(scanner: &mut Scanner) -> LexResult<Token> {
     }
 }
 
+// Lexes a run of non-whitespace characters which contains at least one character that isn't
+// allowed in an id, like "*.pdf" or "$f"
+fn lex_word(scanner: &mut Scanner) -> LexResult<Token> {
+    let is_id_char = |ch: &char| ch.is_ascii_alphabetic() || *ch == '-';
+
+    let word: String = scanner.characters[scanner.cursor..]
+        .iter()
+        .take_while(|ch| !matches!(ch, ' ' | '\t' | '\n'))
+        .collect();
+
+    if word.chars().all(|ch| is_id_char(&ch)) {
+        return Err(LexError::ExpectedWord);
+    }
+
+    for _ in word.chars() {
+        scanner.pop();
+    }
+
+    Ok(Token::new(scanner, TokenKind::Word(word)))
+}
+
 fn lex_mod(scanner: &mut Scanner) -> LexResult<Token> {
     if scanner.take_str("ctrl") {
         Ok(Token::new(scanner, TokenKind::Mod(Mod::Ctrl)))
@@ -115,7 +151,7 @@ fn lex_mod(scanner: &mut Scanner) -> LexResult<Token> {
     }
 }
 
-fn lex_phrase(phrase: &'static str) -> Box<dyn Fn(&mut Scanner) -> LexResult<Token>> {
+fn lex_phrase(phrase: &'static str) -> Box<Lexer> {
     Box::new(move |scanner: &mut Scanner| {
         if scanner.take_str(phrase) {
             Ok(Token::new(scanner, TokenKind::Phrase(phrase)))
@@ -139,6 +175,19 @@ fn lex_whitespace(scanner: &mut Scanner) -> LexResult<Token> {
     }
 }
 
+// Lexes a comment, from a # to the end of the line. Comments are treated like whitespace.
+fn lex_comment(scanner: &mut Scanner) -> LexResult<Token> {
+    if !scanner.take(&'#') {
+        return Err(LexError::ExpectedComment);
+    }
+
+    while scanner.peek().is_some_and(|ch| *ch != '\n') {
+        scanner.pop();
+    }
+
+    Ok(Token::new(scanner, TokenKind::Whitespace))
+}
+
 fn lex_newline(scanner: &mut Scanner) -> LexResult<Token> {
     if scanner.take(&'\n') {
         Ok(Token::new(scanner, TokenKind::Newline))
@@ -167,6 +216,7 @@ impl Token {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum TokenKind {
     Id(String),
+    Word(String),
     Mod(Mod),
     Phrase(&'static str),
     Whitespace,
@@ -193,6 +243,8 @@ fn parse_program(parser: &mut Parser) -> ParseResult<Program> {
 pub fn parse_statement(parser: &mut Parser) -> ParseResult<Statement> {
     if let Ok(map) = parse_map(parser) {
         Ok(Statement::Map(map))
+    } else if let Ok(open_rule) = parse_open_rule(parser) {
+        Ok(Statement::OpenRule(open_rule))
     } else if let Ok(command_use) = parse_command_use(parser) {
         Ok(Statement::CommandUse(command_use))
     } else {
@@ -206,7 +258,7 @@ pub fn parse_statement(parser: &mut Parser) -> ParseResult<Statement> {
 fn parse_command_use(parser: &mut Parser) -> ParseResult<CommandUse> {
     let name = parser.take_id()?;
 
-    let arguments = parser.take_list(None, |parser| parser.take_id())?;
+    let arguments = parser.take_list(None, |parser| parser.take_word())?;
 
     Ok(CommandUse { name, arguments })
 }
@@ -221,6 +273,45 @@ fn parse_map(parser: &mut Parser) -> ParseResult<Map> {
     Ok(Map { key, cmd_name })
 }
 
+// Parses a rule like "open *.pdf zathura $f". Without a pattern and command, "open" is just a use
+// of the open command, so the cursor is left unchanged if this fails.
+fn parse_open_rule(parser: &mut Parser) -> ParseResult<OpenRule> {
+    let orig_cursor = parser.cursor;
+
+    let result = (|| {
+        match parser.take_id()?.as_str() {
+            "open" => (),
+            _ => {
+                return Err(ParseError::new(ParseErrorKind::Message(
+                    "expected an open rule".to_string(),
+                )))
+            }
+        }
+
+        let pattern = parser.take_word()?;
+
+        let command_words = parser.take_list(None, |parser| parser.take_word())?;
+
+        if command_words.is_empty() {
+            return match parser.peek() {
+                Some(token) => Err(ParseError::new_pos(token, ParseErrorKind::ExpectedWord)),
+                None => Err(ParseError::new(ParseErrorKind::ExpectedWord)),
+            };
+        }
+
+        Ok(OpenRule {
+            pattern,
+            command: command_words.join(" "),
+        })
+    })();
+
+    if result.is_err() {
+        parser.cursor = orig_cursor;
+    }
+
+    result
+}
+
 fn parse_key(parser: &mut Parser) -> ParseResult<Key> {
     let modifier = match parser.take_mod() {
         Ok(modifier) => {
@@ -241,9 +332,16 @@ pub type Program = Vec<Statement>;
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement {
     Map(Map),
+    OpenRule(OpenRule),
     CommandUse(CommandUse),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct OpenRule {
+    pub pattern: String,
+    pub command: String,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct CommandUse {
     pub name: String,
@@ -327,6 +425,24 @@ impl Parser {
         }
     }
 
+    /// Like take_id, but also accepts words that contain other characters (like "*.pdf")
+    pub fn take_word(&mut self) -> ParseResult<String> {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Id(word) | TokenKind::Word(word),
+                ..
+            }) => {
+                let copy = word.clone();
+
+                self.pop();
+
+                Ok(copy)
+            }
+            Some(token) => Err(ParseError::new_pos(token, ParseErrorKind::ExpectedWord)),
+            None => Err(ParseError::new(ParseErrorKind::ExpectedWord)),
+        }
+    }
+
     pub fn take_mod(&mut self) -> ParseResult<Mod> {
         match self.peek() {
             Some(Token {
@@ -413,6 +529,7 @@ pub enum ParseErrorKind {
     RemainingTokens,
     Expected(TokenKind),
     ExpectedId,
+    ExpectedWord,
     ExpectedMod,
     ExpectedEof,
     ExpectedList,
@@ -438,6 +555,41 @@ impl ParseError {
     }
 }
 
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Position::EOF => write!(f, "at the end of the input: {}", self.kind),
+            Position::Pos { line, col } => {
+                write!(f, "line {}, column {}: {}", line, col, self.kind)
+            }
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::Message(message) => write!(f, "{}", message),
+            ParseErrorKind::RemainingTokens => write!(f, "unexpected text"),
+            ParseErrorKind::Expected(TokenKind::Newline) => {
+                write!(f, "expected the end of the line")
+            }
+            ParseErrorKind::Expected(TokenKind::Phrase(phrase)) => {
+                write!(f, "expected \"{}\"", phrase)
+            }
+            ParseErrorKind::Expected(kind) => write!(f, "expected {:?}", kind),
+            ParseErrorKind::ExpectedId => write!(f, "expected a name"),
+            ParseErrorKind::ExpectedWord => write!(f, "expected a word"),
+            ParseErrorKind::ExpectedMod => write!(f, "expected a modifier"),
+            ParseErrorKind::ExpectedEof => write!(f, "expected the end of the file"),
+            ParseErrorKind::ExpectedList => write!(f, "expected a list"),
+            ParseErrorKind::LexError(err) => write!(f, "{}", err),
+        }
+    }
+}
+
 pub struct Scanner {
     cursor: usize,
     characters: Vec<char>,
@@ -534,16 +686,12 @@ impl Scanner {
     /// Otherwise, returns false, leaving the cursor unchanged.
     pub fn take(&mut self, target: &char) -> bool {
         match self.characters.get(self.cursor) {
-            Some(character) => {
-                if target == character {
-                    self.pop();
+            Some(character) if target == character => {
+                self.pop();
 
-                    true
-                } else {
-                    false
-                }
+                true
             }
-            None => false,
+            _ => false,
         }
     }
 
@@ -613,8 +761,10 @@ pub enum LexError {
     ExpectedDigit,
     ExpectedLetter,
     ExpectedId,
+    ExpectedWord,
     ExpectedMod,
     ExpectedWhitespace,
+    ExpectedComment,
     ExpectedNewline,
     RemainingInput,
 }
@@ -678,6 +828,64 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_lex_comments_and_blank_lines() -> LexResult<()> {
+        let mut scanner = Scanner::new("# A comment\n\nmap j down # Another\n\n\nquit\n");
+
+        let result = lex_overall(&mut scanner)?;
+
+        let kinds: Vec<TokenKind> = result.into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Phrase("map"),
+                TokenKind::Id("j".to_string()),
+                TokenKind::Id("down".to_string()),
+                TokenKind::Newline,
+                TokenKind::Id("quit".to_string()),
+                TokenKind::Newline,
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lex_word() -> LexResult<()> {
+        let mut scanner = Scanner::new("open *.pdf zathura $f");
+
+        let result = lex_overall(&mut scanner)?;
+
+        assert_eq!(result.len(), 4);
+        assert_eq!(result[0].kind, TokenKind::Id("open".to_string()));
+        assert_eq!(result[1].kind, TokenKind::Word("*.pdf".to_string()));
+        assert_eq!(result[2].kind, TokenKind::Id("zathura".to_string()));
+        assert_eq!(result[3].kind, TokenKind::Word("$f".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_open_rule() -> ParseResult<()> {
+        let program = parse("open *.pdf zathura $f\nopen")?;
+
+        assert_eq!(
+            &program,
+            &[
+                Statement::OpenRule(OpenRule {
+                    pattern: "*.pdf".to_string(),
+                    command: "zathura $f".to_string()
+                }),
+                Statement::CommandUse(CommandUse {
+                    name: "open".to_string(),
+                    arguments: vec![]
+                })
+            ]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_key() -> ParseResult<()> {
         let mut scanner = Scanner::new("j");
@@ -761,4 +969,16 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_error_display() {
+        let err = parse("map j down extra").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "line 1, column 12: expected the end of the line"
+        );
+
+        let err = parse("down\n\"unterminated").unwrap_err();
+        assert_eq!(err.to_string(), "line 2, column 1: unexpected text");
+    }
 }
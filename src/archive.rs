@@ -0,0 +1,453 @@
+// Browses zip and tar files as read-only directories, which are entered with open. Listing an
+// archive reads its index (or, for a tar, the whole thing) once, and files are only extracted when
+// they're previewed, opened, or extracted with the extract command.
+
+use crate::vfs::{
+    DirEntry, DirEntryInfo, FileSystem, Metadata, MountedMetadata, RecordedFileType, S_IFDIR,
+    S_IFLNK, S_IFMT, S_IFREG,
+};
+
+use chrono::{Local, NaiveDate, TimeZone};
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufReader, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// The kind of archive that a file is, going by its extension
+    pub fn from_path(path: &Path) -> Option<ArchiveKind> {
+        let file_name = path.file_name()?.to_str()?.to_lowercase();
+
+        if file_name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if file_name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+struct ArchiveEntry {
+    metadata: MountedMetadata,
+    file_type: RecordedFileType,
+    // Where the file is in a zip's index, which is how it's found again. Tar files don't have an
+    // index, so they're looked through from the start.
+    zip_index: Option<usize>,
+}
+
+pub struct ArchiveFileSystem {
+    // The path of the archive itself, which its files are listed under
+    root: PathBuf,
+    kind: ArchiveKind,
+    // Every file and directory in the archive, by its path inside of it
+    entries: HashMap<PathBuf, ArchiveEntry>,
+}
+
+impl ArchiveFileSystem {
+    pub fn open(path: &Path, kind: ArchiveKind) -> io::Result<ArchiveFileSystem> {
+        let mut file_system = ArchiveFileSystem {
+            root: path.to_path_buf(),
+            kind,
+            entries: HashMap::new(),
+        };
+
+        match kind {
+            ArchiveKind::Zip => {
+                let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+
+                for zip_index in 0..archive.len() {
+                    let file = archive.by_index(zip_index)?;
+
+                    let inner_path = match file.enclosed_name() {
+                        Some(inner_path) => inner_path.to_path_buf(),
+                        None => continue,
+                    };
+
+                    // NOTE(Chris): Zips made on Windows don't have a Unix mode
+                    let mode = file.unix_mode().unwrap_or(if file.is_dir() {
+                        S_IFDIR | 0o755
+                    } else {
+                        S_IFREG | 0o644
+                    });
+
+                    let last_modified = file.last_modified();
+                    let modified = NaiveDate::from_ymd_opt(
+                        last_modified.year().into(),
+                        last_modified.month().into(),
+                        last_modified.day().into(),
+                    )
+                    .and_then(|date| {
+                        date.and_hms_opt(
+                            last_modified.hour().into(),
+                            last_modified.minute().into(),
+                            last_modified.second().into(),
+                        )
+                    })
+                    // NOTE(Chris): Zips store the local time where they were made
+                    .and_then(|date_time| Local.from_local_datetime(&date_time).single())
+                    .map(SystemTime::from);
+
+                    file_system.add_entry(inner_path, file.size(), modified, mode, Some(zip_index));
+                }
+            }
+            ArchiveKind::Tar | ArchiveKind::TarGz => {
+                let mut archive = tar::Archive::new(file_system.open_tar()?);
+
+                for entry in archive.entries()? {
+                    let entry = entry?;
+
+                    let inner_path = match tar_inner_path(&entry)? {
+                        Some(inner_path) => inner_path,
+                        None => continue,
+                    };
+
+                    let header = entry.header();
+                    let entry_type = header.entry_type();
+
+                    let file_type_mode = if entry_type.is_dir() {
+                        S_IFDIR
+                    } else if entry_type.is_symlink() {
+                        S_IFLNK
+                    } else if entry_type.is_file() {
+                        S_IFREG
+                    } else {
+                        0
+                    };
+                    let mode = file_type_mode | (header.mode()? & !S_IFMT);
+
+                    let modified = header
+                        .mtime()
+                        .ok()
+                        .map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime));
+
+                    file_system.add_entry(inner_path, header.size()?, modified, mode, None);
+                }
+            }
+        }
+
+        Ok(file_system)
+    }
+
+    fn add_entry(
+        &mut self,
+        inner_path: PathBuf,
+        len: u64,
+        modified: Option<SystemTime>,
+        mode: u32,
+        zip_index: Option<usize>,
+    ) {
+        if inner_path.as_os_str().is_empty() {
+            return;
+        }
+
+        // NOTE(Chris): Archives don't always list the directories their files are in, so those
+        // are made up from the files' paths
+        for ancestor in inner_path.ancestors().skip(1) {
+            if ancestor.as_os_str().is_empty() || self.entries.contains_key(ancestor) {
+                break;
+            }
+
+            self.insert(ancestor.to_path_buf(), 0, None, S_IFDIR | 0o755, None);
+        }
+
+        self.insert(inner_path, len, modified, mode, zip_index);
+    }
+
+    fn insert(
+        &mut self,
+        inner_path: PathBuf,
+        len: u64,
+        modified: Option<SystemTime>,
+        mode: u32,
+        zip_index: Option<usize>,
+    ) {
+        let file_type = match mode & S_IFMT {
+            S_IFDIR => RecordedFileType::Directory,
+            S_IFREG => RecordedFileType::File,
+            _ => RecordedFileType::Other,
+        };
+
+        // NOTE(Chris): Files in an archive don't have inode numbers, but a path is just as good for
+        // finding a file again
+        let mut hasher = DefaultHasher::new();
+        inner_path.hash(&mut hasher);
+        let file_id = hasher.finish();
+
+        // NOTE(Chris): A later entry replaces an earlier one with the same path, like when
+        // extracting
+        self.entries.insert(
+            inner_path,
+            ArchiveEntry {
+                metadata: MountedMetadata {
+                    len,
+                    modified,
+                    mode,
+                    file_id,
+                },
+                file_type,
+                zip_index,
+            },
+        );
+    }
+
+    fn open_tar(&self) -> io::Result<Box<dyn Read>> {
+        let file = BufReader::new(File::open(&self.root)?);
+
+        Ok(match self.kind {
+            ArchiveKind::TarGz => Box::new(GzDecoder::new(file)),
+            _ => Box::new(file),
+        })
+    }
+
+    fn inner_path<'a>(&self, path: &'a Path) -> io::Result<&'a Path> {
+        path.strip_prefix(&self.root)
+            .map_err(|_| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+// The path of a file in a tar, leaving out the ./ that many tars start with. Paths that would be
+// outside of the archive (like ../file) are left out.
+fn tar_inner_path<R: Read>(entry: &tar::Entry<R>) -> io::Result<Option<PathBuf>> {
+    let mut inner_path = PathBuf::new();
+
+    for component in entry.path()?.components() {
+        match component {
+            Component::Normal(name) => inner_path.push(name),
+            Component::CurDir => (),
+            _ => return Ok(None),
+        }
+    }
+
+    Ok(Some(inner_path))
+}
+
+impl FileSystem for ArchiveFileSystem {
+    fn read_dir(
+        &self,
+        dir: &Path,
+        _is_cancelled: &dyn Fn() -> bool,
+    ) -> io::Result<Vec<DirEntryInfo>> {
+        let inner_dir = self.inner_path(dir)?;
+
+        if !self.is_dir(dir) {
+            return Err(io::Error::from(io::ErrorKind::NotFound));
+        }
+
+        let entries = self
+            .entries
+            .iter()
+            .filter(|(inner_path, _)| inner_path.parent() == Some(inner_dir))
+            .map(|(inner_path, entry)| DirEntryInfo {
+                dir_entry: DirEntry::new(self.root.join(inner_path)),
+                metadata: Metadata::Mounted(entry.metadata.clone()),
+                file_type: entry.file_type,
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        match self.inner_path(path) {
+            Ok(inner_path) if inner_path.as_os_str().is_empty() => true,
+            Ok(inner_path) => self
+                .entries
+                .get(inner_path)
+                .is_some_and(|entry| entry.file_type == RecordedFileType::Directory),
+            Err(_) => false,
+        }
+    }
+
+    fn read_file(&self, path: &Path, w: &mut dyn Write, limit: Option<u64>) -> io::Result<()> {
+        let inner_path = self.inner_path(path)?;
+        let entry = self
+            .entries
+            .get(inner_path)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+        let limit = limit.unwrap_or(u64::MAX);
+
+        match entry.zip_index {
+            Some(zip_index) => {
+                let mut archive = ZipArchive::new(BufReader::new(File::open(&self.root)?))?;
+                let file = archive.by_index(zip_index)?;
+
+                io::copy(&mut file.take(limit), w)?;
+            }
+            None => {
+                let mut archive = tar::Archive::new(self.open_tar()?);
+
+                // NOTE(Chris): The last file with the path is the one that's listed
+                let mut found = false;
+                for tar_entry in archive.entries()? {
+                    let tar_entry = tar_entry?;
+
+                    if tar_inner_path(&tar_entry)?.as_deref() == Some(inner_path)
+                        && tar_entry.header().entry_type().is_file()
+                    {
+                        let mut contents = vec![];
+                        tar_entry.take(limit).read_to_end(&mut contents)?;
+
+                        found = true;
+                        w.write_all(&contents)?;
+                    }
+                }
+
+                if !found {
+                    return Err(io::Error::from(io::ErrorKind::NotFound));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_names(entries: &[DirEntryInfo]) -> Vec<(String, RecordedFileType)> {
+        let mut file_names: Vec<_> = entries
+            .iter()
+            .map(|entry_info| {
+                (
+                    entry_info
+                        .dir_entry
+                        .file_name()
+                        .to_string_lossy()
+                        .into_owned(),
+                    entry_info.file_type,
+                )
+            })
+            .collect();
+        file_names.sort_by(|(name_1, _), (name_2, _)| name_1.cmp(name_2));
+
+        file_names
+    }
+
+    #[test]
+    fn test_zip() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip_path = dir.path().join("notes.zip");
+
+        let mut writer = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+        let options = zip::write::FileOptions::default().unix_permissions(0o600);
+        writer.start_file("todo.txt", options).unwrap();
+        writer.write_all(b"buy milk").unwrap();
+        // NOTE(Chris): There's no entry for the docs directory itself
+        writer.start_file("docs/a/readme.md", options).unwrap();
+        writer.write_all(b"# Notes").unwrap();
+        writer.start_file("../escaped.txt", options).unwrap();
+        writer.finish().unwrap();
+
+        let archive = ArchiveFileSystem::open(&zip_path, ArchiveKind::Zip).unwrap();
+
+        assert_eq!(
+            file_names(&archive.read_dir(&zip_path, &|| false).unwrap()),
+            vec![
+                ("docs".to_string(), RecordedFileType::Directory),
+                ("todo.txt".to_string(), RecordedFileType::File),
+            ]
+        );
+        assert_eq!(
+            file_names(&archive.read_dir(&zip_path.join("docs"), &|| false).unwrap()),
+            vec![("a".to_string(), RecordedFileType::Directory)]
+        );
+        assert!(archive.is_dir(&zip_path.join("docs/a")));
+        assert!(!archive.is_dir(&zip_path.join("todo.txt")));
+
+        let entries = archive.read_dir(&zip_path, &|| false).unwrap();
+        let todo_info = entries
+            .iter()
+            .find(|entry_info| entry_info.dir_entry.file_name() == "todo.txt")
+            .unwrap();
+        assert_eq!(todo_info.metadata.len(), 8);
+        assert_eq!(todo_info.metadata.extra_perms().mode, "-rw-------");
+
+        let mut contents = vec![];
+        archive
+            .read_file(&zip_path.join("docs/a/readme.md"), &mut contents, None)
+            .unwrap();
+        assert_eq!(contents, b"# Notes");
+
+        let mut head = vec![];
+        archive
+            .read_file(&zip_path.join("todo.txt"), &mut head, Some(3))
+            .unwrap();
+        assert_eq!(head, b"buy");
+    }
+
+    #[test]
+    fn test_tar_gz() {
+        let dir = tempfile::tempdir().unwrap();
+        let tar_path = dir.path().join("backup.tar.gz");
+
+        let encoder = flate2::write::GzEncoder::new(
+            File::create(&tar_path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        for (path, contents) in [("./src/main.rs", "fn main() {}"), ("./README", "hi")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o755);
+            header.set_mtime(86400);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, contents.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+
+        assert_eq!(ArchiveKind::from_path(&tar_path), Some(ArchiveKind::TarGz));
+        let archive = ArchiveFileSystem::open(&tar_path, ArchiveKind::TarGz).unwrap();
+
+        let entries = archive.read_dir(&tar_path, &|| false).unwrap();
+        assert_eq!(
+            file_names(&entries),
+            vec![
+                ("README".to_string(), RecordedFileType::File),
+                ("src".to_string(), RecordedFileType::Directory),
+            ]
+        );
+
+        let readme_info = entries
+            .iter()
+            .find(|entry_info| entry_info.dir_entry.file_name() == "README")
+            .unwrap();
+        assert_eq!(readme_info.metadata.extra_perms().mode, "-rwxr-xr-x");
+        assert_eq!(
+            readme_info.metadata.modified().unwrap(),
+            SystemTime::UNIX_EPOCH + Duration::from_secs(86400)
+        );
+
+        let mut contents = vec![];
+        archive
+            .read_file(&tar_path.join("src/main.rs"), &mut contents, None)
+            .unwrap();
+        assert_eq!(contents, b"fn main() {}");
+
+        assert!(archive
+            .read_file(&tar_path.join("src"), &mut vec![], None)
+            .is_err());
+        assert!(archive
+            .write_file(&tar_path.join("README"), &mut "".as_bytes())
+            .is_err());
+    }
+}
@@ -0,0 +1,102 @@
+// Describes and edits the attributes that the attrs command changes across several files at once:
+// the rwx permission bits on Unix, and the read-only and hidden attributes on Windows. They're
+// written like "rw-r?-r--", where a letter means every file has that attribute, "-" means none
+// do, and "?" means only some do. Editing that text and leaving a "?" keeps each file's own value.
+
+use crate::os_abstract::ATTRIBUTE_LETTERS;
+
+const MIXED: char = '?';
+
+// Attributes are bits, where the first letter is the highest bit
+fn letter_bit(index: usize) -> u32 {
+    1 << (ATTRIBUTE_LETTERS.len() - 1 - index)
+}
+
+/// Describes the attributes of every file together.
+pub fn describe(attribute_sets: &[u32]) -> String {
+    ATTRIBUTE_LETTERS
+        .chars()
+        .enumerate()
+        .map(|(index, letter)| {
+            let bit = letter_bit(index);
+            let set_count = attribute_sets
+                .iter()
+                .filter(|&&attributes| attributes & bit != 0)
+                .count();
+
+            if set_count == 0 {
+                '-'
+            } else if set_count == attribute_sets.len() {
+                letter
+            } else {
+                MIXED
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeChanges {
+    set: u32,
+    clear: u32,
+}
+
+impl AttributeChanges {
+    /// Reads the changes from an edited description.
+    pub fn parse(description: &str) -> Result<AttributeChanges, String> {
+        let invalid = || {
+            format!(
+                "Expected {} with - for off and {} to leave alone",
+                ATTRIBUTE_LETTERS, MIXED
+            )
+        };
+
+        if description.chars().count() != ATTRIBUTE_LETTERS.len() {
+            return Err(invalid());
+        }
+
+        let mut changes = AttributeChanges { set: 0, clear: 0 };
+
+        for (index, (ch, letter)) in description
+            .chars()
+            .zip(ATTRIBUTE_LETTERS.chars())
+            .enumerate()
+        {
+            match ch {
+                '-' => changes.clear |= letter_bit(index),
+                MIXED => (),
+                _ if ch == letter => changes.set |= letter_bit(index),
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(changes)
+    }
+
+    pub fn apply(&self, attributes: u32) -> u32 {
+        (attributes | self.set) & !self.clear
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attribute_changes() {
+        assert_eq!(describe(&[0o644]), "rw-r--r--");
+        assert_eq!(describe(&[0o644, 0o755]), "rw?r-?r-?");
+
+        let changes = AttributeChanges::parse("rwxr-?---").unwrap();
+        assert_eq!(changes.apply(0o644), 0o740);
+        assert_eq!(changes.apply(0o655), 0o750);
+
+        // NOTE(Chris): Describing and parsing again changes nothing
+        let changes = AttributeChanges::parse(&describe(&[0o644, 0o755])).unwrap();
+        assert_eq!(changes.apply(0o644), 0o644);
+        assert_eq!(changes.apply(0o755), 0o755);
+
+        assert!(AttributeChanges::parse("rw-r--r-").is_err());
+        assert!(AttributeChanges::parse("wr-r--r--").is_err());
+    }
+}
@@ -0,0 +1,396 @@
+// Runs rolfrc commands without the TUI (with `rolf --batch`), so that scripts and tests can drive
+// rolf. Commands run through the TUI's run_command, but without a screen. What they would ask is
+// answered with their arguments instead, like "rename new-name" and "find c", and a yes/no question
+// left without one is answered yes, so delete doesn't ask for confirmation. Messages which the TUI
+// would show on the bottom line are written to stderr.
+
+use crate::config::Config;
+use crate::{
+    count_files, get_drawing_info, make_renames, run_command, CommandCallback, CommandFlow,
+    CommandRequest, DirStates, FileManager, InputEvent, InputMode, SortOptions, WindowPixels,
+};
+
+use rolf_parser::parser::{parse, CommandUse, Statement};
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+// The size of the screen that the cursor moves through, which decides how far it scrolls
+const BATCH_WIDTH: u16 = 80;
+const BATCH_HEIGHT: u16 = 24;
+
+struct Batch<W: Write> {
+    fm: FileManager,
+    tx: Sender<InputEvent>,
+    rx: Receiver<InputEvent>,
+    to_command_tx: Option<Sender<String>>,
+    // Where picked files are written
+    output: W,
+}
+
+/// Runs every command in the script, starting in start_dir. Stops at the first command that can't
+/// run without the TUI, returning its error message.
+pub fn run_batch<W: Write>(
+    config: Config,
+    start_dir: &Path,
+    data_dir: &Path,
+    script: &str,
+    output: W,
+) -> Result<(), String> {
+    let program = parse(script).map_err(|err| format!("Unable to parse commands: {}", err))?;
+
+    let dir_states = DirStates::new(start_dir.to_path_buf(), SortOptions::new(&config), false)
+        .map_err(|err| format!("Unable to read {}: {}", start_dir.display(), err))?;
+
+    let (tx, rx) = channel();
+
+    let mut fm = FileManager::new(&config, dir_states, data_dir, None, &tx);
+    fm.drawing_info = get_drawing_info(
+        WindowPixels {
+            width: 0,
+            height: 0,
+        },
+        BATCH_WIDTH,
+        BATCH_HEIGHT,
+        fm.layout_ratios(),
+        fm.config.preview_position,
+    );
+
+    let mut batch = Batch {
+        fm,
+        tx,
+        rx,
+        to_command_tx: None,
+        output,
+    };
+
+    for statement in program {
+        match statement {
+            Statement::CommandUse(command_use) => {
+                let should_continue = batch
+                    .run_command(&command_use)
+                    .map_err(|message| format!("{}: {}", command_use.name, message))?;
+
+                if !should_continue {
+                    break;
+                }
+            }
+            // NOTE(Chris): Key maps and open rules only matter in the TUI
+            Statement::Map(_) | Statement::OpenRule(_) => (),
+        }
+    }
+
+    Ok(())
+}
+
+impl<W: Write> Batch<W> {
+    // Returns false once the script should stop
+    fn run_command(&mut self, command_use: &CommandUse) -> Result<bool, String> {
+        let unavailable = || format!("{} isn't available in batch mode", command_use.name);
+
+        let flow = run_command(
+            &mut self.fm,
+            &self.tx,
+            &mut self.to_command_tx,
+            None,
+            command_use,
+        )
+        .map_err(|err| err.to_string())?;
+
+        let mut result = self.answer_command_thread(&command_use.arguments);
+        self.run_callbacks()?;
+
+        if let Some(message) = self.fm.status_message.take() {
+            eprintln!("rolf: {}: {}", command_use.name, message);
+        }
+
+        match flow {
+            CommandFlow::Done => (),
+            CommandFlow::Quit => return Ok(false),
+            // NOTE(Chris): Unlike with --choosefiles, picking doesn't end the script
+            CommandFlow::Pick(paths) => {
+                for path in paths {
+                    writeln!(self.output, "{}", path.display()).map_err(|err| err.to_string())?;
+                }
+            }
+            CommandFlow::Unavailable => return Err(unavailable()),
+            CommandFlow::Unknown => return Err("Unknown command".to_string()),
+        }
+
+        // NOTE(Chris): There's nothing to look at in batch mode, so the panels which commands open
+        // are closed right away
+        match std::mem::replace(&mut self.fm.input_mode, InputMode::Normal) {
+            InputMode::Normal => (),
+            // The TUI waits for the renames to be looked over, but here they're made right away
+            InputMode::RenamePreview { renames, .. } => {
+                let conflict_count = renames
+                    .iter()
+                    .filter(|rename| rename.conflict.is_some())
+                    .count();
+
+                if conflict_count > 0 {
+                    return Err(format!(
+                        "{} of {} conflicting",
+                        conflict_count,
+                        count_files(renames.len())
+                    ));
+                }
+
+                make_renames(&mut self.fm, &renames, &self.tx);
+                self.run_callbacks()?;
+
+                if let Some(message) = self.fm.status_message.take() {
+                    eprintln!("rolf: {}: {}", command_use.name, message);
+                }
+            }
+            // NOTE(Chris): Commands which are missing their argument prompt for it
+            InputMode::Command { .. } => result = Err("expected an argument".to_string()),
+            _ => result = Err(unavailable()),
+        }
+
+        result.map(|()| true)
+    }
+
+    // Answers what the command's thread asks with the arguments, in order, until it finishes. Does
+    // nothing if the command didn't start a thread.
+    fn answer_command_thread(&mut self, arguments: &[String]) -> Result<(), String> {
+        let to_command_tx = match self.to_command_tx.take() {
+            Some(to_command_tx) => to_command_tx,
+            None => return Ok(()),
+        };
+
+        let mut arguments = arguments.iter();
+        let mut result = Ok(());
+
+        loop {
+            let event = self
+                .rx
+                .recv()
+                .expect("Unable to receive from command thread");
+
+            match event {
+                InputEvent::CommandRequest(CommandRequest::Ask(mut prompt)) => {
+                    let answer = match arguments.next() {
+                        Some(argument) => argument.clone(),
+                        None if prompt.label.contains("(y/n)") => "y".to_string(),
+                        None => {
+                            result = Err(format!("expected an answer to \"{}\"", prompt.label));
+                            String::new()
+                        }
+                    };
+
+                    // NOTE(Chris): An empty answer cancels the prompt, like escape in the TUI
+                    let answer = if prompt.validate(&answer) {
+                        answer
+                    } else {
+                        result = Err(prompt.error().unwrap_or_default().to_string());
+                        String::new()
+                    };
+
+                    to_command_tx
+                        .send(answer)
+                        .expect("Failed to send to command thread");
+                }
+                InputEvent::CommandRequest(CommandRequest::Shell(_)) => {
+                    result = Err("running a shell command isn't available in batch mode".into());
+                }
+                InputEvent::CommandRequest(CommandRequest::Quit) => break,
+                InputEvent::CommandCallback(CommandCallback(callback)) => {
+                    callback(&mut self.fm).map_err(|err| err.to_string())?;
+                }
+                // NOTE(Chris): Previews are never drawn
+                _ => (),
+            }
+        }
+
+        result
+    }
+
+    // Applies whatever other threads have sent back so far
+    fn run_callbacks(&mut self) -> Result<(), String> {
+        while let Ok(event) = self.rx.try_recv() {
+            if let InputEvent::CommandCallback(CommandCallback(callback)) = event {
+                callback(&mut self.fm).map_err(|err| err.to_string())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[test]
+    fn test_run_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = fs::canonicalize(dir.path()).unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+
+        for file_name in ["apple.txt", "banana.txt", "cherry.txt"] {
+            fs::write(dir_path.join(file_name), "").unwrap();
+        }
+        fs::create_dir(dir_path.join("docs")).unwrap();
+        fs::write(dir_path.join("docs/notes.txt"), "").unwrap();
+
+        let run = |script: &str| {
+            let mut output = vec![];
+
+            run_batch(
+                Config::default(),
+                &dir_path,
+                data_dir.path(),
+                script,
+                &mut output,
+            )
+            .map(|()| String::from_utf8(output).unwrap())
+        };
+
+        // NOTE(Chris): Directories are sorted first
+        assert_eq!(
+            run(
+                "search ban\nrename berry.txt\ntoggle\nbottom\ntoggle\npick\n\
+                 delete\ndown 3\ntop\nopen\npick\n"
+            ),
+            Ok(format!(
+                "{}\n{}\n{}\n",
+                dir_path.join("berry.txt").display(),
+                dir_path.join("cherry.txt").display(),
+                dir_path.join("docs/notes.txt").display(),
+            ))
+        );
+
+        assert!(dir_path.join("apple.txt").exists());
+        for file_name in ["banana.txt", "berry.txt", "cherry.txt"] {
+            assert!(!dir_path.join(file_name).exists());
+        }
+
+        assert_eq!(
+            run("only-files\npick\nonly-dirs\npick\nclear-filter\nbottom\npick\n"),
+            Ok(format!(
+                "{}\n{}\n{}\n",
+                dir_path.join("apple.txt").display(),
+                dir_path.join("docs").display(),
+                dir_path.join("apple.txt").display(),
+            ))
+        );
+
+        // The commands added for the TUI work here too
+        assert_eq!(
+            run("bottom\ncopy\ntop\nopen\npaste\nbottom\nrename-upper\npick\n"),
+            Ok(format!("{}\n", dir_path.join("docs/NOTES.TXT").display()))
+        );
+        assert!(dir_path.join("apple.txt").exists());
+        assert!(dir_path.join("docs/apple.txt").exists());
+
+        assert_eq!(
+            run("help\n"),
+            Err("help: help isn't available in batch mode".to_string())
+        );
+        assert_eq!(
+            run("search\n"),
+            Err("search: expected an argument".to_string())
+        );
+        assert_eq!(
+            run("edit\n"),
+            Err("edit: edit isn't available in batch mode".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_batch_privileged_paste() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = fs::canonicalize(dir.path()).unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+
+        fs::write(dir_path.join("apple.txt"), "apple").unwrap();
+        let locked_path = dir_path.join("locked");
+        fs::create_dir(&locked_path).unwrap();
+        fs::set_permissions(&locked_path, fs::Permissions::from_mode(0o555)).unwrap();
+
+        // NOTE(Chris): Permissions don't stop root, so there's nothing to retry
+        if fs::write(locked_path.join("probe"), "").is_ok() {
+            return;
+        }
+
+        // Stands in for sudo by unlocking the directory being pasted into, then running the command
+        let helper_path = data_dir.path().join("helper");
+        fs::write(
+            &helper_path,
+            "#!/bin/sh\nfor last; do :; done\nchmod u+w \"$(dirname \"$last\")\"\nexec \"$@\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&helper_path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = Config {
+            privilege_helper: helper_path.to_string_lossy().into_owned(),
+            ..Config::default()
+        };
+
+        let mut output = vec![];
+        run_batch(
+            config,
+            &dir_path,
+            data_dir.path(),
+            "bottom\ncopy\ntop\nopen\npaste\nbottom\npick\n",
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}\n", locked_path.join("apple.txt").display())
+        );
+        assert_eq!(
+            fs::read_to_string(locked_path.join("apple.txt")).unwrap(),
+            "apple"
+        );
+    }
+
+    #[test]
+    fn test_run_batch_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = fs::canonicalize(dir.path()).unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+
+        let zip_path = dir_path.join("notes.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        let options = zip::write::FileOptions::default();
+        writer.start_file("docs/readme.md", options).unwrap();
+        writer.write_all(b"# Notes").unwrap();
+        writer.start_file("todo.txt", options).unwrap();
+        writer.write_all(b"buy milk").unwrap();
+        writer.finish().unwrap();
+
+        let mut output = vec![];
+        run_batch(
+            Config::default(),
+            &dir_path,
+            data_dir.path(),
+            // NOTE(Chris): pick isn't available in the archive, so only the last one picks a file
+            "open\nbottom\nextract\npick\ntop\nopen\nextract\nupdir\nupdir\npick\n",
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("{}\n", zip_path.display())
+        );
+        assert_eq!(
+            fs::read_to_string(dir_path.join("todo.txt")).unwrap(),
+            "buy milk"
+        );
+        assert_eq!(
+            fs::read_to_string(dir_path.join("readme.md")).unwrap(),
+            "# Notes"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+// Works out the renames made by the commands which rename several files at once (like
+// rename-regex and rename-lower). Every rename is checked before any are made, so that the preview
+// can point out the ones which would clobber a file or collide with each other.
+
+use crate::os_abstract;
+use crate::unicode_fold::fold_str;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{self, Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub conflict: Option<Conflict>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    InvalidName,
+    AlreadyExists,
+    SameAsAnother,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conflict::InvalidName => write!(f, "invalid name"),
+            Conflict::AlreadyExists => write!(f, "already exists"),
+            Conflict::SameAsAnother => write!(f, "same as another new name"),
+        }
+    }
+}
+
+/// Gives each file the new name made from its old one, leaving out the files whose names don't
+/// change.
+pub fn plan_renames<F>(paths: &[PathBuf], to_new_name: F) -> Vec<Rename>
+where
+    F: Fn(&str) -> String,
+{
+    let mut renames: Vec<Rename> = paths
+        .iter()
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_str()?;
+            let new_name = to_new_name(file_name);
+
+            if new_name == file_name {
+                return None;
+            }
+
+            let conflict = if new_name.is_empty()
+                || new_name.contains(path::MAIN_SEPARATOR)
+                || new_name == "."
+                || new_name == ".."
+            {
+                Some(Conflict::InvalidName)
+            } else {
+                None
+            };
+
+            Some(Rename {
+                from: path.clone(),
+                to: path.with_file_name(&new_name),
+                conflict,
+            })
+        })
+        .collect();
+
+    // NOTE(Chris): On a case-insensitive file system, names which only differ in case are the
+    // same file, so they're counted together
+    let mut case_sensitive_dirs: HashMap<PathBuf, bool> = HashMap::new();
+    let mut target_key = |path: &Path| {
+        let dir = path.parent().unwrap_or(path).to_path_buf();
+        let is_case_sensitive = *case_sensitive_dirs
+            .entry(dir)
+            .or_insert_with_key(|dir| os_abstract::is_case_sensitive(dir));
+
+        if is_case_sensitive {
+            path.to_path_buf()
+        } else {
+            PathBuf::from(path.to_string_lossy().to_lowercase())
+        }
+    };
+
+    let target_keys: Vec<PathBuf> = renames
+        .iter()
+        .map(|rename| target_key(&rename.to))
+        .collect();
+
+    let mut target_counts: HashMap<&Path, usize> = HashMap::new();
+    for key in &target_keys {
+        *target_counts.entry(key).or_default() += 1;
+    }
+
+    for (rename, key) in renames
+        .iter_mut()
+        .zip(&target_keys)
+        .filter(|(rename, _key)| rename.conflict.is_none())
+    {
+        // NOTE(Chris): A file that's in the way counts even if it's being renamed too, since
+        // the renames would then depend on the order they're made in. Changing just the case of
+        // a name on a case-insensitive file system finds the file itself in the way, though.
+        if target_counts[key.as_path()] > 1 {
+            rename.conflict = Some(Conflict::SameAsAnother);
+        } else if rename.to.symlink_metadata().is_ok()
+            && !os_abstract::is_same_path(&rename.from, &rename.to)
+        {
+            rename.conflict = Some(Conflict::AlreadyExists);
+        }
+    }
+
+    renames.sort_by(|rename1, rename2| rename1.from.cmp(&rename2.from));
+
+    renames
+}
+
+/// Makes a file name safe to use anywhere: accents are removed, whitespace becomes underscores,
+/// and characters which some systems don't allow are dropped. Other non-ASCII characters become
+/// underscores too.
+pub fn sanitize_name(name: &str) -> String {
+    let mut sanitized = String::with_capacity(name.len());
+
+    for ch in fold_str(name, false, true).chars() {
+        let replacement = match ch {
+            'ß' => "ss",
+            'æ' => "ae",
+            'Æ' => "AE",
+            'œ' => "oe",
+            'Œ' => "OE",
+            'þ' => "th",
+            'Þ' => "TH",
+            // NOTE(Chris): These can't be used in file names on Windows
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => continue,
+            _ if ch.is_control() => continue,
+            _ if ch.is_whitespace() || !ch.is_ascii() => "_",
+            _ => {
+                sanitized.push(ch);
+                continue;
+            }
+        };
+
+        // NOTE(Chris): A run of spaces (or other replaced characters) becomes a single underscore
+        if !(replacement == "_" && sanitized.ends_with('_')) {
+            sanitized.push_str(replacement);
+        }
+    }
+
+    sanitized.trim_matches('_').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use regex::Regex;
+
+    use std::fs;
+
+    #[test]
+    fn test_plan_renames() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["IMG_001.jpg", "IMG_002.jpg", "notes.txt", "photo-001.jpg"]
+            .iter()
+            .map(|file_name| dir.path().join(file_name))
+            .collect();
+        for path in &paths {
+            fs::write(path, "").unwrap();
+        }
+
+        let regex = Regex::new(r"^IMG_(\d+)").unwrap();
+        assert_eq!(
+            plan_renames(&paths, |name| regex
+                .replace_all(name, "photo-$1")
+                .into_owned()),
+            [
+                Rename {
+                    from: dir.path().join("IMG_001.jpg"),
+                    to: dir.path().join("photo-001.jpg"),
+                    conflict: Some(Conflict::AlreadyExists),
+                },
+                Rename {
+                    from: dir.path().join("IMG_002.jpg"),
+                    to: dir.path().join("photo-002.jpg"),
+                    conflict: None,
+                },
+            ]
+        );
+
+        let regex = Regex::new(r"\d").unwrap();
+        let renames = plan_renames(&paths[..2], |name| regex.replace_all(name, "").into_owned());
+        assert!(renames
+            .iter()
+            .all(|rename| rename.conflict == Some(Conflict::SameAsAnother)));
+
+        let renames = plan_renames(&paths[2..3], |_name| format!("a{}b", path::MAIN_SEPARATOR));
+        assert_eq!(renames[0].conflict, Some(Conflict::InvalidName));
+
+        // NOTE(Chris): Only the files whose names change are renamed
+        let renames = plan_renames(&paths, str::to_lowercase);
+        assert_eq!(renames.len(), 2);
+        assert_eq!(renames[0].to, dir.path().join("img_001.jpg"));
+        assert_eq!(renames[0].conflict, None);
+    }
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(
+            sanitize_name("My Résumé (final).pdf"),
+            "My_Resume_(final).pdf"
+        );
+        assert_eq!(sanitize_name("  what?  why: *.txt"), "what_why_.txt");
+        assert_eq!(sanitize_name("Straße 日本.md"), "Strasse_.md");
+        assert_eq!(sanitize_name("plain-name.txt"), "plain-name.txt");
+    }
+}
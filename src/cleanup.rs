@@ -0,0 +1,134 @@
+// Finds the clutter that the cleanup command selects for removal: symlinks whose targets are gone,
+// and directories with nothing in them. The whole tree under a directory is scanned, so this is
+// run on its own thread.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Clutter {
+    pub broken_symlinks: Vec<PathBuf>,
+    pub empty_dirs: Vec<PathBuf>,
+}
+
+impl Clutter {
+    pub fn len(&self) -> usize {
+        self.broken_symlinks.len() + self.empty_dirs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Finds the broken symlinks and empty directories under the root (but not the root itself).
+/// Symlinks aren't followed, and anything which can't be read is skipped. With ignore_vcs, so are
+/// the files that git ignores.
+pub fn find_clutter(root: &Path, ignore_vcs: bool) -> Clutter {
+    let mut clutter = Clutter::default();
+
+    let walker = WalkBuilder::new(root)
+        .standard_filters(ignore_vcs)
+        .hidden(false)
+        // NOTE(Chris): Git keeps empty directories (like refs/tags) that it expects to be there, so
+        // repositories are left alone
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .build();
+
+    for entry in walker.filter_map(Result::ok) {
+        let file_type = match entry.file_type() {
+            Some(file_type) if entry.depth() > 0 => file_type,
+            _ => continue,
+        };
+        let path = entry.into_path();
+
+        if file_type.is_symlink() {
+            if fs::metadata(&path).is_err_and(|err| err.kind() == io::ErrorKind::NotFound) {
+                clutter.broken_symlinks.push(path);
+            }
+        } else if file_type.is_dir() {
+            // NOTE(Chris): A directory with only ignored files in it still isn't empty, since
+            // removing it would remove them too
+            let is_empty =
+                fs::read_dir(&path).is_ok_and(|mut dir_entries| dir_entries.next().is_none());
+
+            if is_empty {
+                clutter.empty_dirs.push(path);
+            }
+        }
+    }
+
+    clutter.broken_symlinks.sort();
+    clutter.empty_dirs.sort();
+
+    clutter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_clutter() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        fs::create_dir_all(root.join("a/empty")).unwrap();
+        fs::create_dir_all(root.join("a/full")).unwrap();
+        fs::write(root.join("a/full/file.txt"), "").unwrap();
+        fs::create_dir_all(root.join(".git/refs/tags")).unwrap();
+        std::os::unix::fs::symlink("file.txt", root.join("a/full/good-link")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", root.join("a/full/bad-link")).unwrap();
+        std::os::unix::fs::symlink("a/empty", root.join("dir-link")).unwrap();
+
+        assert_eq!(
+            find_clutter(root, false),
+            Clutter {
+                broken_symlinks: vec![root.join("a/full/bad-link")],
+                empty_dirs: vec![root.join("a/empty")],
+            }
+        );
+
+        assert!(find_clutter(&root.join("a/empty"), false).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_clutter_ignore_vcs() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        // NOTE(Chris): A .gitignore only counts inside a git repository
+        fs::create_dir_all(root.join(".git/refs/tags")).unwrap();
+        fs::write(root.join(".gitignore"), "target/\n*.tmp\n").unwrap();
+        fs::create_dir_all(root.join("target/debug/incremental")).unwrap();
+        fs::create_dir_all(root.join("src/empty")).unwrap();
+        fs::create_dir_all(root.join("cache")).unwrap();
+        fs::write(root.join("cache/build.tmp"), "").unwrap();
+        std::os::unix::fs::symlink("missing.txt", root.join("src/bad-link")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", root.join("bad-link.tmp")).unwrap();
+
+        assert_eq!(
+            find_clutter(root, true),
+            Clutter {
+                broken_symlinks: vec![root.join("src/bad-link")],
+                empty_dirs: vec![root.join("src/empty")],
+            }
+        );
+
+        assert_eq!(
+            find_clutter(root, false),
+            Clutter {
+                broken_symlinks: vec![root.join("bad-link.tmp"), root.join("src/bad-link")],
+                empty_dirs: vec![
+                    root.join("src/empty"),
+                    root.join("target/debug/incremental")
+                ],
+            }
+        );
+    }
+}
@@ -0,0 +1,281 @@
+// Compares two directory trees for the compare-dirs command, finding the files which are only on
+// one side and the ones which differ. A directory that's only on one side is listed by itself,
+// rather than with everything inside it.
+
+use crate::unignored_names;
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File, FileType};
+use std::io::{self, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difference {
+    OnlyLeft,
+    OnlyRight,
+    // A file on one side and a directory on the other
+    Kind,
+    Size,
+    Content,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::OnlyLeft => write!(f, "only left"),
+            Difference::OnlyRight => write!(f, "only right"),
+            Difference::Kind => write!(f, "file and dir"),
+            Difference::Size => write!(f, "size differs"),
+            Difference::Content => write!(f, "differs"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirDifference {
+    // Relative to both directories
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub difference: Difference,
+}
+
+/// Finds the differences between the two trees, in the order of their paths. Subdirectories that
+/// can't be read are skipped, and so are the files git ignores with ignore_vcs.
+pub fn compare_dirs(left: &Path, right: &Path, ignore_vcs: bool) -> io::Result<Vec<DirDifference>> {
+    let mut differences = vec![];
+
+    compare_at(left, right, Path::new(""), ignore_vcs, &mut differences)?;
+
+    differences.sort_by(|difference1, difference2| difference1.path.cmp(&difference2.path));
+
+    Ok(differences)
+}
+
+fn compare_at(
+    left_root: &Path,
+    right_root: &Path,
+    relative_dir: &Path,
+    ignore_vcs: bool,
+    differences: &mut Vec<DirDifference>,
+) -> io::Result<()> {
+    let left_types = read_file_types(&left_root.join(relative_dir), ignore_vcs)?;
+    let mut right_types = read_file_types(&right_root.join(relative_dir), ignore_vcs)?;
+
+    for (name, left_type) in left_types {
+        let path = relative_dir.join(&name);
+
+        let right_type = match right_types.remove(&name) {
+            Some(right_type) => right_type,
+            None => {
+                differences.push(DirDifference {
+                    path,
+                    is_dir: left_type.is_dir(),
+                    difference: Difference::OnlyLeft,
+                });
+                continue;
+            }
+        };
+
+        if left_type.is_dir() && right_type.is_dir() {
+            let _ = compare_at(left_root, right_root, &path, ignore_vcs, differences);
+        } else if left_type.is_dir() != right_type.is_dir() {
+            differences.push(DirDifference {
+                path,
+                is_dir: false,
+                difference: Difference::Kind,
+            });
+        } else if let Some(difference) =
+            compare_files(&left_root.join(&path), &right_root.join(&path))
+        {
+            differences.push(DirDifference {
+                path,
+                is_dir: false,
+                difference,
+            });
+        }
+    }
+
+    for (name, right_type) in right_types {
+        differences.push(DirDifference {
+            path: relative_dir.join(name),
+            is_dir: right_type.is_dir(),
+            difference: Difference::OnlyRight,
+        });
+    }
+
+    Ok(())
+}
+
+// The types of the entries in a directory (without following symlinks), by name
+fn read_file_types(dir: &Path, ignore_vcs: bool) -> io::Result<BTreeMap<OsString, FileType>> {
+    let mut file_types: BTreeMap<OsString, FileType> = fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .filter_map(|entry| Some((entry.file_name(), entry.file_type().ok()?)))
+        .collect();
+
+    // NOTE(Chris): Each side is checked against its own .gitignore files, so a file ignored on
+    // only one side shows up as missing from the other
+    if ignore_vcs {
+        let unignored_names = unignored_names(dir);
+
+        file_types.retain(|name, _| unignored_names.contains(name));
+    }
+
+    Ok(file_types)
+}
+
+// How two files (or two symlinks) differ, if they do. Like rsync, files with the same size and
+// modification time are taken to be the same without reading them.
+fn compare_files(left: &Path, right: &Path) -> Option<Difference> {
+    let (left_metadata, right_metadata) =
+        match (fs::symlink_metadata(left), fs::symlink_metadata(right)) {
+            (Ok(left_metadata), Ok(right_metadata)) => (left_metadata, right_metadata),
+            _ => return None,
+        };
+
+    if left_metadata.file_type().is_symlink() || right_metadata.file_type().is_symlink() {
+        return match (fs::read_link(left), fs::read_link(right)) {
+            (Ok(left_target), Ok(right_target)) if left_target == right_target => None,
+            _ => Some(Difference::Content),
+        };
+    }
+
+    if left_metadata.len() != right_metadata.len() {
+        return Some(Difference::Size);
+    }
+
+    if left_metadata.modified().ok() == right_metadata.modified().ok() {
+        return None;
+    }
+
+    match have_same_contents(left, right) {
+        Ok(true) => None,
+        _ => Some(Difference::Content),
+    }
+}
+
+fn have_same_contents(left: &Path, right: &Path) -> io::Result<bool> {
+    let mut left_reader = BufReader::new(File::open(left)?);
+    let mut right_reader = BufReader::new(File::open(right)?);
+
+    let mut left_buf = [0; 8192];
+    let mut right_buf = [0; 8192];
+
+    loop {
+        let left_len = left_reader.read(&mut left_buf)?;
+        if left_len == 0 {
+            return Ok(right_reader.read(&mut right_buf)? == 0);
+        }
+
+        // NOTE(Chris): Reads can come up short, so we read exactly as much from the other file
+        if right_reader.read_exact(&mut right_buf[..left_len]).is_err()
+            || left_buf[..left_len] != right_buf[..left_len]
+        {
+            return Ok(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_compare_dirs() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+
+        for root in [left.path(), right.path()] {
+            fs::create_dir_all(root.join("sub")).unwrap();
+            fs::write(root.join("same.txt"), "same").unwrap();
+        }
+
+        fs::write(left.path().join("sub/left.txt"), "").unwrap();
+        fs::create_dir(right.path().join("right-dir")).unwrap();
+        fs::write(left.path().join("size.txt"), "short").unwrap();
+        fs::write(right.path().join("size.txt"), "longer").unwrap();
+        fs::write(left.path().join("content.txt"), "abc").unwrap();
+        fs::write(right.path().join("content.txt"), "abd").unwrap();
+        // NOTE(Chris): Files with the same size and modification time aren't read
+        File::options()
+            .write(true)
+            .open(right.path().join("content.txt"))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1000))
+            .unwrap();
+        fs::write(left.path().join("kind"), "").unwrap();
+        fs::create_dir(right.path().join("kind")).unwrap();
+
+        let differences: Vec<(String, Difference)> = compare_dirs(left.path(), right.path(), false)
+            .unwrap()
+            .into_iter()
+            .map(|difference| {
+                (
+                    difference.path.to_string_lossy().into_owned(),
+                    difference.difference,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            differences,
+            [
+                ("content.txt".to_string(), Difference::Content),
+                ("kind".to_string(), Difference::Kind),
+                ("right-dir".to_string(), Difference::OnlyRight),
+                ("size.txt".to_string(), Difference::Size),
+                (
+                    Path::new("sub")
+                        .join("left.txt")
+                        .to_string_lossy()
+                        .into_owned(),
+                    Difference::OnlyLeft
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_dirs_ignore_vcs() {
+        let left = tempfile::tempdir().unwrap();
+        let right = tempfile::tempdir().unwrap();
+
+        // NOTE(Chris): A .gitignore only counts inside a git repository
+        for root in [left.path(), right.path()] {
+            fs::create_dir_all(root.join(".git")).unwrap();
+            fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        }
+
+        fs::create_dir_all(left.path().join("target/debug")).unwrap();
+        fs::write(left.path().join("target/debug/rolf"), "").unwrap();
+        fs::write(left.path().join("build.log"), "left").unwrap();
+        fs::write(right.path().join("build.log"), "right side").unwrap();
+        fs::write(right.path().join("main.rs"), "").unwrap();
+
+        let paths = |ignore_vcs| {
+            compare_dirs(left.path(), right.path(), ignore_vcs)
+                .unwrap()
+                .into_iter()
+                .map(|difference| difference.path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(paths(true), ["main.rs"]);
+        assert_eq!(paths(false), ["build.log", "main.rs", "target"]);
+    }
+
+    #[test]
+    fn test_have_same_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), "abc").unwrap();
+        fs::write(dir.path().join("b"), "abd").unwrap();
+        fs::write(dir.path().join("c"), "abc").unwrap();
+
+        assert!(!have_same_contents(&dir.path().join("a"), &dir.path().join("b")).unwrap());
+        assert!(have_same_contents(&dir.path().join("a"), &dir.path().join("c")).unwrap());
+    }
+}
@@ -1,6 +1,9 @@
+use crate::tools;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
-use nanoserde::DeJson;
+use nanoserde::{DeJson, Toml, TomlParser};
+use rolf_grid::Color;
 use std::collections::HashMap;
+use std::time::Duration;
 use std::vec::Vec;
 use thiserror::Error;
 
@@ -13,6 +16,215 @@ pub struct JsonConfig {
     #[nserde(rename = "image-protocol")]
     #[nserde(default = "ImageProtocol::Kitty")]
     image_protocol: ImageProtocol,
+    // The number of milliseconds the cursor must stay on a file before its preview is generated
+    #[nserde(rename = "preview-delay")]
+    #[nserde(default = "0")]
+    preview_delay: u64,
+    // Whether to show a row above the preview with the file's name, size, and (for text) which
+    // lines are shown
+    #[nserde(rename = "preview-header")]
+    #[nserde(default = "false")]
+    preview_header: bool,
+    // Where the preview is shown: "right" (as a third column), "bottom" (below the other two
+    // columns), or "hidden"
+    #[nserde(rename = "preview-position")]
+    #[nserde(default = "right")]
+    preview_position: String,
+    // How many megabytes of image and highlighted text previews are kept, so that going back to a
+    // file shows its preview right away
+    #[nserde(rename = "preview-cache")]
+    #[nserde(default = "64")]
+    preview_cache: u64,
+    // Whether opening a file picks it when rolf is run with --choosefiles
+    #[nserde(rename = "pick-on-open")]
+    #[nserde(default = "true")]
+    pick_on_open: bool,
+    // A command (like "mimeopen -n") used to open files that the system opener can't handle
+    #[nserde(rename = "fallback-opener")]
+    #[nserde(default = "")]
+    fallback_opener: String,
+    // How to make the editor jump to a line, like "+{line} {file}". Detected from the editor's
+    // name when empty.
+    #[nserde(rename = "editor-line-flag")]
+    #[nserde(default = "")]
+    editor_line_flag: String,
+    #[nserde(rename = "command-prompt")]
+    #[nserde(default = ":")]
+    command_prompt: String,
+    #[nserde(rename = "rename-prompt")]
+    #[nserde(default = "Rename: ")]
+    rename_prompt: String,
+    // Whether rename edits the name in its row of the listing, rather than at the bottom
+    #[nserde(rename = "inline-rename")]
+    #[nserde(default = "false")]
+    inline_rename: bool,
+    // The directory new-from-template copies files from. The templates directory in the config
+    // directory when empty.
+    #[nserde(rename = "templates-dir")]
+    #[nserde(default = "")]
+    templates_dir: String,
+    // The name of the color used to draw prompts, like "blue" or "bright-green"
+    #[nserde(rename = "prompt-color")]
+    #[nserde(default = "default")]
+    prompt_color: String,
+    // The program used to retry operations that failed with "permission denied", like "sudo",
+    // "doas", or "pkexec"
+    #[nserde(rename = "privilege-helper")]
+    #[nserde(default = "sudo")]
+    privilege_helper: String,
+    // The program the diff command runs on the two files, like "vimdiff" or "meld". The files are
+    // compared by rolf itself when empty.
+    #[nserde(default = "")]
+    difftool: String,
+    // The shell command terminal-here runs in the current directory, like "alacritty". A new tmux
+    // window is opened instead when empty and rolf is running in tmux.
+    #[nserde(default = "")]
+    terminal: String,
+    // Which of the current file's dates to show on the info line: "modified", "accessed", or
+    // "created"
+    #[nserde(rename = "info-date")]
+    #[nserde(default = "modified")]
+    info_date: String,
+    // How that date is written: "ctime" (like "Sun Jul  8 00:34:60 2001"), "iso", "locale" (the
+    // way the system's locale writes dates), "relative" (like "3 h ago"), or "custom" (following
+    // date-format)
+    #[nserde(rename = "time-format")]
+    #[nserde(default = "ctime")]
+    time_format: String,
+    // The strftime format of the custom time format, like "%Y-%m-%d %H:%M"
+    #[nserde(rename = "date-format")]
+    #[nserde(default = "%Y-%m-%d %H:%M")]
+    date_format: String,
+    // Whether the ctime and iso time formats use a "24h" or "12h" clock
+    #[nserde(default = "24h")]
+    clock: String,
+    // How sizes are written: "metric" (like lf, where 1K is 1000 bytes) or "iec" (where 1KiB is
+    // 1024 bytes)
+    #[nserde(rename = "size-units")]
+    #[nserde(default = "metric")]
+    size_units: String,
+    // Whether the info line shows the current file's exact size in bytes, like "3,148,408B"
+    #[nserde(rename = "exact-size")]
+    #[nserde(default = "false")]
+    exact_size: bool,
+    // Where file names which don't fit are cut: "end" (like lf), "middle" (keeping the
+    // extension), or "start"
+    #[nserde(default = "end")]
+    truncate: String,
+    // Whether to show how full the current directory's file system is in the top right corner,
+    // like "41G/64G". It's drawn in red once the percentage used reaches disk-usage-warning.
+    #[nserde(rename = "disk-usage")]
+    #[nserde(default = "false")]
+    disk_usage: bool,
+    #[nserde(rename = "disk-usage-warning")]
+    #[nserde(default = "90")]
+    disk_usage_warning: u64,
+    // How recently a file must have been modified for recent-files to show it, like "30m", "12h",
+    // or "2d"
+    #[nserde(rename = "recent-within")]
+    #[nserde(default = "1d")]
+    recent_within: String,
+    // The color of files modified within recent-within, or "default" to not mark them
+    #[nserde(rename = "recent-color")]
+    #[nserde(default = "default")]
+    recent_color: String,
+    // Whether to dim files in the listings which are owned by another user
+    // The background of the listing when the current directory can't be written to, or "default"
+    // to leave it as is
+    #[nserde(rename = "unwritable-color")]
+    #[nserde(default = "235")]
+    unwritable_color: String,
+    #[nserde(rename = "dim-unowned")]
+    #[nserde(default = "false")]
+    dim_unowned: bool,
+    // The columns shown next to each file name in the current directory, separated by colons,
+    // like "size:time"
+    #[nserde(default = "")]
+    info: String,
+    // Whether directories in the listing show how many items they have (like "12 items"), in
+    // place of their size. They're counted in the background as they come into view.
+    #[nserde(rename = "dir-counts")]
+    #[nserde(default = "false")]
+    dir_counts: bool,
+    // Whether to show each file's position in the current directory
+    #[nserde(default = "false")]
+    number: bool,
+    // Whether to show each file's distance from the cursor instead. With both options, the
+    // current file shows its position.
+    #[nserde(rename = "relative-number")]
+    #[nserde(default = "false")]
+    relative_number: bool,
+    #[nserde(rename = "ignore-case")]
+    #[nserde(default = "true")]
+    ignore_case: bool,
+    // Whether searches with uppercase letters in them should match case (requires ignore-case)
+    #[nserde(rename = "smart-case")]
+    #[nserde(default = "true")]
+    smart_case: bool,
+    // Whether the find command should only match the start of file names
+    #[nserde(rename = "anchor-find")]
+    #[nserde(default = "true")]
+    anchor_find: bool,
+    // Whether sorting and searching treat letters with diacritics (like "é") as their base letters
+    #[nserde(rename = "ignore-diacritics")]
+    #[nserde(default = "false")]
+    ignore_diacritics: bool,
+    // Whether the files that git ignores (through .gitignore and the like) are left out of the
+    // listing, and skipped by cleanup, compare-dirs, and find-hardlinks
+    #[nserde(rename = "ignore-vcs")]
+    #[nserde(default = "false")]
+    ignore_vcs: bool,
+    // Where files starting with a dot are sorted: "first", "mixed" (as if they had no dot), or
+    // "last"
+    #[nserde(default = "first")]
+    dotfiles: String,
+    // The relative widths of the parent, current, and preview columns, like lf's ratios
+    #[nserde(default = "1:2:3")]
+    ratios: String,
+    // Named ratios which the layout command switches between
+    #[nserde(default = "Vec::new()")]
+    layouts: Vec<Layout>,
+    // Shell commands whose output previews the directories containing a marker file, like the git
+    // log of a repository
+    #[nserde(rename = "dir-previews")]
+    #[nserde(default = "Vec::new()")]
+    dir_previews: Vec<DirPreview>,
+    // Where to find the external programs rolf uses, in place of looking them up in PATH
+    #[nserde(default = "Vec::new()")]
+    tools: Vec<ToolPath>,
+    // Shell commands run in the background when the directory changes, when the cursor settles on
+    // a new file, and when rolf quits
+    #[nserde(rename = "on-cd")]
+    #[nserde(default = "")]
+    on_cd: String,
+    #[nserde(rename = "on-select")]
+    #[nserde(default = "")]
+    on_select: String,
+    #[nserde(rename = "on-quit")]
+    #[nserde(default = "")]
+    on_quit: String,
+    // Whether to start in the directory rolf was last quit in, rather than the working directory
+    #[nserde(default = "false")]
+    resume: bool,
+    // Whether the commands in a directory's .rolfrc run when it's entered. Only commands which
+    // change how the directory is shown (like set and only-ext) are allowed.
+    #[nserde(rename = "local-rolfrc")]
+    #[nserde(default = "false")]
+    local_rolfrc: bool,
+    // Whether to draw for screen readers and dumb terminals (like with --plain): images aren't
+    // shown, and the cursor and selections are marked with ">" and "*" instead of colors
+    #[nserde(default = "false")]
+    plain: bool,
+    // How pasted files are copied: "preserve" keeps their permissions, timestamps, extended
+    // attributes, and symlinks, while "simple" only copies their contents (following symlinks)
+    #[nserde(rename = "copy-mode")]
+    #[nserde(default = "preserve")]
+    copy_mode: String,
+    // How many files are copied or deleted at once. Lower it on hard drives and network shares,
+    // which slow down when they're asked for many files at once.
+    #[nserde(rename = "io-threads")]
+    #[nserde(default = "4")]
+    io_threads: usize,
     #[nserde(default = "Vec::new()")] // nanoserde requires the use of (), while serde does not
     keybindings: Vec<KeyBinding>,
 }
@@ -23,31 +235,282 @@ pub struct KeyBinding {
     command: String,
 }
 
+#[derive(PartialEq, Debug, DeJson)]
+pub struct Layout {
+    name: String,
+    ratios: String,
+}
+
+// A directory containing the marker (like ".git") is previewed with the output of the command,
+// which is run in it. An empty marker matches every directory.
+#[derive(PartialEq, Debug, Clone, DeJson)]
+pub struct DirPreview {
+    pub marker: String,
+    pub command: String,
+}
+
+// An empty path turns the tool off
+#[derive(PartialEq, Debug, DeJson)]
+pub struct ToolPath {
+    name: String,
+    path: String,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
+    // TODO(Chris): Actually use this when generating previews
+    #[allow(dead_code)]
     pub preview_converter: String,
     pub image_protocol: ImageProtocol,
+    pub preview_delay: u64,
+    pub preview_header: bool,
+    pub preview_position: PreviewPane,
+    pub preview_cache: u64,
+    pub pick_on_open: bool,
+    pub fallback_opener: String,
+    pub editor_line_flag: String,
+    pub command_prompt: String,
+    pub rename_prompt: String,
+    pub inline_rename: bool,
+    pub templates_dir: String,
+    pub prompt_color: Color,
+    pub privilege_helper: String,
+    pub difftool: String,
+    pub terminal: String,
+    pub info_date: InfoDate,
+    pub time_format: TimeFormat,
+    pub date_format: String,
+    pub clock: Clock,
+    pub size_units: SizeUnits,
+    pub exact_size: bool,
+    pub truncate: Truncation,
+    pub disk_usage: bool,
+    pub disk_usage_warning: u64,
+    pub recent_within: Duration,
+    pub recent_color: Color,
+    pub unwritable_color: Color,
+    pub dim_unowned: bool,
+    pub info_columns: Vec<InfoColumn>,
+    pub dir_counts: bool,
+    pub number: bool,
+    pub relative_number: bool,
+    pub ignore_case: bool,
+    pub smart_case: bool,
+    pub anchor_find: bool,
+    pub ignore_diacritics: bool,
+    pub ignore_vcs: bool,
+    pub dotfiles: DotfileOrder,
+    pub ratios: [u16; 3],
+    pub layouts: HashMap<String, [u16; 3]>,
+    pub dir_previews: Vec<DirPreview>,
+    pub tool_paths: HashMap<String, String>,
+    pub on_cd: String,
+    pub on_select: String,
+    pub on_quit: String,
+    pub resume: bool,
+    pub local_rolfrc: bool,
+    pub plain: bool,
+    pub copy_mode: CopyMode,
+    pub io_threads: usize,
     pub keybindings: HashMap<KeyEvent, String>,
 }
 
 #[derive(DeJson, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageProtocol {
     Kitty,
+    // Kitty's unicode placeholder mode, which draws images through ordinary cells
+    KittyUnicode,
     ITerm2,
     None,
     Auto,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PreviewPane {
+    Right,
+    // Across the bottom half of the screen, below the parent and current columns
+    Bottom,
+    Hidden,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoDate {
+    Modified,
+    Accessed,
+    Created,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DotfileOrder {
+    First,
+    // Sorted as if they didn't start with a dot
+    Mixed,
+    Last,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMode {
+    Preserve,
+    Simple,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    Ctime,
+    Iso,
+    Locale,
+    Relative,
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    TwentyFourHour,
+    TwelveHour,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnits {
+    // Powers of 1000, like "3.1M"
+    Metric,
+    // Powers of 1024, like "3.0MiB"
+    Iec,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Truncation {
+    End,
+    Middle,
+    Start,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InfoColumn {
+    Size,
+    Time,
+}
+
 #[derive(Error, Debug)]
 pub enum ConfigError {
     #[error("Failed to parse json config file at line:{} col:{}: {}", .0.line, .0.col, .0.msg)]
     InvalidJson(#[from] nanoserde::DeJsonErr),
+    #[error("Failed to parse toml config file at line:{} col:{}: {}", .0.line + 1, .0.col + 1, .0.msg)]
+    InvalidToml(#[from] nanoserde::TomlErr),
+    #[error("Unsupported value for {0} in toml config file")]
+    UnsupportedTomlValue(String),
     #[error("Failed to bind invalid key: {0}")]
     InvalidKeyBinding(String),
+    #[error("Unknown color: {0}")]
+    UnknownColor(String),
+    #[error("Unknown info date: {0} (expected modified, accessed, or created)")]
+    UnknownInfoDate(String),
+    #[error("Unknown time format: {0} (expected ctime, iso, locale, relative, or custom)")]
+    UnknownTimeFormat(String),
+    #[error("Invalid date format: {0} (expected a strftime format, like \"%Y-%m-%d %H:%M\")")]
+    InvalidDateFormat(String),
+    #[error("Unknown clock: {0} (expected 24h or 12h)")]
+    UnknownClock(String),
+    #[error("Unknown preview position: {0} (expected right, bottom, or hidden)")]
+    UnknownPreviewPosition(String),
+    #[error("Unknown size units: {0} (expected metric or iec)")]
+    UnknownSizeUnits(String),
+    #[error("Unknown truncation: {0} (expected end, middle, or start)")]
+    UnknownTruncation(String),
+    #[error("Unknown info column: {0} (expected size or time)")]
+    UnknownInfoColumn(String),
+    #[error("Unknown dotfile order: {0} (expected first, mixed, or last)")]
+    UnknownDotfileOrder(String),
+    #[error("Unknown copy mode: {0} (expected preserve or simple)")]
+    UnknownCopyMode(String),
+    #[error("Unknown image protocol: {0}")]
+    UnknownImageProtocol(String),
+    #[error("Unknown tool: {0}")]
+    UnknownTool(String),
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidOptionValue(String, String),
+    #[error("Invalid ratios: {0} (expected three numbers like 1:2:3)")]
+    InvalidRatios(String),
+    #[error("Invalid duration: {0} (expected minutes, hours, or days, like 30m, 12h, or 2d)")]
+    InvalidDuration(String),
 }
 
 type ConfigResult<T> = Result<T, ConfigError>;
 
+// NOTE(Chris): A toml config has the same schema as a json one, so we convert it to json and parse
+// it the same way, which keeps the defaults and validation in one place.
+pub fn parse_toml_config(config_data: &str) -> ConfigResult<Config> {
+    let toml = TomlParser::parse(config_data)?;
+
+    parse_config(&toml_table_to_json(&toml)?)
+}
+
+fn toml_table_to_json(table: &HashMap<String, Toml>) -> ConfigResult<String> {
+    // NOTE(Chris): Sorting the keys keeps the output (and any errors) deterministic
+    let mut keys: Vec<&String> = table.keys().collect();
+    keys.sort();
+
+    let mut fields = vec![];
+
+    for key in keys {
+        fields.push(format!(
+            "{}: {}",
+            to_json_string(key),
+            toml_value_to_json(key, &table[key])?
+        ));
+    }
+
+    Ok(format!("{{ {} }}", fields.join(", ")))
+}
+
+fn toml_value_to_json(key: &str, value: &Toml) -> ConfigResult<String> {
+    let json = match value {
+        Toml::Str(string) => to_json_string(string),
+        Toml::Bool(boolean) => boolean.to_string(),
+        Toml::Num(num) if num.is_finite() => num.to_string(),
+        Toml::SimpleArray(values) => {
+            let values: ConfigResult<Vec<String>> = values
+                .iter()
+                .map(|value| toml_value_to_json(key, value))
+                .collect();
+
+            format!("[{}]", values?.join(", "))
+        }
+        Toml::Array(tables) => {
+            let tables: ConfigResult<Vec<String>> = tables.iter().map(toml_table_to_json).collect();
+
+            format!("[{}]", tables?.join(", "))
+        }
+        Toml::Num(_) | Toml::Date(_) => {
+            return Err(ConfigError::UnsupportedTomlValue(key.to_string()))
+        }
+    };
+
+    Ok(json)
+}
+
+fn to_json_string(string: &str) -> String {
+    let mut result = String::with_capacity(string.len() + 2);
+
+    result.push('"');
+
+    for ch in string.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            ch if ch.is_control() => result.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => result.push(ch),
+        }
+    }
+
+    result.push('"');
+
+    result
+}
+
 pub fn parse_config(config_data: &str) -> ConfigResult<Config> {
     let mut contents = String::new();
 
@@ -90,6 +553,70 @@ pub fn parse_config(config_data: &str) -> ConfigResult<Config> {
     Ok(Config {
         preview_converter: json_config.preview_converter,
         image_protocol: json_config.image_protocol,
+        preview_delay: json_config.preview_delay,
+        preview_header: json_config.preview_header,
+        preview_position: to_preview_position(&json_config.preview_position)?,
+        preview_cache: json_config.preview_cache,
+        pick_on_open: json_config.pick_on_open,
+        fallback_opener: json_config.fallback_opener,
+        editor_line_flag: json_config.editor_line_flag,
+        command_prompt: json_config.command_prompt,
+        rename_prompt: json_config.rename_prompt,
+        inline_rename: json_config.inline_rename,
+        templates_dir: json_config.templates_dir,
+        prompt_color: to_color(&json_config.prompt_color)?,
+        privilege_helper: json_config.privilege_helper,
+        difftool: json_config.difftool,
+        terminal: json_config.terminal,
+        info_date: to_info_date(&json_config.info_date)?,
+        time_format: to_time_format(&json_config.time_format)?,
+        date_format: to_date_format(&json_config.date_format)?,
+        clock: to_clock(&json_config.clock)?,
+        size_units: to_size_units(&json_config.size_units)?,
+        exact_size: json_config.exact_size,
+        truncate: to_truncation(&json_config.truncate)?,
+        disk_usage: json_config.disk_usage,
+        disk_usage_warning: to_disk_usage_warning(json_config.disk_usage_warning)?,
+        recent_within: to_recent_within(&json_config.recent_within)?,
+        recent_color: to_color(&json_config.recent_color)?,
+        unwritable_color: to_color(&json_config.unwritable_color)?,
+        dim_unowned: json_config.dim_unowned,
+        info_columns: to_info_columns(&json_config.info)?,
+        dir_counts: json_config.dir_counts,
+        number: json_config.number,
+        relative_number: json_config.relative_number,
+        ignore_case: json_config.ignore_case,
+        smart_case: json_config.smart_case,
+        anchor_find: json_config.anchor_find,
+        ignore_diacritics: json_config.ignore_diacritics,
+        ignore_vcs: json_config.ignore_vcs,
+        dotfiles: to_dotfile_order(&json_config.dotfiles)?,
+        ratios: to_ratios(&json_config.ratios)?,
+        layouts: json_config
+            .layouts
+            .iter()
+            .map(|layout| Ok((layout.name.clone(), to_ratios(&layout.ratios)?)))
+            .collect::<ConfigResult<_>>()?,
+        dir_previews: json_config.dir_previews,
+        tool_paths: json_config
+            .tools
+            .into_iter()
+            .map(|tool_path| {
+                if tools::is_known_tool(&tool_path.name) {
+                    Ok((tool_path.name, tool_path.path))
+                } else {
+                    Err(ConfigError::UnknownTool(tool_path.name))
+                }
+            })
+            .collect::<ConfigResult<_>>()?,
+        on_cd: json_config.on_cd,
+        on_select: json_config.on_select,
+        on_quit: json_config.on_quit,
+        resume: json_config.resume,
+        local_rolfrc: json_config.local_rolfrc,
+        plain: json_config.plain,
+        copy_mode: to_copy_mode(&json_config.copy_mode)?,
+        io_threads: to_io_threads(json_config.io_threads)?,
         keybindings,
     })
 }
@@ -99,6 +626,56 @@ impl Default for Config {
         Config {
             preview_converter: String::new(),
             image_protocol: ImageProtocol::Auto,
+            preview_delay: 0,
+            preview_header: false,
+            preview_position: PreviewPane::Right,
+            preview_cache: 64,
+            pick_on_open: true,
+            fallback_opener: String::new(),
+            editor_line_flag: String::new(),
+            command_prompt: ":".to_string(),
+            rename_prompt: "Rename: ".to_string(),
+            inline_rename: false,
+            templates_dir: String::new(),
+            prompt_color: Color::Foreground,
+            privilege_helper: "sudo".to_string(),
+            difftool: String::new(),
+            terminal: String::new(),
+            info_date: InfoDate::Modified,
+            time_format: TimeFormat::Ctime,
+            date_format: "%Y-%m-%d %H:%M".to_string(),
+            clock: Clock::TwentyFourHour,
+            size_units: SizeUnits::Metric,
+            exact_size: false,
+            truncate: Truncation::End,
+            disk_usage: false,
+            disk_usage_warning: 90,
+            recent_within: Duration::from_secs(24 * 60 * 60),
+            recent_color: Color::Foreground,
+            unwritable_color: Color::Ansi(235),
+            dim_unowned: false,
+            info_columns: vec![],
+            dir_counts: false,
+            number: false,
+            relative_number: false,
+            ignore_case: true,
+            smart_case: true,
+            anchor_find: true,
+            ignore_diacritics: false,
+            ignore_vcs: false,
+            dotfiles: DotfileOrder::First,
+            ratios: [1, 2, 3],
+            layouts: HashMap::new(),
+            dir_previews: vec![],
+            tool_paths: HashMap::new(),
+            on_cd: String::new(),
+            on_select: String::new(),
+            on_quit: String::new(),
+            resume: false,
+            local_rolfrc: false,
+            plain: false,
+            copy_mode: CopyMode::Preserve,
+            io_threads: 4,
             keybindings: make_binding_hash_map(&default_key_bindings())
                 .expect("default keybindings are not valid"),
         }
@@ -130,10 +707,16 @@ fn default_normal_key_bindings() -> Vec<KeyBinding> {
     add_raw_binding(&mut key_bindings, "?", "search-back");
     add_raw_binding(&mut key_bindings, "n", "search-next");
     add_raw_binding(&mut key_bindings, "N", "search-prev");
+    add_raw_binding(&mut key_bindings, "f", "find");
+    add_raw_binding(&mut key_bindings, "F", "find-back");
     add_raw_binding(&mut key_bindings, "space", "toggle-down");
     add_raw_binding(&mut key_bindings, "enter", "open");
     add_raw_binding(&mut key_bindings, "o", "open");
     add_raw_binding(&mut key_bindings, "H", "help");
+    add_raw_binding(&mut key_bindings, "y", "copy");
+    add_raw_binding(&mut key_bindings, "d", "cut");
+    add_raw_binding(&mut key_bindings, "p", "paste");
+    add_raw_binding(&mut key_bindings, "c", "clear");
 
     key_bindings
 }
@@ -170,6 +753,212 @@ fn make_binding_hash_map(raw_bindings: &[KeyBinding]) -> ConfigResult<HashMap<Ke
     Ok(result)
 }
 
+const SAMPLE_CONFIG_OPTIONS: &str = r#"// This is rolf's configuration file. Every option is shown with its default value, and lines
+// starting with two slashes are comments.
+{
+  // A program used to generate previews (not used yet)
+  "preview-converter": "",
+
+  // How to draw image previews: "Auto", "Kitty", "KittyUnicode", "ITerm2", or "None"
+  "image-protocol": "Auto",
+
+  // The number of milliseconds the cursor must stay on a file before its preview is generated
+  "preview-delay": 0,
+
+  // Whether to show the previewed file's name, size, and line count above the preview
+  "preview-header": false,
+
+  // Where the preview is shown: "right" (as a third column), "bottom" (below the other two
+  // columns, which suits wide monitors), or "hidden"
+  "preview-position": "right",
+
+  // How many megabytes of image and highlighted text previews are kept, so that going back to a
+  // file shows its preview right away. 0 turns this off.
+  "preview-cache": 64,
+
+  // Whether opening a file picks it when rolf is run with --choosefiles
+  "pick-on-open": true,
+
+  // A command (like "mimeopen -n") used to open files that the system opener can't handle. When
+  // empty, a default is chosen for your platform.
+  "fallback-opener": "",
+
+  // How to make your editor jump to a line, like "+{line} {file}". When empty, it's detected from
+  // the editor's name.
+  "editor-line-flag": "",
+
+  "command-prompt": ":",
+  "rename-prompt": "Rename: ",
+
+  // Whether rename edits the file name in its row of the listing (starting with the cursor before
+  // the extension), rather than in the prompt at the bottom
+  "inline-rename": false,
+
+  // The directory that new-from-template copies files (like LICENSE or .editorconfig) from. When
+  // empty, it's the "templates" directory next to this file.
+  "templates-dir": "",
+
+  // The color used to draw prompts, like "blue", "bright-green", or an ANSI color number
+  "prompt-color": "default",
+
+  // The program used to retry operations that failed with "permission denied"
+  "privilege-helper": "sudo",
+
+  // The program that the diff command compares two files with, like "vimdiff". When empty, the
+  // differences are shown in rolf.
+  "difftool": "",
+
+  // The command that terminal-here runs in the current directory to open a terminal, like
+  // "alacritty" or "kitty --detach". When empty, it opens a new tmux window if rolf is running in
+  // tmux.
+  "terminal": "",
+
+  // Which date to show on the bottom line: "modified", "accessed", or "created"
+  "info-date": "modified",
+
+  // How that date is written: "ctime", "iso", "locale", "relative" (like "3 h ago"), or "custom"
+  "time-format": "ctime",
+
+  // The strftime format of the custom time format
+  "date-format": "%Y-%m-%d %H:%M",
+
+  // Whether the ctime and iso time formats use a "24h" or "12h" clock
+  "clock": "24h",
+
+  // How sizes are written: "metric" (where 1K is 1000 bytes) or "iec" (where 1KiB is 1024 bytes)
+  "size-units": "metric",
+
+  // Whether the bottom line shows the current file's exact size in bytes
+  "exact-size": false,
+
+  // Where long file names are cut: "end", "middle" (keeping the extension visible), or "start"
+  "truncate": "end",
+
+  // Whether the top line shows how full the current file system is, and the percentage used at
+  // which it turns red
+  "disk-usage": false,
+  "disk-usage-warning": 90,
+
+  // How recently files must have been modified for recent-files to show them, as minutes, hours,
+  // or days like "30m", "12h", or "2d"
+  "recent-within": "1d",
+
+  // The color of files modified within recent-within, or "default" to leave them unmarked
+  "recent-color": "default",
+
+  // The background of the listing when you can't create or delete files in the current
+  // directory (which the top line also marks with a lock), or "default" to leave it unchanged
+  "unwritable-color": "235",
+
+  // Whether to dim files which are owned by another user
+  "dim-unowned": false,
+
+  // The columns shown next to each file name, separated by colons, like "size:time"
+  "info": "",
+
+  // Whether directories show how many items they have, like "12 items"
+  "dir-counts": false,
+
+  // Whether to show line numbers, and whether to show them relative to the cursor
+  "number": false,
+  "relative-number": false,
+
+  // How searches match file names. With smart-case, searches containing uppercase letters match
+  // case. With anchor-find, the find command only matches the start of file names.
+  "ignore-case": true,
+  "smart-case": true,
+  "anchor-find": true,
+
+  // Whether sorting and searching treat letters like "é" as their base letters
+  "ignore-diacritics": false,
+
+  // Whether to hide the files that git ignores, like build output listed in .gitignore
+  "ignore-vcs": false,
+
+  // Where files starting with a dot are sorted: "first", "mixed" (as if they had no dot), or
+  // "last"
+  "dotfiles": "first",
+
+  // The relative widths of the parent, current, and preview columns
+  "ratios": "1:2:3",
+
+  // Named ratios which the layout command switches between, like
+  // { "name": "wide-preview", "ratios": "1:1:4" }
+  "layouts": [],
+
+  // Commands whose output previews the directories that contain a marker file, instead of their
+  // entries, like { "marker": ".git", "command": "git log --oneline --color=always -20" }. The
+  // command runs in the directory, and the first matching preview is used.
+  "dir-previews": [],
+
+  // Where to find the programs rolf uses (which rolf --print-config lists), instead of looking
+  // them up in PATH, like { "name": "bat", "path": "batcat" }. An empty path turns a tool off.
+  "tools": [],
+
+  // Shell commands run in the background when the directory changes, when the cursor settles on
+  // a new file, and when rolf quits. The current file is available as $f, and the selected files
+  // as $fs.
+  "on-cd": "",
+  "on-select": "",
+  "on-quit": "",
+
+  // Whether to start in the directory rolf was last quit in (like with --resume), unless a
+  // directory is given on the command line
+  "resume": false,
+
+  // Whether to run the commands in a directory's .rolfrc file when entering it, like
+  // "set dotfiles last" or "only-ext jpg png". Only commands which change how the directory is
+  // shown are allowed, and they're undone when leaving it.
+  "local-rolfrc": false,
+
+  // Whether to draw for screen readers and dumb terminals (like with --plain), marking the cursor
+  // with ">" and selections with "*" instead of colors, and not showing images
+  "plain": false,
+
+  // How pasted files are copied: "preserve" keeps their permissions, timestamps, extended
+  // attributes, and symlinks, while "simple" only copies their contents
+  "copy-mode": "preserve",
+
+  // How many files are copied or deleted at once. Lower it on hard drives and network shares.
+  "io-threads": 4,
+
+  // Keybindings are added to (or replace) the defaults below, which are commented out
+  "keybindings": [
+"#;
+
+/// Returns a commented config file showing every option's default value, which is written for
+/// new users.
+pub fn sample_config() -> String {
+    let mut result = SAMPLE_CONFIG_OPTIONS.to_string();
+
+    for key_binding in default_key_bindings() {
+        result.push_str(&format!(
+            "    // {{ \"key\": \"{}\", \"command\": \"{}\" }},\n",
+            key_binding.key, key_binding.command
+        ));
+    }
+
+    result.push_str("  ]\n}\n");
+
+    result
+}
+
+/// A commented rolfrc, which is written for new users alongside the sample config.
+pub const SAMPLE_ROLFRC: &str = "\
+# This is rolf's rolfrc, which is read at startup. Each line runs a command, and lines starting
+# with # are comments.
+
+# Show file sizes next to their names
+# set info size
+
+# Map keys to commands, like in lf
+# map D delete
+
+# Open files matching a pattern with a specific program. The file's path is in $f. The program
+# gets the terminal until it exits, so it can be a terminal program like less.
+# open *.pdf zathura $f
+";
+
 fn add_raw_binding(key_bindings: &mut Vec<KeyBinding>, key: &str, command: &str) {
     key_bindings.push(KeyBinding {
         key: key.to_string(),
@@ -252,23 +1041,492 @@ pub fn to_string(key_event: KeyEvent) -> String {
     result
 }
 
+pub fn to_color(name: &str) -> ConfigResult<Color> {
+    let color = match name {
+        "default" => Color::Foreground,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "bright-black" => Color::BrightBlack,
+        "bright-red" => Color::BrightRed,
+        "bright-green" => Color::BrightGreen,
+        "bright-yellow" => Color::BrightYellow,
+        "bright-blue" => Color::BrightBlue,
+        "bright-magenta" => Color::BrightMagenta,
+        "bright-cyan" => Color::BrightCyan,
+        "bright-white" => Color::BrightWhite,
+        _ => match name.parse::<u8>() {
+            Ok(index) => Color::Ansi(index),
+            Err(_) => return Err(ConfigError::UnknownColor(name.to_string())),
+        },
+    };
+
+    Ok(color)
+}
+
+fn to_info_date(name: &str) -> ConfigResult<InfoDate> {
+    match name {
+        "modified" => Ok(InfoDate::Modified),
+        "accessed" => Ok(InfoDate::Accessed),
+        "created" => Ok(InfoDate::Created),
+        _ => Err(ConfigError::UnknownInfoDate(name.to_string())),
+    }
+}
+
+pub fn to_dotfile_order(name: &str) -> ConfigResult<DotfileOrder> {
+    match name {
+        "first" => Ok(DotfileOrder::First),
+        "mixed" => Ok(DotfileOrder::Mixed),
+        "last" => Ok(DotfileOrder::Last),
+        _ => Err(ConfigError::UnknownDotfileOrder(name.to_string())),
+    }
+}
+
+fn to_copy_mode(name: &str) -> ConfigResult<CopyMode> {
+    match name {
+        "preserve" => Ok(CopyMode::Preserve),
+        "simple" => Ok(CopyMode::Simple),
+        _ => Err(ConfigError::UnknownCopyMode(name.to_string())),
+    }
+}
+
+fn to_io_threads(io_threads: usize) -> ConfigResult<usize> {
+    if io_threads == 0 {
+        Err(ConfigError::InvalidOptionValue(
+            "io-threads".to_string(),
+            io_threads.to_string(),
+        ))
+    } else {
+        Ok(io_threads)
+    }
+}
+
+fn to_time_format(name: &str) -> ConfigResult<TimeFormat> {
+    match name {
+        "ctime" => Ok(TimeFormat::Ctime),
+        "iso" => Ok(TimeFormat::Iso),
+        "locale" => Ok(TimeFormat::Locale),
+        "relative" => Ok(TimeFormat::Relative),
+        "custom" => Ok(TimeFormat::Custom),
+        _ => Err(ConfigError::UnknownTimeFormat(name.to_string())),
+    }
+}
+
+// NOTE(Chris): chrono panics while writing a date with an invalid format, so it's checked here
+fn to_date_format(format: &str) -> ConfigResult<String> {
+    let is_valid = chrono::format::StrftimeItems::new(format)
+        .all(|item| !matches!(item, chrono::format::Item::Error));
+
+    if is_valid {
+        Ok(format.to_string())
+    } else {
+        Err(ConfigError::InvalidDateFormat(format.to_string()))
+    }
+}
+
+fn to_clock(name: &str) -> ConfigResult<Clock> {
+    match name {
+        "24h" => Ok(Clock::TwentyFourHour),
+        "12h" => Ok(Clock::TwelveHour),
+        _ => Err(ConfigError::UnknownClock(name.to_string())),
+    }
+}
+
+// The inverse of to_clock
+fn clock_name(clock: Clock) -> String {
+    match clock {
+        Clock::TwentyFourHour => "24h".to_string(),
+        Clock::TwelveHour => "12h".to_string(),
+    }
+}
+
+fn to_truncation(name: &str) -> ConfigResult<Truncation> {
+    match name {
+        "end" => Ok(Truncation::End),
+        "middle" => Ok(Truncation::Middle),
+        "start" => Ok(Truncation::Start),
+        _ => Err(ConfigError::UnknownTruncation(name.to_string())),
+    }
+}
+
+fn to_disk_usage_warning(percent: u64) -> ConfigResult<u64> {
+    if percent > 100 {
+        Err(ConfigError::InvalidOptionValue(
+            "disk-usage-warning".to_string(),
+            percent.to_string(),
+        ))
+    } else {
+        Ok(percent)
+    }
+}
+
+pub fn to_recent_within(value: &str) -> ConfigResult<Duration> {
+    let invalid_duration = || ConfigError::InvalidDuration(value.to_string());
+
+    let unit_len = value.chars().last().map_or(0, char::len_utf8);
+    let (count, unit) = value.split_at(value.len() - unit_len);
+
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid_duration()),
+    };
+
+    match count.parse::<u64>() {
+        Ok(count) if count > 0 => count
+            .checked_mul(unit_secs)
+            .map(Duration::from_secs)
+            .ok_or_else(invalid_duration),
+        _ => Err(invalid_duration()),
+    }
+}
+
+fn to_preview_position(name: &str) -> ConfigResult<PreviewPane> {
+    match name {
+        "right" => Ok(PreviewPane::Right),
+        "bottom" => Ok(PreviewPane::Bottom),
+        "hidden" => Ok(PreviewPane::Hidden),
+        _ => Err(ConfigError::UnknownPreviewPosition(name.to_string())),
+    }
+}
+
+fn to_size_units(name: &str) -> ConfigResult<SizeUnits> {
+    match name {
+        "metric" => Ok(SizeUnits::Metric),
+        "iec" => Ok(SizeUnits::Iec),
+        _ => Err(ConfigError::UnknownSizeUnits(name.to_string())),
+    }
+}
+
+// Parses a list of info columns like lf's, e.g. "size:time"
+pub fn to_info_columns(value: &str) -> ConfigResult<Vec<InfoColumn>> {
+    value
+        .split(':')
+        .filter(|name| !name.is_empty())
+        .map(|name| match name {
+            "size" => Ok(InfoColumn::Size),
+            "time" => Ok(InfoColumn::Time),
+            _ => Err(ConfigError::UnknownInfoColumn(name.to_string())),
+        })
+        .collect()
+}
+
+// Parses the relative widths of the three columns, like lf's "1:2:3". A width of 0 hides a column.
+pub fn to_ratios(value: &str) -> ConfigResult<[u16; 3]> {
+    let invalid_ratios = || ConfigError::InvalidRatios(value.to_string());
+
+    let ratios: Vec<u16> = value
+        .split(':')
+        .map(|ratio| ratio.parse().map_err(|_| invalid_ratios()))
+        .collect::<ConfigResult<_>>()?;
+
+    match ratios[..] {
+        [0, 0, 0] => Err(invalid_ratios()),
+        [first, second, third] => Ok([first, second, third]),
+        _ => Err(invalid_ratios()),
+    }
+}
+
+/// Sets a boolean option by its lf name, where "set number" enables an option and "set nonumber"
+/// disables it. Returns false if there's no such option.
+pub fn set_bool_option(config: &mut Config, name: &str) -> bool {
+    let (name, value) = match name.strip_prefix("no") {
+        Some(name) => (name, false),
+        None => (name, true),
+    };
+
+    let option = match name {
+        "number" => &mut config.number,
+        "relativenumber" => &mut config.relative_number,
+        "ignorecase" => &mut config.ignore_case,
+        "smartcase" => &mut config.smart_case,
+        "anchorfind" => &mut config.anchor_find,
+        "ignoredia" => &mut config.ignore_diacritics,
+        "ignorevcs" => &mut config.ignore_vcs,
+        "diskusage" => &mut config.disk_usage,
+        "dircounts" => &mut config.dir_counts,
+        _ => return false,
+    };
+
+    *option = value;
+
+    true
+}
+
+fn to_image_protocol(name: &str) -> ConfigResult<ImageProtocol> {
+    match name.to_ascii_lowercase().as_str() {
+        "kitty" => Ok(ImageProtocol::Kitty),
+        "kittyunicode" | "kitty-unicode" => Ok(ImageProtocol::KittyUnicode),
+        "iterm2" => Ok(ImageProtocol::ITerm2),
+        "none" => Ok(ImageProtocol::None),
+        "auto" => Ok(ImageProtocol::Auto),
+        _ => Err(ConfigError::UnknownImageProtocol(name.to_string())),
+    }
+}
+
+/// Sets an option by the name it has in the config file, like "preview-delay", parsing the value
+/// from a string. Key bindings can't be set this way.
+pub fn set_option(config: &mut Config, name: &str, value: &str) -> ConfigResult<()> {
+    let invalid_value = || ConfigError::InvalidOptionValue(name.to_string(), value.to_string());
+    let to_bool = |value: &str| value.parse::<bool>().map_err(|_| invalid_value());
+
+    match name {
+        "preview-converter" => config.preview_converter = value.to_string(),
+        "image-protocol" => config.image_protocol = to_image_protocol(value)?,
+        "preview-delay" => config.preview_delay = value.parse().map_err(|_| invalid_value())?,
+        "preview-header" => config.preview_header = to_bool(value)?,
+        "preview-position" => config.preview_position = to_preview_position(value)?,
+        "preview-cache" => config.preview_cache = value.parse().map_err(|_| invalid_value())?,
+        "pick-on-open" => config.pick_on_open = to_bool(value)?,
+        "fallback-opener" => config.fallback_opener = value.to_string(),
+        "editor-line-flag" => config.editor_line_flag = value.to_string(),
+        "command-prompt" => config.command_prompt = value.to_string(),
+        "rename-prompt" => config.rename_prompt = value.to_string(),
+        "inline-rename" => config.inline_rename = to_bool(value)?,
+        "templates-dir" => config.templates_dir = value.to_string(),
+        "prompt-color" => config.prompt_color = to_color(value)?,
+        "privilege-helper" => config.privilege_helper = value.to_string(),
+        "difftool" => config.difftool = value.to_string(),
+        "terminal" => config.terminal = value.to_string(),
+        "info-date" => config.info_date = to_info_date(value)?,
+        "time-format" => config.time_format = to_time_format(value)?,
+        "date-format" => config.date_format = to_date_format(value)?,
+        "clock" => config.clock = to_clock(value)?,
+        "size-units" => config.size_units = to_size_units(value)?,
+        "exact-size" => config.exact_size = to_bool(value)?,
+        "truncate" => config.truncate = to_truncation(value)?,
+        "disk-usage" => config.disk_usage = to_bool(value)?,
+        "disk-usage-warning" => {
+            config.disk_usage_warning =
+                to_disk_usage_warning(value.parse().map_err(|_| invalid_value())?)?
+        }
+        "recent-within" => config.recent_within = to_recent_within(value)?,
+        "recent-color" => config.recent_color = to_color(value)?,
+        "unwritable-color" => config.unwritable_color = to_color(value)?,
+        "dim-unowned" => config.dim_unowned = to_bool(value)?,
+        "info" => config.info_columns = to_info_columns(value)?,
+        "dir-counts" => config.dir_counts = to_bool(value)?,
+        "number" => config.number = to_bool(value)?,
+        "relative-number" => config.relative_number = to_bool(value)?,
+        "ignore-case" => config.ignore_case = to_bool(value)?,
+        "smart-case" => config.smart_case = to_bool(value)?,
+        "anchor-find" => config.anchor_find = to_bool(value)?,
+        "ignore-diacritics" => config.ignore_diacritics = to_bool(value)?,
+        "ignore-vcs" => config.ignore_vcs = to_bool(value)?,
+        "dotfiles" => config.dotfiles = to_dotfile_order(value)?,
+        "ratios" => config.ratios = to_ratios(value)?,
+        "on-cd" => config.on_cd = value.to_string(),
+        "on-select" => config.on_select = value.to_string(),
+        "on-quit" => config.on_quit = value.to_string(),
+        "resume" => config.resume = to_bool(value)?,
+        "local-rolfrc" => config.local_rolfrc = to_bool(value)?,
+        "plain" => config.plain = to_bool(value)?,
+        "copy-mode" => config.copy_mode = to_copy_mode(value)?,
+        "io-threads" => {
+            config.io_threads = to_io_threads(value.parse().map_err(|_| invalid_value())?)?
+        }
+        _ => return Err(ConfigError::UnknownOption(name.to_string())),
+    }
+
+    Ok(())
+}
+
+/// Returns every option that set_option can set, along with its value written the way
+/// set_option parses it.
+pub fn option_values(config: &Config) -> Vec<(&'static str, String)> {
+    let info_columns: Vec<String> = config.info_columns.iter().map(to_name).collect();
+    let ratios: Vec<String> = config.ratios.iter().map(u16::to_string).collect();
+
+    vec![
+        ("preview-converter", config.preview_converter.clone()),
+        ("image-protocol", to_name(&config.image_protocol)),
+        ("preview-delay", config.preview_delay.to_string()),
+        ("preview-header", config.preview_header.to_string()),
+        ("preview-position", to_name(&config.preview_position)),
+        ("preview-cache", config.preview_cache.to_string()),
+        ("pick-on-open", config.pick_on_open.to_string()),
+        ("fallback-opener", config.fallback_opener.clone()),
+        ("editor-line-flag", config.editor_line_flag.clone()),
+        ("command-prompt", config.command_prompt.clone()),
+        ("rename-prompt", config.rename_prompt.clone()),
+        ("inline-rename", config.inline_rename.to_string()),
+        ("templates-dir", config.templates_dir.clone()),
+        ("prompt-color", color_name(config.prompt_color)),
+        ("privilege-helper", config.privilege_helper.clone()),
+        ("difftool", config.difftool.clone()),
+        ("terminal", config.terminal.clone()),
+        ("info-date", to_name(&config.info_date)),
+        ("time-format", to_name(&config.time_format)),
+        ("date-format", config.date_format.clone()),
+        ("clock", clock_name(config.clock)),
+        ("size-units", to_name(&config.size_units)),
+        ("exact-size", config.exact_size.to_string()),
+        ("truncate", to_name(&config.truncate)),
+        ("disk-usage", config.disk_usage.to_string()),
+        ("disk-usage-warning", config.disk_usage_warning.to_string()),
+        ("recent-within", duration_name(config.recent_within)),
+        ("recent-color", color_name(config.recent_color)),
+        ("unwritable-color", color_name(config.unwritable_color)),
+        ("dim-unowned", config.dim_unowned.to_string()),
+        ("info", info_columns.join(":")),
+        ("dir-counts", config.dir_counts.to_string()),
+        ("number", config.number.to_string()),
+        ("relative-number", config.relative_number.to_string()),
+        ("ignore-case", config.ignore_case.to_string()),
+        ("smart-case", config.smart_case.to_string()),
+        ("anchor-find", config.anchor_find.to_string()),
+        ("ignore-diacritics", config.ignore_diacritics.to_string()),
+        ("ignore-vcs", config.ignore_vcs.to_string()),
+        ("dotfiles", to_name(&config.dotfiles)),
+        ("ratios", ratios.join(":")),
+        ("on-cd", config.on_cd.clone()),
+        ("on-select", config.on_select.clone()),
+        ("on-quit", config.on_quit.clone()),
+        ("resume", config.resume.to_string()),
+        ("local-rolfrc", config.local_rolfrc.to_string()),
+        ("plain", config.plain.to_string()),
+        ("copy-mode", to_name(&config.copy_mode)),
+        ("io-threads", config.io_threads.to_string()),
+    ]
+}
+
+// NOTE(Chris): The names of most option values are their variants in lowercase, like "iec" for
+// SizeUnits::Iec
+fn to_name(value: &impl std::fmt::Debug) -> String {
+    format!("{:?}", value).to_lowercase()
+}
+
+// The inverse of to_color
+fn color_name(color: Color) -> String {
+    let name = match color {
+        Color::Foreground | Color::Background => "default",
+        Color::BrightBlack => "bright-black",
+        Color::BrightRed => "bright-red",
+        Color::BrightGreen => "bright-green",
+        Color::BrightYellow => "bright-yellow",
+        Color::BrightBlue => "bright-blue",
+        Color::BrightMagenta => "bright-magenta",
+        Color::BrightCyan => "bright-cyan",
+        Color::BrightWhite => "bright-white",
+        // NOTE(Chris): The rest are displayed just as to_color parses them
+        _ => return color.to_string(),
+    };
+
+    name.to_string()
+}
+
+// The inverse of to_recent_within, using the largest unit that fits evenly
+fn duration_name(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+
+    if minutes.is_multiple_of(24 * 60) {
+        format!("{}d", minutes / (24 * 60))
+    } else if minutes.is_multiple_of(60) {
+        format!("{}h", minutes / 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Applies overrides from environment variables on top of the config file. ROLF_OPT_<NAME> sets
+/// the option named <NAME> (e.g. ROLF_OPT_PREVIEW_DELAY sets "preview-delay"), and
+/// ROLF_IMAGE_PROTOCOL is a shorthand for ROLF_OPT_IMAGE_PROTOCOL. On failure, returns the name
+/// of the offending variable along with the error.
+pub fn apply_env_overrides(
+    config: &mut Config,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Result<(), (String, ConfigError)> {
+    let mut image_protocol = None;
+
+    for (var_name, value) in vars {
+        if var_name == "ROLF_IMAGE_PROTOCOL" {
+            image_protocol = Some(value);
+        } else if let Some(option_name) = var_name.strip_prefix("ROLF_OPT_") {
+            let option_name = option_name.to_ascii_lowercase().replace('_', "-");
+
+            set_option(config, &option_name, &value).map_err(|err| (var_name, err))?;
+        }
+    }
+
+    // NOTE(Chris): The shorthand is applied last so that it wins regardless of the order of the
+    // environment
+    if let Some(value) = image_protocol {
+        config.image_protocol =
+            to_image_protocol(&value).map_err(|err| ("ROLF_IMAGE_PROTOCOL".to_string(), err))?;
+    }
+
+    Ok(())
+}
+
 pub fn get_command_desc(command: &str) -> &'static str {
     match command {
+        "attrs" => "Change the permissions of the selected files (or the current file) together",
         "bottom" => "Move to the last file in the directory",
-        "down" => "Move the cursor down by one file",
-        "edit" => "Edit the current file in a text editor",
+        "cd" => "Go to a directory, or one on a server (sftp://user@host) or in S3 (s3://bucket)",
+        "clear" => "Forget the files to be copied or moved by paste",
+        "cleanup" => "Select the broken symlinks and empty directories in this tree, for delete",
+        "clear-filter" => "Show every file again after only-dirs, only-files, or only-ext",
+        "down" => "Move the cursor down by one file, or by a count like 7j",
+        "compare-dirs" => "List the differences between two selected directories, to copy across",
+        "copy" => "Copy the selected files (or the current file) with the next paste",
+        "cut" => "Move the selected files (or the current file) with the next paste",
+        "devices" => "Mount any connected phones or cameras (on Linux, with gio) and open one",
+        "diff" => "Compare two selected files (or a selected file and the current one)",
+        "dump-screen" => "Write the screen's text and styles to a file, for reporting drawing bugs",
+        "edit" => "Edit the current file in a text editor, optionally at a line",
+        "extract" => "Copy the current file out of an archive (or a server or bucket) to the disk",
+        "quicklook" => "Preview the current file with the system's previewer, like Quick Look",
+        "xattrs" => "List the current file's extended attributes and their values",
+        "stat" => "Show everything about the current file, including its device and inode",
+        "find-hardlinks" => "Find the current file's other hard links in a directory",
+        "acl" => "Show the current file's ACL and SELinux context, like getfacl",
+        "unquarantine" => "Remove macOS's quarantine flag from the selected files",
+        "reveal" => "Show the current file in the system's file browser, like Finder",
+        "tmux-send" => "Put the selected paths in a tmux buffer, or the last pane with \"pane\"",
+        "terminal-here" => "Open a terminal (or a tmux window) in the current directory",
+        "new-from-template" => "Copy a file from the templates directory into this directory",
+        "nohl" => "Stop highlighting the matches from the last search",
+        "only-dirs" => "Only show directories, until clear-filter",
+        "only-ext" => "Only show files with the given extensions, like \"only-ext jpg png\"",
+        "only-files" => "Only show files, until clear-filter",
         "open" => "Enter a directory or open a file",
+        "paste" => "Copy or move the files from copy or cut into the current directory",
+        "pick" => "Pick the selected files when run with --choosefiles",
+        "find" => "Jump to the next file starting with a typed character",
+        "find-back" => "Jump to the previous file starting with a typed character",
         "help" => "Open this help menu",
+        "layout" => "Switch to the column ratios named in the config, or back to the default",
+        "gallery" => "Show the images in this directory as a grid of thumbnails",
+        "log" => "Show the errors which didn't stop rolf, like previews that failed to load",
         "quit" => "Exit the help menu or the program entirely",
         "read" => "Read in a command via an input line",
+        "recent-files" => {
+            "Only show files modified recently (or within a time like 2h), newest first"
+        }
         "rename" => "Rename the current file",
+        "rename-lower" => "Make the names of the selected files lowercase, after a preview",
+        "rename-regex" => "Replace a regex in the names of the selected files, after a preview",
+        "rename-sanitize" => {
+            "Remove spaces, accents, and unsafe characters from the selected files' names"
+        }
+        "rename-upper" => "Make the names of the selected files uppercase, after a preview",
+        "run" => "Run the current file as a program, with the arguments given or prompted for",
         "search" => "Search for a file based on its name",
+        "set" => "Change an option, like \"set info size:time\" or \"set dotfiles last\"",
         "search-back" => "Search for a file, starting with files above the current one",
         "search-next" => "Jump to the next matching file after a search",
+        "search-results" => "List every file matching the search, including any the filter hides",
         "search-prev" => "Jump to the previous matching after a search",
         "top" => "Move to the first file in the directory",
-        "up" => "Move the cursor up by one file",
+        "up" => "Move the cursor up by one file, or by a count like 7k",
         "updir" => "Change to the previous directory",
+        "zoom" => "Toggle showing only the preview, or only the listing with \"zoom listing\"",
         _ => "",
     }
 }
@@ -409,6 +1667,506 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_config_preview_delay() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "preview-delay": 150 }"#)?;
+        assert_eq!(config.preview_delay, 150);
+
+        let config = parse_config("{}")?;
+        assert_eq!(config.preview_delay, 0);
+        assert!(!config.preview_header);
+
+        assert!(parse_config(r#"{ "preview-header": true }"#)?.preview_header);
+
+        assert_eq!(config.preview_cache, 64);
+        assert_eq!(parse_config(r#"{ "preview-cache": 0 }"#)?.preview_cache, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_prompt() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "command-prompt": "> ", "prompt-color": "bright-blue" }"#)?;
+        assert_eq!(config.command_prompt, "> ");
+        assert_eq!(config.prompt_color, Color::BrightBlue);
+
+        let config = parse_config(r#"{ "prompt-color": "208" }"#)?;
+        assert_eq!(config.prompt_color, Color::Ansi(208));
+        assert!(!config.inline_rename);
+
+        assert!(parse_config(r#"{ "inline-rename": true }"#)?.inline_rename);
+
+        assert!(matches!(
+            parse_config(r#"{ "prompt-color": "blurple" }"#),
+            Err(ConfigError::UnknownColor(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_pick_on_open() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "pick-on-open": false }"#)?;
+        assert!(!config.pick_on_open);
+
+        let config = parse_config("{}")?;
+        assert!(config.pick_on_open);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_privilege_helper() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "privilege-helper": "doas" }"#)?;
+        assert_eq!(config.privilege_helper, "doas");
+
+        let config = parse_config("{}")?;
+        assert_eq!(config.privilege_helper, "sudo");
+        assert_eq!(config.difftool, "");
+
+        let config = parse_config(r#"{ "difftool": "vimdiff" }"#)?;
+        assert_eq!(config.difftool, "vimdiff");
+
+        let config = parse_config(r#"{ "terminal": "alacritty" }"#)?;
+        assert_eq!(config.terminal, "alacritty");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_info_date() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "info-date": "created" }"#)?;
+        assert_eq!(config.info_date, InfoDate::Created);
+
+        let config = parse_config("{}")?;
+        assert_eq!(config.info_date, InfoDate::Modified);
+
+        assert!(matches!(
+            parse_config(r#"{ "info-date": "born" }"#),
+            Err(ConfigError::UnknownInfoDate(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_dim_unowned() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "dim-unowned": true }"#)?;
+        assert!(config.dim_unowned);
+
+        let config = parse_config("{}")?;
+        assert!(!config.dim_unowned);
+        assert_eq!(config.unwritable_color, Color::Ansi(235));
+
+        let config = parse_config(r#"{ "unwritable-color": "default" }"#)?;
+        assert_eq!(config.unwritable_color, Color::Foreground);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_info_columns() -> ConfigResult<()> {
+        assert_eq!(
+            to_info_columns("size:time")?,
+            vec![InfoColumn::Size, InfoColumn::Time]
+        );
+        assert_eq!(to_info_columns("time")?, vec![InfoColumn::Time]);
+        assert_eq!(to_info_columns("")?, vec![]);
+
+        assert!(matches!(
+            to_info_columns("size:perm"),
+            Err(ConfigError::UnknownInfoColumn(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_number() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "number": true, "relative-number": true }"#)?;
+        assert!(config.number);
+        assert!(config.relative_number);
+
+        let config = parse_config("{}")?;
+        assert!(!config.number);
+        assert!(!config.relative_number);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_layouts() -> ConfigResult<()> {
+        let config = parse_config(
+            r#"{
+                "ratios": "1:3:4",
+                "layouts": [{ "name": "wide-preview", "ratios": "0:1:3" }]
+            }"#,
+        )?;
+        assert_eq!(config.ratios, [1, 3, 4]);
+        assert_eq!(config.layouts["wide-preview"], [0, 1, 3]);
+
+        let config = parse_config("{}")?;
+        assert_eq!(config.ratios, [1, 2, 3]);
+        assert!(config.layouts.is_empty());
+        assert!(config.dir_previews.is_empty());
+        assert!(config.tool_paths.is_empty());
+
+        let config = parse_config(r#"{ "tools": [{ "name": "bat", "path": "batcat" }] }"#)?;
+        assert_eq!(config.tool_paths["bat"], "batcat");
+
+        assert!(matches!(
+            parse_config(r#"{ "tools": [{ "name": "vim", "path": "" }] }"#),
+            Err(ConfigError::UnknownTool(_))
+        ));
+
+        let config = parse_config(
+            r#"{ "dir-previews": [{ "marker": ".git", "command": "git log --oneline" }] }"#,
+        )?;
+        assert_eq!(
+            config.dir_previews,
+            [DirPreview {
+                marker: ".git".to_string(),
+                command: "git log --oneline".to_string(),
+            }]
+        );
+
+        for ratios in ["1:2", "1:2:3:4", "1:two:3", "0:0:0"] {
+            assert!(matches!(
+                to_ratios(ratios),
+                Err(ConfigError::InvalidRatios(_))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_hooks() -> ConfigResult<()> {
+        let config = parse_config(r#"{ "on-cd": "tmux refresh-client -S", "on-quit": "true" }"#)?;
+        assert_eq!(config.on_cd, "tmux refresh-client -S");
+        assert_eq!(config.on_select, "");
+        assert_eq!(config.on_quit, "true");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_resume() -> ConfigResult<()> {
+        assert!(!parse_config("{}")?.resume);
+        assert!(parse_config(r#"{ "resume": true }"#)?.resume);
+        assert!(!parse_config("{}")?.plain);
+        assert!(parse_config(r#"{ "plain": true }"#)?.plain);
+        assert!(!parse_config("{}")?.local_rolfrc);
+        assert!(parse_config(r#"{ "local-rolfrc": true }"#)?.local_rolfrc);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_time_format() -> ConfigResult<()> {
+        assert_eq!(parse_config("{}")?.time_format, TimeFormat::Ctime);
+
+        let config = parse_config(r#"{ "time-format": "relative" }"#)?;
+        assert_eq!(config.time_format, TimeFormat::Relative);
+
+        assert!(matches!(
+            parse_config(r#"{ "time-format": "unix" }"#),
+            Err(ConfigError::UnknownTimeFormat(_))
+        ));
+
+        let config = parse_config(
+            r#"{ "time-format": "custom", "date-format": "%d.%m.%Y", "clock": "12h" }"#,
+        )?;
+        assert_eq!(config.time_format, TimeFormat::Custom);
+        assert_eq!(config.date_format, "%d.%m.%Y");
+        assert_eq!(config.clock, Clock::TwelveHour);
+
+        assert!(matches!(
+            parse_config(r#"{ "date-format": "%Y-%Q" }"#),
+            Err(ConfigError::InvalidDateFormat(_))
+        ));
+        assert!(matches!(
+            parse_config(r#"{ "clock": "13h" }"#),
+            Err(ConfigError::UnknownClock(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_preview_position() -> ConfigResult<()> {
+        assert_eq!(parse_config("{}")?.preview_position, PreviewPane::Right);
+
+        let config = parse_config(r#"{ "preview-position": "bottom" }"#)?;
+        assert_eq!(config.preview_position, PreviewPane::Bottom);
+
+        assert!(matches!(
+            parse_config(r#"{ "preview-position": "left" }"#),
+            Err(ConfigError::UnknownPreviewPosition(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_size_units() -> ConfigResult<()> {
+        let config = parse_config("{}")?;
+        assert_eq!(config.size_units, SizeUnits::Metric);
+        assert!(!config.exact_size);
+
+        let config = parse_config(r#"{ "size-units": "iec", "exact-size": true }"#)?;
+        assert_eq!(config.size_units, SizeUnits::Iec);
+        assert!(config.exact_size);
+
+        assert!(matches!(
+            parse_config(r#"{ "size-units": "binary" }"#),
+            Err(ConfigError::UnknownSizeUnits(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_truncate() -> ConfigResult<()> {
+        assert_eq!(parse_config("{}")?.truncate, Truncation::End);
+        assert_eq!(
+            parse_config(r#"{ "truncate": "middle" }"#)?.truncate,
+            Truncation::Middle
+        );
+
+        assert!(matches!(
+            parse_config(r#"{ "truncate": "left" }"#),
+            Err(ConfigError::UnknownTruncation(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_recent() -> ConfigResult<()> {
+        let config = parse_config("{}")?;
+        assert_eq!(config.recent_within, Duration::from_secs(24 * 60 * 60));
+        assert_eq!(config.recent_color, Color::Foreground);
+
+        let config = parse_config(r#"{ "recent-within": "90m", "recent-color": "green" }"#)?;
+        assert_eq!(config.recent_within, Duration::from_secs(90 * 60));
+        assert_eq!(config.recent_color, Color::Green);
+
+        assert_eq!(to_recent_within("12h")?, Duration::from_secs(12 * 60 * 60));
+
+        for value in ["", "d", "0d", "-1d", "2w", "2", "1.5h"] {
+            assert!(
+                matches!(
+                    to_recent_within(value),
+                    Err(ConfigError::InvalidDuration(_))
+                ),
+                "{:?} should be invalid",
+                value
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_disk_usage() -> ConfigResult<()> {
+        let config = parse_config("{}")?;
+        assert!(!config.disk_usage);
+        assert_eq!(config.disk_usage_warning, 90);
+
+        let config = parse_config(r#"{ "disk-usage": true, "disk-usage-warning": 75 }"#)?;
+        assert!(config.disk_usage);
+        assert_eq!(config.disk_usage_warning, 75);
+
+        assert!(matches!(
+            parse_config(r#"{ "disk-usage-warning": 101 }"#),
+            Err(ConfigError::InvalidOptionValue(..))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_dotfiles() -> ConfigResult<()> {
+        assert_eq!(parse_config("{}")?.dotfiles, DotfileOrder::First);
+
+        let config = parse_config(r#"{ "dotfiles": "last" }"#)?;
+        assert_eq!(config.dotfiles, DotfileOrder::Last);
+
+        assert!(matches!(
+            parse_config(r#"{ "dotfiles": "hidden" }"#),
+            Err(ConfigError::UnknownDotfileOrder(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_copy_mode() -> ConfigResult<()> {
+        assert_eq!(parse_config("{}")?.copy_mode, CopyMode::Preserve);
+
+        let config = parse_config(r#"{ "copy-mode": "simple" }"#)?;
+        assert_eq!(config.copy_mode, CopyMode::Simple);
+
+        assert!(matches!(
+            parse_config(r#"{ "copy-mode": "exact" }"#),
+            Err(ConfigError::UnknownCopyMode(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_config_io_threads() -> ConfigResult<()> {
+        assert_eq!(parse_config("{}")?.io_threads, 4);
+        assert_eq!(parse_config(r#"{ "io-threads": 1 }"#)?.io_threads, 1);
+
+        assert!(matches!(
+            parse_config(r#"{ "io-threads": 0 }"#),
+            Err(ConfigError::InvalidOptionValue(_, _))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_bool_option() {
+        let mut config = Config::default();
+        assert!(config.smart_case);
+
+        assert!(set_bool_option(&mut config, "nosmartcase"));
+        assert!(!config.smart_case);
+
+        assert!(set_bool_option(&mut config, "relativenumber"));
+        assert!(config.relative_number);
+
+        assert!(set_bool_option(&mut config, "dircounts"));
+        assert!(config.dir_counts);
+
+        assert!(set_bool_option(&mut config, "ignorevcs"));
+        assert!(config.ignore_vcs);
+
+        assert!(!set_bool_option(&mut config, "nosuchoption"));
+    }
+
+    #[test]
+    fn test_option_values() -> ConfigResult<()> {
+        let config = Config {
+            recent_within: Duration::from_secs(90 * 60),
+            recent_color: Color::BrightCyan,
+            unwritable_color: Color::Ansi(235),
+            image_protocol: ImageProtocol::KittyUnicode,
+            info_columns: vec![InfoColumn::Size, InfoColumn::Time],
+            ..Config::default()
+        };
+
+        let values = option_values(&config);
+        assert!(values.contains(&("recent-within", "90m".to_string())));
+        assert!(values.contains(&("recent-color", "bright-cyan".to_string())));
+        assert!(values.contains(&("info", "size:time".to_string())));
+
+        // NOTE(Chris): Setting every value on the defaults gives back the same config
+        let mut round_trip = Config::default();
+        for (name, value) in &values {
+            set_option(&mut round_trip, name, value)?;
+        }
+        assert_eq!(option_values(&round_trip), values);
+
+        assert_eq!(duration_name(Duration::from_secs(2 * 24 * 60 * 60)), "2d");
+        assert_eq!(duration_name(Duration::from_secs(12 * 60 * 60)), "12h");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_env_overrides() {
+        let vars = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<Vec<_>>()
+        };
+
+        let mut config = Config::default();
+        apply_env_overrides(
+            &mut config,
+            vars(&[
+                ("ROLF_OPT_PREVIEW_DELAY", "150"),
+                ("ROLF_OPT_INFO", "size:time"),
+                ("ROLF_IMAGE_PROTOCOL", "kitty"),
+                ("ROLF_OPT_IMAGE_PROTOCOL", "none"),
+                ("HOME", "/home/user"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(config.preview_delay, 150);
+        assert_eq!(
+            config.info_columns,
+            vec![InfoColumn::Size, InfoColumn::Time]
+        );
+        assert_eq!(config.image_protocol, ImageProtocol::Kitty);
+
+        let result = apply_env_overrides(&mut config, vars(&[("ROLF_OPT_NUMBER", "yes")]));
+        assert!(matches!(
+            result,
+            Err((name, ConfigError::InvalidOptionValue(_, _))) if name == "ROLF_OPT_NUMBER"
+        ));
+
+        let result = apply_env_overrides(&mut config, vars(&[("ROLF_OPT_NO_SUCH", "1")]));
+        assert!(matches!(result, Err((_, ConfigError::UnknownOption(_)))));
+    }
+
+    #[test]
+    fn test_sample_config() -> ConfigResult<()> {
+        let config = parse_config(&sample_config())?;
+        let default_config = Config::default();
+
+        assert_eq!(config.image_protocol, default_config.image_protocol);
+        assert_eq!(config.pick_on_open, default_config.pick_on_open);
+        assert_eq!(config.rename_prompt, default_config.rename_prompt);
+        assert_eq!(config.info_date, default_config.info_date);
+        assert_eq!(config.smart_case, default_config.smart_case);
+        assert_eq!(config.keybindings, default_config.keybindings);
+
+        assert!(rolf_parser::parser::parse(SAMPLE_ROLFRC).is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_toml_config() -> ConfigResult<()> {
+        let config = parse_toml_config(
+            r#"
+image-protocol = "None"
+preview-delay = 150
+templates-dir = "/usr/share/templates"
+rename-prompt = "Rename \"it\": "
+smart-case = false
+
+[[keybindings]]
+key = "ctrl+r"
+command = "rename"
+"#,
+        )?;
+
+        assert_eq!(config.image_protocol, ImageProtocol::None);
+        assert_eq!(config.preview_delay, 150);
+        assert_eq!(config.templates_dir, "/usr/share/templates");
+        assert_eq!(config.rename_prompt, "Rename \"it\": ");
+        assert!(!config.smart_case);
+        assert_eq!(
+            config.keybindings.get(&to_key("ctrl+r")?),
+            Some(&"rename".to_string())
+        );
+
+        assert!(matches!(
+            parse_toml_config("preview-delay = "),
+            Err(ConfigError::InvalidToml(_))
+        ));
+
+        Ok(())
+    }
+
     #[test]
     fn test_add_raw_binding() {
         let mut raw_bindings = vec![];
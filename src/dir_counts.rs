@@ -0,0 +1,152 @@
+// Counts the items in directories shown in the listing (with the dir-counts option) on a
+// background thread, so that scrolling past large directories doesn't hold up drawing. Only the
+// rows in view are counted, and each count is kept until its directory is modified.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::SystemTime;
+
+// A directory that was counted, or couldn't be read (leaving item_count as None)
+#[derive(Debug)]
+pub struct Counted {
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    pub item_count: Option<usize>,
+}
+
+// NOTE(Chris): A directory's modification time changes whenever an entry is added or removed, so
+// a count is only used while the directory has the time it was counted at
+struct CachedCount {
+    modified: Option<SystemTime>,
+    // None while it's being counted, or if it couldn't be read
+    item_count: Option<usize>,
+}
+
+pub struct DirCounts {
+    counts: HashMap<PathBuf, CachedCount>,
+    request_tx: Sender<(PathBuf, Option<SystemTime>)>,
+}
+
+impl DirCounts {
+    /// Starts the counting thread, which passes each batch of counts to deliver. They're only
+    /// kept once they've been given back to insert.
+    pub fn new<F>(deliver: F) -> Self
+    where
+        F: Fn(Vec<Counted>) + Send + 'static,
+    {
+        let (request_tx, request_rx) = channel::<(PathBuf, Option<SystemTime>)>();
+
+        thread::spawn(move || {
+            while let Ok(request) = request_rx.recv() {
+                // NOTE(Chris): Everything requested while we were busy is delivered together, so
+                // that a screenful of directories causes one redraw rather than one each
+                let counted = std::iter::once(request)
+                    .chain(request_rx.try_iter())
+                    .map(|(path, modified)| Counted {
+                        item_count: fs::read_dir(&path).ok().map(|entries| entries.count()),
+                        path,
+                        modified,
+                    })
+                    .collect();
+
+                deliver(counted);
+            }
+        });
+
+        DirCounts {
+            counts: HashMap::new(),
+            request_tx,
+        }
+    }
+
+    /// The number of items in the directory, if it's been counted since it was last modified.
+    pub fn get(&self, path: &Path, modified: Option<SystemTime>) -> Option<usize> {
+        self.counts
+            .get(path)
+            .filter(|cached| cached.modified == modified)
+            .and_then(|cached| cached.item_count)
+    }
+
+    /// Counts the directory in the background, unless it's already been counted (or is being
+    /// counted) since it was last modified.
+    pub fn request(&mut self, path: &Path, modified: Option<SystemTime>) {
+        if self
+            .counts
+            .get(path)
+            .is_some_and(|cached| cached.modified == modified)
+        {
+            return;
+        }
+
+        self.counts.insert(
+            path.to_path_buf(),
+            CachedCount {
+                modified,
+                item_count: None,
+            },
+        );
+
+        let _ = self.request_tx.send((path.to_path_buf(), modified));
+    }
+
+    pub fn insert(&mut self, counted: Vec<Counted>) {
+        for Counted {
+            path,
+            modified,
+            item_count,
+        } in counted
+        {
+            self.counts.insert(
+                path,
+                CachedCount {
+                    modified,
+                    item_count,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc::RecvTimeoutError;
+    use std::time::Duration;
+
+    #[test]
+    fn test_dir_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        for file_name in ["a", "b", "c"] {
+            fs::write(dir.path().join(file_name), "").unwrap();
+        }
+
+        let (counted_tx, counted_rx) = channel();
+        let mut dir_counts = DirCounts::new(move |counted| counted_tx.send(counted).unwrap());
+
+        let modified = Some(SystemTime::UNIX_EPOCH);
+        dir_counts.request(dir.path(), modified);
+        assert_eq!(dir_counts.get(dir.path(), modified), None);
+
+        let counted = counted_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        dir_counts.insert(counted);
+        assert_eq!(dir_counts.get(dir.path(), modified), Some(3));
+
+        // NOTE(Chris): Asking again doesn't count again, until the directory is modified
+        dir_counts.request(dir.path(), modified);
+        assert_eq!(
+            counted_rx
+                .recv_timeout(Duration::from_millis(100))
+                .unwrap_err(),
+            RecvTimeoutError::Timeout
+        );
+
+        let modified_later = Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1));
+        assert_eq!(dir_counts.get(dir.path(), modified_later), None);
+        dir_counts.request(dir.path(), modified_later);
+        assert!(counted_rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
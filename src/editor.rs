@@ -0,0 +1,75 @@
+// Builds the shell commands used to open files in the user's editor, optionally jumping to a
+// specific line (e.g. for a search result).
+
+use crate::shell_quote;
+
+use std::path::Path;
+
+// Returns how the given editor is told to open a file at a line, where "{line}" and "{file}" are
+// replaced with the line number and the file path.
+fn detect_line_flag(editor: &str) -> Option<&'static str> {
+    // NOTE(Chris): The editor may be given as a full path, or with arguments (like "code -w")
+    let program = editor.split_whitespace().next()?;
+    let name = Path::new(program).file_name()?.to_str()?;
+
+    match name {
+        "vi" | "vim" | "nvim" | "hx" | "helix" | "kak" | "nano" | "micro" | "emacs"
+        | "emacsclient" => Some("+{line} {file}"),
+        "code" | "code-insiders" | "codium" => Some("--goto {file}:{line}"),
+        "subl" => Some("{file}:{line}"),
+        _ => None,
+    }
+}
+
+/// Returns a shell command which opens `file` in `editor`, jumping to `line` if possible.
+/// `line_flag` overrides the detected flag when it isn't empty.
+pub fn editor_command(editor: &str, line_flag: &str, file: &str, line: Option<usize>) -> String {
+    // NOTE(Chris): The command is run with sh -c, so a file name with spaces or quotes must not be
+    // split up or expanded. A quoted word can still be joined to the rest of a flag, like
+    // 'a.rs':12.
+    let file = shell_quote(file);
+
+    let arguments = match line {
+        Some(line) => {
+            let line_flag = if line_flag.is_empty() {
+                detect_line_flag(editor).unwrap_or("{file}")
+            } else {
+                line_flag
+            };
+
+            line_flag
+                .replace("{line}", &line.to_string())
+                .replace("{file}", &file)
+        }
+        None => file,
+    };
+
+    format!("{} {}", editor, arguments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_command() {
+        assert_eq!(
+            editor_command("nvim", "", "a.rs", Some(12)),
+            "nvim +12 'a.rs'"
+        );
+        assert_eq!(
+            editor_command("/usr/bin/code -w", "", "a.rs", Some(12)),
+            "/usr/bin/code -w --goto 'a.rs':12"
+        );
+        assert_eq!(editor_command("ed", "", "a.rs", Some(12)), "ed 'a.rs'");
+        assert_eq!(editor_command("nvim", "", "a.rs", None), "nvim 'a.rs'");
+        assert_eq!(
+            editor_command("ed", "-l {line} {file}", "a.rs", Some(3)),
+            "ed -l 3 'a.rs'"
+        );
+        assert_eq!(
+            editor_command("nvim", "", "it's $HOME.txt", None),
+            "nvim 'it'\\''s $HOME.txt'"
+        );
+    }
+}
@@ -0,0 +1,816 @@
+// The files to be copied or moved by the next paste. They're kept in a file in the data directory,
+// so that copying in one rolf instance and pasting in another works. Like lf's "files" file (which
+// it can be shared with), the first line is "copy" or "move", followed by one path per line.
+
+use crate::config::{CopyMode, SizeUnits};
+use crate::human_size::format_size;
+use crate::io_pool;
+use crate::os_abstract;
+
+use std::ffi::OsString;
+use std::fs::{self, FileTimes, Metadata};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasteMode {
+    Copy,
+    Move,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileBuffer {
+    pub mode: PasteMode,
+    pub paths: Vec<PathBuf>,
+}
+
+impl FileBuffer {
+    /// Reads the buffer, returning None if there aren't any files in it.
+    pub fn read(files_path: &Path) -> io::Result<Option<FileBuffer>> {
+        match fs::read_to_string(files_path) {
+            Ok(contents) => Ok(parse_buffer(&contents)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn write(&self, files_path: &Path) -> io::Result<()> {
+        let mut contents = match self.mode {
+            PasteMode::Copy => String::from("copy\n"),
+            PasteMode::Move => String::from("move\n"),
+        };
+
+        for path in &self.paths {
+            // TODO(Chris): Store paths which aren't valid unicode, too
+            let path = path.to_str().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} is not valid unicode", path.display()),
+                )
+            })?;
+
+            contents.push_str(path);
+            contents.push('\n');
+        }
+
+        if let Some(data_dir) = files_path.parent() {
+            fs::create_dir_all(data_dir)?;
+        }
+
+        fs::write(files_path, contents)
+    }
+}
+
+/// Empties the buffer. Like lf, we leave an empty file behind rather than removing it.
+pub fn clear(files_path: &Path) -> io::Result<()> {
+    match fs::write(files_path, "") {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+fn parse_buffer(contents: &str) -> Option<FileBuffer> {
+    let mut lines = contents.lines();
+
+    let mode = match lines.next()? {
+        "copy" => PasteMode::Copy,
+        "move" => PasteMode::Move,
+        _ => return None,
+    };
+
+    let paths: Vec<PathBuf> = lines
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect();
+
+    if paths.is_empty() {
+        None
+    } else {
+        Some(FileBuffer { mode, paths })
+    }
+}
+
+// What to do when a pasted file would replace an existing one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictAction {
+    Overwrite,
+    Skip,
+    // Add a suffix like ".~1~", like lf does
+    Rename,
+}
+
+/// Reads the answer to the conflict prompt, where an uppercase letter applies the action to
+/// every conflict left in the paste. Returns the action and whether it's for all of them.
+pub fn parse_conflict_answer(answer: &str) -> Option<(ConflictAction, bool)> {
+    let action = match answer.to_ascii_lowercase().as_str() {
+        "o" => ConflictAction::Overwrite,
+        "s" => ConflictAction::Skip,
+        "r" => ConflictAction::Rename,
+        _ => return None,
+    };
+
+    Some((action, answer.chars().all(|ch| ch.is_ascii_uppercase())))
+}
+
+/// Returns where a file pasted into the destination directory goes, before any conflict with an
+/// existing file is resolved.
+pub fn paste_target(source: &Path, dest_dir: &Path) -> io::Result<PathBuf> {
+    let file_name = source
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+
+    if dest_dir.starts_with(source) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "cannot paste a directory into itself",
+        ));
+    }
+
+    Ok(dest_dir.join(file_name))
+}
+
+/// Whether pasting to the target would replace an existing file.
+pub fn is_conflict(mode: PasteMode, source: &Path, target: &Path) -> bool {
+    // NOTE(Chris): Moving a file to where it already is does nothing, so it can't conflict
+    !(mode == PasteMode::Move && os_abstract::is_same_path(source, target))
+        && target.symlink_metadata().is_ok()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyOptions {
+    pub mode: CopyMode,
+    // How many files are copied (or removed) at once
+    pub thread_count: usize,
+}
+
+// A file (or directory) that's been pasted
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pasted {
+    pub path: PathBuf,
+    // How many files were copied by cloning them, which is much faster than reading and writing
+    // them (see os_abstract::clone_file)
+    pub cloned_count: usize,
+}
+
+/// Copies or moves a file (or a whole directory) to the target from paste_target, returning where
+/// it ended up, or None if it was skipped. The action is only used if there's already a file at
+/// the target. on_progress is called with the number of bytes done as they're done.
+pub fn paste_path(
+    mode: PasteMode,
+    source: &Path,
+    target: &Path,
+    action: ConflictAction,
+    options: CopyOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> io::Result<Option<Pasted>> {
+    if !is_conflict(mode, source, target) {
+        if mode == PasteMode::Move && os_abstract::is_same_path(source, target) {
+            on_progress(io_pool::tree_size(source).byte_count);
+            return Ok(Some(Pasted {
+                path: target.to_path_buf(),
+                cloned_count: 0,
+            }));
+        }
+    } else {
+        match action {
+            ConflictAction::Skip => {
+                on_progress(io_pool::tree_size(source).byte_count);
+                return Ok(None);
+            }
+            ConflictAction::Overwrite if source != target => {
+                if source.starts_with(target) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "cannot overwrite a directory with something inside it",
+                    ));
+                }
+
+                return paste_over(mode, source, target, options, on_progress);
+            }
+            // NOTE(Chris): A file can't be copied over itself, so overwriting it makes a renamed
+            // copy instead
+            ConflictAction::Rename | ConflictAction::Overwrite => {
+                return paste_new(mode, source, &unique_dest(target), options, on_progress);
+            }
+        }
+    }
+
+    paste_new(mode, source, target, options, on_progress)
+}
+
+/// Returns where paste_path would put the file, along with a command that pastes it there. If
+/// paste_path isn't allowed to, the command can be run through the privilege helper instead. It
+/// has to be made before paste_path is tried, since a failed paste can leave part of a copy
+/// behind. The command replaces whatever's at its destination, including that partial copy.
+pub fn privileged_paste_command(
+    mode: PasteMode,
+    source: &Path,
+    target: &Path,
+    action: ConflictAction,
+) -> (PathBuf, Vec<OsString>) {
+    let dest = match action {
+        ConflictAction::Overwrite if source != target => target.to_path_buf(),
+        _ if !is_conflict(mode, source, target) => target.to_path_buf(),
+        _ => unique_dest(target),
+    };
+
+    let script = match mode {
+        PasteMode::Copy => r#"rm -rf -- "$2" && cp -a -- "$1" "$2""#,
+        PasteMode::Move => r#"rm -rf -- "$2" && mv -- "$1" "$2""#,
+    };
+
+    let command = vec![
+        OsString::from("sh"),
+        OsString::from("-c"),
+        OsString::from(script),
+        OsString::from("sh"),
+        source.as_os_str().to_os_string(),
+        dest.as_os_str().to_os_string(),
+    ];
+
+    (dest, command)
+}
+
+// Replaces the file or directory at the target. The paste goes to a sibling first, so that the
+// target is only removed once there's something to put in its place.
+fn paste_over(
+    mode: PasteMode,
+    source: &Path,
+    target: &Path,
+    options: CopyOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> io::Result<Option<Pasted>> {
+    let temp = unique_dest(target);
+
+    let pasted = match paste_new(mode, source, &temp, options, on_progress) {
+        Ok(pasted) => pasted,
+        Err(err) => {
+            // NOTE(Chris): A failed move may have removed some of the source already, so its
+            // partial copy is left for the user to recover
+            if mode == PasteMode::Copy && temp.symlink_metadata().is_ok() {
+                let _ = remove_recursively(&temp, options.thread_count);
+            }
+
+            return Err(err);
+        }
+    };
+
+    // NOTE(Chris): A rename can't replace a directory (or a file with a directory), so the old
+    // target is moved aside rather than renamed over
+    let old = unique_dest(target);
+    fs::rename(target, &old)?;
+
+    if let Err(err) = fs::rename(&temp, target) {
+        let _ = fs::rename(&old, target);
+        return Err(err);
+    }
+
+    remove_recursively(&old, options.thread_count)?;
+
+    Ok(pasted.map(|pasted| Pasted {
+        path: target.to_path_buf(),
+        ..pasted
+    }))
+}
+
+// Pastes to a target which doesn't exist yet
+fn paste_new(
+    mode: PasteMode,
+    source: &Path,
+    target: &Path,
+    options: CopyOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> io::Result<Option<Pasted>> {
+    let cloned_count = match mode {
+        PasteMode::Copy => copy_recursively(source, target, options, on_progress)?,
+        PasteMode::Move => match fs::rename(source, target) {
+            Ok(()) => {
+                on_progress(io_pool::tree_size(target).byte_count);
+                0
+            }
+            // NOTE(Chris): Files can't be renamed onto another filesystem, so they're copied
+            // there instead, keeping everything a rename would have kept
+            Err(err) if err.kind() == io::ErrorKind::CrossesDevices => {
+                let options = CopyOptions {
+                    mode: CopyMode::Preserve,
+                    ..options
+                };
+
+                let cloned_count = copy_recursively(source, target, options, on_progress)?;
+                remove_recursively(source, options.thread_count)?;
+
+                cloned_count
+            }
+            Err(err) => return Err(err),
+        },
+    };
+
+    Ok(Some(Pasted {
+        path: target.to_path_buf(),
+        cloned_count,
+    }))
+}
+
+// Returns the path if nothing is there, or the path with the first free suffix of ".~1~", ".~2~",
+// and so on
+fn unique_dest(path: &Path) -> PathBuf {
+    if path.symlink_metadata().is_err() {
+        return path.to_path_buf();
+    }
+
+    let mut suffix = 1;
+
+    loop {
+        let mut candidate = path.as_os_str().to_os_string();
+        candidate.push(format!(".~{}~", suffix));
+
+        let candidate = PathBuf::from(candidate);
+        if candidate.symlink_metadata().is_err() {
+            return candidate;
+        }
+
+        suffix += 1;
+    }
+}
+
+// Copies a file or directory. When preserving, symlinks are recreated rather than followed, and
+// each copy gets its source's metadata. Returns how many files were cloned.
+fn copy_recursively(
+    source: &Path,
+    dest: &Path,
+    options: CopyOptions,
+    on_progress: &mut dyn FnMut(u64),
+) -> io::Result<usize> {
+    let mut files = vec![];
+    let mut dirs = vec![];
+
+    // NOTE(Chris): Directories and symlinks are quick to make, so only the files are copied in
+    // parallel
+    plan_copy(source, dest, options.mode, true, &mut files, &mut dirs)?;
+
+    let cloned_count = AtomicUsize::new(0);
+
+    io_pool::run_batched(
+        &files,
+        options.thread_count,
+        |file| file.metadata.len(),
+        |file, report| {
+            if copy_file(&file.source, &file.dest, file.metadata.len(), report)? {
+                cloned_count.fetch_add(1, Ordering::Relaxed);
+            }
+
+            // NOTE(Chris): Like cp, even a simple copy keeps its source's permissions, so that
+            // scripts stay executable and private files stay private
+            match options.mode {
+                CopyMode::Preserve => preserve_metadata(&file.source, &file.dest, &file.metadata),
+                CopyMode::Simple => fs::set_permissions(&file.dest, file.metadata.permissions()),
+            }
+        },
+        on_progress,
+    )?;
+
+    // NOTE(Chris): Copying a directory's contents changes its modification time, so directories
+    // get their metadata last, deepest first
+    if options.mode == CopyMode::Preserve {
+        for dir in dirs.iter().rev() {
+            preserve_metadata(&dir.source, &dir.dest, &dir.metadata)?;
+        }
+    }
+
+    Ok(cloned_count.into_inner())
+}
+
+struct CopyItem {
+    source: PathBuf,
+    dest: PathBuf,
+    metadata: Metadata,
+}
+
+// Makes the directories and symlinks of a copy, and lists the files which need copying. The
+// directories are listed before their contents. is_top is whether source is what's being copied,
+// rather than something inside of it.
+fn plan_copy(
+    source: &Path,
+    dest: &Path,
+    copy_mode: CopyMode,
+    is_top: bool,
+    files: &mut Vec<CopyItem>,
+    dirs: &mut Vec<CopyItem>,
+) -> io::Result<()> {
+    let link_metadata = source.symlink_metadata()?;
+
+    // NOTE(Chris): Simple copies follow symlinks, except to directories inside of the copy, which
+    // are copied as links. Those may point back up (like "self -> ."), which would make the copy
+    // go on until the path got too long. Broken symlinks are copied as links too.
+    let metadata = match copy_mode {
+        CopyMode::Simple if link_metadata.file_type().is_symlink() => match source.metadata() {
+            Ok(metadata) if is_top || !metadata.is_dir() => metadata,
+            _ => link_metadata,
+        },
+        _ => link_metadata,
+    };
+
+    let item = CopyItem {
+        source: source.to_path_buf(),
+        dest: dest.to_path_buf(),
+        metadata,
+    };
+
+    if item.metadata.file_type().is_symlink() {
+        copy_symlink(source, dest)
+    } else if item.metadata.is_dir() {
+        fs::create_dir(dest)?;
+        dirs.push(item);
+
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+
+            plan_copy(
+                &entry.path(),
+                &dest.join(entry.file_name()),
+                copy_mode,
+                false,
+                files,
+                dirs,
+            )?;
+        }
+
+        Ok(())
+    } else {
+        files.push(item);
+
+        Ok(())
+    }
+}
+
+// Copies a file's contents, reporting its progress as it goes. Unlike fs::copy, this leaves the
+// permissions to the caller. Returns whether the file was cloned, which is tried first.
+fn copy_file(source: &Path, dest: &Path, len: u64, on_progress: &dyn Fn(u64)) -> io::Result<bool> {
+    // NOTE(Chris): Cloning fails for all sorts of reasons (like the file system not supporting it,
+    // or the destination being on another one), and the normal copy works in all of those cases
+    if os_abstract::clone_file(source, dest).is_ok() {
+        on_progress(len);
+        return Ok(true);
+    }
+
+    let mut source_file = fs::File::open(source)?;
+    let mut dest_file = fs::File::create(dest)?;
+
+    // NOTE(Chris): Big files are copied with a bigger buffer, so they take fewer reads and writes,
+    // while small files don't need more buffer than they have bytes
+    let buffer_size = if len >= LARGE_FILE_SIZE {
+        LARGE_BUFFER_SIZE
+    } else {
+        (len as usize).clamp(1, SMALL_BUFFER_SIZE)
+    };
+    let mut buffer = vec![0; buffer_size];
+
+    loop {
+        let len = match source_file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        dest_file.write_all(&buffer[..len])?;
+        on_progress(len as u64);
+    }
+
+    Ok(false)
+}
+
+const SMALL_BUFFER_SIZE: usize = 128 * 1024;
+const LARGE_BUFFER_SIZE: usize = 4 * 1024 * 1024;
+const LARGE_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+// Gives a copy its source's extended attributes, timestamps, and permissions
+fn preserve_metadata(source: &Path, dest: &Path, metadata: &Metadata) -> io::Result<()> {
+    // NOTE(Chris): The permissions are set last, since they may stop us from changing the rest
+    os_abstract::copy_xattrs(source, dest)?;
+
+    let times = FileTimes::new()
+        .set_accessed(metadata.accessed()?)
+        .set_modified(metadata.modified()?);
+    os_abstract::set_file_times(dest, times)?;
+
+    fs::set_permissions(dest, metadata.permissions())
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, dest: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, dest)
+}
+
+#[cfg(windows)]
+fn copy_symlink(source: &Path, dest: &Path) -> io::Result<()> {
+    let target = fs::read_link(source)?;
+
+    if fs::metadata(source).is_ok_and(|metadata| metadata.is_dir()) {
+        std::os::windows::fs::symlink_dir(target, dest)
+    } else {
+        std::os::windows::fs::symlink_file(target, dest)
+    }
+}
+
+fn remove_recursively(path: &Path, thread_count: usize) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        io_pool::remove_dir_all(path, thread_count)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+// How far along a paste is, shown on the bottom line while it runs
+pub struct PasteProgress {
+    pub total: u64,
+    pub done: u64,
+    pub units: SizeUnits,
+}
+
+impl PasteProgress {
+    /// Describes the progress, like "37% 12M/s 5s". The speed and time left are only shown once
+    /// there's been time to measure them.
+    pub fn describe(&self, elapsed: Duration) -> String {
+        // NOTE(Chris): Pasting only empty files is done as soon as it starts
+        let percent = (self.done.min(self.total) * 100)
+            .checked_div(self.total)
+            .unwrap_or(100);
+
+        let elapsed_secs = elapsed.as_secs_f64();
+        if elapsed_secs < 0.5 || self.done == 0 {
+            return format!("{}%", percent);
+        }
+
+        let bytes_per_sec = self.done as f64 / elapsed_secs;
+        let secs_left = (self.total.saturating_sub(self.done) as f64 / bytes_per_sec).ceil() as u64;
+
+        format!(
+            "{}% {}/s {}",
+            percent,
+            format_size(bytes_per_sec as u64, self.units),
+            format_duration(secs_left)
+        )
+    }
+}
+
+// Like "5s", "2m05s", or "1h02m"
+fn format_duration(secs: u64) -> String {
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 60 * 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / (60 * 60), secs / 60 % 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRESERVE: CopyOptions = CopyOptions {
+        mode: CopyMode::Preserve,
+        thread_count: 2,
+    };
+
+    #[test]
+    fn test_file_buffer() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let files_path = data_dir.path().join("rolf/files");
+
+        assert_eq!(FileBuffer::read(&files_path).unwrap(), None);
+
+        let buffer = FileBuffer {
+            mode: PasteMode::Move,
+            paths: vec![PathBuf::from("/tmp/a"), PathBuf::from("/tmp/b c")],
+        };
+        buffer.write(&files_path).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&files_path).unwrap(),
+            "move\n/tmp/a\n/tmp/b c\n"
+        );
+        assert_eq!(FileBuffer::read(&files_path).unwrap(), Some(buffer));
+
+        clear(&files_path).unwrap();
+        assert_eq!(FileBuffer::read(&files_path).unwrap(), None);
+
+        assert_eq!(parse_buffer("copy\n"), None);
+        assert_eq!(parse_buffer("cut\n/tmp/a\n"), None);
+    }
+
+    #[test]
+    fn test_paste_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+        let dest_dir = dir.path().join("dest");
+
+        fs::create_dir_all(source_dir.join("sub")).unwrap();
+        fs::write(source_dir.join("sub/notes.txt"), "notes").unwrap();
+        fs::write(source_dir.join("a.txt"), "a").unwrap();
+        fs::create_dir(&dest_dir).unwrap();
+
+        let mut done = 0;
+        let mut paste = |mode, name: &str, action| {
+            let source = source_dir.join(name);
+            let target = paste_target(&source, &dest_dir).unwrap();
+
+            paste_path(mode, &source, &target, action, PRESERVE, &mut |bytes| {
+                done += bytes
+            })
+            .unwrap()
+            .map(|pasted| pasted.path)
+        };
+
+        assert_eq!(
+            paste(PasteMode::Copy, "sub", ConflictAction::Rename),
+            Some(dest_dir.join("sub"))
+        );
+        assert_eq!(
+            fs::read_to_string(dest_dir.join("sub/notes.txt")).unwrap(),
+            "notes"
+        );
+
+        assert_eq!(
+            paste(PasteMode::Copy, "sub", ConflictAction::Rename),
+            Some(dest_dir.join("sub.~1~"))
+        );
+        assert_eq!(paste(PasteMode::Copy, "sub", ConflictAction::Skip), None);
+
+        fs::write(dest_dir.join("a.txt"), "old").unwrap();
+        assert_eq!(
+            paste(PasteMode::Move, "a.txt", ConflictAction::Overwrite),
+            Some(dest_dir.join("a.txt"))
+        );
+        assert_eq!(fs::read_to_string(dest_dir.join("a.txt")).unwrap(), "a");
+        assert!(!source_dir.join("a.txt").exists());
+        assert!(!dest_dir.join("a.txt.~1~").exists());
+
+        // A directory can be overwritten too
+        fs::write(dest_dir.join("sub/other.txt"), "other").unwrap();
+        assert_eq!(
+            paste(PasteMode::Copy, "sub", ConflictAction::Overwrite),
+            Some(dest_dir.join("sub"))
+        );
+        assert!(!dest_dir.join("sub/other.txt").exists());
+
+        // A failed overwrite leaves the target as it was
+        assert!(paste_path(
+            PasteMode::Copy,
+            &source_dir.join("missing"),
+            &dest_dir.join("a.txt"),
+            ConflictAction::Overwrite,
+            PRESERVE,
+            &mut |_| ()
+        )
+        .is_err());
+        assert_eq!(fs::read_to_string(dest_dir.join("a.txt")).unwrap(), "a");
+
+        // Every byte is counted, including the skipped ones
+        assert_eq!(done, 5 * 4 + 1);
+
+        assert!(paste_target(&source_dir, &source_dir.join("sub")).is_err());
+
+        // Copying a file onto itself makes a renamed copy instead
+        let source = dest_dir.join("a.txt");
+        assert_eq!(
+            paste_path(
+                PasteMode::Copy,
+                &source,
+                &source,
+                ConflictAction::Overwrite,
+                PRESERVE,
+                &mut |_| ()
+            )
+            .unwrap()
+            .map(|pasted| pasted.path),
+            Some(dest_dir.join("a.txt.~1~"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_modes() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::SystemTime;
+
+        let dir = tempfile::tempdir().unwrap();
+        let source_dir = dir.path().join("source");
+
+        fs::create_dir(&source_dir).unwrap();
+        fs::write(source_dir.join("script.sh"), "echo hi").unwrap();
+        fs::set_permissions(
+            source_dir.join("script.sh"),
+            fs::Permissions::from_mode(0o750),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("script.sh", source_dir.join("link")).unwrap();
+        std::os::unix::fs::symlink(".", source_dir.join("self")).unwrap();
+
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        os_abstract::set_file_times(&source_dir, FileTimes::new().set_modified(modified)).unwrap();
+
+        let copy = |copy_mode, name: &str| {
+            let target = dir.path().join(name);
+
+            paste_path(
+                PasteMode::Copy,
+                &source_dir,
+                &target,
+                ConflictAction::Rename,
+                CopyOptions {
+                    mode: copy_mode,
+                    ..PRESERVE
+                },
+                &mut |_| (),
+            )
+            .unwrap();
+
+            target
+        };
+
+        let preserved = copy(CopyMode::Preserve, "preserved");
+        let metadata = fs::metadata(preserved.join("script.sh")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+        assert_eq!(
+            fs::metadata(&preserved).unwrap().modified().unwrap(),
+            modified
+        );
+        assert!(fs::symlink_metadata(preserved.join("link"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+
+        let simple = copy(CopyMode::Simple, "simple");
+        let metadata = fs::metadata(simple.join("script.sh")).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o750);
+        assert_ne!(fs::metadata(&simple).unwrap().modified().unwrap(), modified);
+        assert_eq!(fs::read_to_string(simple.join("link")).unwrap(), "echo hi");
+        assert!(fs::symlink_metadata(simple.join("link"))
+            .unwrap()
+            .file_type()
+            .is_file());
+        // A symlink to a directory inside of the copy isn't followed, or this would never end
+        assert_eq!(fs::read_link(simple.join("self")).unwrap(), Path::new("."));
+
+        // The directory being copied is followed, though
+        std::os::unix::fs::symlink(&source_dir, dir.path().join("source-link")).unwrap();
+        paste_path(
+            PasteMode::Copy,
+            &dir.path().join("source-link"),
+            &dir.path().join("followed"),
+            ConflictAction::Rename,
+            CopyOptions {
+                mode: CopyMode::Simple,
+                ..PRESERVE
+            },
+            &mut |_| (),
+        )
+        .unwrap();
+        assert!(fs::symlink_metadata(dir.path().join("followed"))
+            .unwrap()
+            .is_dir());
+        assert_eq!(
+            fs::read_to_string(dir.path().join("followed/script.sh")).unwrap(),
+            "echo hi"
+        );
+    }
+
+    #[test]
+    fn test_parse_conflict_answer() {
+        assert_eq!(
+            parse_conflict_answer("o"),
+            Some((ConflictAction::Overwrite, false))
+        );
+        assert_eq!(
+            parse_conflict_answer("S"),
+            Some((ConflictAction::Skip, true))
+        );
+        assert_eq!(parse_conflict_answer("x"), None);
+    }
+
+    #[test]
+    fn test_paste_progress() {
+        let progress = PasteProgress {
+            total: 10_000_000,
+            done: 2_500_000,
+            units: SizeUnits::Metric,
+        };
+
+        assert_eq!(progress.describe(Duration::from_millis(100)), "25%");
+        assert_eq!(progress.describe(Duration::from_secs(1)), "25% 2.5M/s 3s");
+
+        let progress = PasteProgress {
+            total: 1_000_000_000,
+            done: 1_000_000,
+            units: SizeUnits::Metric,
+        };
+        assert_eq!(
+            progress.describe(Duration::from_secs(1)),
+            "0% 1.0M/s 16m39s"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+// Quick filters which restrict the listing to directories, files, files with certain extensions,
+// or recently modified files, set with the only-dirs, only-files, only-ext, and recent-files
+// commands. A filter stays on (even when changing directories) until it's cleared with
+// clear-filter.
+
+use std::fmt;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntryFilter {
+    Dirs,
+    Files,
+    // Extensions without their leading dots, like "rs"
+    Extensions(Vec<String>),
+    // Files modified within the duration before the listing was read
+    Recent(Duration),
+}
+
+impl EntryFilter {
+    /// Builds an extension filter from the arguments to only-ext, which may be written with or
+    /// without their leading dots.
+    pub fn extensions(arguments: &[String]) -> Option<EntryFilter> {
+        if arguments.is_empty() {
+            return None;
+        }
+
+        Some(EntryFilter::Extensions(
+            arguments
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect(),
+        ))
+    }
+
+    pub fn matches(
+        &self,
+        path: &Path,
+        is_dir: bool,
+        modified: Option<SystemTime>,
+        now: SystemTime,
+    ) -> bool {
+        match self {
+            EntryFilter::Dirs => is_dir,
+            EntryFilter::Files => !is_dir,
+            // NOTE(Chris): Extensions are matched case-insensitively, since files like "IMG.JPG"
+            // are common
+            EntryFilter::Extensions(extensions) => path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    extensions
+                        .iter()
+                        .any(|wanted| wanted.eq_ignore_ascii_case(extension))
+                }),
+            // NOTE(Chris): Files with times in the future count as recent, since they were most
+            // likely just made by something with a skewed clock
+            EntryFilter::Recent(within) => {
+                !is_dir
+                    && modified.is_some_and(|modified| {
+                        now.duration_since(modified)
+                            .map_or(true, |age| age <= *within)
+                    })
+            }
+        }
+    }
+}
+
+// How the filter is shown on the bottom line
+impl fmt::Display for EntryFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntryFilter::Dirs => write!(f, "dirs"),
+            EntryFilter::Files => write!(f, "files"),
+            EntryFilter::Extensions(extensions) => {
+                for (i, extension) in extensions.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+
+                    write!(f, "*.{}", extension)?;
+                }
+
+                Ok(())
+            }
+            EntryFilter::Recent(within) => {
+                let secs = within.as_secs();
+
+                match secs {
+                    _ if secs % (24 * 60 * 60) == 0 => {
+                        write!(f, "recent {}d", secs / (24 * 60 * 60))
+                    }
+                    _ if secs % (60 * 60) == 0 => write!(f, "recent {}h", secs / (60 * 60)),
+                    _ => write!(f, "recent {}m", secs / 60),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_filter() {
+        let matches = |filter: &EntryFilter, name, is_dir| {
+            filter.matches(Path::new(name), is_dir, None, SystemTime::UNIX_EPOCH)
+        };
+
+        assert!(matches(&EntryFilter::Dirs, "src", true));
+        assert!(!matches(&EntryFilter::Dirs, "main.rs", false));
+        assert!(matches(&EntryFilter::Files, "main.rs", false));
+
+        let filter = EntryFilter::extensions(&[".rs".to_string(), "TOML".to_string()]).unwrap();
+        assert!(matches(&filter, "main.rs", false));
+        assert!(matches(&filter, "Cargo.toml", false));
+        assert!(!matches(&filter, "Cargo.lock", false));
+        assert!(!matches(&filter, "Makefile", false));
+        assert_eq!(filter.to_string(), "*.rs *.TOML");
+
+        assert_eq!(EntryFilter::extensions(&[]), None);
+    }
+
+    #[test]
+    fn test_recent_filter() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let filter = EntryFilter::Recent(Duration::from_secs(2 * 60 * 60));
+        let matches = |is_dir, age_secs| {
+            filter.matches(
+                Path::new("download.zip"),
+                is_dir,
+                Some(now - Duration::from_secs(age_secs)),
+                now,
+            )
+        };
+
+        assert!(matches(false, 0));
+        assert!(matches(false, 2 * 60 * 60));
+        assert!(!matches(false, 2 * 60 * 60 + 1));
+        assert!(!matches(true, 0));
+        assert!(!filter.matches(Path::new("download.zip"), false, None, now));
+        assert!(filter.matches(
+            Path::new("download.zip"),
+            false,
+            Some(now + Duration::from_secs(60)),
+            now
+        ));
+
+        assert_eq!(filter.to_string(), "recent 2h");
+        assert_eq!(
+            EntryFilter::Recent(Duration::from_secs(3 * 24 * 60 * 60)).to_string(),
+            "recent 3d"
+        );
+        assert_eq!(
+            EntryFilter::Recent(Duration::from_secs(90 * 60)).to_string(),
+            "recent 90m"
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// The layout and drawing helpers for the gallery command, which shows the images in a directory as
+// a grid of thumbnails. Each thumbnail takes up a tile, with the image's name below it and a gap
+// to the right and below.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use image::{ImageFormat, RgbaImage};
+use rolf_grid::{Color, LineBuilder, Style};
+
+// The size of a tile in cells, including the gaps
+pub const TILE_WIDTH: u16 = 18;
+pub const TILE_HEIGHT: u16 = 9;
+
+// The size of the thumbnail within a tile, in cells
+pub const THUMBNAIL_WIDTH: u16 = TILE_WIDTH - 2;
+pub const THUMBNAIL_HEIGHT: u16 = TILE_HEIGHT - 2;
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase())
+        .is_some_and(|ext| matches!(ext.as_str(), "png" | "jpg" | "jpeg"))
+}
+
+/// Whether at least half of the files (not counting directories) are images
+pub fn is_mostly_images(image_count: usize, file_count: usize) -> bool {
+    image_count > 0 && image_count * 2 >= file_count
+}
+
+/// Where the thumbnail of an image at the given size is kept in thumbnail_dir. The name also
+/// depends on the image's modification time and size, so a changed image gets a new thumbnail.
+pub fn thumbnail_cache_path(
+    thumbnail_dir: &Path,
+    path: &Path,
+    max_width: u32,
+    max_height: u32,
+) -> Option<PathBuf> {
+    let metadata = fs::metadata(path).ok()?;
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    metadata.modified().ok().hash(&mut hasher);
+    metadata.len().hash(&mut hasher);
+    (max_width, max_height).hash(&mut hasher);
+
+    Some(thumbnail_dir.join(format!("{:016x}.png", hasher.finish())))
+}
+
+pub fn save_thumbnail(thumbnail: &RgbaImage, cache_path: &Path) -> image::ImageResult<()> {
+    if let Some(thumbnail_dir) = cache_path.parent() {
+        fs::create_dir_all(thumbnail_dir)?;
+    }
+
+    // NOTE(Chris): The thumbnail is written next to where it goes and then renamed, so that a
+    // half-written file is never read back
+    let temp_path = cache_path.with_extension("png.part");
+    thumbnail.save_with_format(&temp_path, ImageFormat::Png)?;
+    fs::rename(&temp_path, cache_path)?;
+
+    Ok(())
+}
+
+/// How many tiles fit across and down the given number of cells, which is always at least one
+pub fn grid_size(width: u16, height: u16) -> (usize, usize) {
+    (
+        (width / TILE_WIDTH).max(1) as usize,
+        (height / TILE_HEIGHT).max(1) as usize,
+    )
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Moves the cursor count tiles in the direction, stopping at the first and last images
+pub fn move_cursor(
+    cursor: usize,
+    len: usize,
+    columns: usize,
+    direction: Direction,
+    count: usize,
+) -> usize {
+    let last = len.saturating_sub(1);
+
+    match direction {
+        Direction::Left => cursor.saturating_sub(count),
+        Direction::Right => cursor.saturating_add(count).min(last),
+        Direction::Up => {
+            let rows_up = count.min(cursor / columns);
+            cursor - rows_up * columns
+        }
+        // NOTE(Chris): Moving down from a column that's past the end of the last row lands on
+        // the last image, like in most file managers' icon views
+        Direction::Down => cursor
+            .saturating_add(count.saturating_mul(columns))
+            .min(last),
+    }
+}
+
+/// Scrolls the grid so that the cursor's row is visible
+pub fn scroll_to_cursor(top_row: &mut usize, cursor: usize, columns: usize, rows: usize) {
+    let cursor_row = cursor / columns;
+
+    if cursor_row < *top_row {
+        *top_row = cursor_row;
+    } else if cursor_row >= *top_row + rows {
+        *top_row = cursor_row + 1 - rows;
+    }
+}
+
+/// Draws an image with "▀" characters, each of which shows two pixels by coloring its
+/// foreground with the upper one and its background with the lower one. This is used when there's
+/// no image protocol to draw with.
+pub fn half_blocks(image: &RgbaImage) -> Vec<LineBuilder> {
+    (0..image.height())
+        .step_by(2)
+        .map(|y| {
+            let mut line_builder = LineBuilder::new();
+
+            for x in 0..image.width() {
+                let upper = to_color(image.get_pixel(x, y).0);
+                let lower = if y + 1 < image.height() {
+                    to_color(image.get_pixel(x, y + 1).0)
+                } else {
+                    Color::Background
+                };
+
+                line_builder.push('▀', Style::new_color(upper, lower));
+            }
+
+            line_builder
+        })
+        .collect()
+}
+
+// Picks the closest color in the terminal's 256-color palette, leaving (mostly) transparent pixels
+// as the background
+fn to_color([red, green, blue, alpha]: [u8; 4]) -> Color {
+    if alpha < 128 {
+        return Color::Background;
+    }
+
+    // NOTE(Chris): Grays get the 24-step gray ramp, which is finer than the color cube's grays
+    if red == green && green == blue {
+        return match red {
+            0..=7 => Color::Ansi(16),
+            249..=255 => Color::Ansi(231),
+            gray => Color::Ansi(232 + ((gray as u16 - 8) * 24 / 241) as u8),
+        };
+    }
+
+    // The 6x6x6 color cube, whose levels are 0, 95, 135, 175, 215, and 255
+    let to_level = |value: u8| match value {
+        0..=47 => 0,
+        48..=114 => 1,
+        value => (value - 35) / 40,
+    };
+
+    Color::Ansi(16 + 36 * to_level(red) + 6 * to_level(green) + to_level(blue))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_move_cursor() {
+        // 10 images in rows of 4:
+        //  0 1 2 3
+        //  4 5 6 7
+        //  8 9
+        assert_eq!(move_cursor(5, 10, 4, Direction::Up, 1), 1);
+        assert_eq!(move_cursor(5, 10, 4, Direction::Up, 3), 1);
+        assert_eq!(move_cursor(1, 10, 4, Direction::Up, 1), 1);
+        assert_eq!(move_cursor(5, 10, 4, Direction::Down, 1), 9);
+        assert_eq!(move_cursor(6, 10, 4, Direction::Down, 1), 9);
+        assert_eq!(move_cursor(1, 10, 4, Direction::Down, 1), 5);
+        assert_eq!(move_cursor(4, 10, 4, Direction::Left, 1), 3);
+        assert_eq!(move_cursor(0, 10, 4, Direction::Left, 1), 0);
+        assert_eq!(move_cursor(9, 10, 4, Direction::Right, 1), 9);
+        assert_eq!(move_cursor(3, 10, 4, Direction::Right, 2), 5);
+    }
+
+    #[test]
+    fn test_thumbnail_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let image_path = dir.path().join("image.png");
+        let thumbnail_dir = dir.path().join("thumbnails");
+
+        fs::write(&image_path, "not really a png").unwrap();
+
+        let cache_path = thumbnail_cache_path(&thumbnail_dir, &image_path, 16, 14).unwrap();
+        assert_eq!(
+            thumbnail_cache_path(&thumbnail_dir, &image_path, 16, 14),
+            Some(cache_path.clone())
+        );
+        assert_ne!(
+            thumbnail_cache_path(&thumbnail_dir, &image_path, 32, 28),
+            Some(cache_path.clone())
+        );
+        assert_eq!(
+            thumbnail_cache_path(&thumbnail_dir, &dir.path().join("missing.png"), 16, 14),
+            None
+        );
+
+        let thumbnail = RgbaImage::from_pixel(2, 3, image::Rgba([10, 20, 30, 255]));
+        save_thumbnail(&thumbnail, &cache_path).unwrap();
+        assert_eq!(image::open(&cache_path).unwrap().to_rgba8(), thumbnail);
+
+        // A changed image gets a new thumbnail
+        fs::write(&image_path, "still not really a png").unwrap();
+        assert_ne!(
+            thumbnail_cache_path(&thumbnail_dir, &image_path, 16, 14),
+            Some(cache_path)
+        );
+    }
+
+    #[test]
+    fn test_scroll_to_cursor() {
+        let mut top_row = 0;
+
+        scroll_to_cursor(&mut top_row, 9, 4, 2);
+        assert_eq!(top_row, 1);
+
+        scroll_to_cursor(&mut top_row, 5, 4, 2);
+        assert_eq!(top_row, 1);
+
+        scroll_to_cursor(&mut top_row, 0, 4, 2);
+        assert_eq!(top_row, 0);
+    }
+
+    #[test]
+    fn test_grid_size() {
+        assert_eq!(grid_size(80, 22), (4, 2));
+        assert_eq!(grid_size(10, 5), (1, 1));
+    }
+
+    #[test]
+    fn test_is_mostly_images() {
+        assert!(is_mostly_images(2, 4));
+        assert!(!is_mostly_images(1, 4));
+        assert!(!is_mostly_images(0, 0));
+
+        assert!(is_image(Path::new("a/photo.JPG")));
+        assert!(!is_image(Path::new("a/notes.txt")));
+        assert!(!is_image(Path::new("a/png")));
+    }
+
+    #[test]
+    fn test_to_color() {
+        assert_eq!(to_color([0, 0, 0, 255]), Color::Ansi(16));
+        assert_eq!(to_color([255, 255, 255, 255]), Color::Ansi(231));
+        assert_eq!(to_color([128, 128, 128, 255]), Color::Ansi(243));
+        assert_eq!(to_color([255, 0, 0, 255]), Color::Ansi(196));
+        assert_eq!(to_color([95, 135, 175, 255]), Color::Ansi(67));
+        assert_eq!(to_color([255, 0, 0, 0]), Color::Background);
+    }
+
+    #[test]
+    fn test_half_blocks() {
+        let image = RgbaImage::from_fn(2, 3, |_x, y| {
+            if y == 0 {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 0, 0, 255])
+            }
+        });
+
+        let lines = half_blocks(&image);
+
+        assert_eq!(lines.len(), 2);
+    }
+}
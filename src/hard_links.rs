@@ -0,0 +1,142 @@
+// Finds the other names of a file with hard links, for the find-hardlinks command. Every link to a
+// file has the same inode on the same device, so the only way to find them is to look through a
+// whole tree for that inode.
+
+use crate::os_abstract::{get_device_id, get_file_id};
+
+use ignore::WalkBuilder;
+
+use std::path::{Path, PathBuf};
+
+/// Looks through the tree under root for the paths with the given device and inode, stopping once
+/// link_count of them have been found (since that's all of them). Directories which can't be read
+/// are skipped, and symlinks aren't followed. With ignore_vcs, the files git ignores are skipped
+/// too.
+pub fn find_hard_links(
+    root: &Path,
+    dev: u64,
+    ino: u64,
+    link_count: u64,
+    ignore_vcs: bool,
+) -> Vec<PathBuf> {
+    let mut links = vec![];
+
+    // NOTE(Chris): Hard links can't cross file systems, so there's no need to look through
+    // anything mounted in the tree
+    let walker = WalkBuilder::new(root)
+        .standard_filters(ignore_vcs)
+        .hidden(false)
+        .filter_entry(move |entry| {
+            entry.depth() == 0
+                || entry
+                    .metadata()
+                    .is_ok_and(|metadata| get_device_id(&metadata) == dev)
+        })
+        .build();
+
+    for entry in walker.filter_map(Result::ok) {
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if !metadata.is_dir() && get_file_id(&metadata) == ino {
+            links.push(entry.into_path());
+
+            if links.len() as u64 >= link_count {
+                break;
+            }
+        }
+    }
+
+    links.sort();
+
+    links
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_hard_links() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        fs::create_dir_all(root.join("a/b")).unwrap();
+        fs::write(root.join("original"), "contents").unwrap();
+        fs::write(root.join("unrelated"), "contents").unwrap();
+        fs::hard_link(root.join("original"), root.join("a/b/link")).unwrap();
+        std::os::unix::fs::symlink(root.join("original"), root.join("a/symlink")).unwrap();
+
+        let metadata = fs::metadata(root.join("original")).unwrap();
+        assert_eq!(metadata.nlink(), 2);
+
+        assert_eq!(
+            find_hard_links(
+                root,
+                metadata.dev(),
+                metadata.ino(),
+                metadata.nlink(),
+                false
+            ),
+            [root.join("a/b/link"), root.join("original")]
+        );
+
+        // Only the links inside the root are found
+        assert_eq!(
+            find_hard_links(
+                &root.join("a"),
+                metadata.dev(),
+                metadata.ino(),
+                metadata.nlink(),
+                false
+            ),
+            [root.join("a/b/link")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_hard_links_ignore_vcs() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+
+        // NOTE(Chris): A .gitignore only counts inside a git repository
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "build/\n").unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("original"), "contents").unwrap();
+        fs::hard_link(root.join("original"), root.join("build/link")).unwrap();
+        fs::hard_link(root.join("original"), root.join("src/link")).unwrap();
+
+        let metadata = fs::metadata(root.join("original")).unwrap();
+        let find = |ignore_vcs| {
+            find_hard_links(
+                root,
+                metadata.dev(),
+                metadata.ino(),
+                metadata.nlink(),
+                ignore_vcs,
+            )
+        };
+
+        assert_eq!(find(true), [root.join("original"), root.join("src/link")]);
+        assert_eq!(
+            find(false),
+            [
+                root.join("build/link"),
+                root.join("original"),
+                root.join("src/link")
+            ]
+        );
+    }
+}
@@ -0,0 +1,80 @@
+// User commands which are run by the shell when something happens in rolf, like lf's on-cd. Hooks
+// run without a terminal, so they're meant for integrations (like updating a tmux status line)
+// rather than for printing anything.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+// What a hook can see of rolf's state, through its environment
+pub struct HookContext {
+    pub current_dir: PathBuf,
+    pub current_file: Option<PathBuf>,
+    pub selections: Vec<PathBuf>,
+}
+
+/// Builds the command for a hook. Like the commands of open rules, the current file is available
+/// as $f, and the selected files are available as $fs, separated by newlines.
+pub fn hook_command(hook: &str, context: &HookContext) -> Command {
+    let mut command = Command::new("sh");
+
+    command
+        .arg("-c")
+        .arg(hook)
+        .current_dir(&context.current_dir)
+        .env("PWD", &context.current_dir)
+        .env(
+            "f",
+            context
+                .current_file
+                .as_deref()
+                .unwrap_or_else(|| Path::new("")),
+        )
+        .env("fs", join_paths(&context.selections))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    command
+}
+
+fn join_paths(paths: &[PathBuf]) -> OsString {
+    let mut result = OsString::new();
+
+    for (i, path) in paths.iter().enumerate() {
+        if i > 0 {
+            result.push("\n");
+        }
+
+        result.push(path);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_hook_command() {
+        let context = HookContext {
+            current_dir: PathBuf::from("/home/user"),
+            current_file: Some(PathBuf::from("/home/user/notes.txt")),
+            selections: vec![PathBuf::from("/home/user/a"), PathBuf::from("/home/user/b")],
+        };
+
+        let command = hook_command("echo \"$f\"", &context);
+
+        assert_eq!(command.get_current_dir(), Some(Path::new("/home/user")));
+
+        let envs: Vec<_> = command.get_envs().collect();
+        assert!(envs.contains(&(OsStr::new("f"), Some(OsStr::new("/home/user/notes.txt")))));
+        assert!(envs.contains(&(
+            OsStr::new("fs"),
+            Some(OsStr::new("/home/user/a\n/home/user/b"))
+        )));
+    }
+}
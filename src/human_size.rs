@@ -1,3 +1,13 @@
+use crate::config::SizeUnits;
+
+/// Writes a size in bytes the way the size-units option asks for.
+pub fn format_size(bytes: u64, units: SizeUnits) -> String {
+    match units {
+        SizeUnits::Metric => human_size(bytes),
+        SizeUnits::Iec => human_size_iec(bytes),
+    }
+}
+
 // This function should behave identically to lf's humanize function.
 // This function converts a size in bytes to a human readable form using metric
 // suffixes (e.g. 1K = 1000). For values less than 10 the first significant
@@ -25,12 +35,58 @@ pub fn human_size(bytes: u64) -> String {
     }
 
     if bytes < 10.0 {
-        return format!("{0:.1}{1}", bytes - 0.0499, UNITS[u - 1]);
+        format!("{0:.1}{1}", bytes - 0.0499, UNITS[u - 1])
+    } else {
+        format!("{0:.0}{1}", bytes - 0.0499, UNITS[u - 1])
+    }
+}
+
+// Like human_size, but with binary suffixes (e.g. 1KiB = 1024), so that sizes match tools like
+// `ls -lh`. Numbers are always rounded down.
+fn human_size_iec(bytes: u64) -> String {
+    const THRESH: u64 = 1024;
+    const UNITS: [&str; 6] = ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+
+    if bytes < THRESH {
+        return format!("{}B", bytes);
+    }
+
+    let mut u = 0;
+    let mut unit_size = THRESH;
+
+    while u + 1 < UNITS.len() && bytes / unit_size >= THRESH {
+        unit_size *= THRESH;
+        u += 1;
+    }
+
+    // NOTE(Chris): Integer division keeps values like 1048575 from rounding up to "1024KiB"
+    let whole = bytes / unit_size;
+
+    if whole < 10 {
+        let tenths = (bytes as u128 * 10 / unit_size as u128) % 10;
+
+        format!("{}.{}{}", whole, tenths, UNITS[u])
     } else {
-        return format!("{0:.0}{1}", bytes - 0.0499, UNITS[u - 1]);
+        format!("{}{}", whole, UNITS[u])
     }
 }
 
+/// Writes a count with commas between each group of three digits, like "3,214".
+pub fn group_digits(count: u64) -> String {
+    let digits = count.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push(',');
+        }
+
+        result.push(digit);
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,4 +98,34 @@ mod tests {
         assert_eq!(human_size(150), "150B");
         assert_eq!(human_size(40075164), "40M");
     }
+
+    #[test]
+    fn test_format_size_boundaries() {
+        assert_eq!(format_size(0, SizeUnits::Metric), "0B");
+        assert_eq!(format_size(999, SizeUnits::Metric), "999B");
+        assert_eq!(format_size(1000, SizeUnits::Metric), "1.0K");
+        assert_eq!(format_size(1023, SizeUnits::Metric), "1.0K");
+        assert_eq!(format_size(1024, SizeUnits::Metric), "1.0K");
+
+        assert_eq!(format_size(0, SizeUnits::Iec), "0B");
+        assert_eq!(format_size(1000, SizeUnits::Iec), "1000B");
+        assert_eq!(format_size(1023, SizeUnits::Iec), "1023B");
+        assert_eq!(format_size(1024, SizeUnits::Iec), "1.0KiB");
+        assert_eq!(format_size(1535, SizeUnits::Iec), "1.4KiB");
+        assert_eq!(format_size(10 * 1024 - 1, SizeUnits::Iec), "9.9KiB");
+        assert_eq!(format_size(10 * 1024, SizeUnits::Iec), "10KiB");
+        assert_eq!(format_size(1024 * 1024 - 1, SizeUnits::Iec), "1023KiB");
+        assert_eq!(format_size(1024 * 1024, SizeUnits::Iec), "1.0MiB");
+        assert_eq!(format_size(3148408, SizeUnits::Iec), "3.0MiB");
+        assert_eq!(format_size(1 << 40, SizeUnits::Iec), "1.0TiB");
+        assert_eq!(format_size(u64::MAX, SizeUnits::Iec), "15EiB");
+    }
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(group_digits(0), "0");
+        assert_eq!(group_digits(999), "999");
+        assert_eq!(group_digits(3214), "3,214");
+        assert_eq!(group_digits(1234567), "1,234,567");
+    }
 }
@@ -0,0 +1,264 @@
+// Runs file operations over whole trees (like copying or deleting a directory) on a bounded
+// number of threads. Fast disks finish sooner with several files in flight, while hard drives and
+// network shares thrash, so the number of threads comes from the io-threads option.
+
+use std::fs;
+use std::io;
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+
+// A worker takes files from the queue until they add up to this many bytes, so that a tree of
+// small files doesn't cost a trip to the queue for each one
+const BATCH_SIZE: u64 = 4 * 1024 * 1024;
+const MAX_BATCH_LEN: usize = 64;
+
+/// Runs the job on every item, on up to thread_count threads. The size of each item decides how
+/// many go in a batch. Jobs report their progress through the function they're given, which
+/// reaches on_progress on this thread. Stops at the first error, returning it.
+pub fn run_batched<T, S, J>(
+    items: &[T],
+    thread_count: usize,
+    size: S,
+    job: J,
+    on_progress: &mut dyn FnMut(u64),
+) -> io::Result<()>
+where
+    T: Sync,
+    S: Fn(&T) -> u64 + Sync,
+    J: Fn(&T, &dyn Fn(u64)) -> io::Result<()> + Sync,
+{
+    let next_index = Mutex::new(0);
+    let has_failed = AtomicBool::new(false);
+    let first_error = Mutex::new(None);
+
+    let take_batch = || {
+        let mut next_index = next_index.lock().unwrap();
+
+        let start = *next_index;
+        let mut batch_size = 0;
+
+        while *next_index < items.len()
+            && *next_index - start < MAX_BATCH_LEN
+            && (*next_index == start || batch_size + size(&items[*next_index]) <= BATCH_SIZE)
+        {
+            batch_size += size(&items[*next_index]);
+            *next_index += 1;
+        }
+
+        &items[start..*next_index]
+    };
+
+    let (progress_tx, progress_rx) = mpsc::channel();
+
+    thread::scope(|scope| {
+        // NOTE(Chris): There's no point in starting more threads than there are items
+        for _ in 0..thread_count.clamp(1, items.len().max(1)) {
+            let progress_tx = progress_tx.clone();
+            let report = move |bytes| {
+                let _ = progress_tx.send(bytes);
+            };
+
+            scope.spawn(|| {
+                let report = report;
+
+                loop {
+                    let batch = take_batch();
+                    if batch.is_empty() {
+                        break;
+                    }
+
+                    for item in batch {
+                        if has_failed.load(Ordering::Acquire) {
+                            return;
+                        }
+
+                        if let Err(err) = job(item, &report) {
+                            has_failed.store(true, Ordering::Release);
+                            first_error.lock().unwrap().get_or_insert(err);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+
+        // NOTE(Chris): The workers hold the only other senders, so this ends once they're done
+        drop(progress_tx);
+        for bytes in progress_rx {
+            on_progress(bytes);
+        }
+    });
+
+    match first_error.into_inner().unwrap() {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+/// Like fs::remove_dir_all, but removes the files on up to thread_count threads. Symlinks are
+/// removed rather than followed.
+pub fn remove_dir_all(path: &Path, thread_count: usize) -> io::Result<()> {
+    // NOTE(Chris): Like fs::remove_dir_all, only the symlink goes if it points to a directory
+    if path.symlink_metadata()?.file_type().is_symlink() {
+        return fs::remove_file(path);
+    }
+
+    let mut files = vec![];
+    let mut dirs = vec![];
+
+    list_tree(path, &mut files, &mut dirs)?;
+
+    run_batched(
+        &files,
+        thread_count,
+        |_| 0,
+        |file, _| match fs::remove_file(file) {
+            // NOTE(Chris): Something else may be removing files from the tree too
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        },
+        &mut |_| (),
+    )?;
+
+    // NOTE(Chris): Directories are listed before their contents, so they're removed in reverse
+    for dir in dirs.iter().rev() {
+        fs::remove_dir(dir)?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TreeSize {
+    // Every file that isn't a directory, including symlinks
+    pub file_count: u64,
+    pub byte_count: u64,
+}
+
+impl AddAssign for TreeSize {
+    fn add_assign(&mut self, other: TreeSize) {
+        self.file_count += other.file_count;
+        self.byte_count += other.byte_count;
+    }
+}
+
+/// Counts the files in a tree (or just the one file) and the bytes in them. Symlinks aren't
+/// followed, and anything which can't be read is left out.
+pub fn tree_size(path: &Path) -> TreeSize {
+    let metadata = match path.symlink_metadata() {
+        Ok(metadata) => metadata,
+        Err(_) => return TreeSize::default(),
+    };
+
+    if !metadata.is_dir() {
+        return TreeSize {
+            file_count: 1,
+            byte_count: metadata.len(),
+        };
+    }
+
+    let mut size = TreeSize::default();
+
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.filter_map(Result::ok) {
+            size += tree_size(&entry.path());
+        }
+    }
+
+    size
+}
+
+// Lists every file (including symlinks) and directory in a tree, with each directory before its
+// contents
+fn list_tree(path: &Path, files: &mut Vec<PathBuf>, dirs: &mut Vec<PathBuf>) -> io::Result<()> {
+    dirs.push(path.to_path_buf());
+
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+
+        if entry.file_type()?.is_dir() {
+            list_tree(&entry.path(), files, dirs)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_batched() {
+        let items: Vec<u64> = (1..=100).collect();
+        let mut total = 0;
+
+        run_batched(
+            &items,
+            4,
+            |&item| item,
+            |&item, report| {
+                report(item);
+                Ok(())
+            },
+            &mut |bytes| total += bytes,
+        )
+        .unwrap();
+
+        assert_eq!(total, 5050);
+
+        let result = run_batched(
+            &items,
+            4,
+            |_| 0,
+            |&item, _| match item {
+                50 => Err(io::Error::other("fifty")),
+                _ => Ok(()),
+            },
+            &mut |_| (),
+        );
+
+        assert_eq!(result.unwrap_err().to_string(), "fifty");
+    }
+
+    #[test]
+    fn test_tree_size() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        fs::write(dir.path().join("a/one.txt"), "1").unwrap();
+        fs::write(dir.path().join("a/b/two.txt"), "22").unwrap();
+
+        assert_eq!(
+            tree_size(&dir.path().join("a")),
+            TreeSize {
+                file_count: 2,
+                byte_count: 3,
+            }
+        );
+        assert_eq!(tree_size(&dir.path().join("missing")), TreeSize::default());
+    }
+
+    #[test]
+    fn test_remove_dir_all() {
+        let dir = tempfile::tempdir().unwrap();
+        let tree = dir.path().join("tree");
+
+        fs::create_dir_all(tree.join("a/b/c")).unwrap();
+        for i in 0..20 {
+            fs::write(tree.join(format!("a/b/{}.txt", i)), "").unwrap();
+        }
+        fs::write(tree.join("a/b/c/deep.txt"), "").unwrap();
+
+        remove_dir_all(&tree, 3).unwrap();
+
+        assert!(!tree.exists());
+        assert!(dir.path().exists());
+    }
+}
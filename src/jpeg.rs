@@ -0,0 +1,84 @@
+// Decodes jpgs with mozjpeg (which is built on libjpeg-turbo), when rolf is built with the
+// "turbojpeg" feature. Besides being much faster than the image crate's decoder, libjpeg can scale
+// an image down by eighths while decoding it, so a large photo never has to be decoded at its full
+// size just to be shrunk for the preview.
+
+use std::io;
+use std::panic;
+
+use image::{DynamicImage, RgbaImage};
+
+/// Decodes a jpg, scaled down as far as it can be while still covering max_width or max_height
+/// (in pixels), so that it can be fitted into that space afterward.
+pub fn decode(bytes: &[u8], max_width: u32, max_height: u32) -> io::Result<DynamicImage> {
+    // NOTE(Chris): mozjpeg reports errors in a corrupt jpg by panicking
+    panic::catch_unwind(|| {
+        let mut decompress = mozjpeg::Decompress::new_mem(bytes)?;
+
+        let (width, height) = decompress.size();
+        decompress.scale(scale_numerator(
+            (width as u32, height as u32),
+            (max_width, max_height),
+        ));
+
+        let mut decompress = decompress.rgba()?;
+
+        let (width, height) = (decompress.width() as u32, decompress.height() as u32);
+        let pixels = decompress.read_scanlines::<u8>()?;
+
+        decompress.finish()?;
+
+        RgbaImage::from_raw(width, height, pixels)
+            .map(DynamicImage::ImageRgba8)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "truncated jpg"))
+    })
+    .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::InvalidData, "corrupt jpg")))
+}
+
+// The smallest scale (in eighths) at which an image is still at least as big as it will be when
+// it's fitted into the given size
+fn scale_numerator((width, height): (u32, u32), (max_width, max_height): (u32, u32)) -> u8 {
+    (1..8)
+        .find(|&numerator| {
+            // NOTE(Chris): A fitted image is limited by only one of its sides, so only one side
+            // needs to be big enough
+            width * u32::from(numerator) >= max_width * 8
+                || height * u32::from(numerator) >= max_height * 8
+        })
+        .unwrap_or(8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::GenericImageView;
+
+    #[test]
+    fn test_scale_numerator() {
+        // A 6000x4000 photo shown in a 750x500 preview can be decoded at an eighth of its size
+        assert_eq!(scale_numerator((6000, 4000), (750, 500)), 1);
+        assert_eq!(scale_numerator((6000, 4000), (751, 501)), 2);
+        // A wide preview is limited by its height instead
+        assert_eq!(scale_numerator((6000, 4000), (3000, 1000)), 2);
+        // Images smaller than the preview are never scaled up
+        assert_eq!(scale_numerator((300, 200), (750, 500)), 8);
+    }
+
+    #[test]
+    fn test_decode() {
+        let mut bytes = vec![];
+        image::jpeg::JpegEncoder::new(&mut bytes)
+            .encode(&[128; 64 * 32 * 3], 64, 32, image::ColorType::Rgb8)
+            .unwrap();
+
+        assert_eq!(decode(&bytes, 8, 4).unwrap().dimensions(), (8, 4));
+        assert_eq!(decode(&bytes, 20, 12).unwrap().dimensions(), (24, 12));
+        assert_eq!(decode(&bytes, 640, 320).unwrap().dimensions(), (64, 32));
+    }
+
+    #[test]
+    fn test_decode_corrupt() {
+        assert!(decode(b"\xff\xd8\xff\xe0 not really a jpg", 100, 100).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+// Compares two texts line by line for the diff command. Like `diff -u`, only the changed lines are
+// shown, along with a few unchanged lines around them.
+
+use std::fmt;
+
+const CONTEXT_LINES: usize = 3;
+
+// NOTE(Chris): Lines are matched up with a table as big as the product of the line counts of the
+// parts which differ (after the common start and end are skipped), so large files which are very
+// different aren't compared
+const MAX_TABLE_SIZE: usize = 4_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    // Starts a group of nearby changes, like "@@ -3,7 +3,8 @@"
+    Hunk(String),
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DiffLine::Hunk(header) => write!(f, "{}", header),
+            DiffLine::Same(line) => write!(f, " {}", line),
+            DiffLine::Removed(line) => write!(f, "-{}", line),
+            DiffLine::Added(line) => write!(f, "+{}", line),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Finds the changes from the old text to the new one, which are empty if the texts have the
+/// same lines. Returns None if the texts are too big and different to compare.
+pub fn diff_lines(old: &str, new: &str) -> Option<Vec<DiffLine>> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+
+    let edits = find_edits(&old_lines, &new_lines)?;
+
+    let mut diff_lines = vec![];
+
+    // The number of old and new lines before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in &edits {
+        positions.push((old_pos, new_pos));
+
+        match edit {
+            Edit::Same => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Removed => old_pos += 1,
+            Edit::Added => new_pos += 1,
+        }
+    }
+    positions.push((old_pos, new_pos));
+
+    let changed_indices: Vec<usize> = edits
+        .iter()
+        .enumerate()
+        .filter(|(_index, edit)| **edit != Edit::Same)
+        .map(|(index, _edit)| index)
+        .collect();
+
+    let mut group_start = 0;
+    while group_start < changed_indices.len() {
+        // NOTE(Chris): Changes close enough for their context to touch are shown together
+        let mut group_end = group_start + 1;
+        while group_end < changed_indices.len()
+            && changed_indices[group_end] - changed_indices[group_end - 1] <= 2 * CONTEXT_LINES + 1
+        {
+            group_end += 1;
+        }
+
+        let start = changed_indices[group_start].saturating_sub(CONTEXT_LINES);
+        let end = (changed_indices[group_end - 1] + CONTEXT_LINES + 1).min(edits.len());
+
+        diff_lines.push(DiffLine::Hunk(hunk_header(
+            positions[start],
+            positions[end],
+        )));
+
+        for index in start..end {
+            let (old_pos, new_pos) = positions[index];
+
+            diff_lines.push(match edits[index] {
+                Edit::Same => DiffLine::Same(old_lines[old_pos].to_string()),
+                Edit::Removed => DiffLine::Removed(old_lines[old_pos].to_string()),
+                Edit::Added => DiffLine::Added(new_lines[new_pos].to_string()),
+            });
+        }
+
+        group_start = group_end;
+    }
+
+    Some(diff_lines)
+}
+
+// Like "@@ -3,7 +3,8 @@", given the numbers of old and new lines before and after the hunk
+fn hunk_header(start: (usize, usize), end: (usize, usize)) -> String {
+    // NOTE(Chris): Like diff, an empty range is numbered by the line before it
+    let range = |start: usize, end: usize| {
+        let count = end - start;
+
+        if count == 0 {
+            format!("{},0", start)
+        } else {
+            format!("{},{}", start + 1, count)
+        }
+    };
+
+    format!(
+        "@@ -{} +{} @@",
+        range(start.0, end.0),
+        range(start.1, end.1)
+    )
+}
+
+// The shortest edits turning the old lines into the new ones, from their longest common
+// subsequence
+fn find_edits(old_lines: &[&str], new_lines: &[&str]) -> Option<Vec<Edit>> {
+    let prefix_len = old_lines
+        .iter()
+        .zip(new_lines)
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+    let suffix_len = old_lines[prefix_len..]
+        .iter()
+        .rev()
+        .zip(new_lines[prefix_len..].iter().rev())
+        .take_while(|(old_line, new_line)| old_line == new_line)
+        .count();
+
+    let old_middle = &old_lines[prefix_len..old_lines.len() - suffix_len];
+    let new_middle = &new_lines[prefix_len..new_lines.len() - suffix_len];
+
+    let width = new_middle.len() + 1;
+    let table_size = (old_middle.len() + 1).checked_mul(width)?;
+    if table_size > MAX_TABLE_SIZE {
+        return None;
+    }
+
+    // The length of the longest common subsequence of old_middle[i..] and new_middle[j..] is at
+    // i * width + j
+    let mut lcs_lens = vec![0u32; table_size];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lcs_lens[i * width + j] = if old_middle[i] == new_middle[j] {
+                lcs_lens[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs_lens[(i + 1) * width + j].max(lcs_lens[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut edits = vec![Edit::Same; prefix_len];
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() && j < new_middle.len() {
+        if old_middle[i] == new_middle[j] {
+            edits.push(Edit::Same);
+            i += 1;
+            j += 1;
+        } else if lcs_lens[(i + 1) * width + j] >= lcs_lens[i * width + j + 1] {
+            edits.push(Edit::Removed);
+            i += 1;
+        } else {
+            edits.push(Edit::Added);
+            j += 1;
+        }
+    }
+    edits.extend(std::iter::repeat_n(Edit::Removed, old_middle.len() - i));
+    edits.extend(std::iter::repeat_n(Edit::Added, new_middle.len() - j));
+
+    edits.extend(std::iter::repeat_n(Edit::Same, suffix_len));
+
+    Some(edits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nb\nC\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+
+        let diff: Vec<String> = diff_lines(old, new)
+            .unwrap()
+            .iter()
+            .map(|diff_line| diff_line.to_string())
+            .collect();
+
+        assert_eq!(
+            diff,
+            [
+                "@@ -1,6 +1,6 @@",
+                " a",
+                " b",
+                "-c",
+                "+C",
+                " d",
+                " e",
+                " f",
+                "@@ -10,3 +10,4 @@",
+                " j",
+                " k",
+                " l",
+                "+m",
+            ]
+        );
+
+        assert_eq!(diff_lines(old, old), Some(vec![]));
+
+        let diff: Vec<String> = diff_lines("", "x\n")
+            .unwrap()
+            .iter()
+            .map(|diff_line| diff_line.to_string())
+            .collect();
+        assert_eq!(diff, ["@@ -0,0 +1,1 @@", "+x"]);
+    }
+}
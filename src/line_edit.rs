@@ -61,6 +61,176 @@ pub fn find_next_word_pos(input_line: &str, cursor_index: usize) -> usize {
     position
 }
 
+pub fn prev_char_pos(input_line: &str, cursor_index: usize) -> usize {
+    match input_line[..cursor_index].chars().next_back() {
+        Some(ch) => cursor_index - ch.len_utf8(),
+        None => cursor_index,
+    }
+}
+
+pub fn next_char_pos(input_line: &str, cursor_index: usize) -> usize {
+    match input_line[cursor_index..].chars().next() {
+        Some(ch) => cursor_index + ch.len_utf8(),
+        None => cursor_index,
+    }
+}
+
+// Returns the number of terminal columns taken up by a character.
+// NOTE(Chris): This only covers the most common double-width ranges (CJK, Hangul, fullwidth forms,
+// and emoji), treating everything else as a single column.
+pub fn char_width(ch: char) -> usize {
+    match ch as u32 {
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x2FFFD
+        | 0x30000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+pub fn str_width(string: &str) -> usize {
+    string.chars().map(char_width).sum()
+}
+
+// Returns the (byte) index of the first character to display when only `view_width` columns are
+// available, scrolling the previous view offset just enough to keep the cursor visible.
+pub fn scroll_view_offset(
+    input_line: &str,
+    cursor_index: usize,
+    view_offset: usize,
+    view_width: usize,
+) -> usize {
+    let mut view_offset = view_offset.min(cursor_index);
+
+    // NOTE(Chris): The cursor needs a column of its own, even at the end of the line
+    while view_offset < cursor_index
+        && str_width(&input_line[view_offset..cursor_index]) >= view_width
+    {
+        view_offset = next_char_pos(input_line, view_offset);
+    }
+
+    // Scroll back if there's room, such as after deleting characters
+    while view_offset > 0 {
+        let prev_offset = prev_char_pos(input_line, view_offset);
+
+        if str_width(&input_line[prev_offset..]) >= view_width {
+            break;
+        }
+
+        view_offset = prev_offset;
+    }
+
+    view_offset
+}
+
+// The number of kills which are remembered
+const KILL_RING_SIZE: usize = 16;
+
+// Text removed by the kill commands (like Ctrl-K or Ctrl-W), which can be yanked back in
+pub struct KillRing {
+    kills: Vec<String>,
+}
+
+impl KillRing {
+    pub fn new() -> Self {
+        KillRing { kills: vec![] }
+    }
+
+    pub fn push(&mut self, killed: String) {
+        if killed.is_empty() {
+            return;
+        }
+
+        if self.kills.len() == KILL_RING_SIZE {
+            self.kills.remove(0);
+        }
+
+        self.kills.push(killed);
+    }
+
+    pub fn last(&self) -> Option<&str> {
+        self.kills.last().map(|killed| killed.as_str())
+    }
+}
+
+// Removes the text in the given range, returning it
+fn kill_range(input_line: &mut String, range: std::ops::Range<usize>) -> String {
+    input_line.drain(range).collect()
+}
+
+// Kills from the start of the line to the cursor (Ctrl-U). The cursor ends up at the start.
+pub fn kill_to_start(input_line: &mut String, cursor_index: usize) -> String {
+    kill_range(input_line, 0..cursor_index)
+}
+
+// Kills from the cursor to the end of the line (Ctrl-K)
+pub fn kill_to_end(input_line: &mut String, cursor_index: usize) -> String {
+    kill_range(input_line, cursor_index..input_line.len())
+}
+
+// Kills the word before the cursor (Ctrl-W or Alt-Backspace), returning the killed text and the
+// new cursor position
+pub fn kill_prev_word(input_line: &mut String, cursor_index: usize) -> (String, usize) {
+    let word_start = find_prev_word_pos(input_line, cursor_index);
+
+    (kill_range(input_line, word_start..cursor_index), word_start)
+}
+
+// Kills the word after the cursor (Alt-D)
+pub fn kill_next_word(input_line: &mut String, cursor_index: usize) -> String {
+    let word_end = find_next_word_pos(input_line, cursor_index);
+
+    kill_range(input_line, cursor_index..word_end)
+}
+
+// Inserts previously killed text at the cursor (Ctrl-Y), returning the new cursor position
+pub fn yank(input_line: &mut String, cursor_index: usize, killed: &str) -> usize {
+    input_line.insert_str(cursor_index, killed);
+
+    cursor_index + killed.len()
+}
+
+// Swaps the character before the cursor with the one under it, moving the cursor forward (Ctrl-T).
+// At the end of the line, the last two characters are swapped instead, like in readline. Returns
+// the new cursor position.
+pub fn transpose_chars(input_line: &mut String, cursor_index: usize) -> usize {
+    if cursor_index == 0 {
+        return cursor_index;
+    }
+
+    let right_end = if cursor_index == input_line.len() {
+        cursor_index
+    } else {
+        next_char_pos(input_line, cursor_index)
+    };
+    let middle = prev_char_pos(input_line, right_end);
+    let left_start = prev_char_pos(input_line, middle);
+
+    if left_start == middle {
+        return cursor_index;
+    }
+
+    let swapped = format!(
+        "{}{}",
+        &input_line[middle..right_end],
+        &input_line[left_start..middle]
+    );
+    input_line.replace_range(left_start..right_end, &swapped);
+
+    right_end
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -83,4 +253,115 @@ mod tests {
 
         assert_eq!(find_next_word_pos("this is", 7), 7);
     }
+
+    #[test]
+    fn test_char_pos() {
+        assert_eq!(prev_char_pos("añb", 3), 1);
+        assert_eq!(next_char_pos("añb", 1), 3);
+        assert_eq!(prev_char_pos("añb", 0), 0);
+        assert_eq!(next_char_pos("añb", 4), 4);
+    }
+
+    #[test]
+    fn test_str_width() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("日本"), 4);
+    }
+
+    #[test]
+    fn test_scroll_view_offset() {
+        // Everything fits
+        assert_eq!(scroll_view_offset("abcd", 4, 0, 10), 0);
+
+        // The cursor at the end of the line needs its own column
+        assert_eq!(scroll_view_offset("abcdef", 6, 0, 4), 3);
+
+        // Moving the cursor left of the view scrolls back to it
+        assert_eq!(scroll_view_offset("abcdefgh", 1, 5, 4), 1);
+
+        // Wide characters take up two columns
+        assert_eq!(scroll_view_offset("日本語", 9, 0, 4), 6);
+
+        // After deleting, the view scrolls back to fill the space
+        assert_eq!(scroll_view_offset("abc", 3, 2, 10), 0);
+    }
+
+    #[test]
+    fn test_kill_ring() {
+        let mut kill_ring = KillRing::new();
+        assert_eq!(kill_ring.last(), None);
+
+        kill_ring.push("first".to_string());
+        kill_ring.push("".to_string());
+        assert_eq!(kill_ring.last(), Some("first"));
+
+        for i in 0..KILL_RING_SIZE {
+            kill_ring.push(i.to_string());
+        }
+        assert_eq!(kill_ring.kills.len(), KILL_RING_SIZE);
+        assert_eq!(kill_ring.kills[0], "0");
+        assert_eq!(kill_ring.last(), Some("15"));
+    }
+
+    #[test]
+    fn test_kill_to_start() {
+        let mut line = "search foo".to_string();
+        assert_eq!(kill_to_start(&mut line, 7), "search ");
+        assert_eq!(line, "foo");
+    }
+
+    #[test]
+    fn test_kill_to_end() {
+        let mut line = "search foo".to_string();
+        assert_eq!(kill_to_end(&mut line, 6), " foo");
+        assert_eq!(line, "search");
+    }
+
+    #[test]
+    fn test_kill_prev_word() {
+        let mut line = "rename my_file.txt".to_string();
+        assert_eq!(kill_prev_word(&mut line, 18), ("txt".to_string(), 15));
+        assert_eq!(line, "rename my_file.");
+
+        let mut line = "a b".to_string();
+        assert_eq!(kill_prev_word(&mut line, 0), ("".to_string(), 0));
+        assert_eq!(line, "a b");
+    }
+
+    #[test]
+    fn test_kill_next_word() {
+        let mut line = "this is".to_string();
+        assert_eq!(kill_next_word(&mut line, 0), "this");
+        assert_eq!(line, " is");
+    }
+
+    #[test]
+    fn test_yank() {
+        let mut line = "ab".to_string();
+        assert_eq!(yank(&mut line, 1, "xyz"), 4);
+        assert_eq!(line, "axyzb");
+    }
+
+    #[test]
+    fn test_transpose_chars() {
+        let mut line = "abcd".to_string();
+        assert_eq!(transpose_chars(&mut line, 1), 2);
+        assert_eq!(line, "bacd");
+
+        // At the end of the line, the last two characters are swapped
+        assert_eq!(transpose_chars(&mut line, 4), 4);
+        assert_eq!(line, "badc");
+
+        // Nothing happens at the start of the line
+        assert_eq!(transpose_chars(&mut line, 0), 0);
+        assert_eq!(line, "badc");
+
+        let mut line = "añ".to_string();
+        assert_eq!(transpose_chars(&mut line, 3), 3);
+        assert_eq!(line, "ña");
+
+        let mut line = "a".to_string();
+        assert_eq!(transpose_chars(&mut line, 1), 1);
+        assert_eq!(line, "a");
+    }
 }
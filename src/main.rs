@@ -7,46 +7,104 @@
 mod natural_sort; // This declares the existence of the natural_sort module, which searches by
                   // default for natural_sort.rs or natural_sort/mod.rs
 
+mod archive;
+mod attrs;
+mod batch;
+mod batch_rename;
+mod cleanup;
+mod compare_dirs;
 mod config;
+mod dir_counts;
+mod editor;
+mod file_buffer;
+mod filter;
+mod gallery;
+mod hard_links;
+mod hooks;
 mod human_size;
+mod io_pool;
+#[cfg(feature = "turbojpeg")]
+mod jpeg;
+mod kitty;
+mod line_diff;
 mod line_edit;
+mod open_rules;
 mod os_abstract;
+mod positions;
+mod preview_cache;
+mod preview_pool;
+mod privilege;
+mod prompt;
+#[cfg(feature = "s3")]
+mod s3;
+mod scaled_decode;
+mod search;
+#[cfg(feature = "sftp")]
+mod sftp;
 #[cfg(unix)]
 mod strmode;
 mod tiff;
+mod time_format;
+mod tools;
+mod truncate;
+mod unicode_fold;
 #[cfg(unix)]
 mod unix_users;
-
-use config::{get_command_desc, to_string, Config, ImageProtocol};
-use human_size::human_size;
+mod vfs;
+
+use archive::{ArchiveFileSystem, ArchiveKind};
+use attrs::AttributeChanges;
+use batch_rename::Rename;
+use chrono::{DateTime, Local};
+use compare_dirs::{Difference, DirDifference};
+use config::{
+    get_command_desc, to_string, Config, DirPreview, DotfileOrder, ImageProtocol, InfoColumn,
+    InfoDate, PreviewPane, SizeUnits, TimeFormat, Truncation,
+};
+use dir_counts::DirCounts;
+use file_buffer::{ConflictAction, CopyOptions, FileBuffer, PasteMode, PasteProgress};
+use filter::EntryFilter;
+use human_size::{format_size, group_digits};
 use image::png::PngEncoder;
-use natural_sort::cmp_natural;
-use os_abstract::{get_file_id, WindowPixels};
+use io_pool::TreeSize;
+use line_diff::DiffLine;
+use natural_sort::cmp_natural_with;
+use open_rules::OpenRules;
+use os_abstract::{get_file_id, CurrentUser, DiskSpace, WindowPixels};
+use positions::{SavedPosition, SavedPositions};
+use preview_cache::PreviewCache;
+use preview_pool::{PreviewPool, PreviewTicket};
+use prompt::{Prompt, PromptHandle};
+use regex::Regex;
 use scopeguard::defer;
-use tiff::{usizeify, Endian, EntryTag, EntryType, IFDEntry};
+use search::MatchOptions;
+use tools::Tools;
+use vfs::{DirEntryInfo, FileSystem, Metadata, Mount, RecordedFileType};
 
 #[cfg(unix)]
 use strmode::strmode;
-use which::which;
 
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::hash_map::HashMap;
 use std::collections::HashSet;
 use std::env;
-use std::fs::{self, DirEntry, Metadata};
-use std::io::{self, BufRead, BufReader, BufWriter, Seek, StdoutLock, Write};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, StdoutLock, Write};
 use std::path::{self, Path, PathBuf};
 use std::process::Command;
 use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::{channel, sync_channel, Sender, TryRecvError};
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender, TryRecvError};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 use std::vec::Vec;
 
 use image::{ColorType, GenericImageView, ImageBuffer, ImageEncoder, Rgba};
 
 use crossterm::{
     cursor,
-    event::{self, Event, KeyCode, KeyModifiers},
+    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
     queue, style, terminal,
 };
 
@@ -58,7 +116,13 @@ type Screen = rolf_grid::Screen<io::Stdout>;
 // TODO(Chris): Make this configurable rather than hard-coding the constant
 const SCROLL_OFFSET: u16 = 10;
 
-type HandlesVec = Vec<DrawHandle>;
+// The number of threads which generate previews, so that scrolling quickly can't spawn an
+// unbounded number of threads
+const PREVIEW_THREAD_COUNT: usize = 4;
+
+// How long the input thread waits for input before checking whether it's been suspended
+const INPUT_POLL_TIMEOUT: Duration = Duration::from_millis(20);
+
 type SelectionsMap = HashSet<PathBuf>;
 
 macro_rules! send_callback_to_main {
@@ -73,54 +137,182 @@ fn main() -> crossterm::Result<()> {
     let args: Vec<String> = std::env::args().collect();
 
     let mut last_dir_path = None;
+    let mut choose_dir_path = None;
+    let mut choose_files_path = None;
+    let mut start_arg = None;
+    let mut is_batch = false;
+    let mut is_resume = false;
+    let mut is_plain = false;
+    let mut is_check_config = false;
+    let mut is_print_config = false;
+
+    let mut index = 1;
+    while index < args.len() {
+        let arg = &args[index];
+        index += 1;
+
+        let path_arg = match arg.as_str() {
+            "--batch" => {
+                is_batch = true;
+                continue;
+            }
+            "--resume" => {
+                is_resume = true;
+                continue;
+            }
+            "--plain" => {
+                is_plain = true;
+                continue;
+            }
+            "--check-config" => {
+                is_check_config = true;
+                continue;
+            }
+            "--print-config" => {
+                is_print_config = true;
+                continue;
+            }
+            "-last-dir-path" => &mut last_dir_path,
+            "--choosedir" => &mut choose_dir_path,
+            "--choosefiles" => &mut choose_files_path,
+            _ => {
+                if start_arg.is_some() {
+                    eprintln!("rolf: unexpected argument '{}'", arg);
+                    std::process::exit(1);
+                }
 
-    for (index, arg) in args.iter().enumerate() {
-        if arg == "-last-dir-path" {
-            if args.len() - 1 <= index + 1 {
-                last_dir_path = Some(PathBuf::from(args[index + 1].clone()));
-            } else {
-                // TODO(Chris): Show a better startup error
-                return Err(io::Error::from(io::ErrorKind::InvalidInput));
+                start_arg = Some(arg);
+                continue;
             }
+        };
+
+        match args.get(index) {
+            Some(path) => *path_arg = Some(PathBuf::from(path)),
+            // TODO(Chris): Show a better startup error
+            None => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
         }
+
+        index += 1;
     }
 
+    // NOTE(Chris): We start in a directory by making it our working directory, since that's where
+    // both the TUI and batch mode start
+    let start_file = match start_arg.map(|arg| resolve_start_path(arg)) {
+        Some(Ok((start_dir, start_file))) => {
+            env::set_current_dir(&start_dir)?;
+            start_file
+        }
+        Some(Err(message)) => {
+            eprintln!("rolf: {}", message);
+            std::process::exit(1);
+        }
+        None => None,
+    };
+
     let project_name = "rolf";
-    let config_dir = os_abstract::config_dir(project_name);
+    let config_dir = match env::var_os("ROLF_CONFIG_DIR") {
+        Some(config_dir) if !config_dir.is_empty() => PathBuf::from(config_dir),
+        _ => os_abstract::config_dir(project_name),
+    };
+
+    // NOTE(Chris): This comes before anything is written to the config directory, so that
+    // checking a config (e.g. in a dotfiles repository's CI) never changes it
+    if is_check_config {
+        let errors = check_config(&config_dir);
+
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+
+        if !errors.is_empty() {
+            std::process::exit(1);
+        }
+
+        println!("No problems found in {}", config_dir.display());
+
+        return Ok(());
+    }
 
     if !config_dir.is_dir() {
         fs::create_dir_all(&config_dir)?;
     }
 
-    let config_result = match fs::read_to_string(config_dir.join("config.json")) {
-        Ok(json) => config::parse_config(&json),
-        Err(err) => match err.kind() {
-            io::ErrorKind::NotFound => {
-                // TODO(Chris): Refactor searching for similar data from multiple files into
-                // its own function
-                if let Ok(json) = fs::read_to_string(config_dir.join("config.jsonc")) {
-                    config::parse_config(&json)
-                } else {
-                    Ok(Config::default())
-                }
-            }
-            _ => panic!("Error opening config file: {}", err),
-        },
+    // NOTE(Chris): On the first run, we write commented config files showing every default, so that
+    // new users can see what's available instead of silently getting the defaults
+    let welcome_message = if !is_batch
+        && !is_print_config
+        && is_first_run(&config_dir)
+        && write_sample_configs(&config_dir).is_ok()
+    {
+        Some(format!(
+            "Welcome to rolf! Commented config files were written to {}",
+            config_dir.display()
+        ))
+    } else {
+        None
     };
 
-    let mut config = match config_result {
+    let mut config = match read_config(&config_dir) {
         Ok(config) => config,
-        Err(err) => {
-            eprintln!("{}", err);
+        Err((config_path, err)) => {
+            eprintln!("{}: {}", config_path.display(), err);
             // NOTE(Chris): This won't cause any destructors to call, so we should only create
             // values with "special" destructors after this
             std::process::exit(1);
         }
     };
 
+    if let Err((var_name, err)) = config::apply_env_overrides(&mut config, unicode_env_vars()) {
+        eprintln!("rolf: {}: {}", var_name, err);
+        std::process::exit(1);
+    }
+
+    if config.templates_dir.is_empty() {
+        config.templates_dir = config_dir.join("templates").to_string_lossy().into_owned();
+    }
+
+    let data_dir = match env::var_os("ROLF_DATA_DIR") {
+        Some(data_dir) if !data_dir.is_empty() => PathBuf::from(data_dir),
+        _ => os_abstract::data_dir(project_name),
+    };
+    let last_dir_file = data_dir.join("last-dir");
+    let cache_dir = os_abstract::cache_dir(project_name);
+
+    // NOTE(Chris): A directory given on the command line always wins over resuming
+    if start_arg.is_none() && (is_resume || config.resume) {
+        if let Some(last_dir) = read_last_dir(&last_dir_file) {
+            // NOTE(Chris): If we can't enter the last directory anymore (e.g. its permissions
+            // changed), we just stay in the working directory
+            let _ = env::set_current_dir(last_dir);
+        }
+    }
+
+    if is_batch {
+        let mut script = String::new();
+        io::stdin().read_to_string(&mut script)?;
+
+        let start_dir = env::current_dir()?;
+
+        if let Err(message) =
+            batch::run_batch(config, &start_dir, &data_dir, &script, io::stdout().lock())
+        {
+            eprintln!("rolf: {}", message);
+            std::process::exit(1);
+        }
+
+        return Ok(());
+    }
+
     let term = env::var("TERM").unwrap_or_default();
 
-    if config.image_protocol == ImageProtocol::Auto {
+    if is_plain {
+        config.plain = true;
+    }
+
+    // NOTE(Chris): Images can't be read aloud, and their escape codes confuse dumb terminals
+    if config.plain {
+        config.image_protocol = ImageProtocol::None;
+    } else if config.image_protocol == ImageProtocol::Auto {
         if config::check_iterm_support() {
             config.image_protocol = ImageProtocol::ITerm2;
         } else if term == "xterm-kitty" {
@@ -130,27 +322,39 @@ fn main() -> crossterm::Result<()> {
         }
     }
 
-    let ast = match fs::read_to_string(config_dir.join("rolfrc")) {
-        Ok(config_text) => {
-            // TODO(Chris): Handle error here
-            parse(&config_text).unwrap()
-        }
-        Err(err) => match err.kind() {
-            io::ErrorKind::NotFound => vec![],
-            _ => panic!("Error opening config file: {}", err),
-        },
-    };
+    if is_print_config {
+        print_config(&config, &config_dir, &mut io::stdout().lock())?;
+
+        return Ok(());
+    }
+
+    let (ast, startup_errors) = read_rolfrc(&config_dir);
 
     Screen::activate_direct(&mut w)?;
 
-    let result = run(&mut config, &ast);
+    let result = run(
+        &mut config,
+        &ast,
+        choose_files_path,
+        &data_dir,
+        &cache_dir,
+        start_file,
+        welcome_message,
+        startup_errors,
+    );
 
     Screen::deactivate_direct(&mut w)?;
 
     match result {
         Ok(current_dir) => {
-            if let Some(last_dir_path) = last_dir_path {
-                std::fs::write(last_dir_path, current_dir.to_str().unwrap()).unwrap()
+            // NOTE(Chris): Failing to record the last directory only breaks --resume, which
+            // isn't worth an error on every quit (e.g. with a read-only home directory)
+            let _ = write_last_dir(&last_dir_file, &current_dir);
+
+            // NOTE(Chris): --choosedir behaves just like -last-dir-path, but matches the name
+            // that other tools use when embedding a file manager as a picker
+            for dir_path in last_dir_path.iter().chain(choose_dir_path.iter()) {
+                std::fs::write(dir_path, current_dir.to_str().unwrap()).unwrap()
             }
         }
         Err(err) => panic!("{}", err),
@@ -159,75 +363,385 @@ fn main() -> crossterm::Result<()> {
     Ok(())
 }
 
-// Returns the path to the last dir
-fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf> {
-    let user_name = whoami::username();
+// The config files rolf reads, in order of precedence. Only the first one which exists is used.
+const CONFIG_FILE_NAMES: [&str; 3] = ["config.json", "config.jsonc", "config.toml"];
 
-    let host_name = whoami::hostname();
+fn read_config(config_dir: &Path) -> Result<Config, (PathBuf, config::ConfigError)> {
+    for file_name in CONFIG_FILE_NAMES {
+        let config_path = config_dir.join(file_name);
 
-    let home_name = os_abstract::get_home_name();
+        let config_data = match fs::read_to_string(&config_path) {
+            Ok(config_data) => config_data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => panic!("Error opening config file: {}", err),
+        };
 
-    let home_path = Path::new(&home_name[..]);
+        let result = if file_name.ends_with(".toml") {
+            config::parse_toml_config(&config_data)
+        } else {
+            config::parse_config(&config_data)
+        };
 
-    // NOTE(Chris): The default column ratio is 1:2:3
+        return result.map_err(|err| (config_path, err));
+    }
 
-    let mut fm = FileManager {
-        available_execs: {
-            let mut available_execs: HashMap<&str, std::path::PathBuf> = HashMap::new();
+    Ok(Config::default())
+}
 
-            insert_executable(&mut available_execs, "highlight");
+// Writes the config as rolf will use it, with the environment overrides applied and the image
+// protocol resolved, along with the tools that were found, for --print-config
+fn print_config(config: &Config, config_dir: &Path, w: &mut impl Write) -> io::Result<()> {
+    let config_file = CONFIG_FILE_NAMES
+        .iter()
+        .map(|file_name| config_dir.join(file_name))
+        .find(|config_path| config_path.exists());
 
-            insert_executable(&mut available_execs, "ffmpeg");
+    writeln!(w, "Config directory: {}", config_dir.display())?;
+    match config_file {
+        Some(config_path) => writeln!(w, "Config file: {}", config_path.display())?,
+        None => writeln!(w, "Config file: none (using the defaults)")?,
+    }
 
-            available_execs
-        },
+    writeln!(w, "\nOptions:")?;
+    for (name, value) in config::option_values(config) {
+        writeln!(w, "  {}: {}", name, value)?;
+    }
 
-        image_handles: vec![],
+    let mut layouts: Vec<_> = config.layouts.iter().collect();
+    layouts.sort();
 
-        dir_states: DirStates::new()?,
+    writeln!(w, "\nLayouts:")?;
+    for (name, [first, second, third]) in layouts {
+        writeln!(w, "  {}: {}:{}:{}", name, first, second, third)?;
+    }
 
-        second: ColumnInfo {
-            starting_index: 0,
-            display_offset: 0,
-        },
+    writeln!(w, "\nDirectory previews:")?;
+    for dir_preview in &config.dir_previews {
+        writeln!(w, "  {}: {}", dir_preview.marker, dir_preview.command)?;
+    }
 
-        left_paths: HashMap::new(),
+    let mut keybindings: Vec<_> = config
+        .keybindings
+        .iter()
+        .map(|(key_event, command)| (to_string(*key_event), command))
+        .collect();
+    keybindings.sort();
 
-        match_positions: vec![],
+    writeln!(w, "\nKeybindings:")?;
+    for (key_display, command) in keybindings {
+        writeln!(w, "  {}: {}", key_display, command)?;
+    }
 
-        should_search_forwards: true,
+    let tools = Tools::detect(&config.tool_paths);
 
-        input_line: String::new(),
+    writeln!(w, "\nTools:")?;
+    for tool in &tools::TOOLS {
+        match tools.get(tool.name) {
+            Some(path) => writeln!(w, "  {}: {} ({})", tool.name, path.display(), tool.purpose)?,
+            None => writeln!(w, "  {}: not found ({})", tool.name, tool.purpose)?,
+        }
+    }
 
-        input_cursor: 0,
+    Ok(())
+}
 
-        input_mode: InputMode::Normal,
+// NOTE(Chris): Variables which aren't valid unicode can't be rolf overrides, so we skip them
+// rather than panicking like env::vars() would
+fn unicode_env_vars() -> impl Iterator<Item = (String, String)> {
+    env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+}
 
-        user_host_display: format!("{}@{}", user_name, host_name),
+// Finds every problem with the config file, the environment overrides, and rolfrc, for
+// --check-config. Each one is described along with where it is.
+fn check_config(config_dir: &Path) -> Vec<String> {
+    let mut errors = vec![];
 
-        // Keys are paths, values are indices in their directory
-        selections: HashSet::new(),
+    match read_config(config_dir) {
+        Ok(mut config) => {
+            let mut keybindings: Vec<_> = config
+                .keybindings
+                .iter()
+                .map(|(key_event, command)| (to_string(*key_event), command))
+                .collect();
+            keybindings.sort();
+
+            // NOTE(Chris): A bound command which can't be parsed does nothing when its key is
+            // pressed
+            for (key_display, command) in keybindings {
+                if let Err(err) = parse_statement_from(command) {
+                    errors.push(format!(
+                        "Keybinding for {}: \"{}\": {}",
+                        key_display, command, err
+                    ));
+                }
+            }
 
-        drawing_info: DrawingInfo {
-            win_pixels: os_abstract::get_win_pixels()?,
-            width: 0,
-            height: 0,
-            column_bot_y: 0,
-            column_height: 0,
-            first_left_x: 0,
-            first_right_x: 0,
-            second_left_x: 0,
-            second_right_x: 0,
-            third_left_x: 0,
-            third_right_x: 0,
-        },
+            if let Err((var_name, err)) =
+                config::apply_env_overrides(&mut config, unicode_env_vars())
+            {
+                errors.push(format!("{}: {}", var_name, err));
+            }
+        }
+        Err((config_path, err)) => errors.push(format!("{}: {}", config_path.display(), err)),
+    }
+
+    let rolfrc_path = config_dir.join("rolfrc");
+    let (_, rolfrc_errors) = read_rolfrc(config_dir);
+
+    errors.extend(
+        rolfrc_errors
+            .into_iter()
+            .map(|err| format!("{}: {}", rolfrc_path.display(), err)),
+    );
+
+    errors
+}
+
+// Reads the commands in rolfrc, along with the problems found in them. If rolfrc can't be parsed,
+// none of its commands are run.
+fn read_rolfrc(config_dir: &Path) -> (Program, Vec<String>) {
+    let rolfrc_text = match fs::read_to_string(config_dir.join("rolfrc")) {
+        Ok(rolfrc_text) => rolfrc_text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return (vec![], vec![]),
+        Err(err) => return (vec![], vec![format!("Unable to read rolfrc: {}", err)]),
+    };
+
+    match parse(&rolfrc_text) {
+        Ok(program) => {
+            let errors = check_rolfrc(&program);
+
+            (program, errors)
+        }
+        Err(err) => (vec![], vec![err.to_string()]),
+    }
+}
+
+// The commands that a directory's .rolfrc may use, which only change how the directory is shown
+const LOCAL_ROLFRC_COMMANDS: [&str; 6] = [
+    "set",
+    "only-dirs",
+    "only-files",
+    "only-ext",
+    "recent-files",
+    "clear-filter",
+];
+
+// The commands that work in a mounted directory. Its files aren't on the disk, so the rest (which
+// work on files in place) aren't available, while edit works on a copy that's saved back.
+const MOUNTED_COMMANDS: [&str; 29] = [
+    "cd",
+    "edit",
+    "extract",
+    "quit",
+    "down",
+    "up",
+    "updir",
+    "open",
+    "top",
+    "bottom",
+    "search",
+    "search-back",
+    "search-next",
+    "search-prev",
+    "search-results",
+    "find",
+    "find-back",
+    "nohl",
+    "only-dirs",
+    "only-files",
+    "only-ext",
+    "recent-files",
+    "clear-filter",
+    "clear",
+    "zoom",
+    "layout",
+    "set",
+    "read",
+    "help",
+];
+
+// Reads the .rolfrc in dir (with the local-rolfrc option), which is empty if there isn't one. An
+// error describes why none of it can be run.
+fn read_local_rolfrc(dir: &Path) -> Result<Program, String> {
+    let rolfrc_text = match fs::read_to_string(dir.join(".rolfrc")) {
+        Ok(rolfrc_text) => rolfrc_text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(format!("Unable to read .rolfrc: {}", err)),
+    };
+
+    let program = parse(&rolfrc_text).map_err(|err| format!(".rolfrc: {}", err))?;
+
+    check_local_rolfrc(&program)?;
+
+    Ok(program)
+}
+
+// NOTE(Chris): Anyone who can write to a directory can put a .rolfrc in it, so it's all or
+// nothing, rather than running the commands that are allowed
+fn check_local_rolfrc(program: &Program) -> Result<(), String> {
+    for statement in program {
+        let name = match statement {
+            Statement::Map(_) => "map",
+            Statement::OpenRule(_) => "open",
+            Statement::CommandUse(command_use) => command_use.name.as_str(),
+        };
+
+        let is_allowed =
+            matches!(statement, Statement::CommandUse(_)) && LOCAL_ROLFRC_COMMANDS.contains(&name);
+
+        if !is_allowed {
+            return Err(format!(
+                ".rolfrc: {} isn't allowed in a directory's .rolfrc",
+                name
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// Undoes whatever a directory's .rolfrc changed, once it's no longer the current directory
+fn restore_local_settings(fm: &mut FileManager, tx: &Sender<InputEvent>) {
+    if let Some(local_settings) = fm.local_settings.take() {
+        // NOTE(Chris): Options set by hand while in the directory are undone as well
+        fm.config = local_settings.config;
+        fm.dir_states.sort_options = SortOptions::new(&fm.config);
 
-        config: _config.clone(),
+        set_entry_filter(fm, local_settings.filter, tx);
+    }
+}
+
+// Finds the problems in rolfrc that show up before any command runs, like maps to invalid keys
+fn check_rolfrc(program: &Program) -> Vec<String> {
+    program
+        .iter()
+        .filter_map(|statement| match statement {
+            Statement::Map(map) => config::to_key(&map.key.key).err(),
+            Statement::OpenRule(_) | Statement::CommandUse(_) => None,
+        })
+        .map(|err| err.to_string())
+        .collect()
+}
+
+// Like "rolfrc: Unknown option: colour", which is shown on the first frame
+fn summarize_startup_errors(errors: &[String]) -> Option<String> {
+    if errors.is_empty() {
+        None
+    } else {
+        Some(format!("rolfrc: {}", errors.join("; ")))
+    }
+}
+
+fn is_first_run(config_dir: &Path) -> bool {
+    CONFIG_FILE_NAMES
+        .iter()
+        .chain(["rolfrc"].iter())
+        .all(|file_name| !config_dir.join(file_name).exists())
+}
+
+fn write_sample_configs(config_dir: &Path) -> io::Result<()> {
+    let sample_config = config::sample_config();
+
+    for (file_name, contents) in [
+        ("config.jsonc", sample_config.as_str()),
+        ("rolfrc", config::SAMPLE_ROLFRC),
+    ] {
+        // NOTE(Chris): create_new makes sure we never overwrite a user's file
+        fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(config_dir.join(file_name))?
+            .write_all(contents.as_bytes())?;
+    }
 
-        preview_data: PreviewData::Loading,
+    Ok(())
+}
+
+// Resolves a path given on the command line into the directory to start in and, if the path is a
+// file, the file to put the cursor on
+fn resolve_start_path(arg: &str) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let path = fs::canonicalize(arg).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => format!("cannot start in '{}': no such file or directory", arg),
+        _ => format!("cannot start in '{}': {}", arg, err),
+    })?;
+
+    if path.is_dir() {
+        return Ok((path, None));
+    }
+
+    match path.parent() {
+        Some(parent) => Ok((parent.to_path_buf(), Some(path))),
+        None => Err(format!("cannot start in '{}': not a directory", arg)),
+    }
+}
+
+// Reads the directory rolf was last quit in, if it still exists
+fn read_last_dir(last_dir_file: &Path) -> Option<PathBuf> {
+    let contents = fs::read_to_string(last_dir_file).ok()?;
+    let last_dir = PathBuf::from(contents.trim_end_matches('\n'));
+
+    if last_dir.is_absolute() && last_dir.is_dir() {
+        Some(last_dir)
+    } else {
+        None
+    }
+}
+
+fn write_last_dir(last_dir_file: &Path, last_dir: &Path) -> io::Result<()> {
+    // TODO(Chris): Store paths which aren't valid unicode, too
+    let last_dir = match last_dir.to_str() {
+        Some(last_dir) => last_dir,
+        None => return Ok(()),
     };
 
-    update_drawing_info_from_resize(&mut fm.drawing_info)?;
+    if let Some(data_dir) = last_dir_file.parent() {
+        fs::create_dir_all(data_dir)?;
+    }
+
+    fs::write(last_dir_file, format!("{}\n", last_dir))
+}
+
+// Returns the path to the last dir
+fn run(
+    config: &mut Config,
+    config_ast: &Program,
+    choose_files_path: Option<PathBuf>,
+    data_dir: &Path,
+    cache_dir: &Path,
+    start_file: Option<PathBuf>,
+    mut welcome_message: Option<String>,
+    startup_errors: Vec<String>,
+) -> crossterm::Result<PathBuf> {
+    let home_name = os_abstract::get_home_name();
+
+    let home_path = Path::new(&home_name[..]);
+
+    // NOTE(Chris): The default column ratio is 1:2:3
+
+    let (tx, rx) = channel();
+
+    let mut fm = FileManager::new(
+        config,
+        DirStates::new(env::current_dir()?, SortOptions::new(config), true)?,
+        data_dir,
+        choose_files_path,
+        &tx,
+    );
+    fm.thumbnail_dir = Some(cache_dir.join("thumbnails"));
+
+    let ratios = fm.layout_ratios();
+    update_drawing_info_from_resize(&mut fm.drawing_info, ratios, fm.config.preview_position)?;
+
+    if let Some(start_file) = start_file {
+        let file_id = get_file_id(&fs::metadata(&start_file)?);
+
+        jump_by_file_id(&mut fm, file_id)?;
+        fm.match_positions.clear();
+    } else {
+        let current_dir = fm.dir_states.current_dir.clone();
+        restore_location(&mut fm, &current_dir)?;
+    }
 
     let screen = Screen::new(io::stdout())?;
     // FIXME(Chris): Remove this mutex entirely
@@ -235,65 +749,74 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
 
     let mut command_queue = config_ast.clone();
 
-    let (tx, rx) = channel();
+    // The problems with rolfrc, which are collected until its commands have run in the first
+    // iteration of the input loop
+    let mut startup_errors = Some(startup_errors);
 
     let crossterm_input_tx = tx.clone();
 
-    let (to_input_tx, from_main_rx) = sync_channel(0);
+    let input_suspended = Arc::clone(&fm.input_suspended);
 
     // Crossterm input loop
-    std::thread::spawn(move || {
-        loop {
-            let input_request_count = match from_main_rx.recv() {
-                Ok(InputRequest::RequestNumber(input_request_count)) => input_request_count,
-                Ok(InputRequest::Quit) => break,
-                Err(err) => panic!("Input thread: Lost connnection to main thread: {:?}", err),
-            };
-
-            // NOTE(Chris): We avoid receiving crossterm input with event::read() until something is
-            // sent via this channel. This allows us to open other terminal programs without
-            // interfering with the stdin input stream.
+    std::thread::spawn(move || loop {
+        // NOTE(Chris): While another terminal program is running, we stop receiving crossterm
+        // input so that we don't interfere with its stdin input stream.
+        if input_suspended.load(std::sync::atomic::Ordering::Acquire) {
+            std::thread::sleep(INPUT_POLL_TIMEOUT);
+            continue;
+        }
 
-            // eprintln!(
-            //     "Input thread: input request received: #{}",
-            //     input_request_count
-            // );
+        if !event::poll(INPUT_POLL_TIMEOUT).expect("Unable to poll for crossterm event") {
+            continue;
+        }
 
-            let crossterm_event = event::read().expect("Unable to read crossterm event");
+        // NOTE(Chris): Input may have been suspended while we were polling
+        if input_suspended.load(std::sync::atomic::Ordering::Acquire) {
+            continue;
+        }
 
-            // eprintln!(
-            //     "Input thread: Obtained crossterm event #{:?}: {:?}",
-            //     input_request_count, crossterm_event,
-            // );
+        let crossterm_event = event::read().expect("Unable to read crossterm event");
 
-            crossterm_input_tx
-                .send(InputEvent::CrosstermEvent {
-                    event: crossterm_event,
-                    input_request_count,
-                })
-                .expect("Unable to send on channel");
+        // NOTE(Chris): Sending only fails once the main loop has finished
+        if crossterm_input_tx
+            .send(InputEvent::CrosstermEvent {
+                event: crossterm_event,
+            })
+            .is_err()
+        {
+            break;
         }
     });
 
-    // NOTE(Chris): These will be used to coordinate input requests to the input thread
-    let mut input_request_count = 0;
-    let mut last_recv_req_count = 0;
-
     let mut to_command_tx = None;
 
     // NOTE(Chris): This contains the value of the current_dir PathBuff from the last iteration of
     // the input loop
     let mut prev_current_dir = PathBuf::new();
+    // The directory whose .rolfrc was last checked for
+    let mut local_rolfrc_dir = PathBuf::new();
     let mut prev_second_entry_index = 0;
+    let mut prev_drawing_info = fm.drawing_info;
     let mut prev_input_mode_top = fm.input_mode.to_top();
 
-    // Main input loop
-    'input: loop {
-        let second_entry_index = fm.get_second_entry_index();
+    // NOTE(Chris): Everything needs to be drawn in the first frame
+    let mut dirty = DirtyRegions::all();
+
+    // The rows drawn in the first, second, and third columns
+    let mut column_caches: [ColumnCache; 3] = Default::default();
 
-        let second_bottom_index = fm.second.starting_index + fm.drawing_info.column_height;
+    // An event which was received early in order to check whether drawing can be skipped
+    let mut pending_event: Option<InputEvent> = None;
 
+    // Main input loop
+    'input: loop {
         for stm in &command_queue {
+            // NOTE(Chris): Anything a rolfrc command reports is an error, which would otherwise
+            // be replaced by the next command's
+            if let Some(startup_errors) = &mut startup_errors {
+                startup_errors.extend(fm.status_message.take());
+            }
+
             match stm {
                 Statement::Map(map) => {
                     // TODO(Chris): Display error message for invalid key map
@@ -303,520 +826,382 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                             .insert(key_event, map.cmd_name.clone());
                     }
                 }
+                Statement::OpenRule(open_rule) => {
+                    fm.open_rules.add(&open_rule.pattern, &open_rule.command);
+                }
                 Statement::CommandUse(command_use) => {
                     let command: &str = &command_use.name;
 
                     match fm.input_mode {
                         InputMode::Normal => {
+                            match run_command(
+                                &mut fm,
+                                &tx,
+                                &mut to_command_tx,
+                                Some(&screen),
+                                command_use,
+                            )? {
+                                CommandFlow::Quit => break 'input,
+                                CommandFlow::Pick(paths) => {
+                                    if pick_files(&fm, &paths)? {
+                                        break 'input;
+                                    }
+                                }
+                                CommandFlow::Done
+                                | CommandFlow::Unavailable
+                                | CommandFlow::Unknown => (),
+                            }
+                        }
+                        InputMode::Command { .. } => (),
+                        InputMode::View {
+                            ref mut top_ind,
+                            view_rect,
+                            ref keybindings_vec,
+                        } => match command {
+                            "quit" => {
+                                fm.input_mode = InputMode::Normal;
+                            }
+                            "down" => {
+                                // NOTE(Chris): We subtract 1 to avoid having a possible blank line
+                                // at the bottom of the listed keybindings
+                                let bot_written_y =
+                                    view_rect.top_y + keybindings_vec.len() as u16 - *top_ind - 1;
+
+                                if bot_written_y >= view_rect.bot_y() {
+                                    *top_ind += 1;
+                                }
+                            }
+                            "up" if *top_ind > 0 => {
+                                *top_ind -= 1;
+                            }
+                            _ => (),
+                        },
+                        InputMode::SearchResults {
+                            ref mut top_ind,
+                            ref mut cursor,
+                            view_rect,
+                            ref search_term,
+                            ref results,
+                        } => {
                             match command {
                                 "quit" => {
-                                    break 'input;
+                                    fm.input_mode = InputMode::Normal;
+                                    continue;
                                 }
                                 "down" => {
-                                    cursor_down(&mut fm, second_entry_index, second_bottom_index);
+                                    *cursor = (*cursor + get_count_argument(command_use))
+                                        .min(results.len() - 1);
                                 }
                                 "up" => {
-                                    if !fm.dir_states.current_entries.is_empty() {
-                                        abort_image_handles(&mut fm.image_handles);
-
-                                        if fm.second.display_offset <= (SCROLL_OFFSET)
-                                            && fm.second.starting_index > 0
-                                        {
-                                            fm.second.starting_index -= 1;
-                                        } else if second_entry_index > 0 {
-                                            fm.second.display_offset -= 1;
-                                        }
-                                    }
+                                    *cursor =
+                                        cursor.saturating_sub(get_count_argument(command_use));
                                 }
-                                "updir" => {
-                                    abort_image_handles(&mut fm.image_handles);
-
-                                    let old_current_dir = fm.dir_states.current_dir.clone();
-                                    if !fm.dir_states.current_entries.is_empty() {
-                                        save_location(&mut fm, second_entry_index);
-                                    }
-
-                                    if let Some(parent_dir) = fm.dir_states.prev_dir.clone() {
-                                        set_current_dir(
-                                            parent_dir,
-                                            &mut fm.dir_states,
-                                            &mut fm.match_positions,
-                                        )?;
+                                "top" => {
+                                    *cursor = 0;
+                                }
+                                "bottom" => {
+                                    *cursor = results.len() - 1;
+                                }
+                                "open" => {
+                                    let result = &results[*cursor];
+                                    let file_id = result.file_id;
+                                    let is_hidden = result.index.is_none();
+                                    let search_term = search_term.clone();
+
+                                    fm.input_mode = InputMode::Normal;
+
+                                    // NOTE(Chris): A match hidden by the entry filter can only be
+                                    // shown by removing the filter
+                                    if is_hidden {
+                                        fm.dir_states.filter = None;
+                                        reload_current_dir_prefer_id(&mut fm, file_id, &tx);
+                                    } else {
+                                        jump_by_file_id(&mut fm, file_id)?;
                                     }
 
-                                    fm.second = find_correct_location(
-                                        &fm.left_paths,
-                                        fm.drawing_info.column_height,
-                                        &fm.dir_states.current_dir,
+                                    // NOTE(Chris): Jumping by file id replaces the matches, so
+                                    // search-next carries on from here
+                                    fm.match_positions = find_match_positions(
                                         &fm.dir_states.current_entries,
-                                        &old_current_dir,
+                                        &search_term,
+                                        fm.match_options(false),
                                     );
-                                }
-                                "open" => {
-                                    enter_entry(&mut fm, second_entry_index)?;
-                                }
-                                // NOTE(Chris): lf doesn't actually provide a specific command for this, instead using
-                                // a default keybinding that takes advantage of EDITOR
-                                "edit" => {
-                                    let editor = get_env_editor();
-
-                                    // It'd be nice if we could do breaking on blocks to exit this whole
-                                    // match statement early, but labeling blocks is still in unstable,
-                                    // as seen in https://github.com/rust-lang/rust/issues/48594
-                                    if !editor.is_empty() {
-                                        let selected_entry = &fm.dir_states.current_entries
-                                            [second_entry_index as usize];
-
-                                        let shell_command = format!(
-                                            "{} {}",
-                                            editor,
-                                            selected_entry
-                                                .dir_entry
-                                                .path()
-                                                .to_str()
-                                                .expect("Failed to convert path to string")
-                                        );
-
-                                        let mut screen_lock =
-                                            screen.lock().expect("Failed to lock screen mutex!");
-                                        let screen_lock = &mut *screen_lock;
 
-                                        let stdout = io::stdout();
-                                        let mut stdout_lock = stdout.lock();
-
-                                        enter_shell_command_then_redraw(
-                                            &mut fm,
-                                            screen_lock,
-                                            &mut stdout_lock,
-                                            &tx,
-                                            second_entry_index,
-                                            shell_command,
-                                        )?;
-                                    }
+                                    continue;
                                 }
-                                "edit-sels" => {
-                                    let editor = get_env_editor();
-
-                                    if !editor.is_empty() {
-                                        let mut tmpfile = tempfile::Builder::new()
-                                            .prefix(".tmp.rolf.selections_")
-                                            .rand_bytes(3)
-                                            .tempfile()?;
-
-                                        let file_ref = tmpfile.as_file_mut();
-
-                                        for selection_path in &fm.selections {
-                                            writeln!(
-                                                file_ref,
-                                                "{}",
-                                                selection_path.to_str().unwrap()
-                                            )?;
-                                        }
+                                _ => (),
+                            }
 
-                                        let shell_command = format!(
-                                            "{} {}",
-                                            editor,
-                                            tmpfile.path().to_str().unwrap(),
-                                        );
-
-                                        let mut screen_lock =
-                                            screen.lock().expect("Failed to lock screen mutex!");
-                                        let screen_lock = &mut *screen_lock;
-
-                                        let stdout = io::stdout();
-                                        let mut stdout_lock = stdout.lock();
-
-                                        enter_shell_command_then_redraw(
-                                            &mut fm,
-                                            screen_lock,
-                                            &mut stdout_lock,
-                                            &tx,
-                                            second_entry_index,
-                                            shell_command,
-                                        )?;
-
-                                        tmpfile.seek(io::SeekFrom::Start(0))?;
-
-                                        fm.selections.clear();
+                            scroll_to_cursor(top_ind, *cursor, view_rect);
+                        }
+                        InputMode::RenamePreview {
+                            ref mut top_ind,
+                            view_rect,
+                            ref mut renames,
+                        } => match command {
+                            "quit" => {
+                                fm.input_mode = InputMode::Normal;
+                            }
+                            "down" if *top_ind + (view_rect.height as usize) < renames.len() => {
+                                *top_ind += 1;
+                            }
+                            "up" => {
+                                *top_ind = top_ind.saturating_sub(1);
+                            }
+                            "open" if renames.iter().all(|rename| rename.conflict.is_none()) => {
+                                let renames = std::mem::take(renames);
 
-                                        let file_reader = BufReader::new(&tmpfile);
-                                        for line in file_reader.lines() {
-                                            let line = line?;
-                                            let path = Path::new(&line);
+                                fm.input_mode = InputMode::Normal;
 
-                                            if Path::exists(path) {
-                                                // FIXME(Chris): Change SelectionsMap to not
-                                                // contain any indices
-                                                fm.selections.insert(path.to_path_buf());
-                                            }
-                                        }
-                                    }
+                                make_renames(&mut fm, &renames, &tx);
+                            }
+                            _ => (),
+                        },
+                        InputMode::CompareDirs {
+                            ref mut cursor,
+                            ref left,
+                            ref right,
+                            ref differences,
+                            ..
+                        } => {
+                            match command {
+                                "quit" => {
+                                    fm.input_mode = InputMode::Normal;
+                                    continue;
+                                }
+                                "down" => {
+                                    *cursor = (*cursor + get_count_argument(command_use))
+                                        .min(differences.len() - 1);
+                                }
+                                "up" => {
+                                    *cursor =
+                                        cursor.saturating_sub(get_count_argument(command_use));
                                 }
                                 "top" => {
-                                    if !fm.dir_states.current_entries.is_empty() {
-                                        abort_image_handles(&mut fm.image_handles);
-
-                                        fm.second.starting_index = 0;
-                                        fm.second.display_offset = 0;
-                                    }
+                                    *cursor = 0;
                                 }
                                 "bottom" => {
-                                    if !fm.dir_states.current_entries.is_empty() {
-                                        abort_image_handles(&mut fm.image_handles);
-
-                                        if fm.dir_states.current_entries.len()
-                                            <= (fm.drawing_info.column_height as usize)
-                                        {
-                                            fm.second.starting_index = 0;
-                                            fm.second.display_offset =
-                                                fm.dir_states.current_entries.len() as u16 - 1;
-                                        } else {
-                                            fm.second.display_offset =
-                                                fm.drawing_info.column_height - 1;
-                                            fm.second.starting_index =
-                                                fm.dir_states.current_entries.len() as u16
-                                                    - fm.second.display_offset
-                                                    - 1;
-                                        }
-                                    }
+                                    *cursor = differences.len() - 1;
                                 }
-                                "search" => {
-                                    if command_use.arguments.is_empty() {
-                                        enter_command_mode_with(
-                                            &mut fm,
-                                            "search ",
-                                            ":".to_string(),
-                                            AskingType::Command,
-                                        );
+                                // NOTE(Chris): open copies the file under the cursor to the side
+                                // it's missing from, and paste copies every missing file
+                                "open" | "paste" => {
+                                    let to_copy = if command == "open" {
+                                        vec![differences[*cursor].clone()]
                                     } else {
-                                        let search_term = &command_use.arguments[0];
+                                        differences.clone()
+                                    };
 
-                                        search_in_direction(&mut fm, search_term, true)?;
-                                    }
+                                    copy_missing_files(
+                                        &mut fm.status_message,
+                                        left.clone(),
+                                        right.clone(),
+                                        to_copy,
+                                        CopyOptions {
+                                            mode: fm.config.copy_mode,
+                                            thread_count: fm.config.io_threads,
+                                        },
+                                        fm.config.ignore_vcs,
+                                        &tx,
+                                    );
                                 }
-                                "search-back" => {
-                                    if command_use.arguments.is_empty() {
-                                        enter_command_mode_with(
-                                            &mut fm,
-                                            "search-back ",
-                                            ":".to_string(),
-                                            AskingType::Command,
-                                        );
-                                    } else {
-                                        let search_term = &command_use.arguments[0];
+                                _ => (),
+                            }
 
-                                        search_in_direction(&mut fm, search_term, false)?;
-                                    }
+                            if let InputMode::CompareDirs {
+                                ref mut top_ind,
+                                cursor,
+                                view_rect,
+                                ..
+                            } = fm.input_mode
+                            {
+                                scroll_to_cursor(top_ind, cursor, view_rect);
+                            }
+                        }
+                        InputMode::Diff {
+                            ref mut top_ind,
+                            view_rect,
+                            ref diff_lines,
+                            ..
+                        } => {
+                            let last_top_ind =
+                                diff_lines.len().saturating_sub(view_rect.height as usize);
+
+                            match command {
+                                "quit" => {
+                                    fm.input_mode = InputMode::Normal;
                                 }
-                                "search-next" => {
-                                    search_jump(&mut fm)?;
+                                "down" => {
+                                    *top_ind = (*top_ind + get_count_argument(command_use))
+                                        .min(last_top_ind);
                                 }
-                                "search-prev" => {
-                                    fm.should_search_forwards = !fm.should_search_forwards;
-
-                                    search_jump(&mut fm)?;
-
-                                    fm.should_search_forwards = !fm.should_search_forwards;
+                                "up" => {
+                                    *top_ind =
+                                        top_ind.saturating_sub(get_count_argument(command_use));
                                 }
-                                "toggle" => {
-                                    toggle_selection(&mut fm, second_entry_index);
+                                "top" => {
+                                    *top_ind = 0;
                                 }
-                                "toggle-down" => {
-                                    toggle_selection(&mut fm, second_entry_index);
-
-                                    cursor_down(&mut fm, second_entry_index, second_bottom_index);
+                                "bottom" => {
+                                    *top_ind = last_top_ind;
                                 }
-                                "read" => {
-                                    enter_command_mode_with(
-                                        &mut fm,
-                                        "",
-                                        ":".to_string(),
-                                        AskingType::Command,
-                                    );
+                                _ => (),
+                            }
+                        }
+                        InputMode::Gallery {
+                            ref mut cursor,
+                            ref images,
+                            ref mut full_screen,
+                            view_rect,
+                            ..
+                        } => {
+                            let (columns, _rows) =
+                                gallery::grid_size(view_rect.width, view_rect.height);
+                            let count = get_count_argument(command_use);
+
+                            let direction = match command {
+                                "quit" if *full_screen => {
+                                    *full_screen = false;
+                                    None
                                 }
-                                "rename" => {
-                                    // Get the full path of the current file
-                                    let current_entry_info =
-                                        &fm.dir_states.current_entries[second_entry_index as usize];
-                                    let current_file = &current_entry_info.dir_entry;
-                                    let current_file_path = current_file.path();
-
-                                    let current_metadata = &current_entry_info.metadata;
-                                    let file_id = get_file_id(current_metadata);
-
-                                    enter_command_mode_with(
-                                        &mut fm,
-                                        // TODO(Chris): Get rid of these unwrap calls (at least the OsStr
-                                        // to str conversion one)
-                                        current_file_path.file_name().unwrap().to_str().unwrap(),
-                                        "Rename: ".to_string(),
-                                        AskingType::AdditionalInput,
-                                    );
-
-                                    let (new_tx, to_command_rx) = channel();
-
-                                    to_command_tx = Some(new_tx);
-
-                                    let to_our_tx = tx.clone();
-
-                                    std::thread::spawn(move || {
-                                        defer! {
-                                            quit_command_thread(&to_our_tx);
-                                        }
-
-                                        let new_name: String = to_command_rx.recv().unwrap();
-                                        if new_name.is_empty() {
-                                            return;
-                                        }
-
-                                        to_our_tx
-                                            .send(InputEvent::CommandRequest(
-                                                CommandRequest::ChangePrompt {
-                                                    new_prompt: "Are you sure (y/n)? ".to_string(),
-                                                    ask_for_single_key: true,
-                                                },
-                                            ))
-                                            .expect("Failed to send to main thread");
-                                        let next_input: String = to_command_rx.recv().unwrap();
-                                        if next_input.is_empty() {
-                                            return;
-                                        }
-
-                                        if next_input != "y" {
-                                            return;
-                                        }
-
-                                        // TODO(Chris): Implement some sort of channel-using,
-                                        // function-requiring handling of errors here. This would display
-                                        // errors in the main thread and gracefully clean up this thread
-
-                                        let new_file_path = current_file_path
-                                            .parent()
-                                            .unwrap()
-                                            .join(PathBuf::from(&new_name));
-                                        fs::rename(current_file_path, new_file_path)
-                                            .expect("Failed to rename file");
-
-                                        send_callback_to_main!(&to_our_tx, move |fm| {
-                                            set_current_dir(
-                                                fm.dir_states.current_dir.clone(),
-                                                &mut fm.dir_states,
-                                                &mut fm.match_positions,
-                                            )
-                                            .expect("Failed to update current directory");
-
-                                            jump_by_file_id(fm, file_id)?;
-
-                                            Ok(())
-                                        });
-                                    });
+                                "quit" => {
+                                    fm.input_mode = InputMode::Normal;
+                                    continue;
                                 }
-                                "delete" => {
-                                    'delete_command: loop {
-                                        if fm.selections.is_empty() {
-                                            // Delete the current file
-
-                                            // TODO(Chris): Show some sort of error message here
-                                            if fm.dir_states.current_entries.is_empty() {
-                                                break 'delete_command;
-                                            }
-
-                                            // Get the full path of the current file
-                                            let current_file = &fm.dir_states.current_entries
-                                                [second_entry_index as usize]
-                                                .dir_entry;
-                                            let current_file_path = current_file.path();
-
-                                            enter_command_mode_with(
-                                                &mut fm,
-                                                // NOTE(Chris): We have a single space to ensure that
-                                                // the cursor is a space after the prompt
-                                                " ",
-                                                format!(
-                                                    "Delete '{}' ? (y/n)",
-                                                    &current_file_path
-                                                        .as_os_str()
-                                                        .to_str()
-                                                        .expect("File name not in UTF-8")
-                                                ),
-                                                AskingType::AdditionalInputKey,
-                                            );
-
-                                            let (new_tx, to_command_rx) = channel();
-
-                                            to_command_tx = Some(new_tx);
-
-                                            let to_our_tx = tx.clone();
-
-                                            std::thread::spawn(move || {
-                                                defer! {
-                                                    quit_command_thread(&to_our_tx);
-                                                }
-
-                                                let next_input: String =
-                                                    to_command_rx.recv().unwrap();
-                                                // NOTE(Chris): We potentially have a space after the
-                                                // y, since the starting prompt is a single space
-                                                if next_input != "y" && next_input != " y" {
-                                                    return;
-                                                }
-
-                                                // TODO(Chris): Handle file to be renamed not found
-                                                let old_file_id = get_file_id(
-                                                    &fs::metadata(&current_file_path).unwrap(),
-                                                );
-
-                                                remove_at_path_if_exists(&current_file_path)
-                                                    .expect("Failed to delete file");
-
-                                                let to_our_tx_2 = to_our_tx.clone();
-                                                send_callback_to_main!(&to_our_tx, move |fm| {
-                                                    reload_current_dir_prefer_id(
-                                                        fm,
-                                                        old_file_id,
-                                                        &to_our_tx_2,
-                                                    );
-
-                                                    Ok(())
-                                                });
-                                            });
-                                        } else {
-                                            // Delete the selected files
-
-                                            let selections_len = fm.selections.len();
-                                            enter_command_mode_with(
-                                                &mut fm,
-                                                // NOTE(Chris): We have a single space to ensure that
-                                                // the cursor is a space after the prompt
-                                                " ",
-                                                format!("Delete {} items? (y/n)", selections_len,),
-                                                AskingType::AdditionalInputKey,
-                                            );
-
-                                            // TODO(Chris): Refactor this thread spawning and
-                                            // channel-sending into its own function, as it's now used
-                                            // three times
-                                            let (new_tx, to_command_rx) = channel();
-
-                                            to_command_tx = Some(new_tx);
-
-                                            let to_our_tx = tx.clone();
-
-                                            std::thread::spawn(move || {
-                                                defer! {
-                                                    quit_command_thread(&to_our_tx);
-                                                }
-
-                                                let next_input: String =
-                                                    to_command_rx.recv().unwrap();
-                                                // NOTE(Chris): We potentially have a space after the
-                                                // y, since the starting prompt is a single space
-                                                if next_input != "y" && next_input != " y" {
-                                                    return;
-                                                }
-
-                                                let to_our_tx_2 = to_our_tx.clone();
-                                                send_callback_to_main!(&to_our_tx, move |fm| {
-                                                    let old_file_id =
-                                                        if fm.dir_states.current_entries.is_empty()
-                                                        {
-                                                            0
-                                                        } else {
-                                                            let current_file_path =
-                                                                fm.dir_states.current_entries[fm
-                                                                    .get_second_entry_index()
-                                                                    as usize]
-                                                                    .dir_entry
-                                                                    .path();
-                                                            get_file_id(
-                                                                &fs::metadata(current_file_path)
-                                                                    .unwrap(),
-                                                            )
-                                                        };
-
-                                                    for selection_path in &fm.selections {
-                                                        remove_at_path_if_exists(selection_path)
-                                                            .expect("Failed to delete file");
-                                                    }
-
-                                                    fm.selections.clear();
-
-                                                    reload_current_dir_prefer_id(
-                                                        fm,
-                                                        old_file_id,
-                                                        &to_our_tx_2,
-                                                    );
+                                "updir" => Some(gallery::Direction::Left),
+                                "open" => Some(gallery::Direction::Right),
+                                // NOTE(Chris): The full-screen view goes through the images one
+                                // at a time
+                                "up" if *full_screen => Some(gallery::Direction::Left),
+                                "down" if *full_screen => Some(gallery::Direction::Right),
+                                "up" => Some(gallery::Direction::Up),
+                                "down" => Some(gallery::Direction::Down),
+                                "top" => {
+                                    *cursor = 0;
+                                    None
+                                }
+                                "bottom" => {
+                                    *cursor = images.len() - 1;
+                                    None
+                                }
+                                _ => None,
+                            };
+
+                            if let Some(direction) = direction {
+                                *cursor = gallery::move_cursor(
+                                    *cursor,
+                                    images.len(),
+                                    columns,
+                                    direction,
+                                    count,
+                                );
+                            }
 
-                                                    Ok(())
-                                                });
-                                            });
-                                        }
+                            update_gallery(&mut fm, &tx);
+                        }
+                        InputMode::Log {
+                            ref mut top_ind,
+                            view_rect,
+                        } => {
+                            let last_top_ind =
+                                fm.log.len().saturating_sub(view_rect.height as usize);
 
-                                        break 'delete_command;
-                                    }
+                            match command {
+                                "quit" => {
+                                    fm.input_mode = InputMode::Normal;
                                 }
-                                "help" => {
-                                    let mut keybindings_vec: Vec<(String, String, String)> = fm
-                                        .config
-                                        .keybindings
-                                        .iter()
-                                        .map(|(key_event, command)| {
-                                            (
-                                                to_string(*key_event),
-                                                command.to_owned(),
-                                                get_command_desc(command).to_string(),
-                                            )
-                                        })
-                                        .collect();
-
-                                    keybindings_vec.sort_unstable_by(
-                                        |(_key_display1, command1, _), (_key_display2, command2, _)| {
-                                            command1.cmp(command2)
-                                        },
-                                    );
-
-                                    fm.input_mode = InputMode::View {
-                                        top_ind: 0,
-                                        view_rect: get_help_view_rect(fm.drawing_info),
-                                        keybindings_vec,
-                                    };
+                                "down" => {
+                                    *top_ind = (*top_ind + get_count_argument(command_use))
+                                        .min(last_top_ind);
+                                }
+                                "up" => {
+                                    *top_ind =
+                                        top_ind.saturating_sub(get_count_argument(command_use));
+                                }
+                                "top" => {
+                                    *top_ind = 0;
+                                }
+                                "bottom" => {
+                                    *top_ind = last_top_ind;
                                 }
                                 _ => (),
                             }
                         }
-                        InputMode::Command { .. } => (),
-                        InputMode::View {
+                        InputMode::Attributes {
                             ref mut top_ind,
                             view_rect,
-                            ref keybindings_vec,
-                        } => match command {
-                            "quit" => {
-                                fm.input_mode = InputMode::Normal;
-                            }
-                            "down" => {
-                                // NOTE(Chris): We subtract 1 to avoid having a possible blank line
-                                // at the bottom of the listed keybindings
-                                let bot_written_y =
-                                    view_rect.top_y + keybindings_vec.len() as u16 - *top_ind - 1;
+                            ref lines,
+                            ..
+                        } => {
+                            let last_top_ind =
+                                lines.len().saturating_sub(view_rect.height as usize);
 
-                                if bot_written_y >= view_rect.bot_y() {
-                                    *top_ind += 1;
+                            match command {
+                                "quit" => {
+                                    fm.input_mode = InputMode::Normal;
                                 }
-                            }
-                            "up" => {
-                                if *top_ind > 0 {
-                                    *top_ind -= 1;
+                                "down" => {
+                                    *top_ind = (*top_ind + get_count_argument(command_use))
+                                        .min(last_top_ind);
                                 }
+                                "up" => {
+                                    *top_ind =
+                                        top_ind.saturating_sub(get_count_argument(command_use));
+                                }
+                                "top" => {
+                                    *top_ind = 0;
+                                }
+                                "bottom" => {
+                                    *top_ind = last_top_ind;
+                                }
+                                _ => (),
                             }
-                            _ => (),
-                        },
+                        }
                     }
                 }
             }
         }
 
+        if let Some(mut startup_errors) = startup_errors.take() {
+            startup_errors.extend(fm.status_message.take());
+
+            fm.status_message =
+                summarize_startup_errors(&startup_errors).or_else(|| welcome_message.take());
+        }
+
+        // Any command could change what's on screen, so we just redraw everything
+        if !command_queue.is_empty() {
+            dirty = DirtyRegions::all();
+        }
+
         command_queue.clear();
 
+        // NOTE(Chris): A directory's .rolfrc runs before the directory is drawn, so that its
+        // filter or sort order shows right away. This comes after the queued commands, so that
+        // rolfrc's settings are in place before they're saved for leaving the directory.
+        if fm.dir_states.current_dir != local_rolfrc_dir {
+            local_rolfrc_dir.clone_from(&fm.dir_states.current_dir);
+
+            restore_local_settings(&mut fm, &tx);
+
+            // NOTE(Chris): Only directories on the disk have their .rolfrc read
+            if fm.config.local_rolfrc && !fm.dir_states.is_mounted() {
+                match read_local_rolfrc(&fm.dir_states.current_dir) {
+                    Ok(program) if !program.is_empty() => {
+                        fm.local_settings = Some(LocalSettings {
+                            config: fm.config.clone(),
+                            filter: fm.dir_states.filter.clone(),
+                        });
+
+                        command_queue = program;
+                        continue 'input;
+                    }
+                    Ok(_) => (),
+                    Err(message) => fm.status_message = Some(message),
+                }
+            }
+        }
+
         // TODO(Chris): Move this second_entry_index computation into function
         // NOTE(Chris): Recompute second_entry_index since the relevant values may have
         // been modified
@@ -824,19 +1209,80 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
 
         let input_mode_top = fm.input_mode.to_top();
 
-        let has_changed_entry = fm.dir_states.current_dir != prev_current_dir
-            || second_entry_index != prev_second_entry_index;
-        let has_changed_input_mode = input_mode_top != prev_input_mode_top;
+        // NOTE(Chris): If another key press is already waiting, we skip drawing this frame (and
+        // starting a preview) so that holding down a key doesn't fall behind.
+        if pending_event.is_none() {
+            pending_event = rx.try_recv().ok();
+        }
+
+        let is_key_pending = matches!(
+            pending_event,
+            Some(InputEvent::CrosstermEvent {
+                event: Event::Key(_),
+                ..
+            })
+        );
+
+        let (has_changed_entry, has_changed_input_mode) = if is_key_pending {
+            (false, false)
+        } else {
+            let has_changed_dir = fm.dir_states.current_dir != prev_current_dir;
+            let has_changed_index = second_entry_index != prev_second_entry_index;
+
+            if !fm.config.disk_usage {
+                fm.disk_space = None;
+            } else if has_changed_dir || fm.disk_space.is_none() {
+                // NOTE(Chris): If this fails (e.g. on some network file systems), it's tried
+                // again each frame, which is as cheap as checking once
+                fm.disk_space = os_abstract::get_disk_space(&fm.dir_states.current_dir).ok();
+            }
+
+            if has_changed_dir {
+                fm.is_current_writable = os_abstract::is_writable_dir(&fm.dir_states.current_dir);
+            }
+
+            if has_changed_dir && !fm.config.on_cd.is_empty() {
+                run_hook_in_background(&fm, &tx, "on-cd", &fm.config.on_cd);
+            }
+
+            if (has_changed_dir || has_changed_index) && !fm.config.on_select.is_empty() {
+                fm.select_hook_deadline = Some(Instant::now() + SELECT_HOOK_DELAY);
+            }
+
+            // NOTE(Chris): When the layout changes, the preview has to be generated again for its
+            // new size
+            let has_changed_entry =
+                has_changed_dir || has_changed_index || fm.drawing_info != prev_drawing_info;
+            let has_changed_input_mode = input_mode_top != prev_input_mode_top;
+
+            prev_current_dir.clone_from(&fm.dir_states.current_dir);
+            prev_drawing_info = fm.drawing_info;
+            prev_input_mode_top = input_mode_top;
+            prev_second_entry_index = second_entry_index;
+
+            (has_changed_entry, has_changed_input_mode)
+        };
 
-        prev_current_dir.clone_from(&fm.dir_states.current_dir);
-        prev_input_mode_top = input_mode_top;
-        prev_second_entry_index = second_entry_index;
+        if has_changed_entry || has_changed_input_mode {
+            dirty = DirtyRegions::all();
+        }
 
         // Main drawing code
-        {
+        if dirty.any() && !is_key_pending {
             let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
             let screen_lock = &mut *screen_lock;
-            screen_lock.clear_logical();
+
+            // NOTE(Chris): An inline prompt's input is drawn over the current row, so the columns
+            // are redrawn along with it (and once more when it's gone)
+            let is_inline_input = inline_input_row(&fm).is_some();
+            if (dirty.status || dirty.columns) && (is_inline_input || fm.drawn_inline_input) {
+                dirty.status = true;
+                dirty.columns = true;
+            }
+            fm.drawn_inline_input = is_inline_input;
+
+            // NOTE(Chris): Regions that aren't dirty keep what was drawn in them last frame
+            clear_dirty_regions(screen_lock, &fm.drawing_info, dirty);
 
             // Clear any parts of the screen that need to be manually cleared
             if has_changed_entry || has_changed_input_mode {
@@ -845,8 +1291,13 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                 match fm.config.image_protocol {
                     ImageProtocol::Kitty => {
                         // https://sw.kovidgoyal.net/kitty/graphics-protocol/#deleting-images
-                        let mut w = io::stdout();
-                        w.write_all(b"\x1b_Ga=d;\x1b\\")?; // Delete all visible images
+                        // Delete all visible images
+                        screen_lock.write_raw(0, 0, b"\x1b_Ga=d;\x1b\\".to_vec());
+                    }
+                    ImageProtocol::KittyUnicode if fm.kitty_image_id != 0 => {
+                        let mut raw = vec![];
+                        kitty::delete_image(&mut raw, fm.kitty_image_id)?;
+                        screen_lock.write_raw(0, 0, raw);
                     }
                     ImageProtocol::ITerm2 => {
                         // NOTE(Chris): We don't actually need to do anything here, it seems
@@ -857,118 +1308,52 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
 
             match &fm.input_mode {
                 InputMode::Normal | InputMode::Command { .. } => {
-                    let current_dir_display = format_current_dir(&fm.dir_states, home_path);
-
-                    let curr_entry;
-                    let file_stem = if fm.dir_states.current_entries.len() <= 0 {
-                        ""
-                    } else {
-                        curr_entry = fm.dir_states.current_entries[second_entry_index as usize]
-                            .dir_entry
-                            .file_name();
-                        curr_entry.to_str().unwrap()
-                    };
-
-                    // TODO(Chris): Use the unicode-segmentation package to count graphemes
-                    // Add 1 because of the ':' that is displayed after user_host_display
-                    // Add 1 again because of the '/' that is displayed at the end of current_dir_display
-                    let remaining_width = fm.drawing_info.width as usize
-                        - (fm.user_host_display.len() + 1 + current_dir_display.len() + 1);
-
-                    let file_stem = if file_stem.len() > remaining_width {
-                        String::from(&file_stem[..remaining_width])
-                    } else {
-                        String::from(file_stem)
-                    };
-
-                    let user_host_len = fm.user_host_display.len().try_into().unwrap();
-                    draw_str(
-                        screen_lock,
-                        0,
-                        0,
-                        &fm.user_host_display,
-                        rolf_grid::Style::new(
-                            rolf_grid::Attribute::Bold,
-                            rolf_grid::Color::Green,
-                            rolf_grid::Color::Background,
-                        ),
-                    );
-                    draw_str(
-                        screen_lock,
-                        user_host_len,
-                        0,
-                        ":",
-                        rolf_grid::Style::default(),
-                    );
-                    draw_str(
-                        screen_lock,
-                        user_host_len + 1, // From the ":"
-                        0,
-                        &format!("{}{}", current_dir_display, path::MAIN_SEPARATOR),
-                        rolf_grid::Style::new(
-                            rolf_grid::Attribute::Bold,
-                            rolf_grid::Color::Blue,
-                            rolf_grid::Color::Background,
-                        ),
-                    );
-                    draw_str(
-                        screen_lock,
-                        user_host_len + 1 + current_dir_display.len() as u16 + 1,
-                        0,
-                        &file_stem,
-                        rolf_grid::Style::new(
-                            rolf_grid::Attribute::Bold,
-                            rolf_grid::Color::Foreground,
-                            rolf_grid::Color::Background,
-                        ),
-                    );
-
-                    draw_first_column(screen_lock, &mut fm);
-
-                    // TODO(Chris): Refactor this into FileManager or DrawingInfo
-                    let second_column_rect = Rect {
-                        left_x: fm.drawing_info.second_left_x,
-                        top_y: 1,
-                        width: fm.drawing_info.second_right_x - fm.drawing_info.second_left_x,
-                        height: fm.drawing_info.column_height,
-                    };
+                    if dirty.top_line {
+                        draw_top_line(screen_lock, &fm, home_path, second_entry_index);
+                    }
 
-                    draw_column(
-                        screen_lock,
-                        second_column_rect,
-                        fm.second.starting_index,
-                        second_entry_index,
-                        &fm.dir_states.current_entries,
-                        &fm.selections,
-                    );
+                    if dirty.columns {
+                        request_dir_counts(&mut fm);
+
+                        draw_first_column(screen_lock, &mut fm, &mut column_caches[0]);
+
+                        // TODO(Chris): Refactor this into FileManager or DrawingInfo
+                        let second_column_rect = Rect {
+                            left_x: fm.drawing_info.second_left_x,
+                            top_y: 1,
+                            width: fm.drawing_info.second_right_x - fm.drawing_info.second_left_x,
+                            height: fm.drawing_info.column_height,
+                        };
+
+                        draw_column(
+                            screen_lock,
+                            second_column_rect,
+                            fm.second.starting_index,
+                            second_entry_index,
+                            &fm.dir_states.current_entries,
+                            &fm.selections,
+                            &ListingOptions {
+                                empty_message: empty_message(fm.dir_states.is_current_denied),
+                                ..fm.listing_options(true)
+                            },
+                            &mut column_caches[1],
+                        );
+                    }
 
-                    let third_column_rect = Rect {
-                        left_x: fm.drawing_info.third_left_x,
-                        top_y: 1,
-                        width: fm.drawing_info.third_right_x - fm.drawing_info.third_left_x,
-                        height: fm.drawing_info.column_height,
-                    };
+                    let third_column_rect = get_preview_rect(&fm);
 
-                    if !fm.dir_states.current_entries.is_empty() {
+                    if dirty.preview
+                        && third_column_rect.width > 0
+                        && !fm.dir_states.current_entries.is_empty()
+                    {
                         // NOTE(Chris): We keep this code block before the preview drawing
                         // functionality in order to properly set up the Loading... message.
                         if has_changed_entry {
-                            set_preview_data_with_thread(&mut fm, &tx, second_entry_index);
+                            schedule_preview(&mut fm, &tx, second_entry_index);
                         }
 
-                        // NOTE(Chris): We manually hide the cursor here to avoid showing it when
-                        // manually sending graphics escape codes or writing raw preview data
-                        // TODO(Chris): Figure out how to avoid explicitly hiding the cursor here,
-                        // as this should be automatically handled by our intermediary
-                        // terminal-drawing layer. Maybe using notcurses, rather than rolling our
-                        // own tcell-like API, would help? We'd want the Rust bindings to move
-                        // beyond a development version first, though.
-                        {
-                            let stdout = io::stdout();
-                            let mut w = stdout.lock();
-
-                            queue!(w, cursor::Hide)?;
-                        }
+                        // How much of a text preview is shown, for the preview header
+                        let mut preview_position = None;
 
                         match &fm.preview_data {
                             PreviewData::Loading => {
@@ -1014,6 +1399,8 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                                     entry_index,
                                     entries_info,
                                     &fm.selections,
+                                    &fm.listing_options(false),
+                                    &mut column_caches[2],
                                 );
                             }
                             PreviewData::UncoloredFile { path } => {
@@ -1026,8 +1413,7 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
 
                                         let inner_left_x = fm.drawing_info.third_left_x + 2;
 
-                                        // NOTE(Chris): 1 is the top_y for all columns
-                                        let mut curr_y = 1;
+                                        let mut curr_y = third_column_rect.top_y;
 
                                         let right_most_x = fm.drawing_info.width - 1;
 
@@ -1035,15 +1421,22 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                                         // the right
                                         let third_width = right_most_x - inner_left_x + 1;
 
-                                        for line in reader.lines() {
+                                        let mut lines = reader.lines();
+                                        let mut is_cut_short = false;
+
+                                        for line in lines.by_ref() {
                                             // TODO(Chris): Handle UTF-8 errors here, possibly by just
                                             // showing an error line
                                             let line = match line {
                                                 Ok(line) => line,
-                                                Err(_) => break,
+                                                Err(_) => {
+                                                    is_cut_short = true;
+                                                    break;
+                                                }
                                             };
 
-                                            if curr_y > fm.drawing_info.column_bot_y {
+                                            if curr_y >= third_column_rect.bot_y() {
+                                                is_cut_short = true;
                                                 break;
                                             }
 
@@ -1067,6 +1460,28 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
 
                                             curr_y += 1;
                                         }
+
+                                        if fm.config.preview_header {
+                                            let shown_count =
+                                                (curr_y - third_column_rect.top_y) as usize;
+
+                                            // NOTE(Chris): Counting the rest of a huge file would
+                                            // hold up drawing, so its total is left out
+                                            let line_count = if !is_cut_short {
+                                                Some(shown_count)
+                                            } else if fs::metadata(path).is_ok_and(|metadata| {
+                                                metadata.len() <= PREVIEW_LINE_COUNT_LIMIT
+                                            }) {
+                                                Some(shown_count + 1 + lines.count())
+                                            } else {
+                                                None
+                                            };
+
+                                            preview_position = Some(PreviewPosition {
+                                                shown_count,
+                                                line_count,
+                                            });
+                                        }
                                     }
                                     Err(err) => match err.kind() {
                                         io::ErrorKind::PermissionDenied => {
@@ -1089,7 +1504,15 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                                                 Style::new_attr(rolf_grid::Attribute::Reverse),
                                             );
                                         }
-                                        _ => panic!("Error opening {:?}: {:?}", path, err),
+                                        _ => {
+                                            draw_str(
+                                                screen_lock,
+                                                third_column_rect.left_x + 2,
+                                                third_column_rect.top_y,
+                                                &format!("unable to preview: {}", err),
+                                                Style::new_attr(rolf_grid::Attribute::Reverse),
+                                            );
+                                        }
                                     },
                                 }
                             }
@@ -1108,42 +1531,85 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                                     ImageProtocol::Kitty => {
                                         let raw_img = buffer.as_raw();
 
-                                        let stdout = io::stdout();
-                                        let mut w = stdout.lock();
-
                                         let path = store_in_tmp_file(raw_img)?;
 
+                                        let mut raw = vec![];
+
                                         queue!(
-                                            w,
-                                            style::SetAttribute(style::Attribute::Reset),
-                                            cursor::MoveTo(fm.drawing_info.third_left_x, 1),
+                                            raw,
                                             // Hide the "Should display!" / "Loading..." message
                                             style::Print("               "),
-                                            cursor::MoveTo(fm.drawing_info.third_left_x, 1),
+                                            cursor::MoveTo(
+                                                fm.drawing_info.third_left_x,
+                                                third_column_rect.top_y
+                                            ),
                                         )?;
 
                                         // TODO(Chris): Optimize drawing so that we don't need to
                                         // draw to the terminal screen every frame. Perhaps by
                                         // using notcurses, once its Rust bindings are up-to-date?
                                         write!(
-                                            w,
+                                            raw,
                                             "\x1b_Gf=32,s={},v={},a=T,t=t;{}\x1b\\",
                                             buffer.width(),
                                             buffer.height(),
                                             base64::encode(path.to_str().unwrap())
                                         )?;
 
-                                        w.flush()?;
+                                        screen_lock.write_raw(
+                                            fm.drawing_info.third_left_x,
+                                            third_column_rect.top_y,
+                                            raw,
+                                        );
 
                                         set_area_dead(&fm, screen_lock, true);
                                     }
-                                    ImageProtocol::ITerm2 => {
-                                        let rgba = buffer;
-                                        let left_x = fm.drawing_info.third_left_x;
+                                    ImageProtocol::KittyUnicode => {
+                                        // NOTE(Chris): The image was already transmitted when it
+                                        // was loaded, so we only need to draw the placeholder
+                                        // cells, which kitty then replaces with the image.
+                                        let (cols, rows) =
+                                            image_cell_size(&fm.drawing_info, buffer);
+
+                                        let placeholder_style = rolf_grid::Style::new_color(
+                                            rolf_grid::Color::Ansi(fm.kitty_image_id),
+                                            rolf_grid::Color::Background,
+                                        );
 
-                                        let mut png_data = vec![];
-                                        {
-                                            let mut writer = BufWriter::new(&mut png_data);
+                                        for row in 0..rows {
+                                            // NOTE(Chris): Rows past the end of kitty's table
+                                            // can't be told apart, so they're left out
+                                            let Some(row_diacritic) = kitty::diacritic(row) else {
+                                                break;
+                                            };
+
+                                            let y = third_column_rect.top_y + row;
+
+                                            screen_lock.set_cell_marks_clipped(
+                                                third_column_rect.left_x,
+                                                y,
+                                                kitty::PLACEHOLDER,
+                                                [Some(row_diacritic), kitty::diacritic(0)],
+                                                placeholder_style,
+                                            );
+
+                                            for col in 1..cols {
+                                                screen_lock.set_cell_style_clipped(
+                                                    third_column_rect.left_x + col,
+                                                    y,
+                                                    kitty::PLACEHOLDER,
+                                                    placeholder_style,
+                                                );
+                                            }
+                                        }
+                                    }
+                                    ImageProtocol::ITerm2 => {
+                                        let rgba = buffer;
+                                        let left_x = fm.drawing_info.third_left_x;
+
+                                        let mut png_data = vec![];
+                                        {
+                                            let mut writer = BufWriter::new(&mut png_data);
                                             PngEncoder::new(&mut writer)
                                                 .write_image(
                                                     rgba,
@@ -1154,36 +1620,37 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                                                 .unwrap();
                                         }
 
-                                        let stdout = io::stdout();
-                                        let mut w = stdout.lock();
+                                        let mut raw = vec![];
 
                                         if cfg!(windows) {
                                             queue!(
-                                                w,
-                                                cursor::MoveTo(left_x, 1),
+                                                raw,
                                                 style::Print("  "),
+                                                cursor::MoveTo(left_x, third_column_rect.top_y),
                                             )?;
                                         } else {
                                             // By adding 2, we match the location of lf's Loading...
                                             let inner_left_x = left_x + 2;
 
                                             queue!(
-                                                w,
-                                                style::SetAttribute(style::Attribute::Reset),
-                                                cursor::MoveTo(inner_left_x, 1),
+                                                raw,
+                                                cursor::MoveTo(
+                                                    inner_left_x,
+                                                    third_column_rect.top_y
+                                                ),
                                                 style::Print("          "),
-                                                cursor::MoveTo(left_x, 1),
+                                                cursor::MoveTo(left_x, third_column_rect.top_y),
                                             )?;
                                         }
 
                                         write!(
-                                            w,
+                                            raw,
                                             "\x1b]1337;File=size={};inline=1:{}\x1b\\",
                                             png_data.len(),
                                             base64::encode(png_data),
                                         )?;
 
-                                        w.flush()?;
+                                        screen_lock.write_raw(left_x, third_column_rect.top_y, raw);
 
                                         set_area_dead(&fm, screen_lock, true);
                                     }
@@ -1196,29 +1663,25 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                                 }
                             }
                             PreviewData::RawBytes { bytes } => {
-                                let stdout = io::stdout();
-                                let mut w = stdout.lock();
+                                let mut w = vec![];
 
                                 let inner_left_x = fm.drawing_info.third_left_x + 2;
 
                                 queue!(
                                     w,
-                                    style::SetAttribute(style::Attribute::Reset),
-                                    cursor::MoveTo(fm.drawing_info.third_left_x, 1),
                                     // Hide the "Should display!" / "Loading..." message
                                     style::Print("               "),
-                                    cursor::MoveTo(fm.drawing_info.third_left_x, 1),
                                 )?;
 
                                 queue!(&mut w, terminal::DisableLineWrap)?;
 
                                 // TODO(Chris): Handle case when file is not valid utf8
                                 if let Ok(text) = std::str::from_utf8(bytes) {
-                                    let mut curr_y = 1; // Columns start at y = 1
+                                    let mut curr_y = third_column_rect.top_y;
                                     queue!(&mut w, cursor::MoveTo(inner_left_x, curr_y))?;
 
                                     for ch in text.as_bytes() {
-                                        if curr_y > fm.drawing_info.column_bot_y {
+                                        if curr_y >= third_column_rect.bot_y() {
                                             break;
                                         }
 
@@ -1227,19 +1690,44 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
 
                                             queue!(&mut w, cursor::MoveTo(inner_left_x, curr_y))?;
                                         } else {
-                                            // NOTE(Chris): We write directly to stdout so as to
+                                            // NOTE(Chris): We write the raw bytes so as to
                                             // allow the ANSI escape codes to match the end of a
                                             // line
-                                            w.write_all(&[*ch])?;
+                                            w.push(*ch);
                                         }
                                     }
                                 }
 
                                 queue!(&mut w, terminal::EnableLineWrap)?;
 
+                                screen_lock.write_raw(
+                                    fm.drawing_info.third_left_x,
+                                    third_column_rect.top_y,
+                                    w,
+                                );
+
+                                if fm.config.preview_header {
+                                    let line_count = count_lines(bytes);
+
+                                    preview_position = Some(PreviewPosition {
+                                        shown_count: line_count
+                                            .min(third_column_rect.height as usize),
+                                        line_count: Some(line_count),
+                                    });
+                                }
+
                                 set_area_dead(&fm, screen_lock, true);
                             }
                         }
+
+                        if fm.config.preview_header {
+                            draw_preview_header(
+                                screen_lock,
+                                &fm,
+                                second_entry_index,
+                                preview_position,
+                            );
+                        }
                     }
                 }
                 InputMode::View {
@@ -1318,1996 +1806,8608 @@ fn run(_config: &mut Config, config_ast: &Program) -> crossterm::Result<PathBuf>
                         screen_lock.build_line(view_rect.left_x, y, &line_builder);
                     }
                 }
-            }
-
-            // Figure out how to draw bottom line
-            match &fm.input_mode {
-                InputMode::Normal => {
-                    draw_bottom_info_line(screen_lock, &mut fm);
-
-                    screen_lock.hide_cursor();
-                }
-                InputMode::Command { prompt, .. } => {
-                    draw_str(
-                        screen_lock,
-                        0,
-                        fm.drawing_info.height - 1,
-                        prompt,
-                        rolf_grid::Style::default(),
-                    );
+                InputMode::SearchResults {
+                    top_ind,
+                    cursor,
+                    view_rect,
+                    search_term,
+                    results,
+                } => {
+                    set_area_dead(&fm, screen_lock, false);
 
-                    let prompt_len: u16 = prompt.len().try_into().unwrap();
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str("Search results")
+                        .use_style(rolf_grid::Style::default())
+                        .push_str(&format!(" for \"{}\"", search_term));
 
-                    draw_str(
-                        screen_lock,
-                        prompt_len, // We need to make room for the prompt
-                        fm.drawing_info.height - 1,
-                        &fm.input_line,
-                        rolf_grid::Style::default(),
-                    );
+                    screen_lock.build_line(0, 0, &top_line_builder);
 
-                    screen_lock.show_cursor(
-                        (fm.input_cursor + prompt.len()).try_into().unwrap(),
-                        fm.drawing_info.height - 1,
-                    );
-                }
-                InputMode::View {
-                    keybindings_vec, ..
-                } => {
-                    let mut line_builder = LineBuilder::new();
-
-                    let command_space = "   ";
-
-                    let mut quit_key_displays = vec![];
-                    let mut down_key_displays = vec![];
-                    let mut up_key_displays = vec![];
-                    for (key_display, command, _desc) in keybindings_vec {
-                        if command == "quit" {
-                            quit_key_displays.push(key_display.as_str());
-                        } else if command == "down" {
-                            down_key_displays.push(key_display.as_str());
-                        } else if command == "up" {
-                            up_key_displays.push(key_display.as_str());
-                        }
-                    }
+                    // NOTE(Chris): Indices are shown counting from 1, like the bottom info line
+                    let index_column_width = results
+                        .iter()
+                        .filter_map(|result| result.index)
+                        .max()
+                        .map_or(1, |index| (index + 1).to_string().len());
 
-                    quit_key_displays.sort_unstable();
-                    down_key_displays.sort_unstable();
-                    up_key_displays.sort_unstable_by_key(|vec| vec.len());
+                    for (y, (ind, result)) in (view_rect.top_y..view_rect.bot_y())
+                        .zip(results.iter().enumerate().skip(*top_ind))
+                    {
+                        let (index_style, name_style) = if ind == *cursor {
+                            let reverse_style = Style::new_attr(rolf_grid::Attribute::Reverse);
 
-                    if !quit_key_displays.is_empty() {
-                        line_builder.push_str(&quit_key_displays.join(","));
-                        line_builder.push_str(":quit");
-                        line_builder.push_str(command_space);
-                    }
+                            (reverse_style, reverse_style)
+                        } else {
+                            (
+                                rolf_grid::Style::new(
+                                    rolf_grid::Attribute::Bold,
+                                    rolf_grid::Color::BrightCyan,
+                                    rolf_grid::Color::Background,
+                                ),
+                                rolf_grid::Style::default(),
+                            )
+                        };
+
+                        let index_display = result
+                            .index
+                            .map_or("-".to_string(), |index| (index + 1).to_string());
 
-                    if !down_key_displays.is_empty() {
-                        line_builder.push_str(&down_key_displays.join(","));
-                        line_builder.push_str(":scroll_down");
-                        line_builder.push_str(command_space);
-                    }
+                        let mut line_builder = LineBuilder::new();
+                        line_builder.use_style(index_style).push_str(&format!(
+                            "{:>width$}",
+                            index_display,
+                            width = index_column_width
+                        ));
+                        line_builder
+                            .use_style(name_style)
+                            .push_str("  ")
+                            .push_str(&result.name);
+
+                        if result.index.is_none() {
+                            line_builder
+                                .use_style(rolf_grid::Style::new_color(
+                                    rolf_grid::Color::Yellow,
+                                    rolf_grid::Color::Background,
+                                ))
+                                .push_str("  (hidden by filter)");
+                        }
 
-                    if !up_key_displays.is_empty() {
-                        line_builder.push_str(&up_key_displays.join(","));
-                        line_builder.push_str(":scroll_up");
-                        line_builder.push_str(command_space);
+                        screen_lock.build_line(view_rect.left_x, y, &line_builder);
                     }
+                }
+                InputMode::RenamePreview {
+                    top_ind,
+                    view_rect,
+                    renames,
+                } => {
+                    set_area_dead(&fm, screen_lock, false);
 
-                    screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str("Rename preview");
 
-                    screen_lock.hide_cursor();
-                }
-            }
+                    screen_lock.build_line(0, 0, &top_line_builder);
 
-            screen_lock.show()?;
-        }
+                    let arrow = if fm.config.plain { "->" } else { "→" };
 
-        // eprintln!("Main thread: Obtaining event...");
-        let event = match rx.try_recv() {
-            Ok(event) => event,
-            Err(TryRecvError::Empty) => {
-                if input_request_count == last_recv_req_count {
-                    input_request_count += 1;
-                    // eprintln!(
-                    //     "Main thread: Main thread send, request input #{}",
-                    //     input_request_count
-                    // );
-                    to_input_tx
-                        .send(InputRequest::RequestNumber(input_request_count))
-                        .expect("Unable to send to input thread");
-                }
+                    for (y, rename) in
+                        (view_rect.top_y..view_rect.bot_y()).zip(renames.iter().skip(*top_ind))
+                    {
+                        // NOTE(Chris): Selections can be in other directories, which are shown
+                        // relative to this one
+                        let old_name = rename
+                            .from
+                            .strip_prefix(&fm.dir_states.current_dir)
+                            .unwrap_or(&rename.from);
+                        let new_name = rename.to.file_name().unwrap_or_default();
 
-                rx.recv().unwrap()
-            }
-            Err(err) => panic!("Unable to obtain input event: {}", err),
-        };
+                        let mut line_builder = LineBuilder::new();
+                        line_builder
+                            .push_str(&old_name.to_string_lossy())
+                            .push_str(&format!(" {} ", arrow))
+                            .use_style(rolf_grid::Style::new_color(
+                                rolf_grid::Color::Green,
+                                rolf_grid::Color::Background,
+                            ))
+                            .push_str(&new_name.to_string_lossy());
+
+                        if let Some(conflict) = rename.conflict {
+                            line_builder
+                                .use_style(rolf_grid::Style::new_color(
+                                    rolf_grid::Color::Red,
+                                    rolf_grid::Color::Background,
+                                ))
+                                .push_str(&format!("  ({})", conflict));
+                        }
 
-        match event {
-            InputEvent::CrosstermEvent {
-                event,
-                input_request_count,
-            } => {
-                last_recv_req_count = input_request_count;
+                        screen_lock.build_line(view_rect.left_x, y, &line_builder);
+                    }
+                }
+                InputMode::CompareDirs {
+                    top_ind,
+                    cursor,
+                    view_rect,
+                    left,
+                    right,
+                    differences,
+                } => {
+                    set_area_dead(&fm, screen_lock, false);
 
-                match event {
-                    Event::Key(event) => {
-                        match &fm.input_mode {
-                            InputMode::Normal | InputMode::View { .. } => {
-                                if let Some(bound_command) = fm.config.keybindings.get(&event) {
-                                    // TODO(Chris): Show an error message if this bound command
-                                    // fails to parse
-                                    if let Ok(stm) = parse_statement_from(bound_command) {
-                                        command_queue.push(stm);
-                                    }
-                                }
-                            }
-                            InputMode::Command {
-                                prompt: _,
-                                asking_type,
-                            } => {
-                                let asking_type_clone = *asking_type;
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str("Compare")
+                        .use_style(rolf_grid::Style::default())
+                        .push_str(&format!(
+                            " left: {}  right: {}",
+                            left.display(),
+                            right.display()
+                        ));
 
-                                match event.code {
-                                    KeyCode::Esc => {
-                                        leave_command_mode_and_additional_thread(
-                                            &mut fm,
-                                            &to_command_tx,
-                                        );
-                                    }
-                                    KeyCode::Char(ch) => {
-                                        if event.modifiers.contains(KeyModifiers::CONTROL) {
-                                            match ch {
-                                                'b' => {
-                                                    if fm.input_cursor > 0 {
-                                                        fm.input_cursor -= 1;
-                                                    }
-                                                }
-                                                'f' => {
-                                                    if fm.input_cursor < fm.input_line.len() {
-                                                        fm.input_cursor += 1;
-                                                    }
-                                                }
-                                                'a' => fm.input_cursor = 0,
-                                                'e' => fm.input_cursor = fm.input_line.len(),
-                                                'c' => leave_command_mode_and_additional_thread(
-                                                    &mut fm,
-                                                    &to_command_tx,
-                                                ),
-                                                'k' => {
-                                                    fm.input_line = fm
-                                                        .input_line
-                                                        .chars()
-                                                        .take(fm.input_cursor)
-                                                        .collect();
-                                                }
-                                                _ => (),
-                                            }
-                                        } else if event.modifiers.contains(KeyModifiers::ALT) {
-                                            match ch {
-                                                'b' => {
-                                                    fm.input_cursor = line_edit::find_prev_word_pos(
-                                                        &fm.input_line,
-                                                        fm.input_cursor,
-                                                    );
-                                                }
-                                                'f' => {
-                                                    fm.input_cursor = line_edit::find_next_word_pos(
-                                                        &fm.input_line,
-                                                        fm.input_cursor,
-                                                    );
-                                                }
-                                                'd' => {
-                                                    let ending_index =
-                                                        line_edit::find_next_word_pos(
-                                                            &fm.input_line,
-                                                            fm.input_cursor,
-                                                        );
-                                                    fm.input_line.replace_range(
-                                                        fm.input_cursor..ending_index,
-                                                        "",
-                                                    );
-                                                }
-                                                _ => (),
-                                            }
-                                        } else {
-                                            fm.input_line.insert(fm.input_cursor, ch);
+                    screen_lock.build_line(0, 0, &top_line_builder);
 
-                                            fm.input_cursor += 1;
-                                        }
-                                    }
-                                    KeyCode::Enter => {
-                                        match asking_type {
-                                            AskingType::Command => {
-                                                // TODO(Chris): Refactor out this manual checking of "search" or
-                                                // "search-back" somehow
-                                                if let Ok(stm) =
-                                                    parse_statement_from(&fm.input_line)
-                                                {
-                                                    match &stm {
-                                                        Statement::CommandUse(
-                                                            parser::CommandUse { name, arguments },
-                                                        ) => {
-                                                            if !((name == "search"
-                                                                || name == "search-back")
-                                                                && arguments.is_empty())
-                                                            {
-                                                                command_queue.push(stm);
-                                                            }
-                                                        }
-                                                        _ => command_queue.push(stm),
-                                                    }
-                                                }
+                    let difference_column_width = differences
+                        .iter()
+                        .map(|difference| difference.difference.to_string().len())
+                        .max()
+                        .unwrap_or(0);
 
-                                                // In theory, no additional input thread should
-                                                // exist, so we shouldn't need to exit this
-                                                // additional input thread.
-                                                leave_command_mode(&mut fm);
-                                            }
-                                            AskingType::AdditionalInput
-                                            | AskingType::AdditionalInputKey => {
-                                                exit_input_mode_command_thread(
-                                                    &mut fm,
-                                                    &to_command_tx,
-                                                );
-                                            }
-                                        }
-                                    }
-                                    KeyCode::Left => {
-                                        if fm.input_cursor > 0 {
-                                            fm.input_cursor -= 1;
-                                        }
-                                    }
-                                    KeyCode::Right => {
-                                        if fm.input_cursor < fm.input_line.len() {
-                                            fm.input_cursor += 1;
-                                        }
-                                    }
-                                    KeyCode::Backspace => {
-                                        if fm.input_cursor > 0 {
-                                            if event.modifiers.contains(KeyModifiers::ALT) {
-                                                let ending_index = fm.input_cursor;
-                                                fm.input_cursor = line_edit::find_prev_word_pos(
-                                                    &fm.input_line,
-                                                    fm.input_cursor,
-                                                );
-                                                fm.input_line.replace_range(
-                                                    fm.input_cursor..ending_index,
-                                                    "",
-                                                );
-                                            } else {
-                                                fm.input_line.remove(fm.input_cursor - 1);
+                    for (y, (ind, difference)) in (view_rect.top_y..view_rect.bot_y())
+                        .zip(differences.iter().enumerate().skip(*top_ind))
+                    {
+                        let (difference_style, path_style) = if ind == *cursor {
+                            let reverse_style = Style::new_attr(rolf_grid::Attribute::Reverse);
 
-                                                fm.input_cursor -= 1;
-                                            }
-                                        }
-                                    }
-                                    _ => (),
+                            (reverse_style, reverse_style)
+                        } else {
+                            let color = match difference.difference {
+                                Difference::OnlyLeft | Difference::OnlyRight => {
+                                    rolf_grid::Color::Green
                                 }
-
-                                if asking_type_clone == AskingType::AdditionalInputKey {
-                                    exit_input_mode_command_thread(&mut fm, &to_command_tx);
+                                Difference::Kind | Difference::Size | Difference::Content => {
+                                    rolf_grid::Color::Yellow
                                 }
-                            }
-                        }
-                    }
-                    Event::Mouse(_) => (),
-                    Event::Resize(width, height) => {
-                        let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
-                        let screen_lock = &mut *screen_lock;
+                            };
 
-                        // NOTE(Chris): This line should come before we resize anything
-                        set_area_dead(&fm, screen_lock, false);
+                            (
+                                rolf_grid::Style::new_color(color, rolf_grid::Color::Background),
+                                rolf_grid::Style::default(),
+                            )
+                        };
 
-                        screen_lock.resize_clear_draw(width, height)?;
+                        let mut path_display = difference.path.to_string_lossy().into_owned();
+                        if difference.is_dir {
+                            path_display.push(path::MAIN_SEPARATOR);
+                        }
 
-                        update_drawing_info_from_resize(&mut fm.drawing_info)?;
+                        let mut line_builder = LineBuilder::new();
+                        line_builder
+                            .use_style(difference_style)
+                            .push_str(&format!(
+                                "{:<width$}",
+                                difference.difference.to_string(),
+                                width = difference_column_width
+                            ))
+                            .use_style(path_style)
+                            .push_str("  ")
+                            .push_str(&path_display);
 
-                        match fm.input_mode {
-                            InputMode::Normal | InputMode::Command { .. } => (),
-                            InputMode::View {
-                                ref mut view_rect, ..
-                            } => {
-                                *view_rect = get_help_view_rect(fm.drawing_info);
-                            }
-                        }
+                        screen_lock.build_line(view_rect.left_x, y, &line_builder);
                     }
                 }
-            }
-            InputEvent::PreviewLoaded(preview_data) => {
-                fm.preview_data = preview_data;
-            }
-            InputEvent::CommandRequest(command_request) => match command_request {
-                CommandRequest::ChangePrompt {
-                    new_prompt,
-                    ask_for_single_key,
+                InputMode::Diff {
+                    top_ind,
+                    view_rect,
+                    title,
+                    diff_lines,
+                } => {
+                    set_area_dead(&fm, screen_lock, false);
+
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str("Diff")
+                        .use_style(rolf_grid::Style::default())
+                        .push_str(" ")
+                        .push_str(title);
+
+                    screen_lock.build_line(0, 0, &top_line_builder);
+
+                    for (y, diff_line) in
+                        (view_rect.top_y..view_rect.bot_y()).zip(diff_lines.iter().skip(*top_ind))
+                    {
+                        let color = match diff_line {
+                            DiffLine::Hunk(_) => rolf_grid::Color::Cyan,
+                            DiffLine::Same(_) => rolf_grid::Color::Foreground,
+                            DiffLine::Removed(_) => rolf_grid::Color::Red,
+                            DiffLine::Added(_) => rolf_grid::Color::Green,
+                        };
+
+                        // NOTE(Chris): Tabs would move the cursor past cells we've drawn
+                        let text = diff_line.to_string().replace('\t', "    ");
+
+                        let mut line_builder = LineBuilder::new();
+                        line_builder
+                            .use_style(rolf_grid::Style::new_color(
+                                color,
+                                rolf_grid::Color::Background,
+                            ))
+                            .push_str(&text);
+
+                        screen_lock.build_line(view_rect.left_x, y, &line_builder);
+                    }
+                }
+                InputMode::Gallery {
+                    top_row,
+                    cursor,
+                    view_rect,
+                    images,
+                    full_screen,
+                    thumbnails,
+                    full_image,
+                } => {
+                    set_area_dead(&fm, screen_lock, false);
+
+                    if fm.config.image_protocol == ImageProtocol::Kitty {
+                        // NOTE(Chris): Every visible image is drawn again below
+                        screen_lock.write_raw(0, 0, b"\x1b_Ga=d;\x1b\\".to_vec());
+                    }
+
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str("Gallery")
+                        .use_style(rolf_grid::Style::default())
+                        .push_str(" ")
+                        .push_str(&fm.dir_states.current_dir.to_string_lossy());
+
+                    screen_lock.build_line(0, 0, &top_line_builder);
+
+                    if *full_screen {
+                        let image = match full_image {
+                            Some((index, image)) if index == cursor => Ok(image.as_ref()),
+                            _ => Err("Loading..."),
+                        };
+
+                        draw_gallery_image(
+                            screen_lock,
+                            &fm,
+                            view_rect.left_x,
+                            view_rect.top_y,
+                            image,
+                        )?;
+
+                        let name = images[*cursor]
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy();
+
+                        draw_str(
+                            screen_lock,
+                            view_rect.left_x,
+                            view_rect.bot_y() - 1,
+                            &truncate::truncate_name(
+                                &name,
+                                view_rect.width as usize,
+                                fm.config.truncate,
+                                fm.config.plain,
+                            ),
+                            Style::default(),
+                        );
+                    } else {
+                        let (columns, rows) = gallery::grid_size(view_rect.width, view_rect.height);
+
+                        for (index, path) in images
+                            .iter()
+                            .enumerate()
+                            .skip(top_row * columns)
+                            .take(rows * columns)
+                        {
+                            let x =
+                                view_rect.left_x + (index % columns) as u16 * gallery::TILE_WIDTH;
+                            let y = view_rect.top_y
+                                + (index / columns - top_row) as u16 * gallery::TILE_HEIGHT;
+
+                            let image = match thumbnails.get(path) {
+                                Some(thumbnail) => Ok(thumbnail.as_ref()),
+                                None => Err("Loading..."),
+                            };
+
+                            draw_gallery_image(screen_lock, &fm, x, y, image)?;
+
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            let style = if index == *cursor {
+                                Style::new_attr(rolf_grid::Attribute::Reverse)
+                            } else {
+                                Style::default()
+                            };
+
+                            draw_str(
+                                screen_lock,
+                                x,
+                                y + gallery::THUMBNAIL_HEIGHT,
+                                &truncate::truncate_name(
+                                    &name,
+                                    gallery::THUMBNAIL_WIDTH as usize,
+                                    fm.config.truncate,
+                                    fm.config.plain,
+                                ),
+                                style,
+                            );
+                        }
+                    }
+                }
+                InputMode::Attributes {
+                    top_ind,
+                    view_rect,
+                    title,
+                    path,
+                    lines,
                 } => {
-                    if let InputMode::Command {
-                        prompt,
-                        asking_type,
-                    } = &mut fm.input_mode
+                    set_area_dead(&fm, screen_lock, false);
+
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str(title)
+                        .use_style(rolf_grid::Style::default())
+                        .push_str(" ")
+                        .push_str(&path.to_string_lossy());
+
+                    screen_lock.build_line(0, 0, &top_line_builder);
+
+                    for (y, line) in
+                        (view_rect.top_y..view_rect.bot_y()).zip(lines.iter().skip(*top_ind))
+                    {
+                        draw_str(screen_lock, view_rect.left_x, y, line, Style::default());
+                    }
+                }
+                InputMode::Log { top_ind, view_rect } => {
+                    set_area_dead(&fm, screen_lock, false);
+
+                    let mut top_line_builder = LineBuilder::new();
+                    top_line_builder
+                        .push_str("rolf - ")
+                        .use_style(rolf_grid::Style::new_color(
+                            rolf_grid::Color::BrightMagenta,
+                            rolf_grid::Color::Background,
+                        ))
+                        .push_str("Log");
+
+                    {
+                        let preview_cache = fm
+                            .preview_cache
+                            .lock()
+                            .expect("Failed to lock preview cache");
+                        let stats = preview_cache.stats;
+
+                        top_line_builder
+                            .use_style(rolf_grid::Style::default())
+                            .push_str(&format!(
+                                "  preview cache: {} of {} in {} previews, {} hits, {} misses, {} evicted",
+                                format_size(preview_cache.used() as u64, fm.config.size_units),
+                                format_size(preview_cache.budget() as u64, fm.config.size_units),
+                                preview_cache.len(),
+                                stats.hits,
+                                stats.misses,
+                                stats.evictions,
+                            ));
+                    }
+
+                    screen_lock.build_line(0, 0, &top_line_builder);
+
+                    if fm.log.is_empty() {
+                        draw_str(
+                            screen_lock,
+                            view_rect.left_x,
+                            view_rect.top_y,
+                            "nothing has gone wrong",
+                            Style::new_attr(rolf_grid::Attribute::Reverse),
+                        );
+                    }
+
+                    for (y, entry) in
+                        (view_rect.top_y..view_rect.bot_y()).zip(fm.log.iter().skip(*top_ind))
                     {
-                        *prompt = new_prompt;
+                        let mut line_builder = LineBuilder::new();
+                        line_builder
+                            .use_style(rolf_grid::Style::new_color(
+                                rolf_grid::Color::Cyan,
+                                rolf_grid::Color::Background,
+                            ))
+                            .push_str(&entry.time.format("%H:%M:%S").to_string())
+                            .use_style(rolf_grid::Style::default())
+                            .push_str("  ")
+                            .push_str(&entry.message.replace(['\t', '\n'], " "));
+
+                        screen_lock.build_line(view_rect.left_x, y, &line_builder);
+                    }
+                }
+            }
+
+            // Figure out how to draw bottom line
+            if dirty.status {
+                match &fm.input_mode {
+                    InputMode::Normal => {
+                        draw_bottom_info_line(screen_lock, &mut fm);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::Command { prompt, .. } => {
+                        let bottom_y = fm.drawing_info.height - 1;
+
+                        // NOTE(Chris): A failed validation replaces the prompt until the next key
+                        // press
+                        let (label, label_color) =
+                            match fm.active_prompt.as_ref().and_then(|prompt| prompt.error()) {
+                                Some(error) => (format!("{} ", error), rolf_grid::Color::Red),
+                                None => (prompt.clone(), fm.config.prompt_color),
+                            };
+
+                        let prompt_right_x = draw_wide_str(
+                            screen_lock,
+                            0,
+                            bottom_y,
+                            &label,
+                            fm.drawing_info.width,
+                            Style::new_color(label_color, rolf_grid::Color::Background),
+                        );
+
+                        // The input goes after the prompt, unless it's edited inline
+                        let (input_left_x, input_y, input_max_x, input_style) =
+                            match inline_input_row(&fm) {
+                                Some((left_x, y, max_x)) => (
+                                    left_x,
+                                    y,
+                                    max_x,
+                                    Style::new_attr(rolf_grid::Attribute::Underlined),
+                                ),
+                                None => (
+                                    prompt_right_x,
+                                    bottom_y,
+                                    fm.drawing_info.width,
+                                    Style::default(),
+                                ),
+                            };
+
+                        let (display_line, display_cursor) = match &fm.active_prompt {
+                            Some(active_prompt) if active_prompt.masked => (
+                                Cow::Owned("*".repeat(fm.input_line.chars().count())),
+                                fm.input_line[..fm.input_cursor].chars().count(),
+                            ),
+                            _ => (Cow::Borrowed(fm.input_line.as_str()), fm.input_cursor),
+                        };
+
+                        // NOTE(Chris): Lines which don't fit after the prompt are scrolled
+                        // horizontally, keeping the cursor in view
+                        let view_width = input_max_x.saturating_sub(input_left_x).max(1);
+
+                        fm.input_view_offset = line_edit::scroll_view_offset(
+                            &display_line,
+                            display_cursor,
+                            fm.input_view_offset,
+                            view_width as usize,
+                        );
+
+                        for x in input_left_x..input_max_x {
+                            screen_lock.set_cell_style_clipped(x, input_y, ' ', input_style);
+                        }
+
+                        draw_wide_str(
+                            screen_lock,
+                            input_left_x,
+                            input_y,
+                            &display_line[fm.input_view_offset..],
+                            input_max_x,
+                            input_style,
+                        );
+
+                        let cursor_offset = line_edit::str_width(
+                            &display_line[fm.input_view_offset..display_cursor],
+                        );
+
+                        screen_lock.show_cursor(
+                            (input_left_x + cursor_offset as u16).min(input_max_x - 1),
+                            input_y,
+                        );
+                    }
+                    InputMode::View {
+                        keybindings_vec, ..
+                    } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        let mut quit_key_displays = vec![];
+                        let mut down_key_displays = vec![];
+                        let mut up_key_displays = vec![];
+                        for (key_display, command, _desc) in keybindings_vec {
+                            if command == "quit" {
+                                quit_key_displays.push(key_display.as_str());
+                            } else if command == "down" {
+                                down_key_displays.push(key_display.as_str());
+                            } else if command == "up" {
+                                up_key_displays.push(key_display.as_str());
+                            }
+                        }
+
+                        quit_key_displays.sort_unstable();
+                        down_key_displays.sort_unstable();
+                        up_key_displays.sort_unstable_by_key(|vec| vec.len());
+
+                        if !quit_key_displays.is_empty() {
+                            line_builder.push_str(&quit_key_displays.join(","));
+                            line_builder.push_str(":quit");
+                            line_builder.push_str(command_space);
+                        }
+
+                        if !down_key_displays.is_empty() {
+                            line_builder.push_str(&down_key_displays.join(","));
+                            line_builder.push_str(":scroll_down");
+                            line_builder.push_str(command_space);
+                        }
+
+                        if !up_key_displays.is_empty() {
+                            line_builder.push_str(&up_key_displays.join(","));
+                            line_builder.push_str(":scroll_up");
+                            line_builder.push_str(command_space);
+                        }
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::SearchResults {
+                        cursor, results, ..
+                    } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        line_builder.push_str(&format!("{}/{}", cursor + 1, results.len()));
+                        line_builder.push_str(command_space);
+
+                        push_key_hints(
+                            &mut line_builder,
+                            &fm.config.keybindings,
+                            &[("open", "jump"), ("quit", "quit")],
+                        );
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::CompareDirs {
+                        cursor,
+                        differences,
+                        ..
+                    } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        // NOTE(Chris): Copying reports back here, since there's no other status
+                        // line while the panel is shown
+                        if let Some(status_message) = &fm.status_message {
+                            line_builder.push_str(status_message);
+                        } else {
+                            let command_space = "   ";
+
+                            line_builder.push_str(&format!("{}/{}", cursor + 1, differences.len()));
+                            line_builder.push_str(command_space);
+
+                            push_key_hints(
+                                &mut line_builder,
+                                &fm.config.keybindings,
+                                &[
+                                    ("open", "copy_across"),
+                                    ("paste", "copy_all_missing"),
+                                    ("quit", "quit"),
+                                ],
+                            );
+                        }
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::Diff {
+                        top_ind,
+                        view_rect,
+                        diff_lines,
+                        ..
+                    } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        let bot_ind = (top_ind + view_rect.height as usize).min(diff_lines.len());
+                        line_builder.push_str(&format!(
+                            "{}-{}/{}",
+                            top_ind + 1,
+                            bot_ind,
+                            diff_lines.len()
+                        ));
+                        line_builder.push_str(command_space);
+
+                        push_key_hints(
+                            &mut line_builder,
+                            &fm.config.keybindings,
+                            &[
+                                ("down", "scroll_down"),
+                                ("up", "scroll_up"),
+                                ("quit", "quit"),
+                            ],
+                        );
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::Gallery {
+                        cursor,
+                        images,
+                        full_screen,
+                        ..
+                    } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        line_builder.push_str(&format!("{}/{}", cursor + 1, images.len()));
+                        line_builder.push_str(command_space);
+
+                        push_key_hints(
+                            &mut line_builder,
+                            &fm.config.keybindings,
+                            &[("down", "down"), ("up", "up"), ("quit", "quit")],
+                        );
+
+                        line_builder.push_str(if *full_screen {
+                            "enter:grid"
+                        } else {
+                            "enter:view"
+                        });
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::Attributes {
+                        top_ind,
+                        view_rect,
+                        lines,
+                        ..
+                    } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        let bot_ind = (top_ind + view_rect.height as usize).min(lines.len());
+                        line_builder.push_str(&format!(
+                            "{}-{}/{}",
+                            top_ind + 1,
+                            bot_ind,
+                            lines.len()
+                        ));
+                        line_builder.push_str(command_space);
+
+                        push_key_hints(
+                            &mut line_builder,
+                            &fm.config.keybindings,
+                            &[
+                                ("down", "scroll_down"),
+                                ("up", "scroll_up"),
+                                ("quit", "quit"),
+                            ],
+                        );
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::Log { top_ind, view_rect } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        let bot_ind = (top_ind + view_rect.height as usize).min(fm.log.len());
+                        line_builder.push_str(&format!(
+                            "{}-{}/{}",
+                            (top_ind + 1).min(bot_ind),
+                            bot_ind,
+                            fm.log.len()
+                        ));
+                        line_builder.push_str(command_space);
+
+                        push_key_hints(
+                            &mut line_builder,
+                            &fm.config.keybindings,
+                            &[
+                                ("down", "scroll_down"),
+                                ("up", "scroll_up"),
+                                ("quit", "quit"),
+                            ],
+                        );
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                    InputMode::RenamePreview { renames, .. } => {
+                        let mut line_builder = LineBuilder::new();
+
+                        let command_space = "   ";
+
+                        let conflict_count = renames
+                            .iter()
+                            .filter(|rename| rename.conflict.is_some())
+                            .count();
+
+                        // NOTE(Chris): Nothing can be renamed until every conflict is resolved
+                        if conflict_count > 0 {
+                            line_builder
+                                .use_style(rolf_grid::Style::new_color(
+                                    rolf_grid::Color::Red,
+                                    rolf_grid::Color::Background,
+                                ))
+                                .push_str(&format!(
+                                    "{} of {} conflicting",
+                                    conflict_count,
+                                    count_files(renames.len())
+                                ))
+                                .use_style(rolf_grid::Style::default());
+                            line_builder.push_str(command_space);
+
+                            push_key_hints(
+                                &mut line_builder,
+                                &fm.config.keybindings,
+                                &[("quit", "cancel")],
+                            );
+                        } else {
+                            line_builder.push_str(&count_files(renames.len()));
+                            line_builder.push_str(command_space);
+
+                            push_key_hints(
+                                &mut line_builder,
+                                &fm.config.keybindings,
+                                &[("open", "rename"), ("quit", "cancel")],
+                            );
+                        }
+
+                        screen_lock.build_line(0, fm.drawing_info.height - 1, &line_builder);
+
+                        screen_lock.hide_cursor();
+                    }
+                }
+            }
+
+            screen_lock.show()?;
+
+            dirty = DirtyRegions::none();
+        }
+
+        // eprintln!("Main thread: Obtaining event...");
+        let next_event = match pending_event.take() {
+            Some(event) => Ok(event),
+            None => rx.try_recv(),
+        };
+
+        let event = match next_event {
+            Ok(event) => event,
+            Err(TryRecvError::Empty) => match fm.next_deadline() {
+                Some(deadline) => {
+                    match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(event) => event,
+                        Err(RecvTimeoutError::Timeout) => {
+                            let now = Instant::now();
+
+                            // The cursor has settled, so actually generate the preview
+                            if fm.preview_deadline.is_some_and(|deadline| deadline <= now) {
+                                fm.preview_deadline = None;
+
+                                let second_entry_index = fm.get_second_entry_index();
+                                set_preview_data_with_thread(&mut fm, &tx, second_entry_index);
+
+                                dirty.preview = true;
+                            }
+
+                            if fm
+                                .select_hook_deadline
+                                .is_some_and(|deadline| deadline <= now)
+                            {
+                                fm.select_hook_deadline = None;
+
+                                run_hook_in_background(&fm, &tx, "on-select", &fm.config.on_select);
+                            }
+
+                            // A relative time on the info line (like "5 s ago") is out of date
+                            if fm
+                                .relative_time_deadline
+                                .is_some_and(|deadline| deadline <= now)
+                            {
+                                fm.relative_time_deadline = None;
+
+                                dirty.status = true;
+                            }
+
+                            // NOTE(Chris): Like vim's timeoutlen, a count that's left alone for
+                            // too long is forgotten
+                            if fm
+                                .pending_keys_deadline
+                                .is_some_and(|deadline| deadline <= now)
+                            {
+                                fm.pending_keys_deadline = None;
+                                fm.pending_keys.clear();
+                                fm.count = None;
+
+                                dirty.status = true;
+                            }
+
+                            continue;
+                        }
+                        Err(err) => panic!("Unable to obtain input event: {}", err),
+                    }
+                }
+                None => rx.recv().unwrap(),
+            },
+            Err(err) => panic!("Unable to obtain input event: {}", err),
+        };
+
+        match event {
+            InputEvent::CrosstermEvent { event } => {
+                match event {
+                    Event::Key(event) => {
+                        match &fm.input_mode {
+                            InputMode::Normal
+                            | InputMode::View { .. }
+                            | InputMode::SearchResults { .. }
+                            | InputMode::RenamePreview { .. }
+                            | InputMode::Diff { .. }
+                            | InputMode::CompareDirs { .. }
+                            | InputMode::Log { .. }
+                            | InputMode::Attributes { .. }
+                            | InputMode::Gallery { .. } => {
+                                if fm.status_message.take().is_some() {
+                                    dirty.status = true;
+                                }
+
+                                let count = fm.count.take();
+
+                                fm.pending_keys_deadline = None;
+                                if !fm.pending_keys.is_empty() {
+                                    fm.pending_keys.clear();
+                                    dirty.status = true;
+                                }
+
+                                // NOTE(Chris): Enter shows the image by itself in the gallery,
+                                // since the keys bound to open (like l) move right instead
+                                if let (InputMode::Gallery { .. }, KeyCode::Enter) =
+                                    (&fm.input_mode, event.code)
+                                {
+                                    toggle_gallery_full_screen(&mut fm, &tx);
+                                    dirty = DirtyRegions::all();
+                                } else if let Some(bound_command) =
+                                    fm.config.keybindings.get(&event)
+                                {
+                                    // TODO(Chris): Show an error message if this bound command
+                                    // fails to parse
+                                    if let Ok(mut stm) = parse_statement_from(bound_command) {
+                                        // NOTE(Chris): Only movement commands take a count for now
+                                        if let (Some(count), Statement::CommandUse(command_use)) =
+                                            (count, &mut stm)
+                                        {
+                                            if matches!(command_use.name.as_str(), "up" | "down") {
+                                                command_use.arguments.push(count.to_string());
+                                            }
+                                        }
+
+                                        command_queue.push(stm);
+                                    }
+                                } else if let (InputMode::Normal, KeyCode::Char(ch @ '0'..='9')) =
+                                    (&fm.input_mode, event.code)
+                                {
+                                    let digit = ch as usize - '0' as usize;
+
+                                    // NOTE(Chris): Like in vim, a count can't start with 0
+                                    fm.count = match count {
+                                        Some(count) => {
+                                            Some(count.saturating_mul(10).saturating_add(digit))
+                                        }
+                                        None if digit != 0 => Some(digit),
+                                        None => None,
+                                    };
+
+                                    if let Some(count) = fm.count {
+                                        fm.pending_keys = count.to_string();
+                                        fm.pending_keys_deadline =
+                                            Some(Instant::now() + PENDING_KEYS_TIMEOUT);
+                                        dirty.status = true;
+                                    }
+                                }
+                            }
+                            InputMode::Command {
+                                prompt: _,
+                                asking_type,
+                            } => {
+                                let asking_type_clone = *asking_type;
+
+                                dirty.status = true;
+
+                                if let Some(prompt) = &mut fm.active_prompt {
+                                    prompt.clear_error();
+                                }
+
+                                match event.code {
+                                    KeyCode::Esc => {
+                                        leave_command_mode_and_additional_thread(
+                                            &mut fm,
+                                            &to_command_tx,
+                                        );
+                                    }
+                                    KeyCode::Char(ch) => {
+                                        if event.modifiers.contains(KeyModifiers::CONTROL) {
+                                            match ch {
+                                                'b' => {
+                                                    fm.input_cursor = line_edit::prev_char_pos(
+                                                        &fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                }
+                                                'f' => {
+                                                    fm.input_cursor = line_edit::next_char_pos(
+                                                        &fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                }
+                                                'a' => fm.input_cursor = 0,
+                                                'e' => fm.input_cursor = fm.input_line.len(),
+                                                'c' => leave_command_mode_and_additional_thread(
+                                                    &mut fm,
+                                                    &to_command_tx,
+                                                ),
+                                                'k' => {
+                                                    let killed = line_edit::kill_to_end(
+                                                        &mut fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                    fm.kill_ring.push(killed);
+                                                }
+                                                'u' => {
+                                                    let killed = line_edit::kill_to_start(
+                                                        &mut fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                    fm.kill_ring.push(killed);
+
+                                                    fm.input_cursor = 0;
+                                                }
+                                                'w' => {
+                                                    let (killed, new_cursor) =
+                                                        line_edit::kill_prev_word(
+                                                            &mut fm.input_line,
+                                                            fm.input_cursor,
+                                                        );
+                                                    fm.kill_ring.push(killed);
+
+                                                    fm.input_cursor = new_cursor;
+                                                }
+                                                'y' => {
+                                                    if let Some(killed) = fm.kill_ring.last() {
+                                                        fm.input_cursor = line_edit::yank(
+                                                            &mut fm.input_line,
+                                                            fm.input_cursor,
+                                                            killed,
+                                                        );
+                                                    }
+                                                }
+                                                't' => {
+                                                    fm.input_cursor = line_edit::transpose_chars(
+                                                        &mut fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                }
+                                                _ => (),
+                                            }
+                                        } else if event.modifiers.contains(KeyModifiers::ALT) {
+                                            match ch {
+                                                'b' => {
+                                                    fm.input_cursor = line_edit::find_prev_word_pos(
+                                                        &fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                }
+                                                'f' => {
+                                                    fm.input_cursor = line_edit::find_next_word_pos(
+                                                        &fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                }
+                                                'd' => {
+                                                    let killed = line_edit::kill_next_word(
+                                                        &mut fm.input_line,
+                                                        fm.input_cursor,
+                                                    );
+                                                    fm.kill_ring.push(killed);
+                                                }
+                                                _ => (),
+                                            }
+                                        } else {
+                                            fm.input_line.insert(fm.input_cursor, ch);
+
+                                            fm.input_cursor += ch.len_utf8();
+                                        }
+                                    }
+                                    KeyCode::Enter => {
+                                        match asking_type {
+                                            AskingType::Command => {
+                                                // TODO(Chris): Refactor out this manual checking of "search" or
+                                                // "search-back" somehow
+                                                if let Ok(stm) =
+                                                    parse_statement_from(&fm.input_line)
+                                                {
+                                                    match &stm {
+                                                        Statement::CommandUse(
+                                                            parser::CommandUse { name, arguments },
+                                                        ) => {
+                                                            if !((name == "search"
+                                                                || name == "search-back")
+                                                                && arguments.is_empty())
+                                                            {
+                                                                command_queue.push(stm);
+                                                            }
+                                                        }
+                                                        _ => command_queue.push(stm),
+                                                    }
+                                                }
+
+                                                // In theory, no additional input thread should
+                                                // exist, so we shouldn't need to exit this
+                                                // additional input thread.
+                                                leave_command_mode(&mut fm);
+                                            }
+                                            AskingType::AdditionalInput
+                                            | AskingType::AdditionalInputKey => {
+                                                let is_valid = match &mut fm.active_prompt {
+                                                    Some(prompt) => prompt.validate(&fm.input_line),
+                                                    None => true,
+                                                };
+
+                                                if is_valid {
+                                                    exit_input_mode_command_thread(
+                                                        &mut fm,
+                                                        &to_command_tx,
+                                                    );
+                                                }
+                                            }
+                                        }
+                                    }
+                                    KeyCode::Tab => {
+                                        if let Some(completed) = fm
+                                            .active_prompt
+                                            .as_ref()
+                                            .and_then(|prompt| prompt.complete(&fm.input_line))
+                                        {
+                                            fm.input_line = completed;
+                                            fm.input_cursor = fm.input_line.len();
+                                        }
+                                    }
+                                    KeyCode::Left => {
+                                        fm.input_cursor = line_edit::prev_char_pos(
+                                            &fm.input_line,
+                                            fm.input_cursor,
+                                        );
+                                    }
+                                    KeyCode::Right => {
+                                        fm.input_cursor = line_edit::next_char_pos(
+                                            &fm.input_line,
+                                            fm.input_cursor,
+                                        );
+                                    }
+                                    KeyCode::Backspace if fm.input_cursor > 0 => {
+                                        if event.modifiers.contains(KeyModifiers::ALT) {
+                                            let (killed, new_cursor) = line_edit::kill_prev_word(
+                                                &mut fm.input_line,
+                                                fm.input_cursor,
+                                            );
+                                            fm.kill_ring.push(killed);
+
+                                            fm.input_cursor = new_cursor;
+                                        } else {
+                                            let prev_cursor = line_edit::prev_char_pos(
+                                                &fm.input_line,
+                                                fm.input_cursor,
+                                            );
+
+                                            fm.input_line
+                                                .replace_range(prev_cursor..fm.input_cursor, "");
+
+                                            fm.input_cursor = prev_cursor;
+                                        }
+                                    }
+                                    _ => (),
+                                }
+
+                                if asking_type_clone == AskingType::AdditionalInputKey {
+                                    exit_input_mode_command_thread(&mut fm, &to_command_tx);
+                                }
+                            }
+                        }
+                    }
+                    Event::Mouse(_) => (),
+                    Event::Resize(width, height) => {
+                        let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                        let screen_lock = &mut *screen_lock;
+
+                        // NOTE(Chris): This line should come before we resize anything
+                        set_area_dead(&fm, screen_lock, false);
+
+                        screen_lock.resize_clear_draw(width, height)?;
+
+                        let ratios = fm.layout_ratios();
+                        update_drawing_info_from_resize(
+                            &mut fm.drawing_info,
+                            ratios,
+                            fm.config.preview_position,
+                        )?;
+
+                        fm.second = fit_column_pos(
+                            fm.dir_states.current_entries.len(),
+                            fm.drawing_info.column_height,
+                            fm.second,
+                        )?;
+
+                        dirty = DirtyRegions::all();
+
+                        match fm.input_mode {
+                            InputMode::Normal | InputMode::Command { .. } => (),
+                            InputMode::View {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::SearchResults {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::RenamePreview {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::Diff {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::CompareDirs {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::Log {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::Attributes {
+                                ref mut view_rect, ..
+                            }
+                            | InputMode::Gallery {
+                                ref mut view_rect, ..
+                            } => {
+                                *view_rect = get_help_view_rect(fm.drawing_info);
+                            }
+                        }
+
+                        // NOTE(Chris): The full-screen image is loaded at the size of the view
+                        if let InputMode::Gallery {
+                            ref mut full_image, ..
+                        } = fm.input_mode
+                        {
+                            *full_image = None;
+                        }
+
+                        update_gallery(&mut fm, &tx);
+                    }
+                }
+            }
+            InputEvent::PreviewLoaded(preview_data) => {
+                if let (ImageProtocol::KittyUnicode, PreviewData::ImageBuffer { buffer }) =
+                    (fm.config.image_protocol, &preview_data)
+                {
+                    // NOTE(Chris): We cycle through image ids so that placeholders still on the
+                    // screen never refer to the image we're about to transmit.
+                    fm.kitty_image_id = fm.kitty_image_id % 255 + 1;
+
+                    let (cols, rows) = image_cell_size(&fm.drawing_info, buffer);
+                    let path = store_in_tmp_file(buffer.as_raw())?;
+
+                    let mut raw = vec![];
+                    kitty::transmit_virtual(
+                        &mut raw,
+                        &path,
+                        fm.kitty_image_id,
+                        buffer.width(),
+                        buffer.height(),
+                        cols,
+                        rows,
+                    )?;
+
+                    let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                    screen_lock.write_raw(0, 0, raw);
+                }
+
+                fm.preview_data = preview_data;
+
+                dirty.preview = true;
+            }
+            InputEvent::PreviewFailed { path, error } => {
+                fm.log(format!("Unable to preview {}: {}", path.display(), error));
+
+                fm.preview_data = PreviewData::Message {
+                    message: format!("unable to preview: {}", error),
+                };
+
+                dirty.preview = true;
+            }
+            InputEvent::CommandRequest(command_request) => {
+                dirty.status = true;
+
+                match command_request {
+                    CommandRequest::Ask(prompt) => {
+                        let asking_type = if prompt.single_key {
+                            AskingType::AdditionalInputKey
+                        } else {
+                            AskingType::AdditionalInput
+                        };
+
+                        enter_command_mode_with(
+                            &mut fm,
+                            &prompt.initial_text,
+                            prompt.label.clone(),
+                            asking_type,
+                        );
+
+                        if let Some(initial_cursor) = prompt
+                            .initial_cursor
+                            .filter(|&index| fm.input_line.is_char_boundary(index))
+                        {
+                            fm.input_cursor = initial_cursor;
+                        }
+
+                        fm.active_prompt = Some(prompt);
+                    }
+                    CommandRequest::Shell(shell_command) => {
+                        let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                        let screen_lock = &mut *screen_lock;
+
+                        let stdout = io::stdout();
+                        let mut stdout_lock = stdout.lock();
+
+                        enter_shell_command_then_redraw(
+                            &mut fm,
+                            screen_lock,
+                            &mut stdout_lock,
+                            &tx,
+                            second_entry_index,
+                            shell_command,
+                        )?;
+
+                        dirty = DirtyRegions::all();
+                    }
+                    CommandRequest::Quit => {
+                        leave_command_mode(&mut fm);
+                    }
+                }
+            }
+            InputEvent::CommandCallback(CommandCallback(cb)) => {
+                cb(&mut fm)?;
+
+                dirty = DirtyRegions::all();
+            }
+        }
+    }
+
+    // NOTE(Chris): Stop reading input, since the terminal is about to be handed back to the shell
+    fm.input_suspended
+        .store(true, std::sync::atomic::Ordering::Release);
+
+    // NOTE(Chris): Unlike the other hooks, we wait for on-quit to finish, so that anything it
+    // writes (like a status file) is in place by the time rolf exits
+    if !fm.config.on_quit.is_empty() {
+        let _ = hooks::hook_command(&fm.config.on_quit, &hook_context(&fm)).status();
+    }
+
+    if !fm.dir_states.current_entries.is_empty() {
+        let second_entry_index = fm.get_second_entry_index();
+        save_location(&mut fm, second_entry_index);
+    }
+
+    // NOTE(Chris): Like the last directory, losing the cursor positions isn't worth an error on
+    // every quit
+    let _ = fm.saved_positions.write();
+
+    // NOTE(Chris): The shell can't cd into a mounted directory
+    Ok(fm.dir_states.working_dir().to_path_buf())
+}
+
+struct FileManager {
+    tools: Tools,
+
+    preview_pool: PreviewPool,
+
+    // Shared with the preview threads, which add each preview they generate
+    preview_cache: Arc<Mutex<PreviewCache<PreviewKey, PreviewData>>>,
+
+    // Whether the input thread should stop reading input, e.g. while a shell command is running
+    input_suspended: Arc<AtomicBool>,
+
+    dir_states: DirStates,
+
+    second: ColumnInfo,
+
+    left_paths: HashMap<std::path::PathBuf, DirLocation>,
+
+    // Where the cursor was in directories from earlier sessions, which are copied into left_paths
+    // as they're visited (see positions.rs)
+    saved_positions: SavedPositions,
+
+    // What to go back to when leaving a directory whose .rolfrc was run (with local-rolfrc)
+    local_settings: Option<LocalSettings>,
+
+    match_positions: Vec<usize>,
+
+    should_search_forwards: bool,
+
+    input_line: String,
+
+    input_cursor: usize,
+
+    // The byte index of the first character of input_line that fits on screen
+    input_view_offset: usize,
+
+    kill_ring: line_edit::KillRing,
+
+    // The prompt that a command thread is currently waiting on, if any
+    active_prompt: Option<Prompt>,
+
+    // Whether the last frame drew an inline prompt's input over the current row
+    drawn_inline_input: bool,
+
+    input_mode: InputMode,
+
+    user_host_display: String,
+
+    selections: SelectionsMap,
+
+    drawing_info: DrawingInfo,
+
+    config: Config,
+
+    preview_data: PreviewData,
+
+    // The id of the last image transmitted with kitty's unicode placeholders, or 0 if none has
+    // been transmitted yet
+    kitty_image_id: u8,
+
+    // When set, the preview for the current entry will be generated once this time has passed
+    preview_deadline: Option<Instant>,
+
+    // When set, the on-select hook will be run once this time has passed
+    select_hook_deadline: Option<Instant>,
+
+    // When set, the relative time on the info line (like "3 min ago") changes at this time, so
+    // the info line has to be drawn again
+    relative_time_deadline: Option<Instant>,
+
+    // The space on the current directory's file system, with the disk-usage option. It's only
+    // checked when the directory changes.
+    disk_space: Option<DiskSpace>,
+
+    // Whether files can be created and removed in the current directory. It's only checked when
+    // the directory changes.
+    is_current_writable: bool,
+
+    // The number of items in directories in the listing, with the dir-counts option
+    dir_counts: DirCounts,
+
+    // When set (via --choosefiles), rolf is being used as a file picker, and picked files are
+    // written to this path
+    choose_files_path: Option<PathBuf>,
+
+    // Where the files to copy or move with the next paste are kept (see file_buffer.rs)
+    files_path: PathBuf,
+
+    // Where the gallery's thumbnails are kept between runs, if anywhere
+    thumbnail_dir: Option<PathBuf>,
+
+    // A message (usually an error) which replaces the bottom info line until the next key press
+    status_message: Option<String>,
+
+    // Errors which didn't stop rolf (like a preview that couldn't be loaded), oldest first, so that
+    // they can still be read in the log view
+    log: Vec<LogEntry>,
+
+    // How far along a running paste is, shown on the bottom line
+    paste_progress: Option<String>,
+
+    // The `open` rules from rolfrc, which are checked before using the system opener
+    open_rules: OpenRules,
+
+    // Used to highlight files which belong to other users
+    current_user: Option<CurrentUser>,
+
+    // A count typed before a command, like the 7 in "7j"
+    count: Option<usize>,
+
+    // The keys typed so far toward a command (like "12" of "12j"), shown left of the ruler like
+    // vim's showcmd
+    pending_keys: String,
+
+    // When set, the pending keys are dropped once this time has passed
+    pending_keys_deadline: Option<Instant>,
+
+    // The last search term, which is highlighted in the matching file names until "nohl"
+    search_highlight: Option<String>,
+
+    // The relative widths of the three columns, which the layout command switches
+    column_ratios: [u16; 3],
+
+    // When set, one column takes up the whole width instead of the current layout
+    zoom: Option<Zoom>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Zoom {
+    Preview,
+    Listing,
+}
+
+// The settings from before a directory's .rolfrc was run
+struct LocalSettings {
+    config: Config,
+    filter: Option<EntryFilter>,
+}
+
+// The most entries kept in the log, past which the oldest are dropped
+const LOG_CAPACITY: usize = 500;
+
+struct LogEntry {
+    time: DateTime<Local>,
+    message: String,
+}
+
+impl FileManager {
+    fn new(
+        config: &Config,
+        dir_states: DirStates,
+        data_dir: &Path,
+        choose_files_path: Option<PathBuf>,
+        tx: &Sender<InputEvent>,
+    ) -> FileManager {
+        let user_name = whoami::username();
+
+        let host_name = whoami::hostname();
+
+        let dir_counts_tx = tx.clone();
+
+        FileManager {
+            tools: Tools::detect(&config.tool_paths),
+
+            preview_pool: PreviewPool::new(PREVIEW_THREAD_COUNT),
+
+            preview_cache: Arc::new(Mutex::new(PreviewCache::new(
+                (config.preview_cache * 1024 * 1024) as usize,
+            ))),
+
+            input_suspended: Arc::new(AtomicBool::new(false)),
+
+            dir_states,
+
+            second: ColumnInfo {
+                starting_index: 0,
+                display_offset: 0,
+            },
+
+            left_paths: HashMap::new(),
+
+            saved_positions: SavedPositions::new(data_dir.join("positions")),
+
+            local_settings: None,
+
+            match_positions: vec![],
+
+            should_search_forwards: true,
+
+            input_line: String::new(),
+
+            input_cursor: 0,
+
+            input_view_offset: 0,
+
+            kill_ring: line_edit::KillRing::new(),
+
+            active_prompt: None,
+
+            drawn_inline_input: false,
+
+            input_mode: InputMode::Normal,
+
+            user_host_display: format!("{}@{}", user_name, host_name),
+
+            // Keys are paths, values are indices in their directory
+            selections: HashSet::new(),
+
+            drawing_info: DrawingInfo {
+                // NOTE(Chris): The front end sets this up once it knows how big the screen is
+                win_pixels: WindowPixels {
+                    width: 0,
+                    height: 0,
+                },
+                width: 0,
+                height: 0,
+                column_bot_y: 0,
+                column_height: 0,
+                first_left_x: 0,
+                first_right_x: 0,
+                second_left_x: 0,
+                second_right_x: 0,
+                third_left_x: 0,
+                third_right_x: 0,
+                third_top_y: 0,
+                third_height: 0,
+            },
+
+            config: config.clone(),
+
+            preview_data: PreviewData::Loading,
+
+            kitty_image_id: 0,
+
+            preview_deadline: None,
+
+            select_hook_deadline: None,
+
+            relative_time_deadline: None,
+
+            disk_space: None,
+
+            is_current_writable: true,
+
+            dir_counts: DirCounts::new(move |counted| {
+                // NOTE(Chris): Counts which finish after rolf starts quitting are thrown away
+                let _ = dir_counts_tx.send(InputEvent::CommandCallback(CommandCallback(Box::new(
+                    move |fm: &mut FileManager| {
+                        fm.dir_counts.insert(counted);
+
+                        Ok(())
+                    },
+                ))));
+            }),
+
+            choose_files_path,
+
+            files_path: data_dir.join("files"),
+
+            // NOTE(Chris): Only the TUI shows thumbnails, so it sets this up
+            thumbnail_dir: None,
+
+            paste_progress: None,
+
+            // NOTE(Chris): This is set once the rolfrc commands have run, since they may have
+            // errors to show instead
+            status_message: None,
+
+            log: vec![],
+
+            open_rules: OpenRules::new(),
+
+            current_user: os_abstract::get_current_user(),
+
+            count: None,
+
+            pending_keys: String::new(),
+
+            pending_keys_deadline: None,
+
+            search_highlight: None,
+
+            column_ratios: config.ratios,
+
+            zoom: None,
+        }
+    }
+
+    fn get_second_entry_index(&self) -> u16 {
+        self.second.starting_index + self.second.display_offset
+    }
+
+    fn log(&mut self, message: String) {
+        if self.log.len() == LOG_CAPACITY {
+            self.log.remove(0);
+        }
+
+        self.log.push(LogEntry {
+            time: Local::now(),
+            message,
+        });
+    }
+
+    // The earliest time at which the main loop has something to do without any input
+    fn next_deadline(&self) -> Option<Instant> {
+        [
+            self.preview_deadline,
+            self.select_hook_deadline,
+            self.relative_time_deadline,
+            self.pending_keys_deadline,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+
+    fn layout_ratios(&self) -> [u16; 3] {
+        match self.zoom {
+            None if self.config.preview_position == PreviewPane::Hidden => {
+                [self.column_ratios[0], self.column_ratios[1], 0]
+            }
+            None => self.column_ratios,
+            Some(Zoom::Preview) => [0, 0, 1],
+            Some(Zoom::Listing) => [0, 1, 0],
+        }
+    }
+
+    // NOTE(Chris): Like lf, only the current directory shows info columns and line numbers
+    fn listing_options(&self, show_info: bool) -> ListingOptions<'_> {
+        ListingOptions {
+            empty_message: "empty",
+            dimming_user: if self.config.dim_unowned {
+                self.current_user.as_ref()
+            } else {
+                None
+            },
+            info_columns: if show_info {
+                &self.config.info_columns
+            } else {
+                &[]
+            },
+            dir_counts: if show_info && self.config.dir_counts {
+                Some(&self.dir_counts)
+            } else {
+                None
+            },
+            size_units: self.config.size_units,
+            recent_marking: if self.config.recent_color == rolf_grid::Color::Foreground {
+                None
+            } else {
+                SystemTime::now()
+                    .checked_sub(self.config.recent_within)
+                    .map(|since| (since, self.config.recent_color))
+            },
+            plain: self.config.plain,
+            truncation: self.config.truncate,
+            number: show_info && self.config.number,
+            relative_number: show_info && self.config.relative_number,
+            // NOTE(Chris): Matches are forgotten when changing directories, so the highlighting
+            // goes away with them
+            search_highlight: if show_info && !self.match_positions.is_empty() {
+                self.search_highlight.as_deref()
+            } else {
+                None
+            },
+            match_options: self.match_options(false),
+            background: if show_info
+                && !self.is_current_writable
+                && !self.config.plain
+                && self.config.unwritable_color != rolf_grid::Color::Foreground
+            {
+                self.config.unwritable_color
+            } else {
+                rolf_grid::Color::Background
+            },
+        }
+    }
+
+    fn match_options(&self, anchored: bool) -> MatchOptions {
+        MatchOptions {
+            ignore_case: self.config.ignore_case,
+            smart_case: self.config.smart_case,
+            anchored,
+            ignore_diacritics: self.config.ignore_diacritics,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum InputMode {
+    Normal,
+    Command {
+        prompt: String,
+        asking_type: AskingType,
+    },
+    View {
+        top_ind: u16,
+        view_rect: Rect,
+        keybindings_vec: Vec<(String, String, String)>,
+    },
+    SearchResults {
+        top_ind: usize,
+        cursor: usize,
+        view_rect: Rect,
+        search_term: String,
+        results: Vec<SearchResult>,
+    },
+    RenamePreview {
+        top_ind: usize,
+        view_rect: Rect,
+        renames: Vec<Rename>,
+    },
+    Diff {
+        top_ind: usize,
+        view_rect: Rect,
+        title: String,
+        diff_lines: Vec<DiffLine>,
+    },
+    CompareDirs {
+        top_ind: usize,
+        cursor: usize,
+        view_rect: Rect,
+        left: PathBuf,
+        right: PathBuf,
+        differences: Vec<DirDifference>,
+    },
+    Log {
+        top_ind: usize,
+        view_rect: Rect,
+    },
+    // Lists some of a file's attributes, like its extended attributes or its ACL
+    Attributes {
+        top_ind: usize,
+        view_rect: Rect,
+        title: &'static str,
+        path: PathBuf,
+        lines: Vec<String>,
+    },
+    Gallery {
+        top_row: usize,
+        cursor: usize,
+        view_rect: Rect,
+        images: Vec<PathBuf>,
+        // Whether the image under the cursor is shown by itself
+        full_screen: bool,
+        // The thumbnails which have been loaded so far, or None for images which couldn't be
+        // decoded
+        thumbnails: HashMap<PathBuf, Option<ImageBufferRgba>>,
+        // The image under the cursor at the size of the whole view, once it's been loaded for the
+        // full-screen view
+        full_image: Option<(usize, Option<ImageBufferRgba>)>,
+    },
+}
+
+impl InputMode {
+    fn to_top(&self) -> InputModeTop {
+        match self {
+            InputMode::Normal => InputModeTop::Normal,
+            InputMode::Command { .. } => InputModeTop::Command,
+            InputMode::View { .. } => InputModeTop::View,
+            InputMode::SearchResults { .. } => InputModeTop::SearchResults,
+            InputMode::RenamePreview { .. } => InputModeTop::RenamePreview,
+            InputMode::Diff { .. } => InputModeTop::Diff,
+            InputMode::CompareDirs { .. } => InputModeTop::CompareDirs,
+            InputMode::Log { .. } => InputModeTop::Log,
+            InputMode::Attributes { .. } => InputModeTop::Attributes,
+            InputMode::Gallery { .. } => InputModeTop::Gallery,
+        }
+    }
+}
+
+// This represents a specific InputMode without any of the corresponding fields
+#[derive(std::cmp::PartialEq)]
+enum InputModeTop {
+    Normal,
+    Command,
+    View,
+    SearchResults,
+    RenamePreview,
+    Diff,
+    CompareDirs,
+    Log,
+    Attributes,
+    Gallery,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum AskingType {
+    // The user is inputting a command
+    Command,
+    // The user is inputting more input, to be used with some earlier input
+    AdditionalInput,
+    // The user is going to enter a single key (e.g. y/n)
+    AdditionalInputKey,
+}
+
+fn leave_command_mode_and_additional_thread(
+    fm: &mut FileManager,
+    to_command_tx: &Option<Sender<String>>,
+) {
+    match &fm.input_mode {
+        InputMode::Normal => unreachable!(),
+        InputMode::Command { asking_type, .. } => match asking_type {
+            AskingType::Command => (),
+            AskingType::AdditionalInput | AskingType::AdditionalInputKey => {
+                // TODO(Chris): Use a different function, one which just directly exits
+                // AdditionalInput mode by always sending an empty input_line
+                fm.input_line.clear();
+                exit_input_mode_command_thread(fm, to_command_tx);
+            }
+        },
+        InputMode::View { .. }
+        | InputMode::SearchResults { .. }
+        | InputMode::RenamePreview { .. }
+        | InputMode::Diff { .. }
+        | InputMode::CompareDirs { .. }
+        | InputMode::Log { .. }
+        | InputMode::Attributes { .. }
+        | InputMode::Gallery { .. } => unreachable!(),
+    }
+
+    leave_command_mode(fm);
+}
+
+// TODO(Chris): Modify this function to actually interpret the current line of input as necessary,
+// "sending" it to the program "for real," rather than just exiting AdditionalInput mode when
+// necessary
+fn leave_command_mode(fm: &mut FileManager) {
+    fm.input_mode = InputMode::Normal;
+
+    clear_input_line(fm);
+
+    fm.active_prompt = None;
+}
+
+fn clear_input_line(fm: &mut FileManager) {
+    fm.input_line.clear();
+    fm.input_cursor = 0;
+    fm.input_view_offset = 0;
+}
+
+fn enter_command_mode_with(
+    fm: &mut FileManager,
+    beginning: &str,
+    prompt: String,
+    asking_type: AskingType,
+) {
+    fm.input_mode = InputMode::Command {
+        prompt,
+        asking_type,
+    };
+
+    fm.input_line.clear();
+    fm.input_line.push_str(beginning);
+
+    fm.input_cursor = fm.input_line.len();
+    fm.input_view_offset = 0;
+}
+
+// Runs a command in its own thread, so that it can wait on prompts for user input
+// What the front end should do after a command from run_command
+enum CommandFlow {
+    Done,
+    Quit,
+    // The files to hand back (see --choosefiles), which are only picked once the front end has
+    // written them out
+    Pick(Vec<PathBuf>),
+    // The command needs the screen, so it can't run in batch mode
+    Unavailable,
+    Unknown,
+}
+
+// Runs a command in the normal input mode. This is shared by the TUI and batch mode, which has no
+// screen and answers the prompts of command threads itself.
+fn run_command(
+    fm: &mut FileManager,
+    tx: &Sender<InputEvent>,
+    to_command_tx: &mut Option<Sender<String>>,
+    screen: Option<&Mutex<Screen>>,
+    command_use: &parser::CommandUse,
+) -> crossterm::Result<CommandFlow> {
+    let command: &str = &command_use.name;
+
+    if fm.dir_states.is_mounted() && !MOUNTED_COMMANDS.contains(&command) {
+        fm.status_message = Some(format!(
+            "{} isn't available in a mounted directory",
+            command
+        ));
+        return Ok(CommandFlow::Done);
+    }
+
+    let second_entry_index = fm.get_second_entry_index();
+    let second_bottom_index = fm.second.starting_index + fm.drawing_info.column_height;
+
+    match command {
+        "quit" => {
+            return Ok(CommandFlow::Quit);
+        }
+        "down" => {
+            cursor_down(fm, second_entry_index, second_bottom_index);
+
+            for _ in 1..get_count_argument(command_use) {
+                let second_entry_index = fm.get_second_entry_index();
+                let second_bottom_index = fm.second.starting_index + fm.drawing_info.column_height;
+
+                cursor_down(fm, second_entry_index, second_bottom_index);
+            }
+        }
+        "up" => {
+            for _ in 0..get_count_argument(command_use) {
+                cursor_up(fm);
+            }
+        }
+        "updir" => {
+            if fm.dir_states.prev_dir.is_none() {
+                fm.status_message = Some("Already at the root directory".to_string());
+                return Ok(CommandFlow::Done);
+            }
+
+            fm.preview_pool.cancel_all();
+
+            let old_current_dir = fm.dir_states.current_dir.clone();
+            if !fm.dir_states.current_entries.is_empty() {
+                save_location(fm, second_entry_index);
+            }
+
+            if let Some(parent_dir) = fm.dir_states.prev_dir.clone() {
+                set_current_dir(parent_dir, &mut fm.dir_states, &mut fm.match_positions)?;
+            }
+
+            fm.second = find_correct_location(
+                &fm.left_paths,
+                fm.drawing_info.column_height,
+                &fm.dir_states.current_dir,
+                &fm.dir_states.current_entries,
+                &old_current_dir,
+            );
+        }
+        "open" => {
+            if fm.config.pick_on_open
+                && is_file_entry(fm, second_entry_index)
+                && pick_files(fm, &picked_paths(fm, second_entry_index))?
+            {
+                return Ok(CommandFlow::Quit);
+            }
+
+            // NOTE(Chris): Rule commands may be terminal programs (like
+            // less), so they get the terminal to themselves
+            if let (Some(screen), Some(shell_command)) =
+                (screen, open_rule_command(fm, second_entry_index))
+            {
+                let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                let screen_lock = &mut *screen_lock;
+
+                let stdout = io::stdout();
+                let mut stdout_lock = stdout.lock();
+
+                enter_shell_command_then_redraw(
+                    fm,
+                    screen_lock,
+                    &mut stdout_lock,
+                    tx,
+                    second_entry_index,
+                    shell_command,
+                )?;
+            } else {
+                enter_entry(fm, tx, second_entry_index)?;
+            }
+        }
+        "pick" => {
+            return Ok(CommandFlow::Pick(picked_paths(fm, second_entry_index)));
+        }
+        // NOTE(Chris): lf doesn't actually provide a specific command for this, instead using
+        // a default keybinding that takes advantage of EDITOR
+        "edit" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            let editor = get_env_editor();
+
+            // It'd be nice if we could do breaking on blocks to exit this whole
+            // match statement early, but labeling blocks is still in unstable,
+            // as seen in https://github.com/rust-lang/rust/issues/48594
+            if !editor.is_empty() {
+                let selected_entry = &fm.dir_states.current_entries[second_entry_index as usize];
+
+                // NOTE(Chris): An optional argument gives the line to
+                // jump to, e.g. "edit 42"
+                let line = command_use
+                    .arguments
+                    .first()
+                    .and_then(|arg| arg.parse::<usize>().ok());
+
+                let selected_path = selected_entry.dir_entry.path();
+                if let Some(mount) = fm
+                    .dir_states
+                    .mount
+                    .clone()
+                    .filter(|mount| mount.contains(&selected_path))
+                {
+                    edit_mounted_file(fm, tx, mount, selected_path, editor, line);
+                    return Ok(CommandFlow::Done);
+                }
+
+                let shell_command = editor::editor_command(
+                    &editor,
+                    &fm.config.editor_line_flag,
+                    selected_entry
+                        .dir_entry
+                        .path()
+                        .to_str()
+                        .expect("Failed to convert path to string"),
+                    line,
+                );
+
+                let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                let screen_lock = &mut *screen_lock;
+
+                let stdout = io::stdout();
+                let mut stdout_lock = stdout.lock();
+
+                enter_shell_command_then_redraw(
+                    fm,
+                    screen_lock,
+                    &mut stdout_lock,
+                    tx,
+                    second_entry_index,
+                    shell_command,
+                )?;
+            }
+        }
+        "edit-sels" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            let editor = get_env_editor();
+
+            if !editor.is_empty() {
+                let mut tmpfile = tempfile::Builder::new()
+                    .prefix(".tmp.rolf.selections_")
+                    .rand_bytes(3)
+                    .tempfile()?;
+
+                let file_ref = tmpfile.as_file_mut();
+
+                for selection_path in &fm.selections {
+                    writeln!(file_ref, "{}", selection_path.to_str().unwrap())?;
+                }
+
+                let shell_command = format!("{} {}", editor, tmpfile.path().to_str().unwrap(),);
+
+                let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                let screen_lock = &mut *screen_lock;
+
+                let stdout = io::stdout();
+                let mut stdout_lock = stdout.lock();
+
+                enter_shell_command_then_redraw(
+                    fm,
+                    screen_lock,
+                    &mut stdout_lock,
+                    tx,
+                    second_entry_index,
+                    shell_command,
+                )?;
+
+                tmpfile.seek(io::SeekFrom::Start(0))?;
+
+                fm.selections.clear();
+
+                let file_reader = BufReader::new(&tmpfile);
+                for line in file_reader.lines() {
+                    let line = line?;
+                    let path = Path::new(&line);
+
+                    if Path::exists(path) {
+                        // FIXME(Chris): Change SelectionsMap to not
+                        // contain any indices
+                        fm.selections.insert(path.to_path_buf());
+                    }
+                }
+            }
+        }
+        "top" => {
+            if !fm.dir_states.current_entries.is_empty() {
+                fm.preview_pool.cancel_all();
+
+                fm.second.starting_index = 0;
+                fm.second.display_offset = 0;
+            }
+        }
+        "bottom" => {
+            if !fm.dir_states.current_entries.is_empty() {
+                fm.preview_pool.cancel_all();
+
+                if fm.dir_states.current_entries.len() <= (fm.drawing_info.column_height as usize) {
+                    fm.second.starting_index = 0;
+                    fm.second.display_offset = fm.dir_states.current_entries.len() as u16 - 1;
+                } else {
+                    fm.second.display_offset = fm.drawing_info.column_height - 1;
+                    fm.second.starting_index =
+                        fm.dir_states.current_entries.len() as u16 - fm.second.display_offset - 1;
+                }
+            }
+        }
+        "search" => {
+            if command_use.arguments.is_empty() {
+                let prompt = fm.config.command_prompt.clone();
+
+                enter_command_mode_with(fm, "search ", prompt, AskingType::Command);
+            } else {
+                let search_term = &command_use.arguments[0];
+
+                search_in_direction(fm, search_term, true)?;
+            }
+        }
+        "search-back" => {
+            if command_use.arguments.is_empty() {
+                let prompt = fm.config.command_prompt.clone();
+
+                enter_command_mode_with(fm, "search-back ", prompt, AskingType::Command);
+            } else {
+                let search_term = &command_use.arguments[0];
+
+                search_in_direction(fm, search_term, false)?;
+            }
+        }
+        "search-next" => {
+            search_jump(fm)?;
+        }
+        "search-results" => {
+            let search_term = match command_use
+                .arguments
+                .first()
+                .or(fm.search_highlight.as_ref())
+            {
+                Some(search_term) => search_term.clone(),
+                None => {
+                    fm.status_message = Some("Search for a file first".to_string());
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            // NOTE(Chris): The search is run again, since the directory
+            // may have changed since, and this time it also looks through
+            // the entries hidden by the entry filter
+            let all_entries = match fm.dir_states.filter {
+                Some(_) => fm.dir_states.read_entries(&fm.dir_states.current_dir).ok(),
+                None => None,
+            };
+
+            let results = find_search_results(
+                &fm.dir_states.current_entries,
+                all_entries.as_deref(),
+                &search_term,
+                fm.match_options(false),
+            );
+
+            fm.match_positions = results.iter().filter_map(|result| result.index).collect();
+            fm.search_highlight = Some(search_term.clone());
+
+            if results.is_empty() {
+                fm.status_message = Some(format!("No matches for \"{}\"", search_term));
+                return Ok(CommandFlow::Done);
+            }
+
+            // NOTE(Chris): The panel starts on the match under the cursor,
+            // or the next one after it
+            let cursor = results
+                .iter()
+                .position(|result| {
+                    result
+                        .index
+                        .is_some_and(|index| index >= second_entry_index as usize)
+                })
+                .unwrap_or(0);
+
+            let view_rect = get_help_view_rect(fm.drawing_info);
+
+            fm.input_mode = InputMode::SearchResults {
+                top_ind: cursor.saturating_sub(view_rect.height as usize - 1),
+                cursor,
+                view_rect,
+                search_term,
+                results,
+            };
+        }
+        "find" | "find-back" => {
+            let should_find_forwards = command == "find";
+            let to_our_tx = tx.clone();
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                let find_term = match prompter.ask(Prompt::new("find: ").single_key()) {
+                    Some(find_term) => find_term,
+                    None => return,
+                };
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    find_in_direction(fm, &find_term, should_find_forwards)
+                });
+            });
+        }
+        "nohl" => {
+            fm.search_highlight = None;
+        }
+        "only-dirs" => {
+            set_entry_filter(fm, Some(EntryFilter::Dirs), tx);
+        }
+        "only-files" => {
+            set_entry_filter(fm, Some(EntryFilter::Files), tx);
+        }
+        "only-ext" => match EntryFilter::extensions(&command_use.arguments) {
+            Some(filter) => {
+                set_entry_filter(fm, Some(filter), tx);
+            }
+            None => {
+                let prompt = fm.config.command_prompt.clone();
+
+                enter_command_mode_with(fm, "only-ext ", prompt, AskingType::Command);
+            }
+        },
+        "recent-files" => {
+            let within = match command_use.arguments.first() {
+                Some(within) => config::to_recent_within(within),
+                None => Ok(fm.config.recent_within),
+            };
+
+            match within {
+                Ok(within) => {
+                    set_entry_filter(fm, Some(EntryFilter::Recent(within)), tx);
+                }
+                Err(err) => fm.status_message = Some(err.to_string()),
+            }
+        }
+        "clear-filter" => {
+            set_entry_filter(fm, None, tx);
+        }
+        "copy" | "cut" => {
+            let mode = if command_use.name == "copy" {
+                PasteMode::Copy
+            } else {
+                PasteMode::Move
+            };
+
+            let mut paths = target_paths(fm, second_entry_index);
+            paths.sort();
+
+            if paths.is_empty() {
+                return Ok(CommandFlow::Done);
+            }
+
+            let paths_len = paths.len();
+
+            match (FileBuffer { mode, paths }).write(&fm.files_path) {
+                Ok(()) => {
+                    fm.selections.clear();
+
+                    fm.status_message = Some(format!(
+                        "{} {} with paste",
+                        match mode {
+                            PasteMode::Copy => "Copy",
+                            PasteMode::Move => "Move",
+                        },
+                        count_files(paths_len)
+                    ));
+                }
+                Err(err) => {
+                    fm.status_message = Some(format!(
+                        "Unable to write {}: {}",
+                        fm.files_path.display(),
+                        err
+                    ));
+                }
+            }
+        }
+        "paste" => {
+            let buffer = match FileBuffer::read(&fm.files_path) {
+                Ok(Some(buffer)) => buffer,
+                Ok(None) => {
+                    fm.status_message = Some("No files to paste".to_string());
+                    return Ok(CommandFlow::Done);
+                }
+                Err(err) => {
+                    fm.status_message = Some(format!(
+                        "Unable to read {}: {}",
+                        fm.files_path.display(),
+                        err
+                    ));
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            let dest_dir = fm.dir_states.current_dir.clone();
+            let files_path = fm.files_path.clone();
+            let copy_options = CopyOptions {
+                mode: fm.config.copy_mode,
+                thread_count: fm.config.io_threads,
+            };
+            let size_units = fm.config.size_units;
+            let privilege_helper = fm.config.privilege_helper.clone();
+            let to_our_tx = tx.clone();
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                let last_pasted_path = paste_files(
+                    &prompter,
+                    &to_our_tx,
+                    &buffer,
+                    &dest_dir,
+                    copy_options,
+                    size_units,
+                    &privilege_helper,
+                );
+
+                // NOTE(Chris): Like in lf, moved files can only be
+                // pasted once, since they aren't where they were
+                if buffer.mode == PasteMode::Move {
+                    if let Err(err) = file_buffer::clear(&files_path) {
+                        report_status(
+                            &to_our_tx,
+                            format!("Unable to clear {}: {}", files_path.display(), err),
+                        );
+                    }
+                }
+
+                let pasted_file_id = last_pasted_path
+                    .and_then(|path| fs::symlink_metadata(path).ok())
+                    .map_or(0, |metadata| get_file_id(&metadata));
+
+                let to_our_tx_2 = to_our_tx.clone();
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    fm.paste_progress = None;
+
+                    // NOTE(Chris): We may have changed directories
+                    // while pasting
+                    if fm.dir_states.current_dir == dest_dir {
+                        reload_current_dir_prefer_id(fm, pasted_file_id, &to_our_tx_2);
+                    }
+
+                    Ok(())
+                });
+            });
+        }
+        "clear" => {
+            if let Err(err) = file_buffer::clear(&fm.files_path) {
+                fm.status_message = Some(format!(
+                    "Unable to clear {}: {}",
+                    fm.files_path.display(),
+                    err
+                ));
+            }
+        }
+        "zoom" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            let zoom = match command_use.arguments.first().map(String::as_str) {
+                None | Some("preview") => Some(Zoom::Preview),
+                Some("listing") => Some(Zoom::Listing),
+                Some(argument) => {
+                    fm.status_message = Some(format!(
+                        "zoom: expected preview or listing, not {}",
+                        argument
+                    ));
+                    None
+                }
+            };
+
+            if let Some(zoom) = zoom {
+                // NOTE(Chris): Zooming into the column that's already
+                // zoomed goes back to the normal layout
+                fm.zoom = if fm.zoom == Some(zoom) {
+                    None
+                } else {
+                    Some(zoom)
+                };
+
+                let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                apply_layout(fm, &mut screen_lock);
+            }
+        }
+        "layout" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            let name = command_use.arguments.first().map(String::as_str);
+
+            let ratios = match name {
+                None | Some("default") => Some(fm.config.ratios),
+                Some(name) => fm.config.layouts.get(name).copied(),
+            };
+
+            match ratios {
+                Some(ratios) => {
+                    fm.column_ratios = ratios;
+                    fm.zoom = None;
+
+                    let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                    apply_layout(fm, &mut screen_lock);
+                }
+                None => {
+                    fm.status_message =
+                        Some(format!("Unknown layout: {}", name.unwrap_or_default()));
+                }
+            }
+        }
+        "dump-screen" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            if command_use.arguments.is_empty() {
+                let prompt = fm.config.command_prompt.clone();
+
+                enter_command_mode_with(fm, "dump-screen ", prompt, AskingType::Command);
+            } else {
+                let dump_path = &command_use.arguments[0];
+
+                // NOTE(Chris): Commands run before the next frame is
+                // drawn, so this is the frame the user is looking at
+                let dump = screen.lock().expect("Failed to lock screen mutex!").dump();
+
+                fm.status_message = Some(match fs::write(dump_path, dump.to_string()) {
+                    Ok(()) => format!("Wrote screen to {}", dump_path),
+                    Err(err) => format!("Unable to write screen to {}: {}", dump_path, err),
+                });
+            }
+        }
+        "search-prev" => {
+            fm.should_search_forwards = !fm.should_search_forwards;
+
+            search_jump(fm)?;
+
+            fm.should_search_forwards = !fm.should_search_forwards;
+        }
+        "toggle" => {
+            toggle_selection(fm, second_entry_index);
+        }
+        "toggle-down" => {
+            toggle_selection(fm, second_entry_index);
+
+            cursor_down(fm, second_entry_index, second_bottom_index);
+        }
+        "set" => {
+            let arguments = &command_use.arguments;
+
+            match arguments.first().map(String::as_str) {
+                Some(option) if config::set_bool_option(&mut fm.config, option) => {
+                    resort_if_needed(fm, second_entry_index, tx);
+                }
+                Some("dotfiles") => {
+                    let value = arguments.get(1).map_or("", String::as_str);
+
+                    match config::to_dotfile_order(value) {
+                        Ok(dotfiles) => {
+                            fm.config.dotfiles = dotfiles;
+
+                            resort_if_needed(fm, second_entry_index, tx);
+                        }
+                        Err(err) => {
+                            fm.status_message = Some(err.to_string());
+                        }
+                    }
+                }
+                Some("info") => {
+                    let value = arguments.get(1).map_or("", String::as_str);
+
+                    match config::to_info_columns(value) {
+                        Ok(info_columns) => {
+                            fm.config.info_columns = info_columns;
+                        }
+                        Err(err) => {
+                            fm.status_message = Some(err.to_string());
+                        }
+                    }
+                }
+                Some(option) => {
+                    fm.status_message = Some(format!("Unknown option: {}", option));
+                }
+                None => {
+                    fm.status_message = Some("set: expected an option".to_string());
+                }
+            }
+        }
+        "read" => {
+            let prompt = fm.config.command_prompt.clone();
+
+            enter_command_mode_with(fm, "", prompt, AskingType::Command);
+        }
+        "rename" => {
+            // Get the full path of the current file
+            let current_entry_info = &fm.dir_states.current_entries[second_entry_index as usize];
+            let current_file = &current_entry_info.dir_entry;
+            let current_file_path = current_file.path();
+
+            let current_metadata = &current_entry_info.metadata;
+            let file_id = current_metadata.file_id();
+
+            // TODO(Chris): Get rid of these unwrap calls (at least the OsStr
+            // to str conversion one)
+            let file_name = current_file_path
+                .file_name()
+                .unwrap()
+                .to_str()
+                .unwrap()
+                .to_string();
+            // NOTE(Chris): Inline, the cursor starts before the
+            // extension, since the rest of the name is usually what
+            // changes
+            let mut rename_prompt =
+                Prompt::new(fm.config.rename_prompt.clone()).validator(validate_file_name);
+            if fm.config.inline_rename {
+                let stem_len = Path::new(&file_name)
+                    .file_stem()
+                    .map_or(file_name.len(), |stem| stem.len());
+
+                rename_prompt = rename_prompt.inline().initial_cursor(stem_len);
+            }
+            let rename_prompt = rename_prompt.initial_text(file_name);
+            let privilege_helper = fm.config.privilege_helper.clone();
+
+            let to_our_tx = tx.clone();
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                let new_name = match prompter.ask(rename_prompt) {
+                    Some(new_name) => new_name,
+                    None => return,
+                };
+
+                let new_file_path = current_file_path
+                    .parent()
+                    .unwrap()
+                    .join(PathBuf::from(&new_name));
+
+                // NOTE(Chris): Changing just the case of a name on a
+                // case-insensitive file system finds the file itself
+                if new_file_path.symlink_metadata().is_ok()
+                    && !os_abstract::is_same_path(&current_file_path, &new_file_path)
+                {
+                    report_status(&to_our_tx, format!("{} already exists", new_name));
+                    return;
+                }
+
+                let answer = prompter.ask(Prompt::new("Are you sure (y/n)? ").single_key());
+                if answer.as_deref() != Some("y") {
+                    return;
+                }
+
+                match fs::rename(&current_file_path, &new_file_path) {
+                    Ok(()) => (),
+                    Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                        let args = [
+                            OsStr::new("mv"),
+                            OsStr::new("--"),
+                            current_file_path.as_os_str(),
+                            new_file_path.as_os_str(),
+                        ];
+
+                        match privilege::retry_privileged(&prompter, &privilege_helper, &args) {
+                            Ok(true) => (),
+                            Ok(false) => return,
+                            Err(message) => {
+                                report_status(&to_our_tx, message);
+                                return;
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        report_status(&to_our_tx, format!("Unable to rename: {}", err));
+                        return;
+                    }
+                }
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    set_current_dir(
+                        fm.dir_states.current_dir.clone(),
+                        &mut fm.dir_states,
+                        &mut fm.match_positions,
+                    )
+                    .expect("Failed to update current directory");
+
+                    jump_by_file_id(fm, file_id)?;
+
+                    Ok(())
+                });
+            });
+        }
+        "rename-regex" => {
+            let (pattern, replacement) = match command_use.arguments.as_slice() {
+                [] => {
+                    let prompt = fm.config.command_prompt.clone();
+
+                    enter_command_mode_with(fm, "rename-regex ", prompt, AskingType::Command);
+                    return Ok(CommandFlow::Done);
+                }
+                [pattern] => (pattern, ""),
+                [pattern, replacement] => (pattern, replacement.as_str()),
+                _ => {
+                    fm.status_message =
+                        Some("Usage: rename-regex <pattern> [replacement]".to_string());
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            let regex = match Regex::new(pattern) {
+                Ok(regex) => regex,
+                Err(err) => {
+                    fm.status_message = Some(describe_regex_error(&err));
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            let renames =
+                batch_rename::plan_renames(&target_paths(fm, second_entry_index), |name| {
+                    regex.replace_all(name, replacement).into_owned()
+                });
+
+            preview_renames(fm, renames, format!("No file names match {}", pattern));
+        }
+        "rename-lower" | "rename-upper" | "rename-sanitize" => {
+            let to_new_name = match command {
+                "rename-lower" => str::to_lowercase,
+                "rename-upper" => str::to_uppercase,
+                _ => batch_rename::sanitize_name,
+            };
+
+            let renames =
+                batch_rename::plan_renames(&target_paths(fm, second_entry_index), to_new_name);
+
+            preview_renames(fm, renames, "No file names would change".to_string());
+        }
+        "new-from-template" => {
+            let templates_dir = PathBuf::from(&fm.config.templates_dir);
+
+            let template_names = match list_templates(&templates_dir) {
+                Ok(template_names) if !template_names.is_empty() => template_names,
+                Ok(_) => {
+                    fm.status_message =
+                        Some(format!("No templates in {}", templates_dir.display()));
+                    return Ok(CommandFlow::Done);
+                }
+                Err(err) => {
+                    fm.status_message = Some(format!(
+                        "Unable to read {}: {}",
+                        templates_dir.display(),
+                        err
+                    ));
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            let template_prompt = Prompt::choice("Template", template_names, fm.drawing_info.width);
+
+            let dest_dir = fm.dir_states.current_dir.clone();
+            let to_our_tx = tx.clone();
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                let template_name = match prompter.ask(template_prompt) {
+                    Some(template_name) => template_name,
+                    None => return,
+                };
+
+                let existing_dir = dest_dir.clone();
+                let new_name = match prompter.ask(
+                    Prompt::new("New file name: ")
+                        .initial_text(template_name.clone())
+                        .validator(move |name| {
+                            validate_file_name(name)?;
+
+                            if existing_dir.join(name).exists() {
+                                Err(format!("{} already exists", name))
+                            } else {
+                                Ok(())
+                            }
+                        }),
+                ) {
+                    Some(new_name) => new_name,
+                    None => return,
+                };
+
+                let new_path = dest_dir.join(&new_name);
+
+                if let Err(err) = fs::copy(templates_dir.join(&template_name), &new_path) {
+                    report_status(
+                        &to_our_tx,
+                        format!("Unable to create {}: {}", new_name, err),
+                    );
+                    return;
+                }
+
+                let new_file_id =
+                    fs::symlink_metadata(&new_path).map_or(0, |metadata| get_file_id(&metadata));
+
+                let to_our_tx_2 = to_our_tx.clone();
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    // NOTE(Chris): We may have changed directories
+                    // while being asked
+                    if fm.dir_states.current_dir == dest_dir {
+                        reload_current_dir_prefer_id(fm, new_file_id, &to_our_tx_2);
+                    }
+
+                    Ok(())
+                });
+            });
+        }
+        "cleanup" => {
+            let root = fm.dir_states.current_dir.clone();
+            let ignore_vcs = fm.config.ignore_vcs;
+            let to_our_tx = tx.clone();
+
+            fm.status_message = Some(format!(
+                "Looking for broken symlinks and empty directories in {}",
+                root.display()
+            ));
+
+            // NOTE(Chris): Scanning a big tree takes a while, so it
+            // happens in the background
+            std::thread::spawn(move || {
+                let clutter = cleanup::find_clutter(&root, ignore_vcs);
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    if clutter.is_empty() {
+                        fm.status_message =
+                            Some("No broken symlinks or empty directories".to_string());
+
+                        return Ok(());
+                    }
+
+                    fm.status_message = Some(format!(
+                        "Selected {} broken symlinks and {} empty \
+                         directories, which delete will remove",
+                        clutter.broken_symlinks.len(),
+                        clutter.empty_dirs.len()
+                    ));
+
+                    // NOTE(Chris): The clutter replaces any other
+                    // selections, so that delete only removes what was
+                    // found
+                    fm.selections = clutter
+                        .broken_symlinks
+                        .into_iter()
+                        .chain(clutter.empty_dirs)
+                        .collect();
+
+                    Ok(())
+                });
+            });
+        }
+        "run" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            let path = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry_info)
+                    if entry_info.file_type == RecordedFileType::File
+                        && entry_info
+                            .metadata
+                            .is_executable(&entry_info.dir_entry.path()) =>
+                {
+                    entry_info.dir_entry.path()
+                }
+                Some(entry_info) => {
+                    fm.status_message = Some(format!(
+                        "{} isn't executable",
+                        entry_info.dir_entry.file_name().to_string_lossy()
+                    ));
+                    return Ok(CommandFlow::Done);
+                }
+                None => return Ok(CommandFlow::Done),
+            };
+
+            let program = shell_quote(&path.to_string_lossy());
+
+            // NOTE(Chris): Arguments given to the command are used as is,
+            // and otherwise the whole command line is prompted for
+            if !command_use.arguments.is_empty() {
+                let arguments: Vec<String> = command_use
+                    .arguments
+                    .iter()
+                    .map(|argument| shell_quote(argument))
+                    .collect();
+
+                let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                let screen_lock = &mut *screen_lock;
+
+                let stdout = io::stdout();
+                let mut stdout_lock = stdout.lock();
+
+                enter_shell_command_then_redraw(
+                    fm,
+                    screen_lock,
+                    &mut stdout_lock,
+                    tx,
+                    second_entry_index,
+                    wait_after_command(&format!("{} {}", program, arguments.join(" "))),
+                )?;
+
+                return Ok(CommandFlow::Done);
+            }
+
+            let to_our_tx = tx.clone();
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                if let Some(command_line) =
+                    prompter.ask(Prompt::new("Run: ").initial_text(format!("{} ", program)))
+                {
+                    to_our_tx
+                        .send(InputEvent::CommandRequest(CommandRequest::Shell(
+                            wait_after_command(&command_line),
+                        )))
+                        .expect("Failed to send to main thread");
+                }
+            });
+        }
+        "terminal-here" => {
+            let is_in_tmux = env::var_os("TMUX").is_some();
+
+            match terminal_command(&fm.config.terminal, is_in_tmux) {
+                Some(terminal) => open_terminal(fm, tx, terminal),
+                None => {
+                    fm.status_message = Some(
+                        "terminal-here: Set the terminal option to a \
+                         terminal, like \"alacritty\""
+                            .to_string(),
+                    );
+                }
+            }
+        }
+        "xattrs" => {
+            let path = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry) => entry.dir_entry.path(),
+                None => return Ok(CommandFlow::Done),
+            };
+
+            match os_abstract::read_xattrs(&path) {
+                Ok(xattrs) if xattrs.is_empty() => {
+                    fm.status_message = Some("No extended attributes".to_string());
+                }
+                Ok(xattrs) => {
+                    let lines = xattrs
+                        .iter()
+                        .map(|(name, value)| format!("{}: {}", name, format_xattr_value(value)))
+                        .collect();
+
+                    fm.input_mode = InputMode::Attributes {
+                        top_ind: 0,
+                        view_rect: get_help_view_rect(fm.drawing_info),
+                        title: "Extended attributes",
+                        path,
+                        lines,
+                    };
+                }
+                Err(err) => {
+                    fm.status_message =
+                        Some(format!("Unable to read extended attributes: {}", err));
+                }
+            }
+        }
+        "stat" => {
+            let entry = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry) => entry,
+                None => return Ok(CommandFlow::Done),
+            };
+            let path = entry.dir_entry.path();
+            let metadata = match entry.metadata.local() {
+                Some(metadata) => metadata,
+                None => {
+                    fm.status_message = Some(format!("{} isn't on the disk", path.display()));
+                    return Ok(CommandFlow::Done);
+                }
+            };
+            let lines = stat_lines(&path, metadata, &fm.config);
+
+            fm.input_mode = InputMode::Attributes {
+                top_ind: 0,
+                view_rect: get_help_view_rect(fm.drawing_info),
+                title: "Info",
+                path,
+                lines,
+            };
+        }
+        "find-hardlinks" => {
+            let entry = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry) => entry,
+                None => return Ok(CommandFlow::Done),
+            };
+            let path = entry.dir_entry.path();
+            let metadata = match entry.metadata.local() {
+                Some(metadata) => metadata,
+                None => {
+                    fm.status_message = Some(format!("{} isn't on the disk", path.display()));
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            let link_count = os_abstract::get_extra_perms(metadata)
+                .hard_link_count
+                .unwrap_or(1);
+            if !metadata.is_file() || link_count < 2 {
+                fm.status_message = Some(format!("{} has no other hard links", path.display()));
+                return Ok(CommandFlow::Done);
+            }
+
+            // NOTE(Chris): An argument gives the directory to look
+            // through, which is the current one by default
+            let root = match command_use.arguments.first() {
+                Some(root) => fm.dir_states.current_dir.join(root),
+                None => fm.dir_states.current_dir.clone(),
+            };
+
+            let dev = os_abstract::get_device_id(metadata);
+            let ino = get_file_id(metadata);
+            let ignore_vcs = fm.config.ignore_vcs;
+            let to_our_tx = tx.clone();
+
+            fm.status_message = Some(format!("Looking for hard links in {}", root.display()));
+
+            // NOTE(Chris): Looking through a big tree takes a while
+            std::thread::spawn(move || {
+                let links = hard_links::find_hard_links(&root, dev, ino, link_count, ignore_vcs);
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    fm.status_message = Some(format!(
+                        "Found {} of {} links in {}",
+                        links.len(),
+                        link_count,
+                        root.display()
+                    ));
+
+                    // NOTE(Chris): The links aren't shown over a prompt
+                    // that was opened in the meantime
+                    if let InputMode::Normal = fm.input_mode {
+                        fm.input_mode = InputMode::Attributes {
+                            top_ind: 0,
+                            view_rect: get_help_view_rect(fm.drawing_info),
+                            title: "Hard links",
+                            path,
+                            lines: links
+                                .iter()
+                                .map(|link| link.display().to_string())
+                                .collect(),
+                        };
+                    }
+
+                    Ok(())
+                });
+            });
+        }
+        "acl" => {
+            let entry = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry) => entry,
+                None => return Ok(CommandFlow::Done),
+            };
+            let path = entry.dir_entry.path();
+            let metadata = match entry.metadata.local() {
+                Some(metadata) => metadata,
+                None => {
+                    fm.status_message = Some(format!("{} isn't on the disk", path.display()));
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            match os_abstract::read_acl(&path, metadata) {
+                Ok(lines) => {
+                    fm.input_mode = InputMode::Attributes {
+                        top_ind: 0,
+                        view_rect: get_help_view_rect(fm.drawing_info),
+                        title: "ACL",
+                        path,
+                        lines,
+                    };
+                }
+                Err(err) => {
+                    fm.status_message = Some(format!("Unable to read ACL: {}", err));
+                }
+            }
+        }
+        "unquarantine" => {
+            let mut removed_count = 0;
+            let mut errors = vec![];
+
+            for path in target_paths(fm, second_entry_index) {
+                match os_abstract::remove_xattr(&path, QUARANTINE_XATTR) {
+                    Ok(true) => removed_count += 1,
+                    Ok(false) => (),
+                    Err(err) => errors.push(format!("{}: {}", path.display(), err)),
+                }
+            }
+
+            fm.status_message = Some(match errors.first() {
+                Some(error) => {
+                    format!("Unable to remove quarantine from {}", error)
+                }
+                None => format!("Removed quarantine from {}", count_files(removed_count)),
+            });
+        }
+        "quicklook" | "reveal" => {
+            let path = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry) => entry.dir_entry.path(),
+                None => return Ok(CommandFlow::Done),
+            };
+
+            let command = if command_use.name == "reveal" {
+                Ok(os_abstract::reveal_command(&path))
+            } else {
+                os_abstract::quick_look_command(&path)
+            };
+
+            match command {
+                Ok(command) => run_system_app(tx, &command_use.name, command),
+                Err(err) => fm.status_message = Some(format!("{}: {}", command_use.name, err)),
+            }
+        }
+        "tmux-send" => {
+            if env::var_os("TMUX").is_none() {
+                fm.status_message = Some("tmux-send: Not running in tmux".to_string());
+                return Ok(CommandFlow::Done);
+            }
+
+            let paths = target_paths(fm, second_entry_index);
+
+            // NOTE(Chris): "tmux-send pane" types the paths into the last
+            // pane instead of loading them into a paste buffer
+            let to_pane = command_use
+                .arguments
+                .first()
+                .is_some_and(|arg| arg == "pane");
+
+            send_to_tmux(tx, tmux_send_args(&paths, to_pane), paths.len());
+        }
+        "diff" => {
+            let Some(screen) = screen else {
+                return Ok(CommandFlow::Unavailable);
+            };
+            let mut paths: Vec<PathBuf> = fm.selections.iter().cloned().collect();
+            paths.sort();
+
+            // NOTE(Chris): With one selection, it's compared to the
+            // current file
+            if paths.len() == 1 {
+                paths.extend(target_paths(fm, second_entry_index));
+                paths.dedup();
+            }
+
+            let (old_path, new_path) = match paths.as_slice() {
+                [old_path, new_path] => (old_path.clone(), new_path.clone()),
+                _ => {
+                    fm.status_message = Some(
+                        "Select two files to compare (or one, and move \
+                         to the other)"
+                            .to_string(),
+                    );
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            if !fm.config.difftool.is_empty() {
+                let shell_command = format!(
+                    "{} {} {}",
+                    fm.config.difftool,
+                    shell_quote(&old_path.to_string_lossy()),
+                    shell_quote(&new_path.to_string_lossy())
+                );
+
+                let mut screen_lock = screen.lock().expect("Failed to lock screen mutex!");
+                let screen_lock = &mut *screen_lock;
+
+                let stdout = io::stdout();
+                let mut stdout_lock = stdout.lock();
+
+                enter_shell_command_then_redraw(
+                    fm,
+                    screen_lock,
+                    &mut stdout_lock,
+                    tx,
+                    second_entry_index,
+                    shell_command,
+                )?;
+
+                return Ok(CommandFlow::Done);
+            }
+
+            let to_our_tx = tx.clone();
+
+            // NOTE(Chris): Big files take a while to read and compare
+            std::thread::spawn(move || {
+                let result = compare_files(&old_path, &new_path);
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    match result {
+                        // NOTE(Chris): The differences aren't shown over
+                        // a prompt that was opened in the meantime
+                        Ok(diff_lines) => {
+                            if let InputMode::Normal = fm.input_mode {
+                                fm.input_mode = InputMode::Diff {
+                                    top_ind: 0,
+                                    view_rect: get_help_view_rect(fm.drawing_info),
+                                    title: format!(
+                                        "{} {} {}",
+                                        old_path.display(),
+                                        if fm.config.plain { "->" } else { "→" },
+                                        new_path.display()
+                                    ),
+                                    diff_lines,
+                                };
+                            }
+                        }
+                        Err(message) => {
+                            fm.status_message = Some(message);
+                        }
+                    }
+
+                    Ok(())
+                });
+            });
+        }
+        "compare-dirs" => {
+            let mut selected_dirs: Vec<PathBuf> = fm
+                .selections
+                .iter()
+                .filter(|path| path.is_dir())
+                .cloned()
+                .collect();
+            selected_dirs.sort();
+
+            // NOTE(Chris): With one selected directory, it's compared to
+            // the current one
+            let (left, right) = match selected_dirs.as_slice() {
+                [left, right] => (left.clone(), right.clone()),
+                [right] if *right != fm.dir_states.current_dir => {
+                    (fm.dir_states.current_dir.clone(), right.clone())
+                }
+                _ => {
+                    fm.status_message = Some(
+                        "Select two directories to compare (or one, to \
+                         compare with this one)"
+                            .to_string(),
+                    );
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            fm.status_message = Some(format!(
+                "Comparing {} with {}",
+                left.display(),
+                right.display()
+            ));
+
+            let ignore_vcs = fm.config.ignore_vcs;
+            let to_our_tx = tx.clone();
+
+            std::thread::spawn(move || {
+                let result = compare_dirs::compare_dirs(&left, &right, ignore_vcs);
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    match result {
+                        Ok(differences) if differences.is_empty() => {
+                            fm.status_message =
+                                Some("The directories have the same files".to_string());
+                        }
+                        Ok(differences) => {
+                            fm.status_message = None;
+
+                            if let InputMode::Normal = fm.input_mode {
+                                fm.input_mode = InputMode::CompareDirs {
+                                    top_ind: 0,
+                                    cursor: 0,
+                                    view_rect: get_help_view_rect(fm.drawing_info),
+                                    left,
+                                    right,
+                                    differences,
+                                };
+                            }
+                        }
+                        Err(err) => {
+                            fm.status_message =
+                                Some(format!("Unable to compare the directories: {}", err));
+                        }
+                    }
+
+                    Ok(())
+                });
+            });
+        }
+        "devices" => {
+            let screen_width = fm.drawing_info.width;
+            let to_our_tx = tx.clone();
+
+            fm.status_message = Some("Looking for phones and cameras...".to_string());
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                let devices = match os_abstract::mount_devices() {
+                    Ok(devices) if !devices.is_empty() => devices,
+                    Ok(_) => {
+                        report_status(&to_our_tx, "No phones or cameras are connected".to_string());
+                        return;
+                    }
+                    Err(err) => {
+                        report_status(&to_our_tx, format!("Unable to find devices: {}", err));
+                        return;
+                    }
+                };
+
+                let device_path = if devices.len() == 1 {
+                    devices[0].path.clone()
+                } else {
+                    let names = devices.iter().map(|device| device.name.clone()).collect();
+
+                    let name = match prompter.ask(Prompt::choice("Device", names, screen_width)) {
+                        Some(name) => name,
+                        None => return,
+                    };
+
+                    match devices.into_iter().find(|device| device.name == name) {
+                        Some(device) => device.path,
+                        None => return,
+                    }
+                };
+
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    fm.status_message = None;
+
+                    if let Err(err) =
+                        set_current_dir(&device_path, &mut fm.dir_states, &mut fm.match_positions)
+                    {
+                        fm.status_message =
+                            Some(format!("Unable to open {}: {}", device_path.display(), err));
+                    }
+
+                    Ok(())
+                });
+            });
+        }
+        "extract" => {
+            let mount = match &fm.dir_states.mount {
+                Some(mount) => mount.clone(),
+                None => {
+                    fm.status_message = Some(
+                        "extract only works in an archive, on a server, or in a bucket".to_string(),
+                    );
+                    return Ok(CommandFlow::Done);
+                }
+            };
+            let entry_info = match fm
+                .dir_states
+                .current_entries
+                .get(second_entry_index as usize)
+            {
+                Some(entry_info) => entry_info.clone(),
+                None => return Ok(CommandFlow::Done),
+            };
+
+            // NOTE(Chris): Files are extracted to the directory the archive is in (or where cd
+            // went to the server from), unless an argument gives another one
+            let dest_dir = match command_use.arguments.first() {
+                Some(dir) => cd_target(mount.working_dir(), dir),
+                None => mount.working_dir().to_path_buf(),
+            };
+            let file_name = entry_info.dir_entry.file_name();
+            let dest = dest_dir.join(&file_name);
+            let to_our_tx = tx.clone();
+
+            fm.status_message = Some(format!("Extracting {}...", file_name.to_string_lossy()));
+
+            spawn_command_thread(tx, to_command_tx, move |_| {
+                let message = match vfs::extract(mount.file_system(), &entry_info, &dest) {
+                    Ok(count) => {
+                        format!("Extracted {} to {}", count_files(count), dest_dir.display())
+                    }
+                    Err(err) => {
+                        format!("Unable to extract {}: {}", file_name.to_string_lossy(), err)
+                    }
+                };
+
+                report_status(&to_our_tx, message);
+            });
+        }
+        "cd" => {
+            let target = match command_use.arguments.first() {
+                Some(target) => target,
+                None => {
+                    let prompt = fm.config.command_prompt.clone();
+
+                    enter_command_mode_with(fm, "cd ", prompt, AskingType::Command);
+                    return Ok(CommandFlow::Done);
+                }
+            };
+
+            if target.starts_with("sftp://") {
+                #[cfg(feature = "sftp")]
+                connect_sftp(fm, tx, to_command_tx, target);
+                #[cfg(not(feature = "sftp"))]
+                {
+                    fm.status_message =
+                        Some("rolf was built without SFTP support (the sftp feature)".to_string());
+                }
+
+                return Ok(CommandFlow::Done);
+            }
+
+            if target.starts_with("s3://") {
+                #[cfg(feature = "s3")]
+                connect_s3(fm, tx, to_command_tx, target);
+                #[cfg(not(feature = "s3"))]
+                {
+                    fm.status_message =
+                        Some("rolf was built without S3 support (the s3 feature)".to_string());
+                }
+
+                return Ok(CommandFlow::Done);
+            }
+
+            let dir = cd_target(&fm.dir_states.current_dir, target);
+
+            if !fm.dir_states.file_system_for(&dir).is_dir(&dir) {
+                fm.status_message = Some(format!("{} isn't a directory", target));
+                return Ok(CommandFlow::Done);
+            }
+
+            if let Err(err) = change_dir(fm, &dir, None) {
+                fm.status_message = Some(format!("Unable to open {}: {}", dir.display(), err));
+            }
+        }
+        "attrs" => {
+            let paths = target_paths(fm, second_entry_index);
+
+            if paths.is_empty() {
+                return Ok(CommandFlow::Done);
+            }
+
+            let to_our_tx = tx.clone();
+
+            spawn_command_thread(tx, to_command_tx, move |prompter| {
+                let files = paths
+                    .into_iter()
+                    .map(|path| fs::metadata(&path).map(|metadata| (path, metadata)))
+                    .collect::<io::Result<Vec<_>>>();
+                let files = match files {
+                    Ok(files) => files,
+                    Err(err) => {
+                        report_status(&to_our_tx, format!("Unable to read attributes: {}", err));
+                        return;
+                    }
+                };
+
+                let attribute_sets: Vec<u32> = files
+                    .iter()
+                    .map(|(_, metadata)| os_abstract::get_attributes(metadata))
+                    .collect();
+
+                let description = match prompter.ask(
+                    Prompt::new(format!(
+                        "Set {} for {}: ",
+                        os_abstract::ATTRIBUTE_LETTERS,
+                        count_files(files.len())
+                    ))
+                    .initial_text(attrs::describe(&attribute_sets))
+                    .validator(|description| AttributeChanges::parse(description).map(|_| ())),
+                ) {
+                    Some(description) => description,
+                    None => return,
+                };
+
+                let changes = match AttributeChanges::parse(&description) {
+                    Ok(changes) => changes,
+                    Err(message) => {
+                        report_status(&to_our_tx, message);
+                        return;
+                    }
+                };
+
+                let mut changed_count = 0;
+                let mut message = None;
+
+                for ((path, metadata), attributes) in files.iter().zip(attribute_sets) {
+                    let new_attributes = changes.apply(attributes);
+
+                    if new_attributes == attributes {
+                        continue;
+                    }
+
+                    match os_abstract::set_attributes(path, metadata, new_attributes) {
+                        Ok(()) => changed_count += 1,
+                        Err(err) => {
+                            message = Some(format!("Unable to change {}: {}", path.display(), err));
+                            break;
+                        }
+                    }
+                }
+
+                let message =
+                    message.unwrap_or_else(|| format!("Changed {}", count_files(changed_count)));
+
+                let to_our_tx_2 = to_our_tx.clone();
+                send_callback_to_main!(&to_our_tx, move |fm| {
+                    fm.status_message = Some(message);
+
+                    let file_id = fm
+                        .dir_states
+                        .current_entries
+                        .get(fm.get_second_entry_index() as usize)
+                        .map_or(0, |entry_info| entry_info.metadata.file_id());
+
+                    reload_current_dir_prefer_id(fm, file_id, &to_our_tx_2);
+
+                    Ok(())
+                });
+            });
+        }
+        "delete" => {
+            'delete_command: loop {
+                if fm.selections.is_empty() {
+                    // Delete the current file
+
+                    // TODO(Chris): Show some sort of error message here
+                    if fm.dir_states.current_entries.is_empty() {
+                        break 'delete_command;
+                    }
+
+                    // Get the full path of the current file
+                    let current_file =
+                        &fm.dir_states.current_entries[second_entry_index as usize].dir_entry;
+                    let current_file_path = current_file.path();
+
+                    let privilege_helper = fm.config.privilege_helper.clone();
+                    let io_threads = fm.config.io_threads;
+                    let size_units = fm.config.size_units;
+                    let to_our_tx = tx.clone();
+
+                    spawn_command_thread(tx, to_command_tx, move |prompter| {
+                        let description = format!(
+                            "'{}'",
+                            &current_file_path
+                                .as_os_str()
+                                .to_str()
+                                .expect("File name not in UTF-8")
+                        );
+                        if !confirm_delete(
+                            &prompter,
+                            &description,
+                            vec![current_file_path.clone()],
+                            size_units,
+                        ) {
+                            return;
+                        }
+
+                        // TODO(Chris): Handle file to be renamed not found
+                        let old_file_id = get_file_id(&fs::metadata(&current_file_path).unwrap());
+
+                        if let Err(message) = remove_or_retry_privileged(
+                            &prompter,
+                            &privilege_helper,
+                            &[current_file_path],
+                            io_threads,
+                        ) {
+                            report_status(&to_our_tx, message);
+                        }
+
+                        let to_our_tx_2 = to_our_tx.clone();
+                        send_callback_to_main!(&to_our_tx, move |fm| {
+                            reload_current_dir_prefer_id(fm, old_file_id, &to_our_tx_2);
+
+                            Ok(())
+                        });
+                    });
+                } else {
+                    // Delete the selected files
+
+                    let selections: Vec<PathBuf> = fm.selections.iter().cloned().collect();
+                    let selections_len = selections.len();
+
+                    let privilege_helper = fm.config.privilege_helper.clone();
+                    let io_threads = fm.config.io_threads;
+                    let size_units = fm.config.size_units;
+                    let to_our_tx = tx.clone();
+
+                    spawn_command_thread(tx, to_command_tx, move |prompter| {
+                        let description = format!("{} items", selections_len);
+                        if !confirm_delete(&prompter, &description, selections.clone(), size_units)
+                        {
+                            return;
+                        }
+
+                        if let Err(message) = remove_or_retry_privileged(
+                            &prompter,
+                            &privilege_helper,
+                            &selections,
+                            io_threads,
+                        ) {
+                            report_status(&to_our_tx, message);
+                        }
+
+                        let to_our_tx_2 = to_our_tx.clone();
+                        send_callback_to_main!(&to_our_tx, move |fm| {
+                            // NOTE(Chris): The current file may have
+                            // just been deleted, so we use the metadata
+                            // we read before deleting it
+                            let old_file_id = if fm.dir_states.current_entries.is_empty() {
+                                0
+                            } else {
+                                fm.dir_states.current_entries[fm.get_second_entry_index() as usize]
+                                    .metadata
+                                    .file_id()
+                            };
+
+                            fm.selections.clear();
+
+                            reload_current_dir_prefer_id(fm, old_file_id, &to_our_tx_2);
+
+                            Ok(())
+                        });
+                    });
+                }
+
+                break 'delete_command;
+            }
+        }
+        "gallery" => {
+            open_gallery(fm, tx);
+        }
+        "log" => {
+            fm.input_mode = InputMode::Log {
+                top_ind: 0,
+                view_rect: get_help_view_rect(fm.drawing_info),
+            };
+        }
+        "help" => {
+            let mut keybindings_vec: Vec<(String, String, String)> = fm
+                .config
+                .keybindings
+                .iter()
+                .map(|(key_event, command)| {
+                    (
+                        to_string(*key_event),
+                        command.to_owned(),
+                        get_command_desc(command).to_string(),
+                    )
+                })
+                .collect();
+
+            keybindings_vec.sort_unstable_by(
+                |(_key_display1, command1, _), (_key_display2, command2, _)| command1.cmp(command2),
+            );
+
+            // NOTE(Chris): The tools are listed after a blank line, so
+            // that it's clear why a preview may look plain
+            keybindings_vec.push(Default::default());
+            keybindings_vec.extend(tools::TOOLS.iter().map(|tool| {
+                let availability = if fm.tools.get(tool.name).is_some() {
+                    "installed"
+                } else {
+                    "not found"
+                };
+
+                (
+                    tool.name.to_string(),
+                    availability.to_string(),
+                    tool.purpose.to_string(),
+                )
+            }));
+
+            fm.input_mode = InputMode::View {
+                top_ind: 0,
+                view_rect: get_help_view_rect(fm.drawing_info),
+                keybindings_vec,
+            };
+        }
+        _ => return Ok(CommandFlow::Unknown),
+    }
+
+    Ok(CommandFlow::Done)
+}
+
+fn spawn_command_thread(
+    tx: &Sender<InputEvent>,
+    to_command_tx: &mut Option<Sender<String>>,
+    command: impl FnOnce(PromptHandle) + Send + 'static,
+) {
+    let (new_tx, to_command_rx) = channel();
+
+    *to_command_tx = Some(new_tx);
+
+    let to_our_tx = tx.clone();
+    let prompter = PromptHandle::new(tx.clone(), to_command_rx);
+
+    std::thread::spawn(move || {
+        defer! {
+            quit_command_thread(&to_our_tx);
+        }
+
+        command(prompter);
+    });
+}
+
+fn validate_file_name(name: &str) -> Result<(), String> {
+    if name.contains(path::MAIN_SEPARATOR) || name == "." || name == ".." {
+        Err(format!("Invalid file name: {}", name))
+    } else {
+        Ok(())
+    }
+}
+
+// The names of the files in the templates directory, sorted. Directories are left out, since only
+// files are copied.
+fn list_templates(templates_dir: &Path) -> io::Result<Vec<String>> {
+    let mut template_names: Vec<String> = fs::read_dir(templates_dir)?
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+
+    template_names.sort();
+
+    Ok(template_names)
+}
+
+fn quit_command_thread(to_main_tx: &Sender<InputEvent>) {
+    to_main_tx
+        .send(InputEvent::CommandRequest(CommandRequest::Quit))
+        .expect("Failed to send to main thread");
+}
+
+fn exit_input_mode_command_thread(fm: &mut FileManager, to_command_tx: &Option<Sender<String>>) {
+    if let Some(to_command_tx) = &to_command_tx {
+        to_command_tx
+            .send(fm.input_line.clone())
+            .expect("Failed to send to command thread");
+
+        clear_input_line(fm);
+
+        fm.active_prompt = None;
+    } else {
+        panic!("Main thread: Asked for additional input despite no command thread being available");
+    }
+}
+
+// NOTE(Chris): When it comes to refactoring many variables into structs, perhaps we should group
+// them by when they are modified. For example, DrawingInfo is modified whenever the terminal
+// window resizes, while ColumnInfo will be modified even when the terminal window isn't resizing.
+// Thus, we should maybe put the left_x value for each column in DrawingInfo (rather than
+// ColumnInfo), since those will primarily be modified when the terminal window changes.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct DrawingInfo {
+    win_pixels: WindowPixels,
+    width: u16,
+    height: u16,
+    column_bot_y: u16,
+    column_height: u16,
+    first_right_x: u16,
+    first_left_x: u16,
+    second_left_x: u16,
+    second_right_x: u16,
+    third_left_x: u16,
+    third_right_x: u16,
+    // The rows of the preview, which only differ from the other columns' rows when it's below them
+    third_top_y: u16,
+    third_height: u16,
+}
+
+// Tracks which regions of the screen need to be redrawn in the next frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct DirtyRegions {
+    top_line: bool,
+    columns: bool,
+    preview: bool,
+    status: bool,
+}
+
+impl DirtyRegions {
+    fn all() -> Self {
+        Self {
+            top_line: true,
+            columns: true,
+            preview: true,
+            status: true,
+        }
+    }
+
+    fn none() -> Self {
+        Self {
+            top_line: false,
+            columns: false,
+            preview: false,
+            status: false,
+        }
+    }
+
+    fn any(&self) -> bool {
+        self.top_line || self.columns || self.preview || self.status
+    }
+}
+
+fn clear_dirty_regions(screen: &mut Screen, drawing_info: &DrawingInfo, dirty: DirtyRegions) {
+    let width = drawing_info.width;
+    let third_left_x = drawing_info.third_left_x;
+
+    if dirty.top_line {
+        screen.clear_logical_rect(0, 0, width, 1);
+    }
+
+    if dirty.columns {
+        // NOTE(Chris): A preview below the columns leaves them the full width, along with the gap
+        // above it
+        if drawing_info.third_top_y > 1 {
+            screen.clear_logical_rect(0, 1, width, drawing_info.third_top_y - 1);
+        } else {
+            screen.clear_logical_rect(0, 1, third_left_x, drawing_info.column_height);
+        }
+    }
+
+    if dirty.preview {
+        screen.clear_logical_rect(
+            third_left_x,
+            drawing_info.third_top_y,
+            width - third_left_x,
+            drawing_info.third_height,
+        );
+    }
+
+    if dirty.status {
+        screen.clear_logical_rect(0, drawing_info.height - 1, width, 1);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ColumnInfo {
+    starting_index: u16,
+    display_offset: u16,
+}
+
+#[derive(Debug)]
+enum InputEvent {
+    CrosstermEvent { event: crossterm::event::Event },
+    PreviewLoaded(PreviewData),
+    // A preview couldn't be loaded, e.g. because the image is corrupt
+    PreviewFailed { path: PathBuf, error: io::Error },
+    CommandRequest(CommandRequest),
+    CommandCallback(CommandCallback),
+}
+
+impl InputEvent {
+    #[allow(dead_code)]
+    fn display_event_type(&self) -> &'static str {
+        match self {
+            InputEvent::CrosstermEvent { .. } => "CrosstermEvent",
+            InputEvent::PreviewLoaded(_) => "PreviewLoaded",
+            InputEvent::PreviewFailed { .. } => "PreviewFailed",
+            InputEvent::CommandRequest(_) => "CommandRequest",
+            InputEvent::CommandCallback(_) => "CommandCallback",
+            // _ => "UNSUPPORTED EVENT DISPLAY",
+        }
+    }
+}
+
+struct CommandCallback(Box<CommandCallbackFn>);
+type CommandCallbackFn = dyn FnOnce(&mut FileManager) -> io::Result<()> + Send;
+
+impl std::fmt::Debug for CommandCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CommandCallback(...)")
+    }
+}
+
+fn send_callback_to_main(to_main_tx: &Sender<InputEvent>, cb: Box<CommandCallbackFn>) {
+    to_main_tx
+        .send(InputEvent::CommandCallback(CommandCallback(cb)))
+        .expect("Failed to send to main thread");
+}
+
+#[derive(Debug)]
+enum CommandRequest {
+    Ask(Prompt),
+    // Runs a shell command in the terminal, with rolf suspended until it exits
+    Shell(String),
+    Quit,
+}
+
+// Changes the filter on the current entries, keeping the cursor on the same file if it's still
+// shown
+fn set_entry_filter(fm: &mut FileManager, filter: Option<EntryFilter>, tx: &Sender<InputEvent>) {
+    let file_id = fm
+        .dir_states
+        .current_entries
+        .get(fm.get_second_entry_index() as usize)
+        .map_or(0, |entry_info| entry_info.metadata.file_id());
+
+    fm.dir_states.filter = filter;
+
+    reload_current_dir_prefer_id(fm, file_id, tx);
+}
+
+/// Reloads the current directory.
+///
+/// If `maybe_existing_file_id` corresponds to the file id of an existing file in the current
+/// directory, place the second entry index on that file.
+///
+/// Otherwise, find the nearest existing file and place the second entry index on that.
+fn reload_current_dir_prefer_id(
+    fm: &mut FileManager,
+    maybe_existing_file_id: u64,
+    tx: &Sender<InputEvent>,
+) {
+    set_current_dir(
+        fm.dir_states.current_dir.clone(),
+        &mut fm.dir_states,
+        &mut fm.match_positions,
+    )
+    .expect("Failed to update current directory");
+
+    // NOTE(Chris): This is how we try to jump to a desired existing file early. The cursor may
+    // not have moved, but the preview is stale if the way entries are sorted has changed (e.g.
+    // with "set ignoredia").
+    if jump_by_file_id(fm, maybe_existing_file_id).is_ok() {
+        set_preview_data_with_thread(fm, tx, fm.get_second_entry_index());
+        return;
+    }
+
+    let mut existing_file_id: Option<u64> = None;
+
+    let initial_second_entry_index = fm.get_second_entry_index();
+
+    for index in initial_second_entry_index as usize..fm.dir_states.current_entries.len() {
+        let current_entry = &fm.dir_states.current_entries[index];
+        let current_metadata = &current_entry.metadata;
+
+        existing_file_id = Some(current_metadata.file_id());
+        break;
+    }
+
+    if existing_file_id.is_none() {
+        for index in (0..initial_second_entry_index as usize).rev() {
+            if let Some(current_entry) = fm.dir_states.current_entries.get(index) {
+                let current_metadata = &current_entry.metadata;
+
+                existing_file_id = Some(current_metadata.file_id());
+                break;
+            }
+        }
+    }
+
+    if let Some(existing_file_id) = existing_file_id {
+        jump_by_file_id(fm, existing_file_id).expect("Unable to jump to file by id");
+    } else {
+        fm.second = ColumnInfo {
+            starting_index: 0,
+            display_offset: 0,
+        }
+    };
+
+    set_preview_data_with_thread(fm, tx, fm.get_second_entry_index());
+}
+
+// NOTE(Chris): The on-select hook only runs once the cursor has stayed on a file for this long, so
+// that scrolling through a directory doesn't start a shell for every file passed over
+const SELECT_HOOK_DELAY: Duration = Duration::from_millis(100);
+
+// How long pending keys (like the count in "12j") wait for the rest of the command
+const PENDING_KEYS_TIMEOUT: Duration = Duration::from_millis(1000);
+
+fn hook_context(fm: &FileManager) -> hooks::HookContext {
+    hooks::HookContext {
+        current_dir: fm.dir_states.working_dir().to_path_buf(),
+        current_file: fm
+            .dir_states
+            .current_entries
+            .get(fm.get_second_entry_index() as usize)
+            .map(|entry_info| entry_info.dir_entry.path()),
+        selections: fm.selections.iter().cloned().collect(),
+    }
+}
+
+// Runs a hook in another thread, only reporting back if it fails
+fn run_hook_in_background(
+    fm: &FileManager,
+    to_main_tx: &Sender<InputEvent>,
+    name: &str,
+    hook: &str,
+) {
+    let mut command = hooks::hook_command(hook, &hook_context(fm));
+    let name = name.to_string();
+    let to_main_tx = to_main_tx.clone();
+
+    std::thread::spawn(move || {
+        let message = match command.status() {
+            Ok(status) if status.success() => return,
+            Ok(status) => format!("{} hook failed ({})", name, status),
+            Err(err) => format!("Unable to run {} hook: {}", name, err),
+        };
+
+        report_status(&to_main_tx, message);
+    });
+}
+
+// The shell command which opens a terminal for terminal-here, which is the terminal option, or a
+// new tmux window when that's empty
+fn terminal_command(terminal: &str, is_in_tmux: bool) -> Option<&str> {
+    if !terminal.is_empty() {
+        Some(terminal)
+    } else if is_in_tmux {
+        Some("tmux new-window -c \"$PWD\"")
+    } else {
+        None
+    }
+}
+
+// Runs the terminal in the current directory, like a hook, since it's left running on its own
+fn open_terminal(fm: &FileManager, to_main_tx: &Sender<InputEvent>, terminal: &str) {
+    let mut command = hooks::hook_command(terminal, &hook_context(fm));
+    let terminal = terminal.to_string();
+    let to_main_tx = to_main_tx.clone();
+
+    // NOTE(Chris): Most terminals don't exit until they're closed, so this waits on its own
+    // thread, only to report a terminal which couldn't start
+    std::thread::spawn(move || {
+        let message = match command.status() {
+            Ok(status) if status.success() => return,
+            Ok(status) => format!("terminal-here: {} failed ({})", terminal, status),
+            Err(err) => format!("terminal-here: Unable to run {}: {}", terminal, err),
+        };
+
+        report_status(&to_main_tx, message);
+    });
+}
+
+// Describes a file like stat does, including the device and inode that tell it apart from every
+// other file (and which all of its hard links share)
+fn stat_lines(path: &Path, metadata: &fs::Metadata, config: &Config) -> Vec<String> {
+    let extra_perms = os_abstract::get_extra_perms(metadata);
+    let now = SystemTime::now();
+
+    let mut lines = vec![format!("File: {}", path.display())];
+
+    let mut mode = extra_perms.mode;
+    mode.extend(os_abstract::mode_suffix(path));
+    lines.push(format!("Mode: {}", mode));
+
+    if let Some(size) = extra_perms.size {
+        lines.push(format!("Size: {}B", group_digits(size)));
+    }
+
+    if let Some(hard_link_count) = extra_perms.hard_link_count {
+        lines.push(format!("Links: {}", hard_link_count));
+    }
+
+    if let (Some(user_name), Some(group_name)) = (extra_perms.user_name, extra_perms.group_name) {
+        lines.push(format!("Owner: {} {}", user_name, group_name));
+    }
+
+    lines.push(format!(
+        "Device: {}  Inode: {}",
+        os_abstract::get_device_id(metadata),
+        get_file_id(metadata)
+    ));
+
+    for (label, time) in [
+        ("Modified", extra_perms.modified),
+        ("Accessed", extra_perms.accessed),
+        ("Created", extra_perms.created),
+    ] {
+        if let Some(time) = time {
+            lines.push(format!(
+                "{}: {}",
+                label,
+                time_format::format_time(time, config, now)
+            ));
+        }
+    }
+
+    lines
+}
+
+// The extended attribute macOS puts on downloaded files, which makes Gatekeeper check them before
+// they're opened
+const QUARANTINE_XATTR: &str = "com.apple.quarantine";
+
+// Shows an extended attribute's value as text when it's printable, or else as hex bytes
+fn format_xattr_value(value: &[u8]) -> String {
+    // NOTE(Chris): Many text values end with a nul, which isn't worth showing as hex
+    let text = value.strip_suffix(&[0]).unwrap_or(value);
+
+    match std::str::from_utf8(text) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ => value
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+// Waits on a native app (like a previewer or file browser) in another thread, since it may stay
+// open until it's closed
+fn run_system_app(to_main_tx: &Sender<InputEvent>, name: &str, mut command: Command) {
+    let name = name.to_string();
+    let to_main_tx = to_main_tx.clone();
+
+    // NOTE(Chris): Only an app that couldn't start is reported, since File Explorer exits with a
+    // failing status even when it works
+    std::thread::spawn(move || {
+        if let Err(err) = command.status() {
+            report_status(
+                &to_main_tx,
+                format!(
+                    "{}: Unable to run {:?}: {}",
+                    name,
+                    command.get_program(),
+                    err
+                ),
+            );
+        }
+    });
+}
+
+// The arguments to tmux which load the paths into a paste buffer, or type them into the last pane,
+// quoted and separated by spaces so that they can be used as a shell command's arguments
+fn tmux_send_args(paths: &[PathBuf], to_pane: bool) -> Vec<String> {
+    let text = paths
+        .iter()
+        .map(|path| shell_quote(&path.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if to_pane {
+        vec![
+            "send-keys".to_string(),
+            "-t".to_string(),
+            "{last}".to_string(),
+            "-l".to_string(),
+            "--".to_string(),
+            text,
+        ]
+    } else {
+        vec!["set-buffer".to_string(), "--".to_string(), text]
+    }
+}
+
+fn send_to_tmux(to_main_tx: &Sender<InputEvent>, args: Vec<String>, count: usize) {
+    let to_main_tx = to_main_tx.clone();
+
+    std::thread::spawn(move || {
+        let output = Command::new("tmux")
+            .args(&args)
+            .stdin(std::process::Stdio::null())
+            .output();
+
+        let message = match output {
+            Ok(output) if output.status.success() => {
+                format!("Sent {} to tmux", count_files(count))
+            }
+            Ok(output) => format!(
+                "tmux-send failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(err) => format!("tmux-send: Unable to run tmux: {}", err),
+        };
+
+        report_status(&to_main_tx, message);
+    });
+}
+
+// Shows a message (usually an error) from another thread on the bottom line
+fn report_status(to_main_tx: &Sender<InputEvent>, message: String) {
+    send_callback_to_main!(to_main_tx, move |fm| {
+        fm.status_message = Some(message);
+
+        Ok(())
+    });
+}
+
+// Pastes the files in the buffer into the destination directory, asking what to do about each
+// file that's already there. Reports how it went, and returns the path of the last file pasted.
+fn paste_files(
+    prompter: &PromptHandle,
+    to_main_tx: &Sender<InputEvent>,
+    buffer: &FileBuffer,
+    dest_dir: &Path,
+    copy_options: CopyOptions,
+    size_units: SizeUnits,
+    privilege_helper: &str,
+) -> Option<PathBuf> {
+    let mut progress = PasteProgress {
+        total: buffer
+            .paths
+            .iter()
+            .map(|path| io_pool::tree_size(path).byte_count)
+            .sum(),
+        done: 0,
+        units: size_units,
+    };
+    let start = Instant::now();
+    let mut last_report = start;
+
+    let mut on_progress = |bytes: u64| {
+        progress.done += bytes;
+
+        // NOTE(Chris): Every report redraws the screen, so they're kept to a few a second
+        if last_report.elapsed() >= PASTE_PROGRESS_INTERVAL {
+            last_report = Instant::now();
+
+            let description = progress.describe(start.elapsed());
+            send_callback_to_main!(to_main_tx, move |fm| {
+                fm.paste_progress = Some(description);
+
+                Ok(())
+            });
+        }
+    };
+
+    let mut action_for_all = None;
+    let mut last_pasted_path = None;
+    let mut pasted_count = 0;
+    let mut cloned_count = 0;
+    let mut error_message = None;
+
+    for path in &buffer.paths {
+        let mut report_error = |err: io::Error| {
+            error_message = Some(format!("Unable to paste {}: {}", path.display(), err));
+        };
+
+        let target = match file_buffer::paste_target(path, dest_dir) {
+            Ok(target) => target,
+            Err(err) => {
+                report_error(err);
+                break;
+            }
+        };
+
+        let action = if !file_buffer::is_conflict(buffer.mode, path, &target) {
+            ConflictAction::Rename
+        } else if let Some(action) = action_for_all {
+            action
+        } else {
+            match ask_paste_conflict(prompter, &target) {
+                Some((action, is_for_all)) => {
+                    if is_for_all {
+                        action_for_all = Some(action);
+                    }
+
+                    action
+                }
+                // NOTE(Chris): Cancelling the prompt cancels the rest of the paste
+                None => break,
+            }
+        };
+
+        let (privileged_dest, privileged_command) =
+            file_buffer::privileged_paste_command(buffer.mode, path, &target, action);
+
+        match file_buffer::paste_path(
+            buffer.mode,
+            path,
+            &target,
+            action,
+            copy_options,
+            &mut on_progress,
+        ) {
+            Ok(Some(pasted)) => {
+                pasted_count += 1;
+                cloned_count += pasted.cloned_count;
+                last_pasted_path = Some(pasted.path);
+            }
+            // Skipped
+            Ok(None) => (),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                let args: Vec<&OsStr> =
+                    privileged_command.iter().map(OsString::as_os_str).collect();
+
+                match privilege::retry_privileged(prompter, privilege_helper, &args) {
+                    Ok(true) => {
+                        pasted_count += 1;
+                        last_pasted_path = Some(privileged_dest);
+                    }
+                    Ok(false) => {
+                        report_error(err);
+                        break;
+                    }
+                    Err(message) => {
+                        error_message = Some(message);
+                        break;
+                    }
+                }
+            }
+            Err(err) => {
+                report_error(err);
+                break;
+            }
+        }
+    }
+
+    if let Some(message) = error_message {
+        report_status(to_main_tx, message);
+    } else if pasted_count > 0 {
+        // NOTE(Chris): Cloned copies are instant, which is worth knowing when a paste seemed
+        // suspiciously fast
+        let message = match cloned_count {
+            0 => format!("Pasted {}", count_files(pasted_count)),
+            _ => format!(
+                "Pasted {} ({} cloned)",
+                count_files(pasted_count),
+                cloned_count
+            ),
+        };
+
+        report_status(to_main_tx, message);
+    }
+
+    last_pasted_path
+}
+
+const PASTE_PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
+
+// Asks what to do about a file that's already where we're pasting, returning None if the prompt
+// was cancelled
+fn ask_paste_conflict(prompter: &PromptHandle, target: &Path) -> Option<(ConflictAction, bool)> {
+    let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+
+    loop {
+        let answer = prompter.ask(
+            Prompt::new(format!(
+                "'{}' exists: (o)verwrite, (s)kip, (r)ename, or O/S/R for all? ",
+                file_name
+            ))
+            .single_key(),
+        )?;
+
+        if let Some(choice) = file_buffer::parse_conflict_answer(&answer) {
+            return Some(choice);
+        }
+    }
+}
+
+// Asks before deleting, saying how many files (and how many bytes) would go. They're counted on
+// another thread, and if that takes too long, the prompt says that the size isn't known yet.
+fn confirm_delete(
+    prompter: &PromptHandle,
+    description: &str,
+    paths: Vec<PathBuf>,
+    size_units: SizeUnits,
+) -> bool {
+    let (size_tx, size_rx) = channel();
+
+    // NOTE(Chris): If we stop waiting, the count finishes in the background and is thrown away
+    std::thread::spawn(move || {
+        let mut size = TreeSize::default();
+        for path in &paths {
+            size += io_pool::tree_size(path);
+        }
+
+        let _ = size_tx.send(size);
+    });
+
+    let prompt = match size_rx.recv_timeout(DELETE_COUNT_TIMEOUT) {
+        Ok(size) => format!(
+            "Delete {} ({}, {})? (y/n) ",
+            description,
+            match size.file_count {
+                1 => String::from("1 file"),
+                file_count => format!("{} files", group_digits(file_count)),
+            },
+            format_size(size.byte_count, size_units)
+        ),
+        Err(_) => format!(
+            "Delete {}? Still counting, so the size is unknown (y/n) ",
+            description
+        ),
+    };
+
+    prompter.ask(Prompt::new(prompt).single_key()).as_deref() == Some("y")
+}
+
+const DELETE_COUNT_TIMEOUT: Duration = Duration::from_millis(500);
+
+// Removes every path, offering to retry the ones we weren't allowed to remove with the privilege
+// helper
+fn remove_or_retry_privileged(
+    prompter: &PromptHandle,
+    privilege_helper: &str,
+    paths: &[PathBuf],
+    thread_count: usize,
+) -> Result<(), String> {
+    let mut denied_paths = vec![];
+
+    for path in paths {
+        match remove_at_path_if_exists(path, thread_count) {
+            Ok(()) => (),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => denied_paths.push(path),
+            Err(err) => return Err(format!("Unable to delete {}: {}", path.display(), err)),
+        }
+    }
+
+    if denied_paths.is_empty() {
+        return Ok(());
+    }
+
+    let mut args = vec![OsStr::new("rm"), OsStr::new("-rf"), OsStr::new("--")];
+    args.extend(denied_paths.iter().map(|path| path.as_os_str()));
+
+    privilege::retry_privileged(prompter, privilege_helper, &args).map(|_| ())
+}
+
+fn remove_at_path_if_exists<P: AsRef<Path>>(path: P, thread_count: usize) -> io::Result<()> {
+    // NOTE(Chris): Symlinks are removed themselves (even when broken), rather than their targets
+    let metadata = match fs::symlink_metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(err) => match err.kind() {
+            io::ErrorKind::NotFound => {
+                return Ok(());
+            }
+            _ => return Err(err),
+        },
+    };
+
+    if metadata.is_dir() {
+        io_pool::remove_dir_all(path.as_ref(), thread_count)?;
+    } else {
+        fs::remove_file(&path)?;
+    }
+
+    Ok(())
+}
+
+fn get_env_editor() -> String {
+    match std::env::var("VISUAL") {
+        Err(std::env::VarError::NotPresent) => match std::env::var("EDITOR") {
+            Err(std::env::VarError::NotPresent) => String::from(""),
+            Err(err) => panic!("{}", err),
+            Ok(editor) => editor,
+        },
+        Err(err) => panic!("{}", err),
+        Ok(visual) => visual,
+    }
+}
+
+fn enter_shell_command_then_redraw(
+    fm: &mut FileManager,
+    screen: &mut Screen,
+    stdout_lock: &mut StdoutLock,
+    tx: &Sender<InputEvent>,
+    second_entry_index: u16,
+    shell_command: String,
+) -> io::Result<()> {
+    queue!(stdout_lock, terminal::LeaveAlternateScreen)?;
+
+    fm.input_suspended
+        .store(true, std::sync::atomic::Ordering::Release);
+
+    Command::new("sh")
+        .arg("-c")
+        .arg(shell_command)
+        .status()
+        .expect("failed to execute editor command");
+
+    fm.input_suspended
+        .store(false, std::sync::atomic::Ordering::Release);
+
+    queue!(stdout_lock, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+    set_preview_data_with_thread(fm, tx, second_entry_index);
+
+    // TODO(Chris): Write a function that achieves this without
+    // resizing anything
+    screen.resize_clear_draw(fm.drawing_info.width, fm.drawing_info.height)?;
+
+    Ok(())
+}
+
+// Keeps the output of a command on screen until the user presses enter, since rolf redraws over
+// it as soon as the command exits
+fn wait_after_command(shell_command: &str) -> String {
+    format!(
+        "{}\nprintf '\\nPress ENTER to continue'; read -r _",
+        shell_command
+    )
+}
+
+fn toggle_selection(fm: &mut FileManager, second_entry_index: u16) {
+    if fm.dir_states.current_entries.is_empty() {
+        return;
+    }
+
+    let selected_entry = &fm.dir_states.current_entries[second_entry_index as usize];
+
+    let entry_path = selected_entry.dir_entry.path();
+
+    let was_selection_present = fm.selections.remove(&entry_path);
+    if !was_selection_present {
+        fm.selections.insert(entry_path);
+    }
+}
+
+fn cursor_up(fm: &mut FileManager) {
+    if !fm.dir_states.current_entries.is_empty() {
+        fm.preview_pool.cancel_all();
+
+        let second_entry_index = fm.get_second_entry_index();
+
+        if fm.second.display_offset <= scroll_offset(fm.drawing_info.column_height)
+            && fm.second.starting_index > 0
+        {
+            fm.second.starting_index -= 1;
+        } else if second_entry_index > 0 {
+            fm.second.display_offset -= 1;
+        }
+    }
+}
+
+// Returns the count given to a movement command, like the 7 in "down 7"
+fn get_count_argument(command_use: &parser::CommandUse) -> usize {
+    command_use
+        .arguments
+        .first()
+        .and_then(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(1)
+}
+
+fn cursor_down(fm: &mut FileManager, second_entry_index: u16, second_bottom_index: u16) {
+    if !fm.dir_states.current_entries.is_empty()
+        && (second_entry_index as usize) < fm.dir_states.current_entries.len() - 1
+    {
+        fm.preview_pool.cancel_all();
+
+        let column_height = fm.drawing_info.column_height;
+
+        if fm.second.display_offset
+            >= column_height.saturating_sub(scroll_offset(column_height) + 1)
+            && (second_bottom_index as usize) < fm.dir_states.current_entries.len()
+        {
+            fm.second.starting_index += 1;
+        } else if second_entry_index < second_bottom_index {
+            fm.second.display_offset += 1;
+        }
+    }
+}
+
+// The part of the third column that previews are drawn in, which is below the preview header when
+// there is one
+fn get_preview_rect(fm: &FileManager) -> Rect {
+    let header_height = u16::from(fm.config.preview_header);
+
+    Rect {
+        left_x: fm.drawing_info.third_left_x,
+        top_y: fm.drawing_info.third_top_y + header_height,
+        width: fm.drawing_info.third_right_x - fm.drawing_info.third_left_x,
+        height: fm.drawing_info.third_height.saturating_sub(header_height),
+    }
+}
+
+// Compares the lines of two files, which can't be binary. An error describes why there are no
+// differences to show.
+fn compare_files(old_path: &Path, new_path: &Path) -> Result<Vec<DiffLine>, String> {
+    let read = |path: &Path| {
+        fs::read(path).map_err(|err| format!("Unable to read {}: {}", path.display(), err))
+    };
+
+    let old_bytes = read(old_path)?;
+    let new_bytes = read(new_path)?;
+
+    if old_bytes == new_bytes {
+        return Err("The files are identical".to_string());
+    }
+
+    // NOTE(Chris): Like diff, any NUL byte means a file isn't text
+    if old_bytes.contains(&0) || new_bytes.contains(&0) {
+        return Err("The binary files differ".to_string());
+    }
+
+    match line_diff::diff_lines(
+        &String::from_utf8_lossy(&old_bytes),
+        &String::from_utf8_lossy(&new_bytes),
+    ) {
+        Some(diff_lines) if diff_lines.is_empty() => {
+            Err("The files only differ in their line endings".to_string())
+        }
+        Some(diff_lines) => Ok(diff_lines),
+        None => {
+            Err("The files are too different to compare here (try the difftool option)".to_string())
+        }
+    }
+}
+
+// Quotes a word for sh, so that it can't be split up or expanded
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+// Scrolls a panel just enough to keep the cursor in view
+fn scroll_to_cursor(top_ind: &mut usize, cursor: usize, view_rect: Rect) {
+    let view_height = view_rect.height as usize;
+
+    if cursor < *top_ind {
+        *top_ind = cursor;
+    } else if cursor >= *top_ind + view_height {
+        *top_ind = cursor + 1 - view_height;
+    }
+}
+
+// Copies the files (or directories) which are only in one of the compared directories to the
+// other one, then compares them again
+fn copy_missing_files(
+    status_message: &mut Option<String>,
+    left: PathBuf,
+    right: PathBuf,
+    differences: Vec<DirDifference>,
+    copy_options: CopyOptions,
+    ignore_vcs: bool,
+    tx: &Sender<InputEvent>,
+) {
+    let copies: Vec<(PathBuf, PathBuf)> = differences
+        .iter()
+        .filter_map(|difference| match difference.difference {
+            Difference::OnlyLeft => {
+                Some((left.join(&difference.path), right.join(&difference.path)))
+            }
+            Difference::OnlyRight => {
+                Some((right.join(&difference.path), left.join(&difference.path)))
+            }
+            _ => None,
+        })
+        .collect();
+
+    if copies.is_empty() {
+        *status_message = Some("Only files missing from one side can be copied".to_string());
+        return;
+    }
+
+    *status_message = Some(format!("Copying {}", count_files(copies.len())));
+
+    let to_our_tx = tx.clone();
+
+    std::thread::spawn(move || {
+        let mut copied_count = 0;
+        let mut error_message = None;
+
+        for (source, target) in &copies {
+            // NOTE(Chris): Anything that's appeared at the target since is left alone
+            match file_buffer::paste_path(
+                PasteMode::Copy,
+                source,
+                target,
+                ConflictAction::Skip,
+                copy_options,
+                &mut |_bytes| (),
+            ) {
+                Ok(Some(_pasted)) => copied_count += 1,
+                Ok(None) => (),
+                Err(err) => {
+                    error_message = Some(format!("Unable to copy {}: {}", source.display(), err));
+                    break;
+                }
+            }
+        }
+
+        let result = compare_dirs::compare_dirs(&left, &right, ignore_vcs);
+
+        send_callback_to_main!(&to_our_tx, move |fm| {
+            fm.status_message = Some(
+                error_message.unwrap_or_else(|| format!("Copied {}", count_files(copied_count))),
+            );
+
+            // NOTE(Chris): The panel may have been closed (or opened for other directories) while
+            // we were copying
+            if let InputMode::CompareDirs {
+                top_ind,
+                cursor,
+                view_rect,
+                left: ref shown_left,
+                right: ref shown_right,
+                differences,
+            } = &mut fm.input_mode
+            {
+                if *shown_left == left && *shown_right == right {
+                    match result {
+                        Ok(new_differences) if !new_differences.is_empty() => {
+                            *differences = new_differences;
+                            *cursor = (*cursor).min(differences.len() - 1);
+                            scroll_to_cursor(top_ind, *cursor, *view_rect);
+                        }
+                        _ => fm.input_mode = InputMode::Normal,
+                    }
+                }
+            }
+
+            Ok(())
+        });
+    });
+}
+
+// Shows the renames to confirm them, unless there aren't any
+fn preview_renames(fm: &mut FileManager, renames: Vec<Rename>, nothing_message: String) {
+    if renames.is_empty() {
+        fm.status_message = Some(nothing_message);
+    } else {
+        fm.input_mode = InputMode::RenamePreview {
+            top_ind: 0,
+            view_rect: get_help_view_rect(fm.drawing_info),
+            renames,
+        };
+    }
+}
+
+// Makes the renames shown in the rename preview, stopping at the first one which fails.
+// Renamed selections stay selected.
+fn make_renames(fm: &mut FileManager, renames: &[Rename], tx: &Sender<InputEvent>) {
+    let file_id = fm
+        .dir_states
+        .current_entries
+        .get(fm.get_second_entry_index() as usize)
+        .map_or(0, |entry_info| entry_info.metadata.file_id());
+
+    let mut renamed_count = 0;
+
+    for rename in renames {
+        if let Err(err) = fs::rename(&rename.from, &rename.to) {
+            fm.status_message = Some(format!(
+                "Unable to rename {}: {}",
+                rename.from.display(),
+                err
+            ));
+            break;
+        }
+
+        if fm.selections.remove(&rename.from) {
+            fm.selections.insert(rename.to.clone());
+        }
+
+        renamed_count += 1;
+    }
+
+    if fm.status_message.is_none() {
+        fm.status_message = Some(format!("Renamed {}", count_files(renamed_count)));
+    }
+
+    reload_current_dir_prefer_id(fm, file_id, tx);
+}
+
+// The regex crate explains syntax errors over several lines, pointing at the mistake, but only
+// the last line (like "error: unclosed group") fits in the status line
+fn describe_regex_error(err: &regex::Error) -> String {
+    let message = err.to_string();
+    let last_line = message.lines().last().unwrap_or_default();
+
+    format!(
+        "Invalid regex: {}",
+        last_line.strip_prefix("error: ").unwrap_or(last_line)
+    )
+}
+
+// Adds hints like "enter,l:jump" to the bottom line of a panel, listing the keys bound to each
+// command
+fn push_key_hints(
+    line_builder: &mut LineBuilder,
+    keybindings: &HashMap<KeyEvent, String>,
+    hints: &[(&str, &str)],
+) {
+    let command_space = "   ";
+
+    for (command, action) in hints {
+        let mut key_displays: Vec<String> = keybindings
+            .iter()
+            .filter(|(_key_event, bound_command)| bound_command == command)
+            .map(|(key_event, _bound_command)| to_string(*key_event))
+            .collect();
+
+        if !key_displays.is_empty() {
+            key_displays.sort_unstable();
+
+            line_builder.push_str(&key_displays.join(","));
+            line_builder.push_str(":");
+            line_builder.push_str(action);
+            line_builder.push_str(command_space);
+        }
+    }
+}
+
+fn get_help_view_rect(drawing_info: DrawingInfo) -> Rect {
+    Rect {
+        left_x: 0,
+        top_y: 1, // We already show the help title in the top line
+        width: drawing_info.width,
+        height: drawing_info.column_height,
+    }
+}
+
+fn set_area_dead(fm: &FileManager, screen_lock: &mut Screen, is_dead: bool) {
+    // NOTE(Chris): The preview header is drawn by us, so it never goes dead
+    let top_y = get_preview_rect(fm).top_y;
+
+    let bot_y = fm.drawing_info.third_top_y + fm.drawing_info.third_height;
+
+    for x in fm.drawing_info.third_left_x..=fm.drawing_info.width - 1 {
+        for y in top_y..bot_y {
+            screen_lock.set_dead(x, y, is_dead);
+        }
+    }
+}
+
+fn search_jump(fm: &mut FileManager) -> io::Result<()> {
+    let second_entry_index = fm.second.starting_index + fm.second.display_offset;
+
+    let next_position = match next_match_position(
+        &fm.match_positions,
+        second_entry_index as usize,
+        fm.should_search_forwards,
+    ) {
+        Some(next_position) => next_position,
+        None => return Ok(()),
+    };
+
+    fm.second = find_column_pos(
+        fm.dir_states.current_entries.len(),
+        fm.drawing_info.column_height,
+        fm.second,
+        next_position,
+    )?;
+
+    Ok(())
+}
+
+// Finds the next match after (or before) the current index, wrapping around at the ends
+fn next_match_position(
+    match_positions: &[usize],
+    current_index: usize,
+    should_search_forwards: bool,
+) -> Option<usize> {
+    if should_search_forwards {
+        match_positions
+            .iter()
+            .find(|pos| **pos > current_index)
+            .or_else(|| match_positions.first())
+            .copied()
+    } else {
+        match_positions
+            .iter()
+            .rev()
+            .find(|pos| **pos < current_index)
+            .or_else(|| match_positions.last())
+            .copied()
+    }
+}
+
+fn jump_by_file_id(fm: &mut FileManager, file_id: u64) -> io::Result<()> {
+    let current_entry_info_index = fm
+        .dir_states
+        .current_entries
+        .iter()
+        .position(|entry_info| entry_info.metadata.file_id() == file_id);
+
+    if let Some(current_entry_info_index) = current_entry_info_index {
+        fm.match_positions = vec![current_entry_info_index];
+
+        search_jump(fm)?;
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Unable to jump to file by id",
+        ));
+    };
+
+    Ok(())
+}
+
+// Shows the images in the current directory as a grid of thumbnails, starting at the image under
+// the cursor
+fn open_gallery(fm: &mut FileManager, tx: &Sender<InputEvent>) {
+    let second_entry_index = fm.get_second_entry_index() as usize;
+
+    let mut images = vec![];
+    let mut file_count = 0;
+    let mut cursor = 0;
+
+    for (index, entry_info) in fm.dir_states.current_entries.iter().enumerate() {
+        if !matches!(
+            entry_info.file_type,
+            RecordedFileType::File | RecordedFileType::FileSymlink
+        ) {
+            continue;
+        }
+
+        file_count += 1;
+
+        let path = entry_info.dir_entry.path();
+        if gallery::is_image(&path) {
+            if index <= second_entry_index {
+                cursor = images.len();
+            }
+
+            images.push(path);
+        }
+    }
+
+    if !gallery::is_mostly_images(images.len(), file_count) {
+        fm.status_message = Some("gallery: Most of the files here aren't images".to_string());
+        return;
+    }
+
+    fm.input_mode = InputMode::Gallery {
+        top_row: 0,
+        cursor,
+        view_rect: get_help_view_rect(fm.drawing_info),
+        images,
+        full_screen: false,
+        thumbnails: HashMap::new(),
+        full_image: None,
+    };
+
+    update_gallery(fm, tx);
+}
+
+fn toggle_gallery_full_screen(fm: &mut FileManager, tx: &Sender<InputEvent>) {
+    if let InputMode::Gallery {
+        ref mut full_screen,
+        ..
+    } = fm.input_mode
+    {
+        *full_screen = !*full_screen;
+    }
+
+    update_gallery(fm, tx);
+}
+
+// Scrolls the gallery to its cursor, then starts loading whichever images are on screen and haven't
+// been loaded yet
+fn update_gallery(fm: &mut FileManager, tx: &Sender<InputEvent>) {
+    let (images, kitty_size, view_rect) = match fm.input_mode {
+        InputMode::Gallery {
+            ref mut top_row,
+            cursor,
+            view_rect,
+            ref images,
+            full_screen,
+            ref thumbnails,
+            ref full_image,
+        } => {
+            let (columns, rows) = gallery::grid_size(view_rect.width, view_rect.height);
+            gallery::scroll_to_cursor(top_row, cursor, columns, rows);
+
+            // Each image is loaded along with whether it's shown full-screen
+            let images: Vec<(usize, PathBuf, bool)> = if full_screen {
+                match full_image {
+                    Some((index, _)) if *index == cursor => vec![],
+                    _ => vec![(cursor, images[cursor].clone(), true)],
+                }
+            } else {
+                images
+                    .iter()
+                    .enumerate()
+                    .skip(*top_row * columns)
+                    .take(rows * columns)
+                    .filter(|(_index, path)| !thumbnails.contains_key(*path))
+                    .map(|(index, path)| (index, path.clone(), false))
+                    .collect()
+            };
+
+            (
+                images,
+                fm.config.image_protocol == ImageProtocol::Kitty,
+                view_rect,
+            )
+        }
+        _ => return,
+    };
+
+    if images.is_empty() {
+        return;
+    }
+
+    let drawing_info = fm.drawing_info;
+    let thumbnail_dir = fm.thumbnail_dir.clone();
+
+    // The size of an image drawn in the given number of cells, in pixels
+    let pixel_size = move |cols: u16, rows: u16| {
+        if kitty_size {
+            (
+                (cols as u32 * drawing_info.win_pixels.width as u32 / drawing_info.width as u32)
+                    .max(1),
+                (rows as u32 * drawing_info.win_pixels.height as u32 / drawing_info.height as u32)
+                    .max(1),
+            )
+        } else {
+            // NOTE(Chris): Each cell shows two pixels with half blocks
+            (cols as u32, rows as u32 * 2)
+        }
+    };
+
+    let to_main_tx = tx.clone();
+
+    fm.preview_pool.spawn(move |ticket| {
+        for (index, path, is_full_screen) in images {
+            if !ticket.is_current() {
+                return;
+            }
+
+            let (max_width, max_height) = if is_full_screen {
+                // NOTE(Chris): The bottom row of the view is left for the image's name
+                pixel_size(view_rect.width, view_rect.height.saturating_sub(1))
+            } else {
+                pixel_size(gallery::THUMBNAIL_WIDTH, gallery::THUMBNAIL_HEIGHT)
+            };
+
+            // NOTE(Chris): Full-screen images are only shown one at a time, so they aren't worth
+            // keeping around
+            let cache_path = thumbnail_dir
+                .as_ref()
+                .filter(|_| !is_full_screen)
+                .and_then(|dir| gallery::thumbnail_cache_path(dir, &path, max_width, max_height));
+
+            let image = match cache_path
+                .as_ref()
+                .and_then(|cache_path| image::open(cache_path).ok())
+            {
+                Some(image) => Ok(image.to_rgba8()),
+                None => {
+                    let ext = path
+                        .extension()
+                        .and_then(OsStr::to_str)
+                        .unwrap_or_default()
+                        .to_lowercase();
+
+                    let image = decode_image(&path, &ext, max_width, max_height)
+                        .map(|image| image.thumbnail(max_width, max_height).to_rgba8());
+
+                    // NOTE(Chris): A thumbnail which can't be saved is just made again next time
+                    if let (Ok(image), Some(cache_path)) = (&image, &cache_path) {
+                        let _ = gallery::save_thumbnail(image, cache_path);
+                    }
+
+                    image
+                }
+            };
+
+            send_callback_to_main!(&to_main_tx, move |fm| {
+                let image = match image {
+                    Ok(image) => Some(image),
+                    Err(err) => {
+                        fm.log(format!("Unable to preview {}: {}", path.display(), err));
+                        None
+                    }
+                };
+
+                if let InputMode::Gallery {
+                    ref mut thumbnails,
+                    ref mut full_image,
+                    ..
+                } = fm.input_mode
+                {
+                    if is_full_screen {
+                        *full_image = Some((index, image));
+                    } else {
+                        thumbnails.insert(path, image);
+                    }
+                }
+
+                Ok(())
+            });
+        }
+    });
+}
+
+// What a cached preview depends on: the file's contents (going by its modification time and
+// size), and the space the preview is drawn in
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct PreviewKey {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+    drawing_info: DrawingInfo,
+    preview_header: bool,
+}
+
+impl PreviewKey {
+    fn new(fm: &FileManager, path: &Path) -> Option<PreviewKey> {
+        // NOTE(Chris): This follows symlinks, so that a changed target isn't shown from the cache
+        let metadata = fs::metadata(path).ok()?;
+
+        Some(PreviewKey {
+            path: path.to_path_buf(),
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            drawing_info: fm.drawing_info,
+            preview_header: fm.config.preview_header,
+        })
+    }
+}
+
+// Shows the file's cached preview, returning whether there was one
+fn send_cached_preview(
+    fm: &FileManager,
+    tx: &Sender<InputEvent>,
+    key: Option<&PreviewKey>,
+) -> bool {
+    let cached = key.and_then(|key| {
+        let mut preview_cache = fm
+            .preview_cache
+            .lock()
+            .expect("Failed to lock preview cache");
+        preview_cache.get(key).and_then(PreviewData::to_cached)
+    });
+
+    match cached {
+        Some((preview_data, _size)) => {
+            // NOTE(Chris): An earlier preview still being generated mustn't replace this one
+            fm.preview_pool.cancel_all();
+
+            // NOTE(Chris): This goes through the main loop like any other preview, so that it's
+            // transmitted to the terminal when needed
+            tx.send(InputEvent::PreviewLoaded(preview_data))
+                .expect("Unable to send on channel");
+
+            true
+        }
+        None => false,
+    }
+}
+
+fn cache_preview(
+    preview_cache: &Mutex<PreviewCache<PreviewKey, PreviewData>>,
+    key: Option<PreviewKey>,
+    preview_data: &PreviewData,
+) {
+    if let (Some(key), Some((cached, size))) = (key, preview_data.to_cached()) {
+        preview_cache
+            .lock()
+            .expect("Failed to lock preview cache")
+            .insert(key, cached, size);
+    }
+}
+
+fn set_preview_data_with_thread(
+    fm: &mut FileManager,
+    tx: &Sender<InputEvent>,
+    second_entry_index: u16,
+) {
+    if fm.dir_states.current_entries.is_empty() {
+        fm.preview_data = PreviewData::Blank;
+        return;
+    }
+
+    let second_entry = &fm.dir_states.current_entries[second_entry_index as usize];
+
+    fm.preview_data = PreviewData::Loading;
+
+    let third_file_path = second_entry.dir_entry.path();
+
+    match second_entry.file_type {
+        // TODO(Chris): Optimize entry gathering to avoid spawning a thread if there's a low (<
+        // 200) number of entries, without reading in entries twice
+        RecordedFileType::Directory | RecordedFileType::DirectorySymlink => {
+            // NOTE(Chris): The preview shows the cursor where it'll be when the directory is
+            // entered
+            recall_location(fm, &third_file_path);
+
+            let preview_tx = tx.clone();
+            let sort_options = fm.dir_states.sort_options;
+            // NOTE(Chris): The filter stays on when entering a directory, so its preview is
+            // filtered too
+            let filter = fm.dir_states.filter.clone();
+            let dir_previews = fm.config.dir_previews.clone();
+            // NOTE(Chris): The preview thread holds onto the mount, so that its files are still
+            // there if it's left in the meantime
+            let mount = fm.dir_states.mount.clone();
+
+            fm.preview_pool.spawn(move |ticket| {
+                let file_system = vfs::file_system_for(mount.as_ref(), &third_file_path);
+
+                // NOTE(Chris): Directory preview commands can only look at directories on the disk
+                if let Some(bytes) = run_dir_preview(&dir_previews, &third_file_path)
+                    .filter(|_| file_system.is_local())
+                {
+                    if ticket.is_current() {
+                        preview_tx
+                            .send(InputEvent::PreviewLoaded(PreviewData::RawBytes { bytes }))
+                            .expect("Unable to send on channel");
+                    }
+
+                    return;
+                }
+
+                match get_sorted_entries_unless_stale(
+                    file_system,
+                    &third_file_path,
+                    sort_options,
+                    &ticket,
+                ) {
+                    Ok(mut preview_entry_info) => {
+                        filter_entries(&mut preview_entry_info, filter.as_ref());
+
+                        if ticket.is_current() {
+                            preview_tx
+                                .send(InputEvent::PreviewLoaded(PreviewData::Directory {
+                                    entries_info: preview_entry_info,
+                                }))
+                                .expect("Unable to send on channel");
+                        }
+                    }
+                    Err(err) => match err.kind() {
+                        io::ErrorKind::PermissionDenied => {
+                            if ticket.is_current() {
+                                preview_tx
+                                    .send(InputEvent::PreviewLoaded(PreviewData::Message {
+                                        message: "permission denied".to_string(),
+                                    }))
+                                    .expect("Unable to send on channel");
+                            }
+                        }
+                        // The preview was cancelled partway through reading the directory
+                        io::ErrorKind::Interrupted => (),
+                        _ => {
+                            if ticket.is_current() {
+                                preview_tx
+                                    .send(InputEvent::PreviewFailed {
+                                        path: third_file_path,
+                                        error: err,
+                                    })
+                                    .expect("Unable to send on channel");
+                            }
+                        }
+                    },
+                }
+            });
+        }
+        RecordedFileType::File | RecordedFileType::FileSymlink => match &fm.dir_states.mount {
+            Some(mount) if mount.contains(&third_file_path) => {
+                let mount = mount.clone();
+                preview_mounted_file(fm, tx, mount, third_file_path);
+            }
+            _ => preview_file(fm, tx, third_file_path),
+        },
+        RecordedFileType::InvalidSymlink | RecordedFileType::Other => {
+            fm.preview_data = PreviewData::Blank;
+        }
+        RecordedFileType::Unknown => {
+            fm.preview_data = PreviewData::Message {
+                message: "unknown file type".to_string(),
+            };
+        }
+    }
+}
+
+// Previews a file on the disk, generating the preview in the background if it's slow to make
+fn preview_file(fm: &mut FileManager, tx: &Sender<InputEvent>, third_file_path: PathBuf) {
+    if let Some(os_str_ext) = third_file_path.extension() {
+        if let Some(ext) = os_str_ext.to_str() {
+            let ext = ext.to_lowercase();
+            let ext = ext.as_str();
+
+            match ext {
+                "png" | "jpg" | "jpeg" | "mp4" | "webm" | "mkv" => {
+                    let preview_tx = tx.clone();
+
+                    let ext_string = ext.to_string();
+                    let drawing_info = fm.drawing_info;
+                    let preview_rect = get_preview_rect(fm);
+
+                    let video_tools = match ext {
+                        "mp4" | "webm" | "mkv" => match VideoTools::from_tools(&fm.tools) {
+                            Some(video_tools) => Some(video_tools),
+                            None => {
+                                fm.preview_data = PreviewData::Message {
+                                    message: "install ffmpeg for video previews".to_string(),
+                                };
+                                return;
+                            }
+                        },
+                        _ => None,
+                    };
+
+                    let preview_key = PreviewKey::new(fm, &third_file_path);
+                    if send_cached_preview(fm, tx, preview_key.as_ref()) {
+                        return;
+                    }
+
+                    let preview_cache = Arc::clone(&fm.preview_cache);
+
+                    fm.preview_pool.spawn(move |ticket| {
+                        let event = match preview_image_or_video(
+                            drawing_info.win_pixels,
+                            third_file_path.clone(),
+                            ext_string,
+                            video_tools,
+                            drawing_info.width,
+                            drawing_info.height,
+                            preview_rect,
+                        ) {
+                            Ok(image_buffer) => {
+                                let preview_data = PreviewData::ImageBuffer {
+                                    buffer: image_buffer,
+                                };
+
+                                cache_preview(&preview_cache, preview_key, &preview_data);
+
+                                InputEvent::PreviewLoaded(preview_data)
+                            }
+                            Err(error) => InputEvent::PreviewFailed {
+                                path: third_file_path,
+                                error,
+                            },
+                        };
+
+                        if ticket.is_current() {
+                            preview_tx.send(event).expect("Unable to send on channel");
+                        }
+                    });
+                }
+                _ => match highlight_command(&fm.tools, &third_file_path) {
+                    None => {
+                        fm.preview_data = PreviewData::UncoloredFile {
+                            path: third_file_path,
+                        };
+                    }
+                    Some(mut highlight_command) => {
+                        let preview_key = PreviewKey::new(fm, &third_file_path);
+                        if send_cached_preview(fm, tx, preview_key.as_ref()) {
+                            return;
+                        }
+
+                        let preview_tx = tx.clone();
+                        let preview_cache = Arc::clone(&fm.preview_cache);
+
+                        fm.preview_pool.spawn(move |ticket| {
+                            let output = match highlight_command.output() {
+                                Ok(output) => output,
+                                Err(error) => {
+                                    if ticket.is_current() {
+                                        preview_tx
+                                            .send(InputEvent::PreviewFailed {
+                                                path: third_file_path,
+                                                error,
+                                            })
+                                            .expect("Unable to send on channel");
+                                    }
+
+                                    return;
+                                }
+                            };
+
+                            let preview_data = PreviewData::RawBytes {
+                                bytes: output.stdout,
+                            };
+
+                            cache_preview(&preview_cache, preview_key, &preview_data);
+
+                            if ticket.is_current() {
+                                preview_tx
+                                    .send(InputEvent::PreviewLoaded(preview_data))
+                                    .expect("Unable to send on channel");
+                            }
+                        });
+                    }
+                },
+            }
+        } else {
+            fm.preview_data = PreviewData::UncoloredFile {
+                path: third_file_path,
+            };
+        }
+    } else {
+        fm.preview_data = PreviewData::UncoloredFile {
+            path: third_file_path,
+        };
+    }
+}
+
+// Mounted files are copied to the disk in the background and then previewed from there. Only the
+// start of a text file is copied, since that's all the preview shows.
+fn preview_mounted_file(
+    fm: &mut FileManager,
+    tx: &Sender<InputEvent>,
+    mount: Mount,
+    third_file_path: PathBuf,
+) {
+    // NOTE(Chris): Images and videos are decoded from the whole file
+    let is_image_or_video = third_file_path
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(str::to_lowercase)
+        .is_some_and(|ext| {
+            matches!(
+                ext.as_str(),
+                "png" | "jpg" | "jpeg" | "mp4" | "webm" | "mkv"
+            )
+        });
+    let limit = if is_image_or_video {
+        None
+    } else {
+        Some(MOUNTED_PREVIEW_LIMIT)
+    };
+    let preview_tx = tx.clone();
+
+    fm.preview_pool.spawn(move |ticket| {
+        let local_copy = mount.local_copy(&third_file_path, limit);
+
+        if !ticket.is_current() {
+            return;
+        }
+
+        match local_copy {
+            Ok(local_path) => {
+                let to_our_tx = preview_tx.clone();
+
+                send_callback_to_main!(&preview_tx, move |fm| {
+                    // NOTE(Chris): The cursor may have moved on while the file was copied
+                    let second_entry_index = fm.get_second_entry_index() as usize;
+                    let is_current = fm
+                        .dir_states
+                        .current_entries
+                        .get(second_entry_index)
+                        .is_some_and(|entry| entry.dir_entry.path() == third_file_path);
+
+                    if is_current {
+                        preview_file(fm, &to_our_tx, local_path);
+                    }
+
+                    Ok(())
+                });
+            }
+            Err(error) => preview_tx
+                .send(InputEvent::PreviewFailed {
+                    path: third_file_path,
+                    error,
+                })
+                .expect("Unable to send on channel"),
+        }
+    });
+}
+
+// The command which colors a text preview, using highlight or else bat
+fn highlight_command(tools: &Tools, path: &Path) -> Option<Command> {
+    if let Some(highlight) = tools.get("highlight") {
+        let mut command = Command::new(highlight);
+        command.args(["-O", "ansi", "--max-size=500K"]).arg(path);
+
+        Some(command)
+    } else if let Some(bat) = tools.get("bat") {
+        let mut command = Command::new(bat);
+        command
+            .args(["--color=always", "--style=plain", "--paging=never"])
+            .arg(path);
+
+        Some(command)
+    } else {
+        None
+    }
+}
+
+// The first of the dir-previews whose marker is in the directory
+fn find_dir_preview<'a>(dir_previews: &'a [DirPreview], dir: &Path) -> Option<&'a DirPreview> {
+    dir_previews.iter().find(|dir_preview| {
+        dir_preview.marker.is_empty() || dir.join(&dir_preview.marker).symlink_metadata().is_ok()
+    })
+}
+
+// Runs the command of the directory's matching dir-previews entry (if there is one), returning
+// what it printed. An error message is shown if the command printed nothing else, so that a
+// broken command doesn't just look empty.
+fn run_dir_preview(dir_previews: &[DirPreview], dir: &Path) -> Option<Vec<u8>> {
+    let dir_preview = find_dir_preview(dir_previews, dir)?;
+
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(&dir_preview.command)
+        .current_dir(dir)
+        .env("f", dir)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .ok()?;
+
+    if output.stdout.is_empty() {
+        Some(output.stderr)
+    } else {
+        Some(output.stdout)
+    }
+}
+
+// Generates the preview for the current entry, waiting until the cursor has settled for the
+// configured preview delay if there is one.
+fn schedule_preview(fm: &mut FileManager, tx: &Sender<InputEvent>, second_entry_index: u16) {
+    if fm.config.preview_delay == 0 {
+        fm.preview_deadline = None;
+
+        set_preview_data_with_thread(fm, tx, second_entry_index);
+    } else {
+        fm.preview_data = PreviewData::Loading;
+
+        fm.preview_deadline = Some(Instant::now() + Duration::from_millis(fm.config.preview_delay));
+    }
+}
+
+// The width of the line number gutter in a column of item_count entries
+fn gutter_width(item_count: usize, options: &ListingOptions) -> u16 {
+    // NOTE(Chris): Every number fits in the gutter, since relative numbers are always smaller than
+    // the entry count
+    if (options.number || options.relative_number) && item_count > 0 {
+        item_count.to_string().len() as u16 + 1
+    } else {
+        0
+    }
+}
+
+// Where an inline prompt's input is drawn over the current row of the second column, as its left
+// x, its y, and the x it stops before. None if the prompt isn't inline, or there's no row.
+fn inline_input_row(fm: &FileManager) -> Option<(u16, u16, u16)> {
+    let is_inline = fm
+        .active_prompt
+        .as_ref()
+        .is_some_and(|prompt| prompt.inline);
+
+    if !is_inline || fm.dir_states.current_entries.is_empty() {
+        return None;
+    }
+
+    let gutter_width = gutter_width(
+        fm.dir_states.current_entries.len(),
+        &fm.listing_options(true),
+    );
+
+    // NOTE(Chris): Like the file name in draw_column, this comes after the selection marking, the
+    // gutter, and the cursor marker, and a row includes the cell at second_right_x
+    let left_x = fm.drawing_info.second_left_x + 1 + gutter_width + 1;
+    let max_x = fm.drawing_info.second_right_x + 1;
+
+    if left_x >= max_x {
+        return None;
+    }
+
+    Some((left_x, 1 + fm.second.display_offset, max_x))
+}
+
+fn draw_column(
+    screen: &mut Screen,
+    rect: Rect,
+    file_top_ind: u16,
+    file_curr_ind: u16,
+    items: &[DirEntryInfo],
+    selections: &SelectionsMap,
+    options: &ListingOptions,
+    cache: &mut ColumnCache,
+) {
+    let gutter_width = gutter_width(items.len(), options);
+
+    // NOTE(Chris): This also skips hidden columns, which have a width of 0
+    if rect.width < gutter_width + 2 {
+        return;
+    }
+
+    let inner_left_x = rect.left_x + 1 + gutter_width;
+
+    if options.background != rolf_grid::Color::Background {
+        let background_style = Style::new_color(rolf_grid::Color::Foreground, options.background);
+
+        for y in rect.top_y..rect.bot_y() {
+            for x in rect.left_x..=rect.right_x() {
+                screen.set_cell_style_clipped(x, y, ' ', background_style);
+            }
+        }
+    }
+
+    if items.is_empty() {
+        draw_str(
+            screen,
+            inner_left_x + 1,
+            rect.top_y,
+            options.empty_message,
+            Style::new_attr(rolf_grid::Attribute::Reverse),
+        );
+    }
+
+    cache.reset_if_changed(ColumnLayout {
+        width: rect.width,
+        gutter_width,
+        dimming_uid: options.dimming_user.map(|user| user.uid),
+        info_columns: options.info_columns.to_vec(),
+        search_highlight: options.search_highlight.map(str::to_string),
+        match_options: options.match_options,
+        background: options.background,
+    });
+
+    // NOTE(Chris): We declare this outside of the loop to avoid re-allocating.
+    let mut file_name = String::new();
+
+    for y in rect.top_y..rect.bot_y() {
+        let ind = file_top_ind + (y - rect.top_y);
+
+        if (ind as usize) >= items.len() {
+            break;
+        }
+
+        let entry_info = &items[ind as usize];
+        let is_selected = selections.contains(&entry_info.dir_entry.path());
+        let line_number = get_line_number(ind, file_curr_ind, options);
+
+        let row = (y - rect.top_y) as usize;
+        let dir_count = options.dir_counts.and_then(|dir_counts| {
+            dir_counts.get(
+                &entry_info.dir_entry.path(),
+                entry_info.metadata.modified().ok(),
+            )
+        });
+        let recent_color = options.recent_marking.and_then(|(since, color)| {
+            let is_recent = entry_info.file_type == RecordedFileType::File
+                && entry_info
+                    .metadata
+                    .modified()
+                    .is_ok_and(|modified| modified >= since);
+
+            is_recent.then_some(color)
+        });
+        let is_executable = entry_info.file_type == RecordedFileType::File
+            && entry_info
+                .metadata
+                .is_executable(&entry_info.dir_entry.path());
+        let row_key = RowKey::new(
+            entry_info,
+            ind == file_curr_ind,
+            is_selected,
+            line_number,
+            dir_count,
+            recent_color.is_some(),
+            is_executable,
+        );
+
+        // NOTE(Chris): A row includes the cell at rect.right_x()
+        let row_width = rect.width + 1;
+
+        if let Some(line) = cache.get(row, &row_key) {
+            screen.build_line(rect.left_x, y, line);
+            continue;
+        }
+
+        // Draw the selection marking
+
+        if is_selected && options.plain {
+            screen.set_cell_style_clipped(rect.left_x, y, '*', rolf_grid::Style::default());
+        } else if is_selected {
+            screen.set_cell_style_clipped(
+                rect.left_x,
+                y,
+                ' ',
+                rolf_grid::Style::new_color(
+                    rolf_grid::Color::Foreground,
+                    rolf_grid::Color::Magenta,
+                ),
+            );
+        } else {
+            screen.set_cell_style_clipped(
+                rect.left_x,
+                y,
+                ' ',
+                rolf_grid::Style::new_color(rolf_grid::Color::Foreground, options.background),
+            );
+        }
+
+        if let Some(line_number) = line_number {
+            draw_str(
+                screen,
+                rect.left_x + 1,
+                y,
+                &format!("{:>1$} ", line_number, gutter_width as usize - 1),
+                Style::new_color(rolf_grid::Color::Yellow, options.background),
+            );
+        }
+
+        // Draw the file name
+
+        let mut draw_style = if ind == file_curr_ind && !options.plain {
+            Style::new_attr(rolf_grid::Attribute::Reverse)
+        } else {
+            Style::new_color(rolf_grid::Color::Foreground, options.background)
+        };
+
+        match entry_info.file_type {
+            RecordedFileType::Directory => {
+                draw_style.fg = rolf_grid::Color::Blue;
+                draw_style.attribute |= rolf_grid::Attribute::Bold;
+            }
+            RecordedFileType::FileSymlink | RecordedFileType::DirectorySymlink => {
+                draw_style.fg = rolf_grid::Color::Cyan;
+                draw_style.attribute |= rolf_grid::Attribute::Bold;
+            }
+            RecordedFileType::InvalidSymlink => {
+                draw_style.fg = rolf_grid::Color::Red;
+                draw_style.attribute |= rolf_grid::Attribute::Bold;
+            }
+            RecordedFileType::File if is_executable => {
+                draw_style.fg = rolf_grid::Color::Green;
+                draw_style.attribute |= rolf_grid::Attribute::Bold;
+            }
+            _ => (),
+        }
+
+        if let Some(recent_color) = recent_color {
+            draw_style.fg = recent_color;
+        }
+
+        if let Some(dimming_user) = options.dimming_user {
+            if let Some((uid, _)) = entry_info.metadata.owner_ids() {
+                if !dimming_user.is_user(uid) {
+                    draw_style.attribute |= rolf_grid::Attribute::Dim;
+                }
+            }
+        }
+
+        let file_name_os = entry_info.dir_entry.file_name();
+
+        // let file_name = file_name_os.to_str().unwrap();
+
+        let full_name = file_name_os.to_str().unwrap();
+        let display_width: usize = (rect.right_x() - inner_left_x).into();
+
+        let info = format_entry_info(
+            &entry_info.metadata,
+            options.info_columns,
+            options.size_units,
+            dir_count,
+        );
+        // NOTE(Chris): The info is dropped entirely when the column is too narrow to show it
+        // alongside a few characters of the file name
+        let info = if info.len() + 4 < display_width {
+            info
+        } else {
+            String::new()
+        };
+        let name_width = display_width - info.len();
+
+        file_name.clear();
+        file_name.push_str(&truncate::truncate_name(
+            full_name,
+            name_width,
+            options.truncation,
+            options.plain,
+        ));
+
+        // NOTE(Chris): In plain mode, the cursor is a character that screen readers can announce
+        let cursor_marker = if ind == file_curr_ind && options.plain {
+            '>'
+        } else {
+            ' '
+        };
+        screen.set_cell_style_clipped(inner_left_x, y, cursor_marker, draw_style);
+        let name_pos_x = inner_left_x + 1;
+        draw_str(screen, name_pos_x, y, &file_name, draw_style);
+
+        let file_name_len: u16 = line_edit::str_width(&file_name)
+            .try_into()
+            .expect("A file name length did not fit within a u16");
+
+        for x in name_pos_x + file_name_len..=rect.right_x() {
+            screen.set_cell_style_clipped(x, y, ' ', draw_style);
+        }
+
+        if let Some(match_range) = options.search_highlight.and_then(|search_term| {
+            search::find_match_range(&file_name, search_term, options.match_options)
+        }) {
+            draw_str(
+                screen,
+                name_pos_x + line_edit::str_width(&file_name[..match_range.start]) as u16,
+                y,
+                &file_name[match_range],
+                Style::new(
+                    rolf_grid::Attribute::Bold,
+                    rolf_grid::Color::Black,
+                    rolf_grid::Color::Yellow,
+                ),
+            );
+        }
+
+        if !info.is_empty() {
+            // NOTE(Chris): The info ends one cell before the right edge, leaving a space after it
+            let info_x = rect.right_x() - info.len() as u16;
+            draw_str(screen, info_x, y, &info, draw_style);
+        }
+
+        cache.insert(row, row_key, screen.copy_line(rect.left_x, y, row_width));
+    }
+}
+
+// The rows of a column as they were last drawn. Drawing a row means formatting its info and
+// searching its name, so rows whose entry and state haven't changed are copied to the screen from
+// here instead of being drawn again.
+#[derive(Default)]
+struct ColumnCache {
+    layout: Option<ColumnLayout>,
+    rows: Vec<Option<(RowKey, LineBuilder)>>,
+}
+
+impl ColumnCache {
+    // Forgets every row if anything affecting the whole column has changed
+    fn reset_if_changed(&mut self, layout: ColumnLayout) {
+        if self.layout.as_ref() != Some(&layout) {
+            self.layout = Some(layout);
+            self.rows.clear();
+        }
+    }
+
+    fn get(&self, row: usize, key: &RowKey) -> Option<&LineBuilder> {
+        match self.rows.get(row) {
+            Some(Some((cached_key, line))) if cached_key == key => Some(line),
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, row: usize, key: RowKey, line: LineBuilder) {
+        if self.rows.len() <= row {
+            self.rows.resize_with(row + 1, || None);
+        }
+
+        self.rows[row] = Some((key, line));
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ColumnLayout {
+    width: u16,
+    gutter_width: u16,
+    dimming_uid: Option<u32>,
+    info_columns: Vec<InfoColumn>,
+    search_highlight: Option<String>,
+    match_options: MatchOptions,
+    background: rolf_grid::Color,
+}
+
+// Everything about an entry which affects how its row is drawn
+#[derive(Debug, PartialEq)]
+struct RowKey {
+    file_name: OsString,
+    file_type: RecordedFileType,
+    size: u64,
+    modified: Option<SystemTime>,
+    owner_ids: Option<(u32, u32)>,
+    is_current: bool,
+    is_selected: bool,
+    line_number: Option<usize>,
+    dir_count: Option<usize>,
+    // NOTE(Chris): Files stop being recent as time passes, without anything else changing
+    is_recent: bool,
+    // NOTE(Chris): chmod doesn't change the modification time
+    is_executable: bool,
+}
+
+impl RowKey {
+    fn new(
+        entry_info: &DirEntryInfo,
+        is_current: bool,
+        is_selected: bool,
+        line_number: Option<usize>,
+        dir_count: Option<usize>,
+        is_recent: bool,
+        is_executable: bool,
+    ) -> Self {
+        RowKey {
+            file_name: entry_info.dir_entry.file_name(),
+            file_type: entry_info.file_type,
+            size: entry_info.metadata.len(),
+            modified: entry_info.metadata.modified().ok(),
+            owner_ids: entry_info.metadata.owner_ids(),
+            is_current,
+            is_selected,
+            line_number,
+            dir_count,
+            is_recent,
+            is_executable,
+        }
+    }
+}
+
+// How the entries in a column are drawn, apart from their file types
+struct ListingOptions<'a> {
+    // Shown in place of the entries when there aren't any
+    empty_message: &'static str,
+    // When set, files which aren't owned by this user are dimmed
+    dimming_user: Option<&'a CurrentUser>,
+    info_columns: &'a [InfoColumn],
+    // When set, directories show how many items they have in place of their size
+    dir_counts: Option<&'a DirCounts>,
+    size_units: SizeUnits,
+    // When set, files modified since the time are drawn in the color
+    recent_marking: Option<(SystemTime, rolf_grid::Color)>,
+    // Whether the cursor and selections are marked with characters rather than colors
+    plain: bool,
+    truncation: Truncation,
+    number: bool,
+    relative_number: bool,
+    search_highlight: Option<&'a str>,
+    match_options: MatchOptions,
+    // Behind everything but the cursor, which marks a directory that can't be written to
+    background: rolf_grid::Color,
+}
+
+fn empty_message(is_denied: bool) -> &'static str {
+    if is_denied {
+        "permission denied"
+    } else {
+        "empty"
+    }
+}
+
+// Returns the number shown in the gutter next to an entry, if there should be one. This follows
+// vim, where the current line shows its absolute number only if both options are set.
+fn get_line_number(index: u16, current_index: u16, options: &ListingOptions) -> Option<usize> {
+    if options.relative_number && !(options.number && index == current_index) {
+        Some(index.abs_diff(current_index) as usize)
+    } else if options.number {
+        Some(index as usize + 1)
+    } else {
+        None
+    }
+}
+
+// Formats the info columns shown after a file name, matching lf's layout
+fn format_entry_info(
+    metadata: &Metadata,
+    info_columns: &[InfoColumn],
+    size_units: SizeUnits,
+    dir_count: Option<usize>,
+) -> String {
+    let mut info = String::new();
+
+    // NOTE(Chris): A directory's size is just the size of its list of entries, so its item count
+    // is shown in its place
+    match dir_count {
+        Some(1) => info.push_str(" 1 item"),
+        Some(item_count) => info.push_str(&format!(" {} items", item_count)),
+        None => (),
+    }
+
+    for info_column in info_columns {
+        match info_column {
+            InfoColumn::Size if dir_count.is_some() => (),
+            InfoColumn::Size => {
+                info.push_str(&format!(" {:>4}", format_size(metadata.len(), size_units)));
+            }
+            InfoColumn::Time => {
+                let time = match metadata.modified() {
+                    Ok(modified) => DateTime::<Local>::from(modified)
+                        .format("%b %e %H:%M")
+                        .to_string(),
+                    Err(_) => String::new(),
+                };
+
+                info.push_str(&format!(" {:>12}", time));
+            }
+        }
+    }
+
+    info
+}
+
+fn draw_top_line(screen: &mut Screen, fm: &FileManager, home_path: &Path, second_entry_index: u16) {
+    let current_dir_display = format_current_dir(&fm.dir_states.current_dir, home_path);
+
+    let curr_entry;
+    let file_stem = if fm.dir_states.current_entries.len() <= 0 {
+        ""
+    } else {
+        curr_entry = fm.dir_states.current_entries[second_entry_index as usize]
+            .dir_entry
+            .file_name();
+        curr_entry.to_str().unwrap()
+    };
+
+    // Like "41G/64G", with a space to separate it from the file name
+    let disk_usage_display = match fm.disk_space {
+        Some(disk_space) => format!(
+            " {}/{}",
+            format_size(disk_space.used(), fm.config.size_units),
+            format_size(disk_space.total, fm.config.size_units)
+        ),
+        None => String::new(),
+    };
+
+    // NOTE(Chris): This warns that commands like paste and rename will fail here
+    let lock_display = match (fm.is_current_writable, fm.config.plain) {
+        (true, _) => "",
+        (false, true) => " [read-only]",
+        (false, false) => " 🔒",
+    };
+    let right_display_width = line_edit::str_width(lock_display) + disk_usage_display.len();
+
+    // TODO(Chris): Use the unicode-segmentation package to count graphemes
+    // Add 1 because of the ':' that is displayed after user_host_display
+    // Add 1 again because of the '/' that is displayed at the end of current_dir_display
+    let remaining_width = (fm.drawing_info.width as usize).saturating_sub(
+        fm.user_host_display.len() + 1 + current_dir_display.len() + 1 + right_display_width,
+    );
+
+    let file_stem = truncate::truncate_name(
+        file_stem,
+        remaining_width,
+        fm.config.truncate,
+        fm.config.plain,
+    );
+
+    let user_host_len = fm.user_host_display.len().try_into().unwrap();
+    draw_str(
+        screen,
+        0,
+        0,
+        &fm.user_host_display,
+        rolf_grid::Style::new(
+            rolf_grid::Attribute::Bold,
+            rolf_grid::Color::Green,
+            rolf_grid::Color::Background,
+        ),
+    );
+    draw_str(screen, user_host_len, 0, ":", rolf_grid::Style::default());
+    draw_str(
+        screen,
+        user_host_len + 1, // From the ":"
+        0,
+        &format!("{}{}", current_dir_display, path::MAIN_SEPARATOR),
+        rolf_grid::Style::new(
+            rolf_grid::Attribute::Bold,
+            rolf_grid::Color::Blue,
+            rolf_grid::Color::Background,
+        ),
+    );
+    draw_str(
+        screen,
+        user_host_len + 1 + current_dir_display.len() as u16 + 1,
+        0,
+        &file_stem,
+        rolf_grid::Style::new(
+            rolf_grid::Attribute::Bold,
+            rolf_grid::Color::Foreground,
+            rolf_grid::Color::Background,
+        ),
+    );
+
+    // NOTE(Chris): On narrow terminals, these are drawn over the directory rather than left out
+    draw_str(
+        screen,
+        fm.drawing_info
+            .width
+            .saturating_sub(right_display_width as u16),
+        0,
+        lock_display,
+        rolf_grid::Style::new(
+            rolf_grid::Attribute::Bold,
+            rolf_grid::Color::Yellow,
+            rolf_grid::Color::Background,
+        ),
+    );
+
+    if let Some(disk_space) = fm.disk_space {
+        let disk_usage_style = if disk_space.used_percent() >= fm.config.disk_usage_warning {
+            rolf_grid::Style::new(
+                rolf_grid::Attribute::Bold,
+                rolf_grid::Color::Red,
+                rolf_grid::Color::Background,
+            )
+        } else {
+            rolf_grid::Style::default()
+        };
+
+        draw_str(
+            screen,
+            fm.drawing_info
+                .width
+                .saturating_sub(disk_usage_display.len() as u16),
+            0,
+            &disk_usage_display,
+            disk_usage_style,
+        );
+    }
+}
+
+// Counts the items in the directories in view, for the dir-counts option. Those already counted
+// since they were last modified aren't counted again.
+fn request_dir_counts(fm: &mut FileManager) {
+    if !fm.config.dir_counts {
+        return;
+    }
+
+    let entries = &fm.dir_states.current_entries;
+    let start = (fm.second.starting_index as usize).min(entries.len());
+    let end = (start + fm.drawing_info.column_height as usize).min(entries.len());
+
+    for entry_info in &entries[start..end] {
+        if matches!(
+            entry_info.file_type,
+            RecordedFileType::Directory | RecordedFileType::DirectorySymlink
+        ) && entry_info.metadata.local().is_some()
+        {
+            fm.dir_counts.request(
+                &entry_info.dir_entry.path(),
+                entry_info.metadata.modified().ok(),
+            );
+        }
+    }
+}
+
+// Files bigger than this don't have their lines counted for the preview header
+const PREVIEW_LINE_COUNT_LIMIT: u64 = 1024 * 1024;
+
+// How much of a mounted text file is copied to the disk to preview it, which is far more than fits
+// in the preview
+const MOUNTED_PREVIEW_LIMIT: u64 = 64 * 1024;
+
+// How much of a text file the preview shows
+#[derive(Clone, Copy, Debug)]
+struct PreviewPosition {
+    // Previews always start at the first line, so this is also the last line shown
+    shown_count: usize,
+    // Left out when the file is too big to count its lines quickly
+    line_count: Option<usize>,
+}
+
+// Draws the row above the preview, like " notes.txt    4.1K  lines 1-20 of 350 "
+fn draw_preview_header(
+    screen: &mut Screen,
+    fm: &FileManager,
+    second_entry_index: u16,
+    position: Option<PreviewPosition>,
+) {
+    let entry_info = &fm.dir_states.current_entries[second_entry_index as usize];
+    let header_y = get_preview_rect(fm).top_y - 1;
+    let width = (fm.drawing_info.width - fm.drawing_info.third_left_x) as usize;
+
+    let mut details = vec![];
+
+    match &fm.preview_data {
+        PreviewData::Directory { entries_info } => details.push(match entries_info.len() {
+            1 => String::from("1 item"),
+            item_count => format!("{} items", item_count),
+        }),
+        _ => details.push(format_size(entry_info.metadata.len(), fm.config.size_units)),
+    }
+
+    // TODO(Chris): Show the lines actually in view once previews can be scrolled
+    if let Some(position) = position {
+        details.push(match position.line_count {
+            Some(1) if position.shown_count == 1 => String::from("1 line"),
+            Some(line_count) if line_count <= position.shown_count => {
+                format!("{} lines", line_count)
+            }
+            Some(line_count) => format!("lines 1-{} of {}", position.shown_count, line_count),
+            None => format!("lines 1-{}", position.shown_count),
+        });
+    }
+
+    let mut details = details.join("  ");
+
+    // NOTE(Chris): The name keeps at least a few columns, dropping the details if it must
+    if details.len() + 6 > width {
+        details.clear();
+    }
+
+    let file_name = entry_info.dir_entry.file_name();
+    let file_name = truncate::truncate_name(
+        &file_name.to_string_lossy(),
+        width.saturating_sub(details.len() + 3),
+        fm.config.truncate,
+        fm.config.plain,
+    );
+
+    let padding = width.saturating_sub(line_edit::str_width(&file_name) + details.len() + 2);
+    let header = format!(" {}{:padding$}{} ", file_name, "", details);
+
+    draw_str(
+        screen,
+        fm.drawing_info.third_left_x,
+        header_y,
+        &header,
+        rolf_grid::Style::new(
+            rolf_grid::Attribute::Bold,
+            rolf_grid::Color::Black,
+            rolf_grid::Color::Cyan,
+        ),
+    );
+}
+
+// The number of lines in some text, counting a last line without a newline at its end
+fn count_lines(bytes: &[u8]) -> usize {
+    let newline_count = bytes.iter().filter(|&&byte| byte == b'\n').count();
+
+    match bytes.last() {
+        Some(b'\n') | None => newline_count,
+        Some(_) => newline_count + 1,
+    }
+}
+
+fn draw_first_column(screen: &mut Screen, fm: &mut FileManager, cache: &mut ColumnCache) {
+    let first_column_rect = Rect {
+        left_x: fm.drawing_info.first_left_x,
+        top_y: 1,
+        width: fm.drawing_info.first_right_x - fm.drawing_info.first_left_x,
+        height: fm.drawing_info.column_height,
+    };
+
+    if let Some(prev_dir) = &fm.dir_states.prev_dir {
+        let result_column_info = find_correct_location(
+            &fm.left_paths,
+            fm.drawing_info.column_height,
+            prev_dir,
+            &fm.dir_states.prev_entries,
+            &fm.dir_states.current_dir,
+        );
+
+        let starting_index = result_column_info.starting_index;
+        let entry_index = result_column_info.starting_index + result_column_info.display_offset;
+
+        draw_column(
+            screen,
+            first_column_rect,
+            starting_index,
+            entry_index,
+            &fm.dir_states.prev_entries,
+            &fm.selections,
+            &ListingOptions {
+                empty_message: empty_message(fm.dir_states.is_prev_denied),
+                ..fm.listing_options(false)
+            },
+            cache,
+        );
+    } else {
+        // NOTE(Chris): The root directory has no parent, which we say rather than leaving the
+        // column blank
+        draw_column(
+            screen,
+            first_column_rect,
+            0,
+            0,
+            &[],
+            &fm.selections,
+            &ListingOptions {
+                empty_message: "(root)",
+                ..fm.listing_options(false)
+            },
+            cache,
+        );
+    }
+}
+
+fn search_in_direction(
+    fm: &mut FileManager,
+    search_term: &str,
+    should_search_forwards: bool,
+) -> io::Result<()> {
+    fm.match_positions = find_match_positions(
+        &fm.dir_states.current_entries,
+        search_term,
+        fm.match_options(false),
+    );
+    fm.search_highlight = Some(search_term.to_string());
+
+    fm.should_search_forwards = should_search_forwards;
+
+    search_jump(fm)?;
+
+    Ok(())
+}
+
+// Like search_in_direction, but for a file name's first characters (when anchorfind is set) and
+// without highlighting
+fn find_in_direction(
+    fm: &mut FileManager,
+    find_term: &str,
+    should_search_forwards: bool,
+) -> io::Result<()> {
+    fm.match_positions = find_match_positions(
+        &fm.dir_states.current_entries,
+        find_term,
+        fm.match_options(fm.config.anchor_find),
+    );
+
+    fm.should_search_forwards = should_search_forwards;
+
+    search_jump(fm)?;
+
+    Ok(())
+}
+
+// A file matching a search, as listed in the search results panel. Files hidden by the entry
+// filter have no index in the listing.
+#[derive(Debug, PartialEq)]
+struct SearchResult {
+    index: Option<usize>,
+    name: String,
+    file_id: u64,
+}
+
+// Finds the shown entries which match the search term, followed by the matches among the rest of
+// the directory's entries (which are hidden by the entry filter)
+fn find_search_results(
+    shown_entries: &[DirEntryInfo],
+    all_entries: Option<&[DirEntryInfo]>,
+    search_term: &str,
+    options: MatchOptions,
+) -> Vec<SearchResult> {
+    let to_result = |index, entry_info: &DirEntryInfo| SearchResult {
+        index,
+        name: entry_info
+            .dir_entry
+            .file_name()
+            .to_string_lossy()
+            .into_owned(),
+        file_id: entry_info.metadata.file_id(),
+    };
+
+    let mut results: Vec<SearchResult> = find_match_positions(shown_entries, search_term, options)
+        .into_iter()
+        .map(|index| to_result(Some(index), &shown_entries[index]))
+        .collect();
+
+    if let Some(all_entries) = all_entries {
+        let shown_count = results.len();
+
+        for index in find_match_positions(all_entries, search_term, options) {
+            let hidden_result = to_result(None, &all_entries[index]);
+
+            if !results[..shown_count]
+                .iter()
+                .any(|result| result.name == hidden_result.name)
+            {
+                results.push(hidden_result);
+            }
+        }
+    }
+
+    results
+}
+
+fn find_match_positions(
+    current_entries: &[DirEntryInfo],
+    search_term: &str,
+    options: MatchOptions,
+) -> Vec<usize> {
+    current_entries
+        .iter()
+        .enumerate()
+        .filter_map(|(index, entry_info)| {
+            if search::is_match(
+                entry_info.dir_entry.file_name().to_str().unwrap(),
+                search_term,
+                options,
+            ) {
+                Some(index)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn set_current_dir<P: AsRef<Path>>(
+    target_new_current_dir: P,
+    dir_states: &mut DirStates,
+    match_positions: &mut Vec<usize>,
+) -> crossterm::Result<()> {
+    let mut new_current_dir: &Path = target_new_current_dir.as_ref();
+    let mut is_dir = dir_states
+        .file_system_for(new_current_dir)
+        .is_dir(new_current_dir);
+
+    while !is_dir && new_current_dir.parent().is_some() {
+        new_current_dir = new_current_dir.parent().expect("No parent of dir");
+        is_dir = dir_states
+            .file_system_for(new_current_dir)
+            .is_dir(new_current_dir);
+    }
 
-                        *asking_type = if ask_for_single_key {
-                            AskingType::AdditionalInputKey
-                        } else {
-                            AskingType::AdditionalInput
-                        }
-                    } else {
-                        panic!(
-                            "Requested a prompt change when input mode is: {:?}",
-                            &fm.input_mode
-                        );
-                    }
-                }
-                CommandRequest::Quit => {
-                    leave_command_mode(&mut fm);
-                }
-            },
-            InputEvent::CommandCallback(CommandCallback(cb)) => {
-                cb(&mut fm)?;
-            }
-        }
+    if !is_dir && new_current_dir.parent().is_none() {
+        panic!("Cannot find directory to make the current one.");
     }
 
-    to_input_tx
-        .send(InputRequest::Quit)
-        .expect("Unable to send to input thread");
+    dir_states.set_current_dir(new_current_dir)?;
+    match_positions.clear();
 
-    Ok(fm.dir_states.current_dir)
+    Ok(())
 }
 
-struct FileManager<'a> {
-    available_execs: HashMap<&'a str, std::path::PathBuf>,
+// Moves to another directory (like with cd), entering the file system mounted there if there is
+// one. The cursor goes back to wherever it was last left in the directory.
+fn change_dir(fm: &mut FileManager, dir: &Path, mount: Option<Mount>) -> crossterm::Result<()> {
+    fm.preview_pool.cancel_all();
 
-    image_handles: HandlesVec,
+    if !fm.dir_states.current_entries.is_empty() {
+        save_location(fm, fm.get_second_entry_index());
+    }
 
-    dir_states: DirStates,
+    match mount {
+        Some(mount) => fm.dir_states.enter_mount(mount, dir.to_path_buf())?,
+        None => fm.dir_states.set_current_dir(dir)?,
+    }
+    fm.match_positions.clear();
 
-    second: ColumnInfo,
+    restore_location(fm, dir)?;
 
-    left_paths: HashMap<std::path::PathBuf, DirLocation>,
+    Ok(())
+}
 
-    match_positions: Vec<usize>,
+// Opens an archive as a read-only directory, which stays mounted until it's left
+fn enter_archive(fm: &mut FileManager, path: PathBuf, kind: ArchiveKind) -> crossterm::Result<()> {
+    let file_system = match ArchiveFileSystem::open(&path, kind) {
+        Ok(file_system) => file_system,
+        Err(err) => {
+            fm.status_message = Some(format!("Unable to read {}: {}", path.display(), err));
+            return Ok(());
+        }
+    };
 
-    should_search_forwards: bool,
+    // NOTE(Chris): Leaving the archive goes back to the directory it's in
+    let mount = Mount::new(
+        path.clone(),
+        Arc::new(file_system),
+        path.parent().map(Path::to_path_buf),
+        fm.dir_states.working_dir().to_path_buf(),
+    )?;
 
-    input_line: String,
+    if let Err(err) = change_dir(fm, &path, Some(mount)) {
+        fm.status_message = Some(format!("Unable to open {}: {}", path.display(), err));
+    }
 
-    input_cursor: usize,
+    Ok(())
+}
 
-    input_mode: InputMode,
+// Finds the directory that cd goes to. Like in a shell, ~ is the home directory, relative paths
+// start in the current directory, and .. is resolved without following symlinks.
+fn cd_target(current_dir: &Path, target: &str) -> PathBuf {
+    let (mut dir, target) = match target.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(path::is_separator) => {
+            let home_dir = PathBuf::from(os_abstract::get_home_name());
 
-    user_host_display: String,
+            (home_dir, rest.trim_start_matches(path::is_separator))
+        }
+        _ => (current_dir.to_path_buf(), target),
+    };
 
-    selections: SelectionsMap,
+    // NOTE(Chris): Pushing an absolute path replaces the directory entirely
+    for component in Path::new(target).components() {
+        match component {
+            path::Component::CurDir => (),
+            path::Component::ParentDir => {
+                dir.pop();
+            }
+            component => dir.push(component),
+        }
+    }
 
-    drawing_info: DrawingInfo,
+    dir
+}
 
-    config: Config,
+// Logs into a server (which may ask for a password) in the background, then opens the URL's
+// directory on it
+#[cfg(feature = "sftp")]
+fn connect_sftp(
+    fm: &mut FileManager,
+    tx: &Sender<InputEvent>,
+    to_command_tx: &mut Option<Sender<String>>,
+    url: &str,
+) {
+    let address = match sftp::Address::parse(url) {
+        Some(address) => address,
+        None => {
+            fm.status_message = Some(format!("Invalid address: {}", url));
+            return;
+        }
+    };
 
-    preview_data: PreviewData,
-}
+    let working_dir = fm.dir_states.working_dir().to_path_buf();
+    let to_our_tx = tx.clone();
 
-impl FileManager<'_> {
-    fn get_second_entry_index(&self) -> u16 {
-        self.second.starting_index + self.second.display_offset
-    }
-}
+    fm.status_message = Some(format!("Connecting to {}...", address.host));
 
-#[derive(Debug)]
-enum InputMode {
-    Normal,
-    Command {
-        prompt: String,
-        asking_type: AskingType,
-    },
-    View {
-        top_ind: u16,
-        view_rect: Rect,
-        keybindings_vec: Vec<(String, String, String)>,
-    },
-}
+    spawn_command_thread(tx, to_command_tx, move |prompter| {
+        let connection = sftp::connect(&address, |label| prompter.ask(Prompt::new(label).masked()));
 
-impl InputMode {
-    fn to_top(&self) -> InputModeTop {
-        match self {
-            InputMode::Normal => InputModeTop::Normal,
-            InputMode::Command { .. } => InputModeTop::Command,
-            InputMode::View { .. } => InputModeTop::View,
-        }
-    }
-}
+        let (file_system, start_dir) = match connection {
+            Ok(connection) => connection,
+            // The password prompt was cancelled
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                send_callback_to_main!(&to_our_tx, |fm| {
+                    fm.status_message = None;
 
-// This represents a specific InputMode without any of the corresponding fields
-#[derive(std::cmp::PartialEq)]
-enum InputModeTop {
-    Normal,
-    Command,
-    View,
-}
+                    Ok(())
+                });
+                return;
+            }
+            Err(err) => {
+                report_status(
+                    &to_our_tx,
+                    format!("Unable to connect to {}: {}", address.host, err),
+                );
+                return;
+            }
+        };
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum AskingType {
-    // The user is inputting a command
-    Command,
-    // The user is inputting more input, to be used with some earlier input
-    AdditionalInput,
-    // The user is going to enter a single key (e.g. y/n)
-    AdditionalInputKey,
+        send_callback_to_main!(&to_our_tx, move |fm| {
+            fm.status_message = None;
+
+            // NOTE(Chris): There's nothing above a server's root directory
+            let result = Mount::new(address.root(), Arc::new(file_system), None, working_dir)
+                .and_then(|mount| change_dir(fm, &start_dir, Some(mount)));
+
+            if let Err(err) = result {
+                fm.status_message =
+                    Some(format!("Unable to open {}: {}", start_dir.display(), err));
+            }
+
+            Ok(())
+        });
+    });
 }
 
-fn leave_command_mode_and_additional_thread(
+#[cfg(feature = "s3")]
+fn connect_s3(
     fm: &mut FileManager,
-    to_command_tx: &Option<Sender<String>>,
+    tx: &Sender<InputEvent>,
+    to_command_tx: &mut Option<Sender<String>>,
+    url: &str,
 ) {
-    match &fm.input_mode {
-        InputMode::Normal => unreachable!(),
-        InputMode::Command { asking_type, .. } => match asking_type {
-            AskingType::Command => (),
-            AskingType::AdditionalInput | AskingType::AdditionalInputKey => {
-                // TODO(Chris): Use a different function, one which just directly exits
-                // AdditionalInput mode by always sending an empty input_line
-                fm.input_line.clear();
-                exit_input_mode_command_thread(fm, to_command_tx);
+    let url = url.to_string();
+    let working_dir = fm.dir_states.working_dir().to_path_buf();
+    let to_our_tx = tx.clone();
+
+    fm.status_message = Some(format!("Opening {}...", url));
+
+    // NOTE(Chris): Finding the credentials can take a while (like when it has to ask the
+    // instance's metadata service), so it happens in the background
+    spawn_command_thread(tx, to_command_tx, move |_| {
+        let (file_system, start_dir) = match s3::connect(&url) {
+            Ok(connection) => connection,
+            Err(err) => {
+                report_status(&to_our_tx, format!("Unable to open {}: {}", url, err));
+                return;
             }
-        },
-        InputMode::View { .. } => unreachable!(),
-    }
+        };
 
-    leave_command_mode(fm);
-}
+        // NOTE(Chris): The start directory is checked here, since it's the first request to S3
+        match file_system.check_dir(&start_dir) {
+            Ok(true) => (),
+            Ok(false) => {
+                report_status(
+                    &to_our_tx,
+                    format!("{} isn't a bucket or a prefix in one", url),
+                );
+                return;
+            }
+            Err(err) => {
+                report_status(&to_our_tx, format!("Unable to open {}: {}", url, err));
+                return;
+            }
+        }
 
-// TODO(Chris): Modify this function to actually interpret the current line of input as necessary,
-// "sending" it to the program "for real," rather than just exiting AdditionalInput mode when
-// necessary
-fn leave_command_mode(fm: &mut FileManager) {
-    fm.input_mode = InputMode::Normal;
+        send_callback_to_main!(&to_our_tx, move |fm| {
+            fm.status_message = None;
 
-    clear_input_line(fm);
-}
+            // NOTE(Chris): There's nothing above the list of buckets
+            let result = Mount::new(s3::root(), Arc::new(file_system), None, working_dir)
+                .and_then(|mount| change_dir(fm, &start_dir, Some(mount)));
 
-fn clear_input_line(fm: &mut FileManager) {
-    fm.input_line.clear();
-    fm.input_cursor = 0;
+            if let Err(err) = result {
+                fm.status_message =
+                    Some(format!("Unable to open {}: {}", start_dir.display(), err));
+            }
+
+            Ok(())
+        });
+    });
 }
 
-fn enter_command_mode_with(
+// Edits a copy of a mounted file, which is saved back to the file system if the editor changed it
+fn edit_mounted_file(
     fm: &mut FileManager,
-    beginning: &str,
-    prompt: String,
-    asking_type: AskingType,
+    tx: &Sender<InputEvent>,
+    mount: Mount,
+    path: PathBuf,
+    editor: String,
+    line: Option<usize>,
 ) {
-    fm.input_mode = InputMode::Command {
-        prompt,
-        asking_type,
-    };
+    if !mount.file_system().is_writable() {
+        fm.status_message = Some(format!("{} is read-only", path.display()));
+        return;
+    }
 
-    fm.input_line.clear();
-    fm.input_line.push_str(beginning);
+    let editor_line_flag = fm.config.editor_line_flag.clone();
+    let to_our_tx = tx.clone();
 
-    fm.input_cursor = fm.input_line.len();
-}
+    fm.status_message = Some(format!("Downloading {}...", path.display()));
 
-fn quit_command_thread(to_main_tx: &Sender<InputEvent>) {
-    to_main_tx
-        .send(InputEvent::CommandRequest(CommandRequest::Quit))
-        .expect("Failed to send to main thread");
+    std::thread::spawn(move || {
+        let local_path = match mount.download(&path) {
+            Ok(local_path) => local_path,
+            Err(err) => {
+                report_status(
+                    &to_our_tx,
+                    format!("Unable to download {}: {}", path.display(), err),
+                );
+                return;
+            }
+        };
+        let modified = fs::metadata(&local_path).and_then(|metadata| metadata.modified());
+
+        let shell_command = editor::editor_command(
+            &editor,
+            &editor_line_flag,
+            local_path
+                .to_str()
+                .expect("Failed to convert path to string"),
+            line,
+        );
+
+        to_our_tx
+            .send(InputEvent::CommandRequest(CommandRequest::Shell(
+                shell_command,
+            )))
+            .expect("Failed to send to main thread");
+
+        let to_upload_tx = to_our_tx.clone();
+
+        // NOTE(Chris): The main thread handles this after the editor exits, since it runs the
+        // editor before looking at anything else that was sent
+        send_callback_to_main!(&to_our_tx, move |fm| {
+            let new_modified = fs::metadata(&local_path).and_then(|metadata| metadata.modified());
+
+            if new_modified.ok() == modified.ok() {
+                fm.status_message = None;
+                return Ok(());
+            }
+
+            fm.status_message = Some(format!("Saving {}...", path.display()));
+
+            std::thread::spawn(move || {
+                let result = fs::File::open(&local_path)
+                    .and_then(|mut file| mount.file_system().write_file(&path, &mut file));
+
+                let message = match result {
+                    Ok(()) => format!("Saved {}", path.display()),
+                    Err(err) => format!("Unable to save {}: {}", path.display(), err),
+                };
+
+                report_status(&to_upload_tx, message);
+            });
+
+            Ok(())
+        });
+    });
 }
 
-fn exit_input_mode_command_thread(fm: &mut FileManager, to_command_tx: &Option<Sender<String>>) {
-    if let Some(to_command_tx) = &to_command_tx {
-        to_command_tx
-            .send(fm.input_line.clone())
-            .expect("Failed to send to command thread");
+fn enter_entry(
+    fm: &mut FileManager,
+    tx: &Sender<InputEvent>,
+    second_entry_index: u16,
+) -> crossterm::Result<()> {
+    // NOTE(Chris): We only need to abort asynchronous "image" drawing if we're opening a
+    // directory¸ since we're now drawing directory previews asychronously with the same system as
+    // the image drawing.
 
-        clear_input_line(fm);
+    if fm.dir_states.current_entries.len() <= 0 {
+        return Ok(());
+    }
+
+    save_location(fm, second_entry_index);
+
+    let selected_entry = &fm.dir_states.current_entries[second_entry_index as usize];
+    let selected_entry_path = &selected_entry.dir_entry.path();
+
+    // NOTE(Chris): A mounted entry's file type already says what its target is, since the file
+    // system resolved it when listing the directory
+    let (is_dir, is_file) = if fm.dir_states.is_mounted_path(selected_entry_path) {
+        match selected_entry.file_type {
+            RecordedFileType::Directory | RecordedFileType::DirectorySymlink => (true, false),
+            RecordedFileType::File | RecordedFileType::FileSymlink => (false, true),
+            _ => return Ok(()),
+        }
     } else {
-        panic!("Main thread: Asked for additional input despite no command thread being available");
+        // TODO(Chris): Show this error without crashing the program
+        match selected_entry_path.metadata() {
+            Ok(metadata) => (metadata.is_dir(), metadata.is_file()),
+            Err(_) => return Ok(()),
+        }
+    };
+
+    if is_dir {
+        fm.preview_pool.cancel_all();
+
+        let selected_dir_path = selected_entry_path;
+
+        match set_current_dir(
+            selected_dir_path,
+            &mut fm.dir_states,
+            &mut fm.match_positions,
+        ) {
+            Ok(_) => (),
+            Err(err) => match err.kind() {
+                io::ErrorKind::PermissionDenied => {
+                    fm.status_message = Some(format!(
+                        "Permission denied: {}",
+                        selected_dir_path.display()
+                    ));
+
+                    return Ok(());
+                }
+                _ => panic!("{}", err),
+            },
+        }
+
+        restore_location(fm, selected_dir_path)?;
+    } else if let Some(kind) = ArchiveKind::from_path(selected_entry_path)
+        .filter(|_| is_file && !fm.dir_states.is_mounted_path(selected_entry_path))
+    {
+        // NOTE(Chris): An archive's open rule (if it has one) was already run instead
+        let archive_path = selected_entry_path.clone();
+
+        enter_archive(fm, archive_path, kind)?;
+    } else if is_file {
+        let selected_file_path = selected_entry_path.clone();
+        let mount = fm
+            .dir_states
+            .mount
+            .clone()
+            .filter(|mount| mount.contains(&selected_file_path));
+        let fallback_opener = if fm.config.fallback_opener.is_empty() {
+            os_abstract::default_fallback_opener().map(String::from)
+        } else {
+            Some(fm.config.fallback_opener.clone())
+        };
+        let to_main_tx = tx.clone();
+
+        // NOTE(Chris): Openers may not exit until the file is closed, so we wait for them in
+        // another thread and only report back if something went wrong
+        std::thread::spawn(move || {
+            // NOTE(Chris): Openers need a file on the disk, so a mounted file is copied there
+            // first
+            let selected_file_path = match mount {
+                Some(mount) => match mount.download(&selected_file_path) {
+                    Ok(local_path) => local_path,
+                    Err(err) => {
+                        report_status(
+                            &to_main_tx,
+                            format!("Unable to copy {}: {}", selected_file_path.display(), err),
+                        );
+                        return;
+                    }
+                },
+                None => selected_file_path,
+            };
+
+            let mut result = check_opener_status(open::that(&selected_file_path));
+
+            if result.is_err() {
+                if let Some(fallback_opener) = fallback_opener {
+                    result = run_fallback_opener(&fallback_opener, &selected_file_path);
+                }
+            }
+
+            if let Err(err) = result {
+                let message = format!(
+                    "Unable to open {}: {}",
+                    selected_file_path
+                        .file_name()
+                        .unwrap_or(selected_file_path.as_os_str())
+                        .to_string_lossy(),
+                    err
+                );
+
+                report_status(&to_main_tx, message);
+            }
+        });
     }
+
+    Ok(())
 }
 
-// NOTE(Chris): When it comes to refactoring many variables into structs, perhaps we should group
-// them by when they are modified. For example, DrawingInfo is modified whenever the terminal
-// window resizes, while ColumnInfo will be modified even when the terminal window isn't resizing.
-// Thus, we should maybe put the left_x value for each column in DrawingInfo (rather than
-// ColumnInfo), since those will primarily be modified when the terminal window changes.
+// Returns the shell command of the open rule matching the file at the index, if there is one
+fn open_rule_command(fm: &FileManager, second_entry_index: u16) -> Option<String> {
+    let path = fm
+        .dir_states
+        .current_entries
+        .get(second_entry_index as usize)?
+        .dir_entry
+        .path();
 
-#[derive(Clone, Copy)]
-struct DrawingInfo {
-    win_pixels: WindowPixels,
-    width: u16,
-    height: u16,
-    column_bot_y: u16,
-    column_height: u16,
-    first_right_x: u16,
-    first_left_x: u16,
-    second_left_x: u16,
-    second_right_x: u16,
-    third_left_x: u16,
-    third_right_x: u16,
-}
+    // NOTE(Chris): Rule commands run on files on the disk
+    if fm.dir_states.is_mounted_path(&path) || !path.metadata().ok()?.is_file() {
+        return None;
+    }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-struct ColumnInfo {
-    starting_index: u16,
-    display_offset: u16,
-}
+    let rule_command = fm.open_rules.find_command(&path)?;
 
-#[derive(Debug)]
-enum InputEvent {
-    CrosstermEvent {
-        event: crossterm::event::Event,
-        input_request_count: usize,
-    },
-    PreviewLoaded(PreviewData),
-    CommandRequest(CommandRequest),
-    CommandCallback(CommandCallback),
+    // NOTE(Chris): Like lf, the path is available to the rule's command as $f
+    Some(format!(
+        "export f={}\n{}",
+        shell_quote(path.to_str()?),
+        rule_command
+    ))
 }
 
-impl InputEvent {
-    #[allow(dead_code)]
-    fn display_event_type(&self) -> &'static str {
-        match self {
-            InputEvent::CrosstermEvent { .. } => "CrosstermEvent",
-            InputEvent::PreviewLoaded(_) => "PreviewLoaded",
-            InputEvent::CommandRequest(_) => "CommandRequest",
-            InputEvent::CommandCallback(_) => "CommandCallback",
-            // _ => "UNSUPPORTED EVENT DISPLAY",
-        }
+fn check_opener_status(status: io::Result<std::process::ExitStatus>) -> io::Result<()> {
+    let status = status?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("opener failed ({})", status)))
     }
 }
 
-struct CommandCallback(Box<CommandCallbackFn>);
-type CommandCallbackFn = dyn FnOnce(&mut FileManager) -> io::Result<()> + Send;
+// Runs an opener command like "mimeopen -n", passing the path as its last argument
+fn run_fallback_opener(fallback_opener: &str, path: &Path) -> io::Result<()> {
+    let mut words = fallback_opener.split_whitespace();
 
-impl std::fmt::Debug for CommandCallback {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "CommandCallback(...)")
+    let program = match words.next() {
+        Some(program) => program,
+        None => return Err(io::Error::from(io::ErrorKind::InvalidInput)),
+    };
+
+    check_opener_status(Command::new(program).args(words).arg(path).status())
+}
+
+fn is_file_entry(fm: &FileManager, second_entry_index: u16) -> bool {
+    match fm
+        .dir_states
+        .current_entries
+        .get(second_entry_index as usize)
+    {
+        Some(entry) => matches!(
+            entry.file_type,
+            RecordedFileType::File | RecordedFileType::FileSymlink
+        ),
+        None => false,
     }
 }
 
-fn send_callback_to_main(to_main_tx: &Sender<InputEvent>, cb: Box<CommandCallbackFn>) {
-    to_main_tx
-        .send(InputEvent::CommandCallback(CommandCallback(cb)))
-        .expect("Failed to send to main thread");
+// Writes the selected files (or the current file, if none are selected) to the --choosefiles path,
+// one per line. Returns whether anything was picked, in which case rolf should exit.
+// The selected files, or the current file if none are selected
+fn target_paths(fm: &FileManager, second_entry_index: u16) -> Vec<PathBuf> {
+    if fm.selections.is_empty() {
+        fm.dir_states
+            .current_entries
+            .get(second_entry_index as usize)
+            .map(|entry| entry.dir_entry.path())
+            .into_iter()
+            .collect()
+    } else {
+        fm.selections.iter().cloned().collect()
+    }
 }
 
-enum InputRequest {
-    RequestNumber(usize),
-    Quit,
+// Like "1 file" or "3 files"
+fn count_files(count: usize) -> String {
+    if count == 1 {
+        String::from("1 file")
+    } else {
+        format!("{} files", count)
+    }
 }
 
-#[derive(Debug)]
-enum CommandRequest {
-    ChangePrompt {
-        new_prompt: String,
-        ask_for_single_key: bool,
-    },
-    Quit,
+// The files which pick hands back, which are the same as target_paths, but sorted
+fn picked_paths(fm: &FileManager, second_entry_index: u16) -> Vec<PathBuf> {
+    let mut paths = target_paths(fm, second_entry_index);
+    paths.sort();
+
+    paths
 }
 
-/// Reloads the current directory.
-///
-/// If `maybe_existing_file_id` corresponds to the file id of an existing file in the current
-/// directory, place the second entry index on that file.
-///
-/// Otherwise, find the nearest existing file and place the second entry index on that.
-fn reload_current_dir_prefer_id(
-    fm: &mut FileManager,
-    maybe_existing_file_id: u64,
-    tx: &Sender<InputEvent>,
-) {
-    set_current_dir(
-        fm.dir_states.current_dir.clone(),
-        &mut fm.dir_states,
-        &mut fm.match_positions,
-    )
-    .expect("Failed to update current directory");
+// Writes the picked files to the --choosefiles path, returning whether rolf should quit because
+// they were written
+fn pick_files(fm: &FileManager, picked_paths: &[PathBuf]) -> io::Result<bool> {
+    let choose_files_path = match &fm.choose_files_path {
+        Some(choose_files_path) => choose_files_path,
+        None => return Ok(false),
+    };
 
-    // NOTE(Chris): This is how we try to jump to a desired existing file early.
-    if jump_by_file_id(fm, maybe_existing_file_id).is_ok() {
-        return;
+    if picked_paths.is_empty() {
+        return Ok(false);
     }
 
-    let mut existing_file_id: Option<u64> = None;
+    let mut picked_text = String::new();
+    for path in picked_paths {
+        picked_text.push_str(path.to_str().expect("Failed to convert path to string"));
+        picked_text.push('\n');
+    }
 
-    let initial_second_entry_index = fm.get_second_entry_index();
+    fs::write(choose_files_path, picked_text)?;
 
-    for index in initial_second_entry_index as usize..fm.dir_states.current_entries.len() {
-        let current_entry = &fm.dir_states.current_entries[index];
-        let current_metadata = &current_entry.metadata;
+    Ok(true)
+}
 
-        existing_file_id = Some(get_file_id(current_metadata));
-        break;
-    }
+// Sets the values underlying column_starting_index and column_display_offset to properly set a
+// cursor at the next_position index in a vector of entries.
+fn find_column_pos(
+    current_entries_len: usize,
+    column_height: u16,
+    column: ColumnInfo,
+    next_position: usize,
+) -> crossterm::Result<ColumnInfo> {
+    assert!(next_position <= current_entries_len);
 
-    if existing_file_id.is_none() {
-        for index in (0..initial_second_entry_index as usize).rev() {
-            if let Some(current_entry) = fm.dir_states.current_entries.get(index) {
-                let current_metadata = &current_entry.metadata;
+    let second_entry_index = column.starting_index + column.display_offset;
 
-                existing_file_id = Some(get_file_id(current_metadata));
-                break;
-            }
-        }
-    }
+    // let lower_offset = (column.height * 2 / 3) as usize;
+    // let upper_offset = (column.height / 3) as usize;
+    let lesser_offset = scroll_offset(column_height) as usize;
+    let greater_offset = column_height.saturating_sub(scroll_offset(column_height) + 1) as usize;
 
-    if let Some(existing_file_id) = existing_file_id {
-        jump_by_file_id(fm, existing_file_id).expect("Unable to jump to file by id");
-    } else {
-        fm.second = ColumnInfo {
-            starting_index: 0,
-            display_offset: 0,
+    let mut result_column = column;
+
+    if column_height as usize > current_entries_len {
+        assert_eq!(column.starting_index, 0);
+
+        result_column.display_offset = next_position as u16;
+    } else if next_position < second_entry_index as usize {
+        // Moving up
+        if next_position <= lesser_offset {
+            result_column.starting_index = 0;
+
+            result_column.display_offset = next_position as u16;
+        } else if next_position <= result_column.starting_index as usize + lesser_offset {
+            result_column.display_offset = lesser_offset as u16;
+
+            result_column.starting_index = next_position as u16 - result_column.display_offset;
+        } else if next_position > result_column.starting_index as usize + lesser_offset {
+            result_column.display_offset = next_position as u16 - result_column.starting_index;
         }
-    };
+    } else if next_position > second_entry_index as usize {
+        // Moving down
+        if next_position <= result_column.starting_index as usize + greater_offset {
+            result_column.display_offset = next_position as u16 - result_column.starting_index;
+        } else if next_position > result_column.starting_index as usize + greater_offset {
+            result_column.display_offset = greater_offset as u16;
 
-    set_preview_data_with_thread(fm, tx, fm.get_second_entry_index());
-}
+            result_column.starting_index = next_position as u16 - result_column.display_offset;
+        } else {
+            panic!();
+        }
 
-fn remove_at_path_if_exists<P: AsRef<Path>>(path: P) -> io::Result<()> {
-    let metadata = match fs::metadata(&path) {
-        Ok(metadata) => metadata,
-        Err(err) => match err.kind() {
-            io::ErrorKind::NotFound => {
-                return Ok(());
-            }
-            _ => return Err(err),
-        },
-    };
+        // Stop us from going too far down the third column
+        if result_column.starting_index > current_entries_len as u16 - column_height {
+            result_column.starting_index = current_entries_len as u16 - column_height;
 
-    if metadata.is_dir() {
-        fs::remove_dir_all(&path)?;
+            result_column.display_offset = next_position as u16 - result_column.starting_index;
+        }
+    } else if next_position == second_entry_index as usize {
+        // Do nothing.
     } else {
-        fs::remove_file(&path)?;
+        panic!();
     }
 
-    Ok(())
+    assert_eq!(
+        next_position,
+        (result_column.starting_index + result_column.display_offset) as usize
+    );
+
+    Ok(result_column)
 }
 
-fn get_env_editor() -> String {
-    match std::env::var("VISUAL") {
-        Err(std::env::VarError::NotPresent) => match std::env::var("EDITOR") {
-            Err(std::env::VarError::NotPresent) => String::from(""),
-            Err(err) => panic!("{}", err),
-            Ok(editor) => editor,
-        },
-        Err(err) => panic!("{}", err),
-        Ok(visual) => visual,
-    }
+// The number of entries kept visible above and below the cursor when scrolling. Short columns
+// can't fit SCROLL_OFFSET on both sides, so they get as much as fits.
+fn scroll_offset(column_height: u16) -> u16 {
+    SCROLL_OFFSET.min(column_height.saturating_sub(1) / 2)
 }
 
-fn enter_shell_command_then_redraw(
-    fm: &mut FileManager,
-    screen: &mut Screen,
-    stdout_lock: &mut StdoutLock,
-    tx: &Sender<InputEvent>,
-    second_entry_index: u16,
-    shell_command: String,
-) -> io::Result<()> {
-    queue!(stdout_lock, terminal::LeaveAlternateScreen)?;
+// Moves a column's view so that it fits in a column of the given height, keeping the cursor on the
+// same entry (or the last entry, if there are fewer entries now). This is needed after the
+// terminal shrinks, and for positions remembered from before a resize.
+fn fit_column_pos(
+    current_entries_len: usize,
+    column_height: u16,
+    column: ColumnInfo,
+) -> crossterm::Result<ColumnInfo> {
+    let position = ((column.starting_index + column.display_offset) as usize)
+        .min(current_entries_len.saturating_sub(1));
 
-    Command::new("sh")
-        .arg("-c")
-        .arg(shell_command)
-        .status()
-        .expect("failed to execute editor command");
+    // NOTE(Chris): We start from the top of the old view (as far as it can still go), so that
+    // find_column_pos only scrolls if the cursor would end up too close to the bottom
+    let starting_index = (column.starting_index as usize)
+        .min(current_entries_len.saturating_sub(column_height as usize))
+        .min(position) as u16;
 
-    queue!(stdout_lock, terminal::EnterAlternateScreen, cursor::Hide)?;
+    find_column_pos(
+        current_entries_len,
+        column_height,
+        ColumnInfo {
+            starting_index,
+            display_offset: 0,
+        },
+        position,
+    )
+}
 
-    set_preview_data_with_thread(fm, tx, second_entry_index);
+fn update_drawing_info_from_resize(
+    drawing_info: &mut DrawingInfo,
+    ratios: [u16; 3],
+    preview_pane: PreviewPane,
+) -> crossterm::Result<()> {
+    let (width, height) = terminal::size()?;
 
-    // TODO(Chris): Write a function that achieves this without
-    // resizing anything
-    screen.resize_clear_draw(fm.drawing_info.width, fm.drawing_info.height)?;
+    *drawing_info = get_drawing_info(
+        os_abstract::get_win_pixels()?,
+        width,
+        height,
+        ratios,
+        preview_pane,
+    );
 
     Ok(())
 }
 
-fn toggle_selection(fm: &mut FileManager, second_entry_index: u16) {
-    if fm.dir_states.current_entries.is_empty() {
-        return;
-    }
+// The narrowest that the parent and preview columns can be before they're hidden
+const MIN_SIDE_COLUMN_WIDTH: u16 = 8;
 
-    let selected_entry = &fm.dir_states.current_entries[second_entry_index as usize];
+// Hides the preview column, and then the parent column, while either would be narrower than
+// MIN_SIDE_COLUMN_WIDTH, so that a narrow terminal goes to the current column instead. Since this
+// is worked out from the configured ratios on every resize, the columns come back once there's
+// room for them.
+fn fit_ratios(width: u16, ratios: [u16; 3]) -> [u16; 3] {
+    let mut ratios = ratios;
 
-    let entry_path = selected_entry.dir_entry.path();
+    // NOTE(Chris): The preview goes first, since the parent column is more useful for keeping
+    // track of where you are
+    for index in [2, 0] {
+        let total: u32 = ratios.iter().map(|&ratio| u32::from(ratio)).sum();
 
-    let was_selection_present = fm.selections.remove(&entry_path);
-    if !was_selection_present {
-        fm.selections.insert(entry_path);
-    }
-}
+        let is_cramped = [0, 2].iter().any(|&side_index| {
+            let column_width = u32::from(width) * u32::from(ratios[side_index]) / total.max(1);
 
-fn cursor_down(fm: &mut FileManager, second_entry_index: u16, second_bottom_index: u16) {
-    if !fm.dir_states.current_entries.is_empty()
-        && (second_entry_index as usize) < fm.dir_states.current_entries.len() - 1
-    {
-        abort_image_handles(&mut fm.image_handles);
+            ratios[side_index] > 0 && column_width < u32::from(MIN_SIDE_COLUMN_WIDTH)
+        });
 
-        if fm.second.display_offset >= (fm.drawing_info.column_height - SCROLL_OFFSET - 1)
-            && (second_bottom_index as usize) < fm.dir_states.current_entries.len()
-        {
-            fm.second.starting_index += 1;
-        } else if second_entry_index < second_bottom_index {
-            fm.second.display_offset += 1;
+        // NOTE(Chris): A column which is shown on its own (like when zoomed) is never hidden
+        if is_cramped && u32::from(ratios[index]) != total {
+            ratios[index] = 0;
         }
     }
+
+    ratios
 }
 
-fn get_help_view_rect(drawing_info: DrawingInfo) -> Rect {
-    Rect {
-        left_x: 0,
-        top_y: 1, // We already show the help title in the top line
-        width: drawing_info.width,
-        height: drawing_info.column_height,
+// Splits the screen into columns by their relative widths, leaving a gap of one cell after each
+// column. A column with a ratio of 0 is hidden, with a width of 0, as are side columns which
+// don't fit (see fit_ratios). A preview at the bottom takes up the full width of the bottom half
+// instead, with a gap of one row above it.
+fn get_drawing_info(
+    win_pixels: WindowPixels,
+    width: u16,
+    height: u16,
+    ratios: [u16; 3],
+    preview_pane: PreviewPane,
+) -> DrawingInfo {
+    // NOTE(Chris): A preview which is shown on its own (like when zoomed) is laid out the same way
+    // wherever it goes
+    let is_preview_below =
+        preview_pane == PreviewPane::Bottom && ratios[2] > 0 && ratios[0] + ratios[1] > 0;
+
+    let ratios = if is_preview_below {
+        fit_ratios(width, [ratios[0], ratios[1], 0])
+    } else {
+        fit_ratios(width, ratios)
+    };
+
+    // Represents the bottom-most y-cell of a column
+    let column_bot_y = height.saturating_sub(2);
+    // Represents the number of cells in a column vertically.
+    let column_height = height.saturating_sub(2);
+
+    let total: u32 = ratios.iter().map(|&ratio| u32::from(ratio)).sum();
+
+    // NOTE(Chris): The boundaries are computed from the running totals, so that rounding never
+    // leaves a gap at the right edge
+    let boundary = |ratio_sum: u32| (u32::from(width) * ratio_sum / total.max(1)) as u16;
+    let first_left_x = 0;
+    let second_left_x = boundary(u32::from(ratios[0]));
+    let third_left_x = boundary(u32::from(ratios[0]) + u32::from(ratios[1]));
+
+    let right_x = |left_x: u16, next_left_x: u16| next_left_x.saturating_sub(2).max(left_x);
+
+    let mut drawing_info = DrawingInfo {
+        win_pixels,
+        width,
+        height,
+        column_bot_y,
+        column_height,
+        first_left_x,
+        first_right_x: right_x(first_left_x, second_left_x),
+        second_left_x,
+        second_right_x: right_x(second_left_x, third_left_x),
+        third_left_x,
+        third_right_x: right_x(third_left_x, width),
+        third_top_y: 1,
+        third_height: column_height,
+    };
+
+    if is_preview_below {
+        let listing_height = (column_height / 2).max(1);
+
+        drawing_info.column_bot_y = listing_height;
+        drawing_info.column_height = listing_height;
+        drawing_info.third_left_x = 0;
+        drawing_info.third_right_x = right_x(0, width);
+        drawing_info.third_top_y = listing_height + 2;
+        drawing_info.third_height = column_height.saturating_sub(listing_height + 1);
     }
+
+    drawing_info
+}
+
+// Switches to the layout given by fm.column_ratios and fm.zoom
+fn apply_layout(fm: &mut FileManager, screen: &mut Screen) {
+    // NOTE(Chris): Like when resizing, this has to come before the preview area moves
+    set_area_dead(fm, screen, false);
+
+    let drawing_info = fm.drawing_info;
+    fm.drawing_info = get_drawing_info(
+        drawing_info.win_pixels,
+        drawing_info.width,
+        drawing_info.height,
+        fm.layout_ratios(),
+        fm.config.preview_position,
+    );
 }
 
-fn set_area_dead(fm: &FileManager, screen_lock: &mut Screen, is_dead: bool) {
-    for x in fm.drawing_info.third_left_x..=fm.drawing_info.width - 1 {
-        for y in 1..=fm.drawing_info.column_bot_y {
-            screen_lock.set_dead(x, y, is_dead);
-        }
-    }
+// Grabs the frame halfway through a video, by asking ffprobe for its length and ffmpeg for the
+// frame
+fn video_middle_frame(video_tools: &VideoTools, path: &Path) -> io::Result<image::DynamicImage> {
+    let ffprobe_output = Command::new(&video_tools.ffprobe)
+        .args([
+            "-loglevel",
+            "error",
+            "-of",
+            "csv=p=0",
+            "-show_entries",
+            "format=duration",
+        ])
+        .arg(path)
+        .output()?;
+
+    // Truncate the decimal portion
+    let video_duration = String::from_utf8_lossy(&ffprobe_output.stdout)
+        .trim()
+        .parse::<f64>()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+        as i64;
+
+    let ffmpeg_output = Command::new(&video_tools.ffmpeg)
+        .arg("-ss")
+        .arg(format!("{}", video_duration / 2))
+        .arg("-i")
+        .arg(path)
+        .args([
+            "-frames:v",
+            "1",
+            "-c:v",
+            "ppm",
+            "-f",
+            "image2pipe",
+            "pipe:1",
+        ])
+        .output()?;
+
+    let to_io_error = |err| io::Error::new(io::ErrorKind::InvalidData, err);
+
+    let decoder = image::pnm::PnmDecoder::new(&ffmpeg_output.stdout[..]).map_err(to_io_error)?;
+    image::DynamicImage::from_decoder(decoder).map_err(to_io_error)
 }
 
-fn search_jump(fm: &mut FileManager) -> io::Result<()> {
-    if fm.match_positions.len() <= 0 {
-        return Ok(());
-    }
+// The programs a video preview needs, both of which come with ffmpeg
+struct VideoTools {
+    ffprobe: PathBuf,
+    ffmpeg: PathBuf,
+}
 
-    let second_entry_index = fm.second.starting_index + fm.second.display_offset;
+impl VideoTools {
+    fn from_tools(tools: &Tools) -> Option<Self> {
+        Some(VideoTools {
+            ffprobe: tools.get("ffprobe")?.to_path_buf(),
+            ffmpeg: tools.get("ffmpeg")?.to_path_buf(),
+        })
+    }
+}
 
-    let next_position = if fm.should_search_forwards {
-        let result = fm
-            .match_positions
-            .iter()
-            .find(|pos| **pos > second_entry_index as usize);
+// Decodes a png or jpg (turned the right way up), at a size which is as close as it can be to
+// fitting in max_width and max_height pixels without being smaller
+fn decode_image(
+    path: &Path,
+    ext: &str,
+    max_width: u32,
+    max_height: u32,
+) -> io::Result<image::DynamicImage> {
+    let is_jpg = ext == "jpg" || ext == "jpeg";
+
+    let jpg_bytes = if is_jpg {
+        Some(std::fs::read(path)?)
+    } else {
+        None
+    };
 
-        match result {
-            None => fm.match_positions[0],
-            Some(next_position) => *next_position,
-        }
+    // NOTE(Chris): sxiv only rotates jpgs somewhat-correctly, but Eye of
+    // Gnome (eog) rotates them correctly
+    let orientation = jpg_bytes
+        .as_deref()
+        .and_then(tiff::find_orientation)
+        .unwrap_or(1);
+
+    // NOTE(Chris): The image is decoded before it's rotated, so a sideways image has to cover a
+    // sideways space
+    let (max_width, max_height) = if orientation >= 5 {
+        (max_height, max_width)
     } else {
-        let result = fm
-            .match_positions
-            .iter()
-            .rev()
-            .find(|pos| **pos < second_entry_index as usize);
+        (max_width, max_height)
+    };
 
-        match result {
-            None => *fm.match_positions.last().unwrap(),
-            Some(next_position) => *next_position,
-        }
+    let mut img = match jpg_bytes {
+        #[cfg(feature = "turbojpeg")]
+        Some(jpg_bytes) => jpeg::decode(&jpg_bytes, max_width, max_height)?,
+        #[cfg(not(feature = "turbojpeg"))]
+        Some(jpg_bytes) => scaled_decode::decode_jpg(&jpg_bytes, max_width, max_height)?,
+        None if ext == "png" => scaled_decode::decode_png(path, max_width, max_height)?,
+        None => image::io::Reader::open(path)?
+            .decode()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
     };
 
-    fm.second = find_column_pos(
-        fm.dir_states.current_entries.len(),
-        fm.drawing_info.column_height,
-        fm.second,
-        next_position,
-    )?;
+    // Rotate jpgs according to their orientation value
+    match orientation {
+        2 => img = img.fliph(),
+        3 => img = img.rotate180(),
+        4 => img = img.flipv(),
+        5 => img = img.rotate90().fliph(),
+        6 => img = img.rotate90(),
+        7 => img = img.rotate270().fliph(),
+        8 => img = img.rotate270(),
+        _ => (),
+    }
 
-    Ok(())
+    Ok(img)
 }
 
-fn jump_by_file_id(fm: &mut FileManager, file_id: u64) -> io::Result<()> {
-    let current_entry_info_index = fm
-        .dir_states
-        .current_entries
-        .iter()
-        .position(|entry_info| get_file_id(&entry_info.metadata) == file_id);
-
-    if let Some(current_entry_info_index) = current_entry_info_index {
-        fm.match_positions = vec![current_entry_info_index];
+fn preview_image_or_video(
+    win_pixels: WindowPixels,
+    third_file: PathBuf,
+    ext: String,
+    video_tools: Option<VideoTools>,
+    width: u16,
+    height: u16,
+    preview_rect: Rect,
+) -> io::Result<ImageBufferRgba> {
+    let win_px_width = win_pixels.width;
+    let win_px_height = win_pixels.height;
 
-        search_jump(fm)?;
-    } else {
-        return Err(io::Error::new(
-            io::ErrorKind::NotFound,
-            "Unable to jump to file by id",
-        ));
+    let third_column_width = preview_rect.width as u32;
+    let third_column_height = preview_rect.height as u32;
+
+    let mut img = match video_tools {
+        Some(video_tools) => video_middle_frame(&video_tools, &third_file)?,
+        None => decode_image(
+            &third_file,
+            &ext,
+            third_column_width * (win_px_width as u32) / (width as u32),
+            third_column_height * (win_px_height as u32) / (height as u32),
+        )?,
     };
 
-    Ok(())
-}
+    let (img_width, img_height) = img.dimensions();
 
-fn set_preview_data_with_thread(
-    fm: &mut FileManager,
-    tx: &Sender<InputEvent>,
-    second_entry_index: u16,
-) {
-    if fm.dir_states.current_entries.is_empty() {
-        fm.preview_data = PreviewData::Blank;
-        return;
-    }
+    let mut img_cells_width = img_width * (width as u32) / (win_px_width as u32);
+    let mut img_cells_height = img_height * (height as u32) / (win_px_height as u32);
 
-    let second_entry = &fm.dir_states.current_entries[second_entry_index as usize];
+    // eprintln!("   image: {:?}", &third_file);
 
-    fm.preview_data = PreviewData::Loading;
+    // eprintln!(
+    //     "beginning - img_cells_width: {:3}, img_cells_height: {:3}",
+    //     img_cells_width, img_cells_height
+    // );
 
-    let third_file_path = second_entry.dir_entry.path();
+    let orig_img_cells_width = img_cells_width;
+    let orig_img_cells_height = img_cells_height;
 
-    match second_entry.file_type {
-        // TODO(Chris): Optimize entry gathering to avoid spawning a thread if there's a low (<
-        // 200) number of entries, without reading in entries twice
-        RecordedFileType::Directory | RecordedFileType::DirectorySymlink => {
-            let (can_draw_clone, preview_tx) = clone_thread_helpers(fm, tx);
+    // eprintln!(
+    //     "               column_width: {:3},    column_height: {:3}",
+    //     third_column_width, third_column_height
+    // );
 
-            std::thread::spawn(move || match get_sorted_entries(&third_file_path) {
-                Ok(preview_entry_info) => {
-                    let can_display = can_draw_clone.load(std::sync::atomic::Ordering::Acquire);
+    // Scale the image down to fit the width, if necessary
+    if img_cells_width > third_column_width {
+        img_cells_width = third_column_width;
+    }
 
-                    if can_display {
-                        preview_tx
-                            .send(InputEvent::PreviewLoaded(PreviewData::Directory {
-                                entries_info: preview_entry_info,
-                            }))
-                            .expect("Unable to send on channel");
-                    }
-                }
-                Err(err) => match err.kind() {
-                    io::ErrorKind::PermissionDenied => {
-                        let can_display = can_draw_clone.load(std::sync::atomic::Ordering::Acquire);
+    // Scale the image even further down to fit the height, if
+    // necessary
+    if img_cells_height > third_column_height {
+        img_cells_height = third_column_height;
+    }
 
-                        if can_display {
-                            preview_tx
-                                .send(InputEvent::PreviewLoaded(PreviewData::Message {
-                                    message: "permission denied",
-                                }))
-                                .expect("Unable to send on channel");
-                        }
-                    }
-                    _ => panic!("Error opening {:?}: {:?}", &third_file_path, &err),
-                },
-            });
-        }
-        RecordedFileType::File | RecordedFileType::FileSymlink => {
-            if let Some(os_str_ext) = third_file_path.extension() {
-                if let Some(ext) = os_str_ext.to_str() {
-                    let ext = ext.to_lowercase();
-                    let ext = ext.as_str();
-
-                    match ext {
-                        "png" | "jpg" | "jpeg" | "mp4" | "webm" | "mkv" => {
-                            let (can_draw_clone, preview_tx) = clone_thread_helpers(fm, tx);
-
-                            let ext_string = ext.to_string();
-                            let drawing_info = fm.drawing_info;
-
-                            std::thread::spawn(move || {
-                                let image_buffer = match preview_image_or_video(
-                                    drawing_info.win_pixels,
-                                    third_file_path,
-                                    ext_string,
-                                    drawing_info.width,
-                                    drawing_info.height,
-                                    drawing_info.third_left_x,
-                                ) {
-                                    Ok(image_buffer) => image_buffer,
-                                    Err(_) => return,
-                                };
+    // eprintln!(
+    //     "   ending - img_cells_width: {:3}, img_cells_height: {:3}",
+    //     img_cells_width, img_cells_height
+    // );
 
-                                let can_display_image =
-                                    can_draw_clone.load(std::sync::atomic::Ordering::Acquire);
+    if orig_img_cells_width != img_cells_width || orig_img_cells_height != img_cells_height {
+        let display_width_px = img_cells_width * (win_px_width as u32) / (width as u32);
+        let display_height_px = img_cells_height * (win_px_height as u32) / (height as u32);
 
-                                if can_display_image {
-                                    preview_tx
-                                        .send(InputEvent::PreviewLoaded(PreviewData::ImageBuffer {
-                                            buffer: image_buffer,
-                                        }))
-                                        .expect("Unable to send on channel");
-                                }
-                            });
-                        }
-                        _ => match fm.available_execs.get("highlight") {
-                            None => {
-                                fm.preview_data = PreviewData::UncoloredFile {
-                                    path: third_file_path,
-                                };
-                            }
-                            Some(highlight) => {
-                                let highlight = highlight.clone();
-
-                                // TODO(Chris): Actually use can_draw_clone here
-                                let (_can_draw_clone, preview_tx) = clone_thread_helpers(fm, tx);
-
-                                std::thread::spawn(move || {
-                                    // TODO(Chris): Actually show that something went wrong
-                                    let output = Command::new(highlight)
-                                        .arg("-O")
-                                        .arg("ansi")
-                                        .arg("--max-size=500K")
-                                        .arg(third_file_path)
-                                        .output()
-                                        .unwrap();
-
-                                    preview_tx
-                                        .send(InputEvent::PreviewLoaded(PreviewData::RawBytes {
-                                            bytes: output.stdout,
-                                        }))
-                                        .expect("Unable to send on channel");
-                                });
-                            }
-                        },
-                    }
-                } else {
-                    fm.preview_data = PreviewData::UncoloredFile {
-                        path: third_file_path,
-                    };
-                }
-            } else {
-                fm.preview_data = PreviewData::UncoloredFile {
-                    path: third_file_path,
-                };
-            }
-        }
-        RecordedFileType::InvalidSymlink | RecordedFileType::Other => {
-            fm.preview_data = PreviewData::Blank;
-        }
-        RecordedFileType::Unknown => {
-            fm.preview_data = PreviewData::Message {
-                message: "unknown file type",
-            };
+        if orig_img_cells_width > third_column_width * 3
+            || orig_img_cells_height > third_column_height * 3
+        {
+            img = img.thumbnail(display_width_px, display_height_px);
+        } else {
+            img = img.resize(
+                display_width_px,
+                display_height_px,
+                image::imageops::FilterType::Triangle,
+            );
         }
     }
+
+    let rgba = img.to_rgba8();
+
+    Ok(rgba)
 }
 
-fn clone_thread_helpers(
-    fm: &mut FileManager,
-    tx: &Sender<InputEvent>,
-) -> (Arc<AtomicBool>, Sender<InputEvent>) {
-    let can_draw = Arc::new(AtomicBool::new(true));
-    let can_draw_clone = Arc::clone(&can_draw);
-    let preview_tx = tx.clone();
+// Returns the number of cells (columns, rows) that an image will cover in the third column
+fn image_cell_size(drawing_info: &DrawingInfo, buffer: &ImageBufferRgba) -> (u16, u16) {
+    let win_px_width = (drawing_info.win_pixels.width as u32).max(1);
+    let win_px_height = (drawing_info.win_pixels.height as u32).max(1);
 
-    fm.image_handles.push(DrawHandle { can_draw });
+    // Round up, so that a partially-covered cell still gets a placeholder
+    let cols = (buffer.width() * drawing_info.width as u32).div_ceil(win_px_width);
+    let rows = (buffer.height() * drawing_info.height as u32).div_ceil(win_px_height);
 
-    (can_draw_clone, preview_tx)
+    let max_cols = drawing_info.third_right_x - drawing_info.third_left_x;
+
+    (
+        (cols as u16).min(max_cols),
+        (rows as u16).min(drawing_info.third_height),
+    )
 }
 
-fn draw_column(
-    screen: &mut Screen,
-    rect: Rect,
-    file_top_ind: u16,
-    file_curr_ind: u16,
-    items: &[DirEntryInfo],
-    selections: &SelectionsMap,
-) {
-    let inner_left_x = rect.left_x + 1;
+fn draw_bottom_info_line(screen: &mut Screen, fm: &mut FileManager) {
+    fm.relative_time_deadline = None;
 
-    if items.is_empty() {
+    if let Some(status_message) = &fm.status_message {
         draw_str(
             screen,
-            inner_left_x + 1,
-            rect.top_y,
-            "empty",
-            Style::new_attr(rolf_grid::Attribute::Reverse),
+            0,
+            fm.drawing_info.height - 1,
+            status_message,
+            Style::new_color(rolf_grid::Color::Red, rolf_grid::Color::Background),
         );
+
+        return;
     }
 
-    // NOTE(Chris): We declare this outside of the loop to avoid re-allocating.
-    let mut file_name = String::new();
+    // NOTE(Chris): Like lf, an empty directory only shows its position, so that nothing is left
+    // over from the last directory
+    if fm.dir_states.current_entries.is_empty() {
+        draw_ruler(screen, fm, 0);
 
-    // NOTE(Chris): 1 is the starting row for columns
-    for y in rect.top_y..rect.bot_y() {
-        let ind = file_top_ind + y - 1;
+        return;
+    }
 
-        if (ind as usize) >= items.len() {
-            break;
-        }
+    let updated_second_entry_index = fm.second.starting_index + fm.second.display_offset;
 
-        let entry_info = &items[ind as usize];
+    let extra_perms = fm.dir_states.current_entries[updated_second_entry_index as usize]
+        .metadata
+        .extra_perms();
 
-        // Draw the selection marking
+    // NOTE(Chris): Like ls -l, the mode ends with a + for an ACL, a . for an SELinux context, or
+    // an @ for other extended attributes
+    let mut mode_str = extra_perms.mode.clone();
+    mode_str.extend(os_abstract::mode_suffix(
+        &fm.dir_states.current_entries[updated_second_entry_index as usize]
+            .dir_entry
+            .path(),
+    ));
 
-        if selections.contains(&entry_info.dir_entry.path()) {
-            screen.set_cell_style(
-                rect.left_x,
-                y,
-                ' ',
-                rolf_grid::Style::new_color(
-                    rolf_grid::Color::Foreground,
-                    rolf_grid::Color::Magenta,
-                ),
-            );
-        } else {
-            screen.set_cell_style(rect.left_x, y, ' ', rolf_grid::Style::default());
+    let mut draw_style = Style::new_attr(rolf_grid::Attribute::Bold);
+
+    let mut info_line_builder = LineBuilder::new();
+
+    {
+        // The Windows mode string is only 6 characters long, so this avoids the Windows mode
+        // string.
+        if mode_str.len() > 6 {
+            // TODO(Chris): Reimplement this on Windows
+            // queue!(colored_mode, style::SetAttribute(Attribute::Bold)).unwrap();
         }
+        for (index, byte) in mode_str.bytes().enumerate() {
+            if index > 3 {
+                draw_style.attribute = rolf_grid::Attribute::None;
+            }
 
-        // Draw the file name
+            match &[byte] {
+                b"d" => {
+                    draw_style.fg = rolf_grid::Color::Blue;
+                    info_line_builder.push(byte as char, draw_style);
+                }
+                b"r" => {
+                    draw_style.fg = rolf_grid::Color::Yellow;
+                    info_line_builder.push(byte as char, draw_style);
+                }
+                b"w" => {
+                    draw_style.fg = rolf_grid::Color::Red;
+                    info_line_builder.push(byte as char, draw_style);
+                }
+                b"x" => {
+                    draw_style.fg = rolf_grid::Color::Green;
+                    info_line_builder.push(byte as char, draw_style);
+                }
+                b"-" => {
+                    draw_style.fg = rolf_grid::Color::Blue;
+                    info_line_builder.push(byte as char, draw_style);
+                }
+                b"l" => {
+                    draw_style.attribute = rolf_grid::Attribute::None;
+                    draw_style.fg = rolf_grid::Color::Cyan;
 
-        let mut draw_style = if ind == file_curr_ind {
-            Style::new_attr(rolf_grid::Attribute::Reverse)
-        } else {
-            Style::new_attr(rolf_grid::Attribute::None)
-        };
+                    info_line_builder.push(byte as char, draw_style);
 
-        match entry_info.file_type {
-            RecordedFileType::Directory => {
-                draw_style.fg = rolf_grid::Color::Blue;
-                draw_style.attribute |= rolf_grid::Attribute::Bold;
-            }
-            RecordedFileType::FileSymlink | RecordedFileType::DirectorySymlink => {
-                draw_style.fg = rolf_grid::Color::Cyan;
-                draw_style.attribute |= rolf_grid::Attribute::Bold;
-            }
-            RecordedFileType::InvalidSymlink => {
-                draw_style.fg = rolf_grid::Color::Red;
-                draw_style.attribute |= rolf_grid::Attribute::Bold;
+                    draw_style.attribute = rolf_grid::Attribute::Bold;
+                    draw_style.fg = rolf_grid::Color::Foreground;
+                }
+                b"c" | b"b" => {
+                    draw_style.fg = rolf_grid::Color::Yellow;
+                    info_line_builder.push(byte as char, draw_style);
+                }
+                _ => {
+                    draw_style.attribute = rolf_grid::Attribute::None;
+                    info_line_builder.push(byte as char, draw_style);
+                }
             }
-            _ => (),
         }
+    };
 
-        let file_name_os = entry_info.dir_entry.file_name();
-
-        // let file_name = file_name_os.to_str().unwrap();
-
-        let full_name = file_name_os.to_str().unwrap();
-        let display_width: usize = (rect.right_x() - inner_left_x).into();
-
-        file_name.clear();
-        if full_name.len() > display_width {
-            file_name.push_str(&full_name[0..display_width - 1]);
-            file_name.push('~');
+    // NOTE(Chris): Like lf, we show the user and group in white when they don't match the
+    // current user
+    let owner_ids = fm.dir_states.current_entries[updated_second_entry_index as usize]
+        .metadata
+        .owner_ids();
+    let (is_own_user, is_own_group) = match (&fm.current_user, owner_ids) {
+        (Some(current_user), Some((uid, gid))) => {
+            (current_user.is_user(uid), current_user.is_in_group(gid))
+        }
+        _ => (true, true),
+    };
+    let owner_color = |is_own| {
+        if is_own {
+            rolf_grid::Color::Yellow
         } else {
-            file_name.push_str(full_name);
+            rolf_grid::Color::White
         }
+    };
 
-        screen.set_cell_style(inner_left_x, y, ' ', draw_style);
-        let name_pos_x = inner_left_x + 1;
-        draw_str(screen, name_pos_x, y, &file_name, draw_style);
+    if let Some(hard_link_count) = extra_perms.hard_link_count {
+        info_line_builder
+            .use_fg_color(rolf_grid::Color::Red)
+            .use_attribute(rolf_grid::Attribute::Bold)
+            .push_str(&format!(" {:2}", hard_link_count));
+    }
 
-        let file_name_len: u16 = file_name
-            .len()
-            .try_into()
-            .expect("A file name length did not fit within a u16");
+    if let Some(user_name) = extra_perms.user_name {
+        info_line_builder
+            .use_fg_color(owner_color(is_own_user))
+            .use_attribute(rolf_grid::Attribute::Bold)
+            .push_str(&format!(" {:2}", user_name));
+    }
 
-        for x in name_pos_x + file_name_len..=rect.right_x() {
-            screen.set_cell_style(x, y, ' ', draw_style);
-        }
+    if let Some(group_name) = extra_perms.group_name {
+        info_line_builder
+            .use_fg_color(owner_color(is_own_group))
+            .use_attribute(rolf_grid::Attribute::Bold)
+            .push_str(&format!(" {}", group_name));
     }
-}
 
-fn draw_first_column(screen: &mut Screen, fm: &mut FileManager) {
-    let first_column_rect = Rect {
-        left_x: fm.drawing_info.first_left_x,
-        top_y: 1,
-        width: fm.drawing_info.first_right_x - fm.drawing_info.first_left_x,
-        height: fm.drawing_info.column_height,
-    };
+    if let Some(size) = extra_perms.size {
+        info_line_builder
+            .use_fg_color(rolf_grid::Color::Green)
+            .use_attribute(rolf_grid::Attribute::Bold)
+            .push_str(&format!(" {:>4}", info_line_size(size, &fm.config)));
+    }
 
-    if let Some(prev_dir) = &fm.dir_states.prev_dir {
-        let result_column_info = find_correct_location(
-            &fm.left_paths,
-            fm.drawing_info.column_height,
-            prev_dir,
-            &fm.dir_states.prev_entries,
-            &fm.dir_states.current_dir,
-        );
+    let time = match fm.config.info_date {
+        InfoDate::Modified => extra_perms.modified,
+        InfoDate::Accessed => extra_perms.accessed,
+        InfoDate::Created => extra_perms.created,
+    };
 
-        let starting_index = result_column_info.starting_index;
-        let entry_index = result_column_info.starting_index + result_column_info.display_offset;
+    if let Some(time) = time {
+        let now = SystemTime::now();
 
-        draw_column(
-            screen,
-            first_column_rect,
-            starting_index,
-            entry_index,
-            &fm.dir_states.prev_entries,
-            &fm.selections,
-        );
-    }
-}
+        info_line_builder
+            .use_fg_color(rolf_grid::Color::Blue)
+            .use_attribute(rolf_grid::Attribute::None)
+            .push_str(" ")
+            .push_str(&time_format::format_time(time, &fm.config, now));
 
-fn insert_executable<'a>(
-    available_execs: &mut HashMap<&'a str, std::path::PathBuf>,
-    executable_name: &'a str,
-) {
-    match which(executable_name) {
-        Ok(path) => {
-            available_execs.insert(executable_name, path);
-        }
-        Err(which::Error::CannotFindBinaryPath) => (), // Do nothing when binary not found
-        Err(err) => {
-            panic!("{}", err);
+        if fm.config.time_format == TimeFormat::Relative {
+            fm.relative_time_deadline =
+                Some(Instant::now() + time_format::until_relative_time_changes(time, now));
         }
     }
-}
-
-fn search_in_direction(
-    fm: &mut FileManager,
-    search_term: &str,
-    should_search_forwards: bool,
-) -> io::Result<()> {
-    fm.match_positions = find_match_positions(&fm.dir_states.current_entries, search_term);
-
-    fm.should_search_forwards = should_search_forwards;
 
-    search_jump(fm)?;
+    screen.build_line(0, fm.drawing_info.height - 1, &info_line_builder);
 
-    Ok(())
+    draw_ruler(screen, fm, updated_second_entry_index as usize + 1);
 }
 
-fn find_match_positions(current_entries: &[DirEntryInfo], search_term: &str) -> Vec<usize> {
-    current_entries
-        .iter()
-        .enumerate()
-        .filter_map(|(index, entry_info)| {
-            if entry_info
-                .dir_entry
-                .file_name()
-                .to_str()
-                .unwrap()
-                .to_lowercase()
-                .contains(&search_term.to_lowercase())
-            {
-                Some(index)
-            } else {
-                None
-            }
-        })
-        .collect()
+// The current file's size on the info line, which can be exact (like "3,148,408B") with the
+// exact-size option
+fn info_line_size(size: u64, config: &Config) -> String {
+    if config.exact_size {
+        format!("{}B", group_digits(size))
+    } else {
+        format_size(size, config.size_units)
+    }
 }
 
-fn set_current_dir<P: AsRef<Path>>(
-    target_new_current_dir: P,
-    dir_states: &mut DirStates,
-    match_positions: &mut Vec<usize>,
-) -> crossterm::Result<()> {
-    let mut new_current_dir: &Path = target_new_current_dir.as_ref();
-    let mut metadata = fs::metadata(&target_new_current_dir);
+// Draws the cursor's position (counting from 1) at the right of the bottom line, after the filter
+// if there is one and the pending keys if any have been typed
+fn draw_ruler(screen: &mut Screen, fm: &FileManager, position: usize) {
+    let position = format!("{}/{}", position, fm.dir_states.current_entries.len());
 
-    while metadata.is_err() && new_current_dir.parent().is_some() {
-        new_current_dir = new_current_dir.parent().expect("No parent of dir");
-        metadata = fs::metadata(&new_current_dir);
-    }
+    let mut ruler = match &fm.dir_states.filter {
+        Some(filter) => format!("[{}] {}", filter, position),
+        None => position,
+    };
 
-    if metadata.is_err() && new_current_dir.parent().is_none() {
-        panic!("Cannot find directory to make the current one.");
+    if let Some(paste_progress) = &fm.paste_progress {
+        ruler = format!("paste {}  {}", paste_progress, ruler);
     }
 
-    dir_states.set_current_dir(new_current_dir)?;
-    match_positions.clear();
-
-    Ok(())
-}
-
-fn enter_entry(fm: &mut FileManager, second_entry_index: u16) -> crossterm::Result<()> {
-    // NOTE(Chris): We only need to abort asynchronous "image" drawing if we're opening a
-    // directory¸ since we're now drawing directory previews asychronously with the same system as
-    // the image drawing.
-
-    if fm.dir_states.current_entries.len() <= 0 {
-        return Ok(());
+    if !fm.pending_keys.is_empty() {
+        ruler = format!("{}  {}", fm.pending_keys, ruler);
     }
 
-    save_location(fm, second_entry_index);
-
-    let selected_entry_path = &fm.dir_states.current_entries[second_entry_index as usize]
-        .dir_entry
-        .path();
+    draw_str(
+        screen,
+        fm.drawing_info
+            .width
+            .saturating_sub(ruler.chars().count() as u16),
+        fm.drawing_info.height - 1,
+        &ruler,
+        rolf_grid::Style::default(),
+    );
+}
 
-    // TODO(Chris): Show this error without crashing the program
-    let selected_target_file_type = match selected_entry_path.metadata() {
-        Ok(metadata) => metadata.file_type(),
-        Err(_) => return Ok(()),
+// Draws an image from the gallery with its top-left corner at (x, y), or a message if it hasn't been
+// loaded (Err) or couldn't be loaded (None)
+fn draw_gallery_image(
+    screen_lock: &mut Screen,
+    fm: &FileManager,
+    x: u16,
+    y: u16,
+    image: Result<Option<&ImageBufferRgba>, &str>,
+) -> io::Result<()> {
+    let message = match image {
+        Ok(Some(image)) => {
+            draw_gallery_pixels(screen_lock, fm, x, y, image)?;
+            return Ok(());
+        }
+        Ok(None) => "unable to load",
+        Err(message) => message,
     };
 
-    if selected_target_file_type.is_dir() {
-        abort_image_handles(&mut fm.image_handles);
-
-        let selected_dir_path = selected_entry_path;
+    draw_str(
+        screen_lock,
+        x,
+        y,
+        message,
+        Style::new_attr(rolf_grid::Attribute::Reverse),
+    );
 
-        match set_current_dir(
-            selected_dir_path,
-            &mut fm.dir_states,
-            &mut fm.match_positions,
-        ) {
-            Ok(_) => (),
-            Err(err) => match err.kind() {
-                io::ErrorKind::PermissionDenied => {
-                    // TODO(Chris): Implement an error message for permission being denied
-                    return Ok(());
-                }
-                _ => panic!("{}", err),
-            },
-        }
+    Ok(())
+}
 
-        match fm.left_paths.get(selected_dir_path) {
-            Some(dir_location) => {
-                let curr_entry_index = fm
-                    .dir_states
-                    .current_entries
-                    .iter()
-                    .position(|entry| entry.dir_entry.path() == *dir_location.dir_path);
-
-                match curr_entry_index {
-                    Some(curr_entry_index) => {
-                        let orig_entry_index =
-                            (dir_location.starting_index + dir_location.display_offset) as usize;
-                        if curr_entry_index == orig_entry_index {
-                            fm.second.starting_index = dir_location.starting_index;
-                            fm.second.display_offset = dir_location.display_offset;
-                        } else {
-                            fm.second.starting_index = (curr_entry_index / 2) as u16;
-                            fm.second.display_offset =
-                                (curr_entry_index as u16) - fm.second.starting_index;
-                        }
-                    }
-                    None => {
-                        fm.second.starting_index = 0;
-                        fm.second.display_offset = 0;
-                    }
-                }
-            }
-            None => {
-                fm.second.starting_index = 0;
-                fm.second.display_offset = 0;
-            }
-        };
-    } else if selected_target_file_type.is_file() {
-        if cfg!(windows) {
-            open::that(selected_entry_path)?;
-        } else {
-            // Should we display some sort of error message according to the exit status
-            // here?
-            open::that_in_background(selected_entry_path);
+fn draw_gallery_pixels(
+    screen_lock: &mut Screen,
+    fm: &FileManager,
+    x: u16,
+    y: u16,
+    image: &ImageBufferRgba,
+) -> io::Result<()> {
+    if fm.config.image_protocol == ImageProtocol::Kitty {
+        let path = store_in_tmp_file(image.as_raw())?;
+
+        let mut raw = vec![];
+        write!(
+            raw,
+            "\x1b_Gf=32,s={},v={},a=T,t=t;{}\x1b\\",
+            image.width(),
+            image.height(),
+            base64::encode(path.to_str().unwrap())
+        )?;
+
+        screen_lock.write_raw(x, y, raw);
+    } else {
+        for (row, line_builder) in gallery::half_blocks(image).iter().enumerate() {
+            screen_lock.build_line(x, y + row as u16, line_builder);
         }
     }
 
     Ok(())
 }
 
-// Sets the values underlying column_starting_index and column_display_offset to properly set a
-// cursor at the next_position index in a vector of entries.
-fn find_column_pos(
-    current_entries_len: usize,
-    column_height: u16,
-    column: ColumnInfo,
-    next_position: usize,
-) -> crossterm::Result<ColumnInfo> {
-    assert!(next_position <= current_entries_len);
-
-    let second_entry_index = column.starting_index + column.display_offset;
-
-    // let lower_offset = (column.height * 2 / 3) as usize;
-    // let upper_offset = (column.height / 3) as usize;
-    let lesser_offset = SCROLL_OFFSET as usize;
-    let greater_offset = (column_height - SCROLL_OFFSET - 1) as usize;
-
-    let mut result_column = column;
-
-    if column_height as usize > current_entries_len {
-        assert_eq!(column.starting_index, 0);
+fn store_in_tmp_file(buf: &[u8]) -> std::result::Result<std::path::PathBuf, io::Error> {
+    let (mut tmpfile, path) = tempfile::Builder::new()
+        .prefix(".tmp.rolf")
+        .rand_bytes(1)
+        .tempfile()?
+        // Since the file is persisted, the user is responsible for deleting it afterwards. However,
+        // Kitty does this automatically after printing from a temp file.
+        .keep()?;
 
-        result_column.display_offset = next_position as u16;
-    } else if next_position < second_entry_index as usize {
-        // Moving up
-        if next_position <= lesser_offset {
-            result_column.starting_index = 0;
+    tmpfile.write_all(buf)?;
+    tmpfile.flush()?;
+    Ok(path)
+}
 
-            result_column.display_offset = next_position as u16;
-        } else if next_position <= result_column.starting_index as usize + lesser_offset {
-            result_column.display_offset = lesser_offset as u16;
+// Formats a directory for the top line, which draws a separator after it
+fn format_current_dir(current_dir: &Path, home_path: &Path) -> String {
+    // NOTE(Chris): This creates a new String, and it'd be nice to avoid making a heap
+    // allocation here, but it's probably not worth trying to figure out how to use only a str
 
-            result_column.starting_index = next_position as u16 - result_column.display_offset;
-        } else if next_position > result_column.starting_index as usize + lesser_offset {
-            result_column.display_offset = next_position as u16 - result_column.starting_index;
-        }
-    } else if next_position > second_entry_index as usize {
-        // Moving down
-        if next_position <= result_column.starting_index as usize + greater_offset {
-            result_column.display_offset = next_position as u16 - result_column.starting_index;
-        } else if next_position > result_column.starting_index as usize + greater_offset {
-            result_column.display_offset = greater_offset as u16;
+    if current_dir == home_path {
+        String::from("~")
+    } else if current_dir.starts_with(home_path) {
+        // "~"
+        format!(
+            "~{}{}",
+            path::MAIN_SEPARATOR,
+            current_dir
+                .strip_prefix(home_path)
+                .unwrap()
+                .to_str()
+                .unwrap()
+        )
+    } else if current_dir.parent().is_none() {
+        // NOTE(Chris): A root already ends with a separator (like "/" or "C:\"), so we leave it
+        // off to avoid drawing two
+        current_dir
+            .to_str()
+            .unwrap()
+            .trim_end_matches(path::MAIN_SEPARATOR)
+            .to_string()
+    } else {
+        current_dir.to_str().unwrap().to_string()
+    }
+}
 
-            result_column.starting_index = next_position as u16 - result_column.display_offset;
-        } else {
-            panic!();
-        }
+// For the list consisting of the entries in parent_entries, find the correct display offset and
+// starting index that will put the cursor on dir
+fn find_correct_location(
+    left_paths: &HashMap<std::path::PathBuf, DirLocation>,
+    column_height: u16,
+    parent_dir: &Path,
+    parent_entries: &[DirEntryInfo],
+    dir: &Path,
+) -> ColumnInfo {
+    match left_paths.get(parent_dir) {
+        // NOTE(Chris): The terminal may have shrunk since this location was remembered
+        Some(dir_location) => fit_column_pos(
+            parent_entries.len(),
+            column_height,
+            ColumnInfo {
+                display_offset: dir_location.display_offset,
+                starting_index: dir_location.starting_index,
+            },
+        )
+        .unwrap(),
+        None => {
+            let parent_entry_index = parent_entries
+                .iter()
+                .position(|entry| entry.dir_entry.path() == *dir);
 
-        // Stop us from going too far down the third column
-        if result_column.starting_index > current_entries_len as u16 - column_height {
-            result_column.starting_index = current_entries_len as u16 - column_height;
+            // NOTE(Chris): It's not clear that we'd want to use a less-hacky ColumnInfo
+            let top_column = ColumnInfo {
+                starting_index: 0,
+                display_offset: 0,
+            };
 
-            result_column.display_offset = next_position as u16 - result_column.starting_index;
+            // NOTE(Chris): The directory isn't listed if we can't read its parent
+            match parent_entry_index {
+                Some(parent_entry_index) => find_column_pos(
+                    parent_entries.len(),
+                    column_height,
+                    top_column,
+                    parent_entry_index,
+                )
+                .unwrap(),
+                None => top_column,
+            }
         }
-    } else if next_position == second_entry_index as usize {
-        // Do nothing.
-    } else {
-        panic!();
     }
-
-    assert_eq!(
-        next_position,
-        (result_column.starting_index + result_column.display_offset) as usize
-    );
-
-    Ok(result_column)
 }
 
-fn update_drawing_info_from_resize(drawing_info: &mut DrawingInfo) -> crossterm::Result<()> {
-    let (width, height) = terminal::size()?;
-    // Represents the bottom-most y-cell of a column
-    let column_bot_y = height - 2;
-    // Represents the number of cells in a column vertically.
-    let column_height = height - 2;
-
-    *drawing_info = DrawingInfo {
-        win_pixels: os_abstract::get_win_pixels()?,
-        width,
-        height,
-        column_bot_y,
-        column_height,
-        first_left_x: 0,
-        first_right_x: width / 6 - 2,
-        second_left_x: width / 6,
-        second_right_x: width / 2 - 2,
-        third_left_x: width / 2,
-        third_right_x: width - 2,
-    };
-
-    Ok(())
+#[derive(Debug)]
+struct DirLocation {
+    dir_path: std::path::PathBuf,
+    starting_index: u16,
+    display_offset: u16,
 }
 
-// Handle for a task which displays an image
-struct DrawHandle {
-    can_draw: Arc<AtomicBool>,
+#[derive(Debug)]
+struct DirStates {
+    current_dir: std::path::PathBuf,
+    current_entries: Vec<DirEntryInfo>,
+    prev_dir: Option<std::path::PathBuf>,
+    prev_entries: Vec<DirEntryInfo>,
+    // Whether we weren't allowed to read the current and parent directories, in which case their
+    // entries are empty
+    is_current_denied: bool,
+    is_prev_denied: bool,
+    // Mirrors the options which affect how entries are sorted
+    sort_options: SortOptions,
+    // Restricts the current entries (but not the parent's, which have to include the current
+    // directory)
+    filter: Option<EntryFilter>,
+    // Whether the working directory follows the current directory, so that shell commands run in
+    // it. Batch mode leaves the working directory alone.
+    changes_working_dir: bool,
+    // The file system that's been entered (like an archive), if any. It's dropped once the current
+    // directory leaves it.
+    mount: Option<Mount>,
 }
 
-fn preview_image_or_video(
-    win_pixels: WindowPixels,
-    third_file: PathBuf,
-    ext: String,
-    width: u16,
-    height: u16,
-    left_x: u16,
-) -> io::Result<ImageBufferRgba> {
-    let win_px_width = win_pixels.width;
-    let win_px_height = win_pixels.height;
+impl DirStates {
+    fn new(
+        start_dir: PathBuf,
+        sort_options: SortOptions,
+        changes_working_dir: bool,
+    ) -> crossterm::Result<DirStates> {
+        // This is a slightly wasteful way to do this, but I'm too lazy to add anything better
+        let mut dir_states = DirStates {
+            current_dir: PathBuf::with_capacity(0),
+            current_entries: Vec::with_capacity(0),
+            prev_dir: None,
+            prev_entries: Vec::with_capacity(0),
+            is_current_denied: false,
+            is_prev_denied: false,
+            sort_options,
+            filter: None,
+            changes_working_dir,
+            mount: None,
+        };
 
-    let mut img = match ext.as_str() {
-        "mp4" | "webm" | "mkv" => {
-            let input = third_file.to_str().unwrap();
-
-            let ffprobe_output = Command::new("ffprobe")
-                .args(&[
-                    "-loglevel",
-                    "error",
-                    "-of",
-                    "csv=p=0",
-                    "-show_entries",
-                    "format=duration",
-                    input,
-                ])
-                .output()
-                .unwrap();
-
-            let ffprobe_stdout = std::str::from_utf8(&ffprobe_output.stdout).unwrap().trim();
-
-            // Truncate the decimal portion
-            let video_duration = ffprobe_stdout.parse::<f64>().unwrap() as i64;
-
-            let ffmpeg_output = Command::new("ffmpeg")
-                .args(&[
-                    "-ss",
-                    &format!("{}", video_duration / 2),
-                    "-i",
-                    input,
-                    "-frames:v",
-                    "1",
-                    "-c:v",
-                    "ppm",
-                    "-f",
-                    "image2pipe",
-                    "pipe:1",
-                ])
-                .output()
-                .unwrap();
-
-            let decoder = image::pnm::PnmDecoder::new(&ffmpeg_output.stdout[..]).unwrap();
-            image::DynamicImage::from_decoder(decoder).unwrap()
-        }
-        // TODO(Chris): Look into using libjpeg-turbo (https://github.com/ImageOptim/mozjpeg-rust)
-        // to decode large jpegs faster
-        _ => image::io::Reader::open(&third_file)?.decode().unwrap(),
-    };
+        dir_states.set_current_dir(start_dir)?;
 
-    // NOTE(Chris): sxiv only rotates jpgs somewhat-correctly, but Eye of
-    // Gnome (eog) rotates them correctly
+        Ok(dir_states)
+    }
 
-    // Rotate jpgs according to their orientation value
-    // One-iteration loop for early break
-    loop {
-        if ext == "jpg" || ext == "jpeg" {
-            let bytes = std::fs::read(&third_file)?;
+    fn set_current_dir<P: AsRef<Path>>(self: &mut DirStates, path: P) -> crossterm::Result<()> {
+        if matches!(&self.mount, Some(mount) if !mount.contains(path.as_ref())) {
+            self.mount = None;
+        }
 
-            // Find the location of the Exif header
-            let exif_header = b"Exif\x00\x00";
-            let exif_header_index = match tiff::find_bytes(&bytes, exif_header) {
-                Some(value) => value,
-                None => break,
-            };
+        // NOTE(Chris): A mounted directory isn't on the disk, so shell commands run in the
+        // directory that it was mounted from
+        if self.changes_working_dir && self.mount.is_none() {
+            std::env::set_current_dir(&path)?;
+        }
 
-            // This assumes that the beginning of the TIFF section
-            // comes right after the Exif header
-            let tiff_index = exif_header_index + exif_header.len();
-            let tiff_bytes = &bytes[tiff_index..];
+        self.current_dir = path.as_ref().to_path_buf();
 
-            let byte_order = match &tiff_bytes[0..=1] {
-                b"II" => Endian::LittleEndian,
-                b"MM" => Endian::BigEndian,
-                _ => panic!("Unable to determine endianness of TIFF section!"),
-            };
+        (self.current_entries, self.is_current_denied) = read_entries_unless_denied(
+            self.file_system_for(&self.current_dir),
+            &self.current_dir,
+            self.sort_options,
+        )?;
+        filter_entries(&mut self.current_entries, self.filter.as_ref());
 
-            if tiff_bytes[2] != 42 && tiff_bytes[3] != 42 {
-                panic!("Could not confirm existence of TIFF section with 42!");
+        let parent_path = match &self.mount {
+            Some(mount) => mount.parent(&self.current_dir),
+            None => self.current_dir.parent().map(Path::to_path_buf),
+        };
+        match parent_path {
+            Some(parent_path) => {
+                (self.prev_entries, self.is_prev_denied) = read_entries_unless_denied(
+                    self.file_system_for(&parent_path),
+                    &parent_path,
+                    self.sort_options,
+                )?;
+                self.prev_dir = Some(parent_path);
             }
-
-            // From the beginning of the TIFF section
-            let first_ifd_offset = usizeify(&tiff_bytes[4..=7], byte_order);
-
-            let num_ifd_entries = usizeify(
-                &tiff_bytes[first_ifd_offset..first_ifd_offset + 2],
-                byte_order,
-            );
-
-            let first_ifd_entry_offset = first_ifd_offset + 2;
-
-            // NOTE(Chris): We don't actually need info on all of the
-            // IFD entries, but I'm too lazy to break early from the
-            // for loop
-            let mut ifd_entries = vec![];
-            for entry_index in 0..num_ifd_entries {
-                let entry_bytes = &tiff_bytes[first_ifd_entry_offset + (12 * entry_index)..];
-                let entry = IFDEntry::from_slice(entry_bytes, byte_order);
-                ifd_entries.push(entry);
+            None => {
+                self.prev_entries = vec![];
+                self.prev_dir = None;
+                self.is_prev_denied = false;
             }
+        };
 
-            let orientation_ifd = ifd_entries.iter().find(|entry| {
-                entry.tag == EntryTag::Orientation
-                    && entry.field_type == EntryType::Short
-                    && entry.count == 1
-            });
+        Ok(())
+    }
 
-            let orientation_value = match orientation_ifd {
-                Some(value) => value,
-                None => break,
-            };
+    // Enters a directory in a file system, which stays mounted until it's left
+    fn enter_mount(&mut self, mount: Mount, dir: PathBuf) -> crossterm::Result<()> {
+        let old_dir = self.current_dir.clone();
+        let old_mount = self.mount.replace(mount);
 
-            match orientation_value.value_offset {
-                1 => (),
-                2 => img = img.fliph(),
-                3 => img = img.rotate180(),
-                4 => img = img.flipv(),
-                5 => img = img.rotate90().fliph(),
-                6 => img = img.rotate90(),
-                7 => img = img.rotate270().fliph(),
-                8 => img = img.rotate270(),
-                _ => (),
-            }
+        if let Err(err) = self.set_current_dir(dir) {
+            self.mount = old_mount;
+            self.set_current_dir(old_dir)?;
 
-            tiff::IFDEntry::from_slice(&bytes, byte_order);
+            return Err(err);
         }
 
-        break;
+        Ok(())
     }
 
-    let (img_width, img_height) = img.dimensions();
-
-    let mut img_cells_width = img_width * (width as u32) / (win_px_width as u32);
-    let mut img_cells_height = img_height * (height as u32) / (win_px_height as u32);
-
-    // eprintln!("   image: {:?}", &third_file);
+    fn file_system_for(&self, path: &Path) -> &dyn FileSystem {
+        vfs::file_system_for(self.mount.as_ref(), path)
+    }
 
-    // eprintln!(
-    //     "beginning - img_cells_width: {:3}, img_cells_height: {:3}",
-    //     img_cells_width, img_cells_height
-    // );
+    /// Reads the entries of any directory, whether it's mounted or not
+    fn read_entries(&self, path: &Path) -> io::Result<Vec<DirEntryInfo>> {
+        get_sorted_entries(self.file_system_for(path), path, self.sort_options)
+    }
 
-    let orig_img_cells_width = img_cells_width;
-    let orig_img_cells_height = img_cells_height;
+    /// The directory that shell commands run in, which is the current directory unless it's
+    /// mounted
+    fn working_dir(&self) -> &Path {
+        match &self.mount {
+            Some(mount) => mount.working_dir(),
+            None => &self.current_dir,
+        }
+    }
 
-    // let third_column_width = width - left_x - 2;
+    fn is_mounted(&self) -> bool {
+        self.mount.is_some()
+    }
 
-    let third_column_width = (width - left_x - 2) as u32;
-    // Subtract 1 because columns start at y = 1, subtract 1 again
-    // because columns stop at the penultimate row
-    let third_column_height = (height - 2) as u32;
+    fn is_mounted_path(&self, path: &Path) -> bool {
+        matches!(&self.mount, Some(mount) if mount.contains(path))
+    }
+}
 
-    // eprintln!(
-    //     "               column_width: {:3},    column_height: {:3}",
-    //     third_column_width, third_column_height
-    // );
+enum BroadFileType {
+    File,
+    Directory,
+}
 
-    // Scale the image down to fit the width, if necessary
-    if img_cells_width > third_column_width {
-        img_cells_width = third_column_width;
+fn broaden_file_type(file_type: &RecordedFileType) -> BroadFileType {
+    match file_type {
+        RecordedFileType::File
+        | RecordedFileType::FileSymlink
+        | RecordedFileType::InvalidSymlink
+        | RecordedFileType::Other
+        | RecordedFileType::Unknown => BroadFileType::File,
+        RecordedFileType::Directory | RecordedFileType::DirectorySymlink => {
+            BroadFileType::Directory
+        }
     }
+}
 
-    // Scale the image even further down to fit the height, if
-    // necessary
-    if img_cells_height > third_column_height {
-        img_cells_height = third_column_height;
+// Sorts std::fs::DirEntry by file type first (with directory coming before files),
+// then by file name. Symlinks are ignored in favor of the original files' file types.
+// lf seems to do this with symlinks as well.
+// TODO(Chris): Get rid of all the zany unwrap() calls in this function, since it's not supposed to
+// fail
+fn cmp_dir_entry_info(
+    entry_info_1: &DirEntryInfo,
+    entry_info_2: &DirEntryInfo,
+    sort_options: SortOptions,
+) -> Ordering {
+    let broad_ft_1 = broaden_file_type(&entry_info_1.file_type);
+    let broad_ft_2 = broaden_file_type(&entry_info_2.file_type);
+
+    match (broad_ft_1, broad_ft_2) {
+        (BroadFileType::Directory, BroadFileType::File) => Ordering::Less,
+        (BroadFileType::File, BroadFileType::Directory) => Ordering::Greater,
+        _ => cmp_file_names(
+            entry_info_1.dir_entry.file_name().to_str().unwrap(),
+            entry_info_2.dir_entry.file_name().to_str().unwrap(),
+            sort_options,
+        ),
     }
+}
 
-    // eprintln!(
-    //     "   ending - img_cells_width: {:3}, img_cells_height: {:3}",
-    //     img_cells_width, img_cells_height
-    // );
+// Compares file names naturally, putting dotfiles where the dotfiles option says
+fn cmp_file_names(name_1: &str, name_2: &str, sort_options: SortOptions) -> Ordering {
+    let is_dotfile_1 = name_1.starts_with('.');
+    let is_dotfile_2 = name_2.starts_with('.');
 
-    if orig_img_cells_width != img_cells_width || orig_img_cells_height != img_cells_height {
-        let display_width_px = img_cells_width * (win_px_width as u32) / (width as u32);
-        let display_height_px = img_cells_height * (win_px_height as u32) / (height as u32);
+    let ignore_diacritics = sort_options.ignore_diacritics;
 
-        if orig_img_cells_width > third_column_width * 3
-            || orig_img_cells_height > third_column_height * 3
-        {
-            img = img.thumbnail(display_width_px, display_height_px);
-        } else {
-            img = img.resize(
-                display_width_px,
-                display_height_px,
-                image::imageops::FilterType::Triangle,
-            );
-        }
+    match sort_options.dotfiles {
+        DotfileOrder::First => is_dotfile_2.cmp(&is_dotfile_1),
+        DotfileOrder::Last => is_dotfile_1.cmp(&is_dotfile_2),
+        DotfileOrder::Mixed => cmp_natural_with(
+            name_1.strip_prefix('.').unwrap_or(name_1),
+            name_2.strip_prefix('.').unwrap_or(name_2),
+            ignore_diacritics,
+        ),
     }
+    // NOTE(Chris): When dots are ignored, ".bashrc" and "bashrc" only differ by their dots, so
+    // the dotfile comes first
+    .then_with(|| cmp_natural_with(name_1, name_2, ignore_diacritics))
+}
 
-    let rgba = img.to_rgba8();
-
-    Ok(rgba)
+// How entries are sorted, from the ignore-diacritics and dotfiles options, and which are left out,
+// from the ignore-vcs option
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SortOptions {
+    ignore_diacritics: bool,
+    dotfiles: DotfileOrder,
+    ignore_vcs: bool,
 }
 
-fn draw_bottom_info_line(screen: &mut Screen, fm: &mut FileManager) {
-    // TODO(Chris): Display info for empty directory when in empty directory, like in lf
-    if fm.dir_states.current_entries.len() <= 0 {
-        return;
+impl SortOptions {
+    fn new(config: &Config) -> SortOptions {
+        SortOptions {
+            ignore_diacritics: config.ignore_diacritics,
+            dotfiles: config.dotfiles,
+            ignore_vcs: config.ignore_vcs,
+        }
     }
+}
 
-    let updated_second_entry_index = fm.second.starting_index + fm.second.display_offset;
+// Sorts the entries again if an option changed how they're compared or which are listed
+fn resort_if_needed(fm: &mut FileManager, second_entry_index: u16, tx: &Sender<InputEvent>) {
+    let sort_options = SortOptions::new(&fm.config);
 
-    let extra_perms = os_abstract::get_extra_perms(
-        &fm.dir_states.current_entries[updated_second_entry_index as usize].metadata,
-    );
+    if fm.dir_states.sort_options != sort_options {
+        fm.dir_states.sort_options = sort_options;
 
-    let mode_str = &extra_perms.mode;
+        let file_id = fm
+            .dir_states
+            .current_entries
+            .get(second_entry_index as usize)
+            .map_or(0, |entry_info| entry_info.metadata.file_id());
 
-    let mut draw_style = Style::new_attr(rolf_grid::Attribute::Bold);
+        reload_current_dir_prefer_id(fm, file_id, tx);
+    }
+}
 
-    let mut info_line_builder = LineBuilder::new();
+fn save_location(fm: &mut FileManager, second_entry_index: u16) {
+    let dir_entry = &fm.dir_states.current_entries[second_entry_index as usize].dir_entry;
 
-    {
-        // The Windows mode string is only 6 characters long, so this avoids the Windows mode
-        // string.
-        if mode_str.len() > 6 {
-            // TODO(Chris): Reimplement this on Windows
-            // queue!(colored_mode, style::SetAttribute(Attribute::Bold)).unwrap();
-        }
-        for (index, byte) in mode_str.bytes().enumerate() {
-            if index > 3 {
-                draw_style.attribute = rolf_grid::Attribute::None;
-            }
+    fm.saved_positions.insert(
+        fm.dir_states.current_dir.clone(),
+        SavedPosition {
+            file_name: dir_entry.file_name().to_string_lossy().into_owned(),
+            starting_index: fm.second.starting_index,
+            display_offset: fm.second.display_offset,
+        },
+    );
 
-            match &[byte] {
-                b"d" => {
-                    draw_style.fg = rolf_grid::Color::Blue;
-                    info_line_builder.push(byte as char, draw_style);
-                }
-                b"r" => {
-                    draw_style.fg = rolf_grid::Color::Yellow;
-                    info_line_builder.push(byte as char, draw_style);
-                }
-                b"w" => {
-                    draw_style.fg = rolf_grid::Color::Red;
-                    info_line_builder.push(byte as char, draw_style);
-                }
-                b"x" => {
-                    draw_style.fg = rolf_grid::Color::Green;
-                    info_line_builder.push(byte as char, draw_style);
-                }
-                b"-" => {
-                    draw_style.fg = rolf_grid::Color::Blue;
-                    info_line_builder.push(byte as char, draw_style);
-                }
-                b"l" => {
-                    draw_style.attribute = rolf_grid::Attribute::None;
-                    draw_style.fg = rolf_grid::Color::Cyan;
+    fm.left_paths.insert(
+        fm.dir_states.current_dir.clone(),
+        DirLocation {
+            dir_path: dir_entry.path(),
+            starting_index: fm.second.starting_index,
+            display_offset: fm.second.display_offset,
+        },
+    );
+}
 
-                    info_line_builder.push(byte as char, draw_style);
+// Puts the cursor back where it was the last time dir was the current directory, which may have
+// been in an earlier session
+fn restore_location(fm: &mut FileManager, dir: &Path) -> io::Result<()> {
+    recall_location(fm, dir);
 
-                    draw_style.attribute = rolf_grid::Attribute::Bold;
-                    draw_style.fg = rolf_grid::Color::Foreground;
-                }
-                b"c" | b"b" => {
-                    draw_style.fg = rolf_grid::Color::Yellow;
-                    info_line_builder.push(byte as char, draw_style);
-                }
-                _ => {
-                    draw_style.attribute = rolf_grid::Attribute::None;
-                    info_line_builder.push(byte as char, draw_style);
+    match fm.left_paths.get(dir) {
+        Some(dir_location) => {
+            let curr_entry_index = fm
+                .dir_states
+                .current_entries
+                .iter()
+                .position(|entry| entry.dir_entry.path() == *dir_location.dir_path);
+
+            match curr_entry_index {
+                Some(curr_entry_index) => {
+                    let orig_entry_index =
+                        (dir_location.starting_index + dir_location.display_offset) as usize;
+                    if curr_entry_index == orig_entry_index {
+                        fm.second.starting_index = dir_location.starting_index;
+                        fm.second.display_offset = dir_location.display_offset;
+                    } else {
+                        fm.second.starting_index = (curr_entry_index / 2) as u16;
+                        fm.second.display_offset =
+                            (curr_entry_index as u16) - fm.second.starting_index;
+                    }
+                }
+                None => {
+                    fm.second.starting_index = 0;
+                    fm.second.display_offset = 0;
                 }
             }
         }
+        None => {
+            fm.second.starting_index = 0;
+            fm.second.display_offset = 0;
+        }
     };
 
-    // TODO(Chris): Display user/group names in white if they are not the current user/the current
-    // user is not in the group
+    // NOTE(Chris): A remembered location may be from before the terminal shrank
+    fm.second = fit_column_pos(
+        fm.dir_states.current_entries.len(),
+        fm.drawing_info.column_height,
+        fm.second,
+    )?;
 
-    if let Some(hard_link_count) = extra_perms.hard_link_count {
-        info_line_builder
-            .use_fg_color(rolf_grid::Color::Red)
-            .use_attribute(rolf_grid::Attribute::Bold)
-            .push_str(&format!(" {:2}", hard_link_count));
-    }
+    Ok(())
+}
 
-    if let Some(user_name) = extra_perms.user_name {
-        info_line_builder
-            .use_fg_color(rolf_grid::Color::Yellow)
-            .use_attribute(rolf_grid::Attribute::Bold)
-            .push_str(&format!(" {:2}", user_name));
+// Copies the location saved in an earlier session into left_paths, unless dir has already been
+// visited in this one
+fn recall_location(fm: &mut FileManager, dir: &Path) {
+    if fm.left_paths.contains_key(dir) {
+        return;
     }
 
-    if let Some(group_name) = extra_perms.group_name {
-        info_line_builder
-            .use_fg_color(rolf_grid::Color::Yellow)
-            .use_attribute(rolf_grid::Attribute::Bold)
-            .push_str(&format!(" {}", group_name));
-    }
+    if let Some(saved_position) = fm.saved_positions.get(dir) {
+        let dir_location = DirLocation {
+            dir_path: dir.join(&saved_position.file_name),
+            starting_index: saved_position.starting_index,
+            display_offset: saved_position.display_offset,
+        };
 
-    if let Some(size) = extra_perms.size {
-        info_line_builder
-            .use_fg_color(rolf_grid::Color::Green)
-            .use_attribute(rolf_grid::Attribute::Bold)
-            .push_str(&format!(" {:>4}", human_size(size)));
+        fm.left_paths.insert(dir.to_path_buf(), dir_location);
     }
+}
 
-    if let Some(modify_date_time) = extra_perms.modify_date_time {
-        info_line_builder
-            .use_fg_color(rolf_grid::Color::Blue)
-            .use_attribute(rolf_grid::Attribute::None)
-            .push_str(" ")
-            .push_str(&modify_date_time);
-    }
+type ImageBufferRgba = ImageBuffer<Rgba<u8>, Vec<u8>>;
 
-    let display_position = format!(
-        "{}/{}",
-        updated_second_entry_index + 1,
-        fm.dir_states.current_entries.len()
-    );
+#[derive(Debug)]
+enum PreviewData {
+    Loading,
+    Blank,
+    Message { message: String },
+    Directory { entries_info: Vec<DirEntryInfo> },
+    UncoloredFile { path: PathBuf },
+    ImageBuffer { buffer: ImageBufferRgba },
+    RawBytes { bytes: Vec<u8> },
+}
 
-    screen.build_line(0, fm.drawing_info.height - 1, &info_line_builder);
+impl PreviewData {
+    // A copy of the preview to keep in the preview cache, along with how many bytes it takes up.
+    // Only the previews which are slow to generate are kept.
+    fn to_cached(&self) -> Option<(PreviewData, usize)> {
+        match self {
+            PreviewData::ImageBuffer { buffer } => Some((
+                PreviewData::ImageBuffer {
+                    buffer: buffer.clone(),
+                },
+                buffer.as_raw().len(),
+            )),
+            PreviewData::RawBytes { bytes } => Some((
+                PreviewData::RawBytes {
+                    bytes: bytes.clone(),
+                },
+                bytes.len(),
+            )),
+            _ => None,
+        }
+    }
+}
 
-    draw_str(
-        screen,
-        fm.drawing_info.width - (display_position.len() as u16),
-        fm.drawing_info.height - 1,
-        &display_position,
-        rolf_grid::Style::default(),
-    );
+#[derive(Clone, Copy, Debug)]
+struct Rect {
+    left_x: u16,
+    top_y: u16,
+    width: u16,
+    height: u16,
 }
 
-fn abort_image_handles(image_handles: &mut Vec<DrawHandle>) {
-    while !image_handles.is_empty() {
-        let image_handle = image_handles.pop().unwrap();
-        image_handle
-            .can_draw
-            .store(false, std::sync::atomic::Ordering::Release);
+impl Rect {
+    // If a Rect conceptually has a top_y of 1 and a bot_y of 2, it will have a height of 1.
+    fn bot_y(&self) -> u16 {
+        self.top_y + self.height
+    }
+
+    fn right_x(&self) -> u16 {
+        self.left_x + self.width
     }
 }
 
-fn store_in_tmp_file(buf: &[u8]) -> std::result::Result<std::path::PathBuf, io::Error> {
-    let (mut tmpfile, path) = tempfile::Builder::new()
-        .prefix(".tmp.rolf")
-        .rand_bytes(1)
-        .tempfile()?
-        // Since the file is persisted, the user is responsible for deleting it afterwards. However,
-        // Kitty does this automatically after printing from a temp file.
-        .keep()?;
+// Draws a string which may contain double-width characters, stopping before max_x. Returns the x
+// position just after the last character drawn.
+fn draw_wide_str(
+    screen: &mut Screen,
+    x: u16,
+    y: u16,
+    string: &str,
+    max_x: u16,
+    style: Style,
+) -> u16 {
+    let mut curr_x = x;
+
+    for ch in string.chars() {
+        let ch_width = line_edit::char_width(ch) as u16;
+
+        if curr_x + ch_width > max_x {
+            break;
+        }
 
-    tmpfile.write_all(buf)?;
-    tmpfile.flush()?;
-    Ok(path)
+        if ch_width == 2 {
+            screen.set_wide_cell_style_clipped(curr_x, y, ch, style);
+        } else {
+            screen.set_cell_style_clipped(curr_x, y, ch, style);
+        }
+
+        curr_x += ch_width;
+    }
+
+    curr_x
 }
 
-fn format_current_dir(dir_states: &DirStates, home_path: &Path) -> String {
-    // NOTE(Chris): This creates a new String, and it'd be nice to avoid making a heap
-    // allocation here, but it's probably not worth trying to figure out how to use only a str
+fn draw_str(screen: &mut Screen, x: u16, y: u16, string: &str, style: Style) {
+    // NOTE(Chris): Characters are placed by their width rather than their byte index, so that
+    // something like the "…" in a truncated name doesn't leave a gap after it
+    let mut offset: usize = 0;
+
+    for ch in string.chars() {
+        // NOTE(Chris): Anything this far along would be off the screen anyway
+        let i = match u16::try_from(offset) {
+            Ok(i) => i,
+            Err(_) => break,
+        };
 
-    if dir_states.current_dir == *home_path {
-        String::from("~")
-    } else if dir_states.current_dir.starts_with(home_path) {
-        // "~"
-        format!(
-            "~{}{}",
-            path::MAIN_SEPARATOR,
-            dir_states
-                .current_dir
-                .strip_prefix(home_path)
-                .unwrap()
-                .to_str()
-                .unwrap()
-        )
-    } else if dir_states.prev_dir.is_none() {
-        String::from("")
-    } else {
-        dir_states.current_dir.to_str().unwrap().to_string()
+        screen.set_cell_style_clipped(x.saturating_add(i), y, ch, style);
+
+        offset += line_edit::char_width(ch);
     }
 }
 
-// For the list consisting of the entries in parent_entries, find the correct display offset and
-// starting index that will put the cursor on dir
-fn find_correct_location(
-    left_paths: &HashMap<std::path::PathBuf, DirLocation>,
-    column_height: u16,
-    parent_dir: &Path,
-    parent_entries: &[DirEntryInfo],
-    dir: &Path,
-) -> ColumnInfo {
-    return match left_paths.get(parent_dir) {
-        Some(dir_location) => ColumnInfo {
-            display_offset: dir_location.display_offset,
-            starting_index: dir_location.starting_index,
-        },
-        None => {
-            let parent_entry_index = parent_entries
-                .iter()
-                .position(|entry| entry.dir_entry.path() == *dir)
-                .unwrap();
-
-            let entries_len = parent_dir.read_dir().unwrap().count();
-
-            find_column_pos(
-                entries_len,
-                column_height,
-                // NOTE(Chris): It's not clear that we'd want to use a less-hacky ColumnInfo
-                ColumnInfo {
-                    starting_index: 0,
-                    display_offset: 0,
-                },
-                parent_entry_index,
+fn get_sorted_entries<P: AsRef<Path>>(
+    file_system: &dyn FileSystem,
+    path: P,
+    sort_options: SortOptions,
+) -> io::Result<Vec<DirEntryInfo>> {
+    read_sorted_entries(file_system, path, sort_options, || false)
+}
+
+fn filter_entries(entries: &mut Vec<DirEntryInfo>, filter: Option<&EntryFilter>) {
+    if let Some(filter) = filter {
+        let now = SystemTime::now();
+
+        entries.retain(|entry_info| {
+            let is_dir = matches!(
+                broaden_file_type(&entry_info.file_type),
+                BroadFileType::Directory
+            );
+
+            filter.matches(
+                &entry_info.dir_entry.path(),
+                is_dir,
+                entry_info.metadata.modified().ok(),
+                now,
             )
-            .unwrap()
+        });
+
+        // NOTE(Chris): Recent files are listed newest first, so that the file which was just
+        // saved is at the top
+        if let EntryFilter::Recent(_) = filter {
+            entries
+                .sort_by_key(|entry_info| std::cmp::Reverse(entry_info.metadata.modified().ok()));
         }
-    };
+    }
 }
 
-#[derive(Debug)]
-struct DirLocation {
-    dir_path: std::path::PathBuf,
-    starting_index: u16,
-    display_offset: u16,
+// Like get_sorted_entries, but a directory we aren't allowed to read (like one with permissions of
+// 0311) is treated as empty, since we may still be able to enter it. Returns whether permission
+// was denied along with the entries.
+fn read_entries_unless_denied(
+    file_system: &dyn FileSystem,
+    path: &Path,
+    sort_options: SortOptions,
+) -> io::Result<(Vec<DirEntryInfo>, bool)> {
+    match get_sorted_entries(file_system, path, sort_options) {
+        Ok(entries) => Ok((entries, false)),
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Ok((vec![], true)),
+        Err(err) => Err(err),
+    }
 }
 
-#[derive(Debug)]
-struct DirStates {
-    current_dir: std::path::PathBuf,
-    current_entries: Vec<DirEntryInfo>,
-    prev_dir: Option<std::path::PathBuf>,
-    prev_entries: Vec<DirEntryInfo>,
+// Like get_sorted_entries, but gives up with an Interrupted error as soon as the ticket's preview
+// is no longer wanted, so that large directories don't keep a preview thread busy.
+fn get_sorted_entries_unless_stale<P: AsRef<Path>>(
+    file_system: &dyn FileSystem,
+    path: P,
+    sort_options: SortOptions,
+    ticket: &PreviewTicket,
+) -> io::Result<Vec<DirEntryInfo>> {
+    read_sorted_entries(file_system, path, sort_options, || !ticket.is_current())
 }
 
-impl DirStates {
-    fn new() -> crossterm::Result<DirStates> {
-        // This is a slightly wasteful way to do this, but I'm too lazy to add anything better
-        let mut dir_states = DirStates {
-            current_dir: PathBuf::with_capacity(0),
-            current_entries: Vec::with_capacity(0),
-            prev_dir: None,
-            prev_entries: Vec::with_capacity(0),
+fn read_sorted_entries<P, F>(
+    file_system: &dyn FileSystem,
+    path: P,
+    sort_options: SortOptions,
+    is_cancelled: F,
+) -> io::Result<Vec<DirEntryInfo>>
+where
+    P: AsRef<Path>,
+    F: Fn() -> bool,
+{
+    let mut entries = file_system.read_dir(path.as_ref(), &is_cancelled)?;
+
+    if is_cancelled() {
+        return Err(io::Error::from(io::ErrorKind::Interrupted));
+    }
+
+    // NOTE(Chris): Only git can tell which files it ignores, and it can only read them from the disk
+    if sort_options.ignore_vcs && file_system.is_local() {
+        let unignored_names = unignored_names(path.as_ref());
+
+        entries.retain(|entry_info| unignored_names.contains(&entry_info.dir_entry.file_name()));
+    }
+
+    entries.sort_by(|entry_info_1, entry_info_2| {
+        cmp_dir_entry_info(entry_info_1, entry_info_2, sort_options)
+    });
+
+    Ok(entries)
+}
+
+// The names of the entries in dir which git doesn't ignore, going by the .gitignore files in it and
+// its parents, along with .git/info/exclude and the global gitignore. Outside of a git repository,
+// nothing is ignored.
+fn unignored_names(dir: &Path) -> HashSet<OsString> {
+    ignore::WalkBuilder::new(dir)
+        .hidden(false)
+        .max_depth(Some(1))
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.depth() == 1)
+        .map(|entry| entry.file_name().to_os_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_line_number() {
+        let mut options = ListingOptions {
+            empty_message: "empty",
+            dimming_user: None,
+            info_columns: &[],
+            dir_counts: None,
+            size_units: SizeUnits::Metric,
+            recent_marking: None,
+            plain: false,
+            truncation: Truncation::End,
+            number: false,
+            relative_number: false,
+            search_highlight: None,
+            match_options: MatchOptions {
+                ignore_case: true,
+                smart_case: true,
+                anchored: false,
+                ignore_diacritics: false,
+            },
+            background: rolf_grid::Color::Background,
         };
 
-        dir_states.set_current_dir(std::env::current_dir().unwrap())?;
+        assert_eq!(get_line_number(3, 5, &options), None);
 
-        Ok(dir_states)
+        options.number = true;
+        assert_eq!(get_line_number(3, 5, &options), Some(4));
+
+        options.relative_number = true;
+        assert_eq!(get_line_number(3, 5, &options), Some(2));
+        assert_eq!(get_line_number(5, 5, &options), Some(6));
+
+        options.number = false;
+        assert_eq!(get_line_number(7, 5, &options), Some(2));
+        assert_eq!(get_line_number(5, 5, &options), Some(0));
     }
 
-    fn set_current_dir<P: AsRef<Path>>(self: &mut DirStates, path: P) -> crossterm::Result<()> {
-        std::env::set_current_dir(&path)?;
+    #[test]
+    fn test_get_drawing_info() {
+        let win_pixels = WindowPixels {
+            width: 800,
+            height: 480,
+        };
 
-        self.current_dir = path.as_ref().to_path_buf();
+        let drawing_info = get_drawing_info(win_pixels, 80, 24, [1, 2, 3], PreviewPane::Right);
+        assert_eq!(
+            (drawing_info.first_left_x, drawing_info.first_right_x),
+            (0, 11)
+        );
+        assert_eq!(
+            (drawing_info.second_left_x, drawing_info.second_right_x),
+            (13, 38)
+        );
+        assert_eq!(
+            (drawing_info.third_left_x, drawing_info.third_right_x),
+            (40, 78)
+        );
+        assert_eq!(drawing_info.column_height, 22);
 
-        self.current_entries = get_sorted_entries(&self.current_dir).unwrap();
+        // A zoomed preview hides the other columns
+        let drawing_info = get_drawing_info(win_pixels, 80, 24, [0, 0, 1], PreviewPane::Right);
+        assert_eq!(drawing_info.first_right_x, drawing_info.first_left_x);
+        assert_eq!(drawing_info.second_right_x, drawing_info.second_left_x);
+        assert_eq!(
+            (drawing_info.third_left_x, drawing_info.third_right_x),
+            (0, 78)
+        );
 
-        let parent_path = self.current_dir.parent();
-        match parent_path {
-            Some(parent_path) => {
-                let parent_path = parent_path.to_path_buf();
-                self.prev_entries = get_sorted_entries(&parent_path).unwrap();
-                self.prev_dir = Some(parent_path);
-            }
-            None => {
-                self.prev_entries = vec![];
-                self.prev_dir = None;
-            }
+        let drawing_info = get_drawing_info(win_pixels, 80, 24, [0, 1, 0], PreviewPane::Right);
+        assert_eq!(
+            (drawing_info.second_left_x, drawing_info.second_right_x),
+            (0, 78)
+        );
+        assert_eq!(drawing_info.third_right_x, drawing_info.third_left_x);
+
+        // A preview at the bottom leaves the width to the other columns
+        let drawing_info = get_drawing_info(win_pixels, 80, 24, [1, 2, 3], PreviewPane::Bottom);
+        assert_eq!(
+            (drawing_info.second_left_x, drawing_info.second_right_x),
+            (26, 78)
+        );
+        assert_eq!(
+            (drawing_info.third_left_x, drawing_info.third_right_x),
+            (0, 78)
+        );
+        assert_eq!(
+            (drawing_info.column_bot_y, drawing_info.column_height),
+            (11, 11)
+        );
+        assert_eq!(
+            (drawing_info.third_top_y, drawing_info.third_height),
+            (13, 10)
+        );
+    }
+
+    #[test]
+    fn test_fit_ratios() {
+        assert_eq!(fit_ratios(80, [1, 2, 3]), [1, 2, 3]);
+        // The preview is hidden first, giving the parent column enough room again
+        assert_eq!(fit_ratios(40, [1, 2, 3]), [1, 2, 0]);
+        assert_eq!(fit_ratios(20, [1, 2, 3]), [0, 2, 0]);
+        assert_eq!(fit_ratios(3, [1, 2, 3]), [0, 2, 0]);
+
+        // A zoomed preview stays, however narrow the terminal is
+        assert_eq!(fit_ratios(5, [0, 0, 1]), [0, 0, 1]);
+
+        let drawing_info = get_drawing_info(
+            WindowPixels {
+                width: 100,
+                height: 60,
+            },
+            10,
+            6,
+            [1, 2, 3],
+            PreviewPane::Right,
+        );
+        assert_eq!(
+            (drawing_info.second_left_x, drawing_info.second_right_x),
+            (0, 8)
+        );
+        assert_eq!(drawing_info.third_right_x, drawing_info.third_left_x);
+    }
+
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines(b""), 0);
+        assert_eq!(count_lines(b"one"), 1);
+        assert_eq!(count_lines(b"one\n"), 1);
+        assert_eq!(count_lines(b"one\ntwo"), 2);
+        assert_eq!(count_lines(b"\n\n"), 2);
+    }
+
+    #[test]
+    fn test_column_cache() {
+        let layout = || ColumnLayout {
+            width: 20,
+            gutter_width: 0,
+            dimming_uid: None,
+            info_columns: vec![],
+            search_highlight: None,
+            match_options: MatchOptions {
+                ignore_case: true,
+                smart_case: true,
+                anchored: false,
+                ignore_diacritics: false,
+            },
+            background: rolf_grid::Color::Background,
+        };
+        let row_key = |is_current| RowKey {
+            file_name: OsString::from("Cargo.toml"),
+            file_type: RecordedFileType::File,
+            size: 1024,
+            modified: None,
+            owner_ids: None,
+            is_current,
+            is_selected: false,
+            line_number: None,
+            dir_count: None,
+            is_recent: false,
+            is_executable: false,
         };
 
-        Ok(())
+        let mut cache = ColumnCache::default();
+        cache.reset_if_changed(layout());
+        cache.insert(2, row_key(false), LineBuilder::new());
+
+        assert!(cache.get(2, &row_key(false)).is_some());
+        assert!(cache.get(2, &row_key(true)).is_none());
+        assert!(cache.get(0, &row_key(false)).is_none());
+
+        cache.reset_if_changed(layout());
+        assert!(cache.get(2, &row_key(false)).is_some());
+
+        cache.reset_if_changed(ColumnLayout {
+            search_highlight: Some("cargo".to_string()),
+            ..layout()
+        });
+        assert!(cache.get(2, &row_key(false)).is_none());
+
+        cache.reset_if_changed(layout());
+        cache.insert(2, row_key(false), LineBuilder::new());
+        cache.reset_if_changed(ColumnLayout {
+            background: rolf_grid::Color::Ansi(235),
+            ..layout()
+        });
+        assert!(cache.get(2, &row_key(false)).is_none());
     }
-}
 
-#[derive(Debug, PartialEq, Eq)]
-enum RecordedFileType {
-    File,
-    Directory,
-    FileSymlink,
-    DirectorySymlink,
-    InvalidSymlink,
-    Unknown,
-    Other,
-}
+    #[test]
+    fn test_check_config() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(check_config(dir.path()).is_empty());
 
-#[derive(Debug)]
-struct DirEntryInfo {
-    dir_entry: DirEntry,
-    metadata: Metadata,
-    file_type: RecordedFileType,
-}
+        fs::write(
+            dir.path().join("config.json"),
+            r#"{ "keybindings": [{ "key": "y", "command": "map" }] }"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("rolfrc"), "map foo up\n").unwrap();
 
-enum BroadFileType {
-    File,
-    Directory,
-}
+        assert_eq!(
+            check_config(dir.path()),
+            [
+                "Keybinding for y: \"map\": at the end of the input: expected a list".to_string(),
+                format!(
+                    "{}: Failed to bind invalid key: foo",
+                    dir.path().join("rolfrc").display()
+                ),
+            ]
+        );
+    }
 
-fn broaden_file_type(file_type: &RecordedFileType) -> BroadFileType {
-    match file_type {
-        RecordedFileType::File
-        | RecordedFileType::FileSymlink
-        | RecordedFileType::InvalidSymlink
-        | RecordedFileType::Other
-        | RecordedFileType::Unknown => BroadFileType::File,
-        RecordedFileType::Directory | RecordedFileType::DirectorySymlink => {
-            BroadFileType::Directory
-        }
+    #[test]
+    fn test_print_config() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut output = vec![];
+        print_config(&Config::default(), dir.path(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("Config file: none (using the defaults)\n"));
+        assert!(output.contains("\n  ratios: 1:2:3\n"));
+        assert!(output.contains("\n  j: down\n"));
+    }
+
+    #[test]
+    fn test_read_rolfrc() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_rolfrc(dir.path()), (vec![], vec![]));
+
+        fs::write(dir.path().join("rolfrc"), "map j down\nmap foo up\n").unwrap();
+        let (program, errors) = read_rolfrc(dir.path());
+        assert_eq!(program.len(), 2);
+        assert_eq!(errors, ["Failed to bind invalid key: foo"]);
+
+        // NOTE(Chris): None of a rolfrc which can't be parsed is run
+        fs::write(dir.path().join("rolfrc"), "map j down extra\n").unwrap();
+        let (program, errors) = read_rolfrc(dir.path());
+        assert!(program.is_empty());
+        assert_eq!(errors, ["line 1, column 12: expected the end of the line"]);
+
+        assert_eq!(summarize_startup_errors(&[]), None);
+        assert_eq!(
+            summarize_startup_errors(&["a".to_string(), "b".to_string()]),
+            Some("rolfrc: a; b".to_string())
+        );
     }
-}
 
-// Sorts std::fs::DirEntry by file type first (with directory coming before files),
-// then by file name. Symlinks are ignored in favor of the original files' file types.
-// lf seems to do this with symlinks as well.
-// TODO(Chris): Get rid of all the zany unwrap() calls in this function, since it's not supposed to
-// fail
-fn cmp_dir_entry_info(entry_info_1: &DirEntryInfo, entry_info_2: &DirEntryInfo) -> Ordering {
-    let broad_ft_1 = broaden_file_type(&entry_info_1.file_type);
-    let broad_ft_2 = broaden_file_type(&entry_info_2.file_type);
+    #[test]
+    fn test_stat_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "12345").unwrap();
+        let metadata = fs::symlink_metadata(&path).unwrap();
+
+        let lines = stat_lines(&path, &metadata, &Config::default());
+
+        assert_eq!(lines[0], format!("File: {}", path.display()));
+        assert!(lines.contains(&"Size: 5B".to_string()));
+        assert!(lines.contains(&format!(
+            "Device: {}  Inode: {}",
+            os_abstract::get_device_id(&metadata),
+            get_file_id(&metadata)
+        )));
+    }
 
-    match (broad_ft_1, broad_ft_2) {
-        (BroadFileType::Directory, BroadFileType::File) => Ordering::Less,
-        (BroadFileType::File, BroadFileType::Directory) => Ordering::Greater,
-        _ => cmp_natural(
-            entry_info_1.dir_entry.file_name().to_str().unwrap(),
-            entry_info_2.dir_entry.file_name().to_str().unwrap(),
-        ),
+    #[test]
+    fn test_format_xattr_value() {
+        assert_eq!(
+            format_xattr_value(b"0083;6512a3f1;Safari;"),
+            "0083;6512a3f1;Safari;"
+        );
+        assert_eq!(format_xattr_value(b"user\0"), "user");
+        assert_eq!(format_xattr_value(&[0, 1, 255]), "00 01 ff");
+        assert_eq!(format_xattr_value(b""), "");
     }
-}
 
-fn save_location(fm: &mut FileManager, second_entry_index: u16) {
-    fm.left_paths.insert(
-        fm.dir_states.current_dir.clone(),
-        DirLocation {
-            dir_path: fm.dir_states.current_entries[second_entry_index as usize]
-                .dir_entry
-                .path(),
-            starting_index: fm.second.starting_index,
-            display_offset: fm.second.display_offset,
-        },
-    );
-}
+    #[test]
+    fn test_tmux_send_args() {
+        let paths = [PathBuf::from("/tmp/a b"), PathBuf::from("/tmp/c")];
 
-type ImageBufferRgba = ImageBuffer<Rgba<u8>, Vec<u8>>;
+        assert_eq!(
+            tmux_send_args(&paths, false),
+            vec!["set-buffer", "--", "'/tmp/a b' '/tmp/c'"]
+        );
+        assert_eq!(
+            tmux_send_args(&paths, true),
+            vec![
+                "send-keys",
+                "-t",
+                "{last}",
+                "-l",
+                "--",
+                "'/tmp/a b' '/tmp/c'"
+            ]
+        );
+    }
 
-#[derive(Debug)]
-enum PreviewData {
-    Loading,
-    Blank,
-    Message { message: &'static str },
-    Directory { entries_info: Vec<DirEntryInfo> },
-    UncoloredFile { path: PathBuf },
-    ImageBuffer { buffer: ImageBufferRgba },
-    RawBytes { bytes: Vec<u8> },
-}
+    #[test]
+    fn test_terminal_command() {
+        assert_eq!(terminal_command("alacritty", true), Some("alacritty"));
+        assert_eq!(
+            terminal_command("", true),
+            Some("tmux new-window -c \"$PWD\"")
+        );
+        assert_eq!(terminal_command("", false), None);
+    }
 
-#[derive(Clone, Copy, Debug)]
-struct Rect {
-    left_x: u16,
-    top_y: u16,
-    width: u16,
-    height: u16,
-}
+    #[test]
+    fn test_read_local_rolfrc() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(read_local_rolfrc(dir.path()), Ok(vec![]));
 
-impl Rect {
-    // If a Rect conceptually has a top_y of 1 and a bot_y of 2, it will have a height of 1.
-    fn bot_y(&self) -> u16 {
-        self.top_y + self.height
+        fs::write(
+            dir.path().join(".rolfrc"),
+            "set dotfiles last\nonly-ext jpg png\n",
+        )
+        .unwrap();
+        assert_eq!(
+            read_local_rolfrc(dir.path()).map(|program| program.len()),
+            Ok(2)
+        );
+
+        // NOTE(Chris): One command that isn't allowed stops the rest from running too
+        fs::write(dir.path().join(".rolfrc"), "set number\ndelete\n").unwrap();
+        assert_eq!(
+            read_local_rolfrc(dir.path()),
+            Err(".rolfrc: delete isn't allowed in a directory's .rolfrc".to_string())
+        );
+
+        fs::write(dir.path().join(".rolfrc"), "map x delete\n").unwrap();
+        assert_eq!(
+            read_local_rolfrc(dir.path()),
+            Err(".rolfrc: map isn't allowed in a directory's .rolfrc".to_string())
+        );
     }
 
-    fn right_x(&self) -> u16 {
-        self.left_x + self.width
+    #[test]
+    fn test_resolve_start_path() {
+        let dir = fs::canonicalize(env!("CARGO_MANIFEST_DIR")).unwrap();
+
+        assert_eq!(
+            resolve_start_path(dir.to_str().unwrap()),
+            Ok((dir.clone(), None))
+        );
+
+        let file = dir.join("Cargo.toml");
+        assert_eq!(
+            resolve_start_path(file.to_str().unwrap()),
+            Ok((dir.clone(), Some(file)))
+        );
+
+        let missing = dir.join("does-not-exist");
+        assert!(resolve_start_path(missing.to_str().unwrap())
+            .unwrap_err()
+            .contains("no such file or directory"));
     }
-}
 
-fn draw_str(screen: &mut Screen, x: u16, y: u16, string: &str, style: Style) {
-    for (i, ch) in string.char_indices() {
-        let i: u16 = i.try_into().expect("Should be able to fit into a u16.");
-        screen.set_cell_style(x + i, y, ch, style);
+    #[test]
+    fn test_cd_target() {
+        let current_dir = Path::new("/home/chris/src");
+
+        assert_eq!(
+            cd_target(current_dir, "rolf/.."),
+            PathBuf::from("/home/chris/src")
+        );
+        assert_eq!(
+            cd_target(current_dir, "../docs/./notes"),
+            PathBuf::from("/home/chris/docs/notes")
+        );
+        assert_eq!(cd_target(current_dir, "/etc"), PathBuf::from("/etc"));
+        assert_eq!(
+            cd_target(current_dir, "~/music"),
+            PathBuf::from(os_abstract::get_home_name()).join("music")
+        );
+        assert_eq!(cd_target(current_dir, "~music"), current_dir.join("~music"));
+
+        // NOTE(Chris): Mounted directories keep their URL-like paths
+        assert_eq!(
+            cd_target(Path::new("sftp://chris@host:22/var"), "log").to_str(),
+            Some("sftp://chris@host:22/var/log")
+        );
     }
-}
 
-fn get_sorted_entries<P: AsRef<Path>>(path: P) -> io::Result<Vec<DirEntryInfo>> {
-    let mut entries = std::fs::read_dir(path)?
-        .filter_map(|entry| {
-            let dir_entry = entry.unwrap();
-            let entry_path = dir_entry.path();
-            let metadata = match std::fs::symlink_metadata(&entry_path) {
-                Ok(metadata) => metadata,
-                // TODO(Chris): Handles error in this case in more detail
-                Err(_) => return None,
-            };
+    #[test]
+    fn test_last_dir() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let last_dir_file = data_dir.path().join("rolf/last-dir");
 
-            let file_type = {
-                let curr_file_type = metadata.file_type();
-
-                if curr_file_type.is_file() {
-                    RecordedFileType::File
-                } else if curr_file_type.is_dir() {
-                    RecordedFileType::Directory
-                } else if curr_file_type.is_symlink() {
-                    match fs::canonicalize(&entry_path) {
-                        Ok(canonical_path) => {
-                            let canonical_metadata = fs::metadata(canonical_path).unwrap();
-                            let canonical_file_type = canonical_metadata.file_type();
-
-                            if canonical_file_type.is_file() {
-                                RecordedFileType::FileSymlink
-                            } else if canonical_file_type.is_dir() {
-                                RecordedFileType::DirectorySymlink
-                            } else {
-                                RecordedFileType::Other
-                            }
-                        }
-                        Err(err) => match err.kind() {
-                            io::ErrorKind::NotFound => RecordedFileType::InvalidSymlink,
-                            io::ErrorKind::PermissionDenied => RecordedFileType::Unknown,
-                            _ => {
-                                match err.raw_os_error() {
-                                    // This error code represents "Too many levels of symbolic
-                                    // links."
-                                    // The ErrorKind (FilesystemLoop) for this error requires the
-                                    // unstable io_error_more feature:
-                                    // https://github.com/rust-lang/rust/issues/86442
-                                    Some(40) => RecordedFileType::InvalidSymlink,
-                                    Some(_) | None => {
-                                        panic!(
-                                            "Error finding out file type of {:?}: {:?}",
-                                            &entry_path, err
-                                        );
-                                    }
-                                }
-                            }
-                        },
-                    }
-                } else {
-                    RecordedFileType::Other
-                }
-            };
+        assert_eq!(read_last_dir(&last_dir_file), None);
 
-            Some(DirEntryInfo {
-                dir_entry,
-                metadata,
-                file_type,
-            })
-        })
-        .collect::<Vec<DirEntryInfo>>();
+        let last_dir = data_dir.path().join("project");
+        fs::create_dir(&last_dir).unwrap();
 
-    entries.sort_by(cmp_dir_entry_info);
+        write_last_dir(&last_dir_file, &last_dir).unwrap();
+        assert_eq!(read_last_dir(&last_dir_file), Some(last_dir.clone()));
 
-    Ok(entries)
-}
+        // NOTE(Chris): If the last directory was removed, we fall back to the working directory
+        fs::remove_dir(&last_dir).unwrap();
+        assert_eq!(read_last_dir(&last_dir_file), None);
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_list_templates() {
+        let templates_dir = tempfile::tempdir().unwrap();
+
+        fs::write(templates_dir.path().join("LICENSE"), "MIT").unwrap();
+        fs::write(templates_dir.path().join(".editorconfig"), "").unwrap();
+        fs::create_dir(templates_dir.path().join("project")).unwrap();
+
+        assert_eq!(
+            list_templates(templates_dir.path()).unwrap(),
+            vec![".editorconfig".to_string(), "LICENSE".to_string()]
+        );
+
+        assert!(list_templates(&templates_dir.path().join("missing")).is_err());
+    }
 
     #[test]
     fn test_find_column_pos_1() {
@@ -3354,4 +10454,347 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_find_column_pos_short_column() {
+        // The scroll offset shrinks to fit, instead of underflowing
+        assert_eq!(scroll_offset(28), SCROLL_OFFSET);
+        assert_eq!(scroll_offset(5), 2);
+        assert_eq!(scroll_offset(0), 0);
+
+        let result_column = find_column_pos(
+            50,
+            5,
+            ColumnInfo {
+                starting_index: 0,
+                display_offset: 0,
+            },
+            20,
+        )
+        .unwrap();
+
+        assert_eq!(
+            result_column,
+            ColumnInfo {
+                starting_index: 18,
+                display_offset: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_sorted_entries_ignore_vcs() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // NOTE(Chris): A .gitignore only counts inside a git repository
+        fs::create_dir_all(dir.path().join(".git/info")).unwrap();
+        fs::create_dir(dir.path().join("target")).unwrap();
+        fs::write(dir.path().join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(dir.path().join(".git/info/exclude"), "notes.txt\n").unwrap();
+        for file_name in ["main.rs", "build.log", "notes.txt"] {
+            fs::write(dir.path().join(file_name), "").unwrap();
+        }
+
+        let sorted_names = |ignore_vcs| {
+            let sort_options = SortOptions {
+                ignore_diacritics: false,
+                dotfiles: DotfileOrder::First,
+                ignore_vcs,
+            };
+
+            get_sorted_entries(&vfs::Local, dir.path(), sort_options)
+                .unwrap()
+                .iter()
+                .map(|entry_info| entry_info.dir_entry.file_name().into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(sorted_names(true), [".git", ".gitignore", "main.rs"]);
+        assert_eq!(
+            sorted_names(false),
+            [
+                ".git",
+                "target",
+                ".gitignore",
+                "build.log",
+                "main.rs",
+                "notes.txt"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_get_sorted_entries_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir(dir.path().join("dir")).unwrap();
+        std::os::unix::fs::symlink("dir", dir.path().join("dir-link")).unwrap();
+        std::os::unix::fs::symlink("missing", dir.path().join("missing-link")).unwrap();
+        std::os::unix::fs::symlink("loop-b", dir.path().join("loop-a")).unwrap();
+        std::os::unix::fs::symlink("loop-a", dir.path().join("loop-b")).unwrap();
+
+        let sort_options = SortOptions {
+            ignore_diacritics: false,
+            dotfiles: DotfileOrder::First,
+            ignore_vcs: false,
+        };
+
+        let file_types = get_sorted_entries(&vfs::Local, dir.path(), sort_options)
+            .unwrap()
+            .iter()
+            .map(|entry_info| {
+                (
+                    entry_info.dir_entry.file_name().into_string().unwrap(),
+                    entry_info.file_type,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            file_types,
+            [
+                ("dir".to_string(), RecordedFileType::Directory),
+                ("dir-link".to_string(), RecordedFileType::DirectorySymlink),
+                ("loop-a".to_string(), RecordedFileType::InvalidSymlink),
+                ("loop-b".to_string(), RecordedFileType::InvalidSymlink),
+                ("missing-link".to_string(), RecordedFileType::InvalidSymlink),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cmp_dir_entry_info_dotfiles() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir(dir.path().join(".config")).unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        for file_name in [".bashrc", "bashrc", "Cargo.toml", ".gitignore", "a.txt"] {
+            fs::write(dir.path().join(file_name), "").unwrap();
+        }
+
+        let sorted_names = |dotfiles| {
+            let sort_options = SortOptions {
+                ignore_diacritics: false,
+                dotfiles,
+                ignore_vcs: false,
+            };
+
+            get_sorted_entries(&vfs::Local, dir.path(), sort_options)
+                .unwrap()
+                .iter()
+                .map(|entry_info| entry_info.dir_entry.file_name().into_string().unwrap())
+                .collect::<Vec<_>>()
+        };
+
+        // NOTE(Chris): Directories still come before files, whatever the dotfiles option
+        assert_eq!(
+            sorted_names(DotfileOrder::First),
+            [
+                ".config",
+                "src",
+                ".bashrc",
+                ".gitignore",
+                "a.txt",
+                "bashrc",
+                "Cargo.toml"
+            ]
+        );
+        assert_eq!(
+            sorted_names(DotfileOrder::Mixed),
+            [
+                ".config",
+                "src",
+                "a.txt",
+                ".bashrc",
+                "bashrc",
+                "Cargo.toml",
+                ".gitignore"
+            ]
+        );
+        assert_eq!(
+            sorted_names(DotfileOrder::Last),
+            [
+                "src",
+                ".config",
+                "a.txt",
+                "bashrc",
+                "Cargo.toml",
+                ".bashrc",
+                ".gitignore"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_dir_preview() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+
+        let dir_preview = |marker: &str, command: &str| DirPreview {
+            marker: marker.to_string(),
+            command: command.to_string(),
+        };
+        let dir_previews = [
+            dir_preview("Cargo.toml", "cargo tree"),
+            dir_preview(".git", "git log"),
+            dir_preview("", "ls"),
+        ];
+
+        assert_eq!(
+            find_dir_preview(&dir_previews, dir.path()).map(|dir_preview| &dir_preview.command[..]),
+            Some("git log")
+        );
+        assert_eq!(find_dir_preview(&dir_previews[..1], dir.path()), None);
+
+        assert_eq!(run_dir_preview(&dir_previews[..1], dir.path()), None);
+        assert_eq!(
+            run_dir_preview(&[dir_preview("", "basename \"$f\"")], dir.path()),
+            Some(format!("{}\n", dir.path().file_name().unwrap().to_string_lossy()).into_bytes())
+        );
+        // NOTE(Chris): A command that fails shows why
+        assert_eq!(
+            run_dir_preview(&[dir_preview("", "echo oops >&2; exit 1")], dir.path()),
+            Some(b"oops\n".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("my file.txt"), "'my file.txt'");
+        assert_eq!(shell_quote("it's $HOME"), "'it'\\''s $HOME'");
+    }
+
+    #[test]
+    fn test_find_search_results() {
+        let dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir(dir.path().join("notes")).unwrap();
+        for file_name in ["notes.txt", "notes.md", "todo.txt"] {
+            fs::write(dir.path().join(file_name), "").unwrap();
+        }
+
+        let sort_options = SortOptions {
+            ignore_diacritics: false,
+            dotfiles: DotfileOrder::First,
+            ignore_vcs: false,
+        };
+        let all_entries = get_sorted_entries(&vfs::Local, dir.path(), sort_options).unwrap();
+        let mut shown_entries = get_sorted_entries(&vfs::Local, dir.path(), sort_options).unwrap();
+        filter_entries(&mut shown_entries, Some(&EntryFilter::Files));
+
+        let options = MatchOptions {
+            ignore_case: true,
+            smart_case: true,
+            anchored: false,
+            ignore_diacritics: false,
+        };
+
+        let describe = |results: Vec<SearchResult>| {
+            results
+                .into_iter()
+                .map(|result| (result.index, result.name))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            describe(find_search_results(&shown_entries, None, "notes", options)),
+            [
+                (Some(0), "notes.md".to_string()),
+                (Some(1), "notes.txt".to_string())
+            ]
+        );
+
+        // NOTE(Chris): The directory hidden by the filter comes after the shown matches
+        assert_eq!(
+            describe(find_search_results(
+                &shown_entries,
+                Some(&all_entries),
+                "notes",
+                options
+            )),
+            [
+                (Some(0), "notes.md".to_string()),
+                (Some(1), "notes.txt".to_string()),
+                (None, "notes".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_correct_location_unreadable_parent() {
+        // NOTE(Chris): When we can't read the parent directory, it has no entries to find the
+        // current directory in
+        assert_eq!(
+            find_correct_location(
+                &HashMap::new(),
+                20,
+                Path::new("/home"),
+                &[],
+                Path::new("/home/user"),
+            ),
+            ColumnInfo {
+                starting_index: 0,
+                display_offset: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_format_current_dir() {
+        let home_path = Path::new("/home/user");
+
+        assert_eq!(format_current_dir(Path::new("/home/user"), home_path), "~");
+        assert_eq!(
+            format_current_dir(Path::new("/home/user/src"), home_path),
+            "~/src"
+        );
+        assert_eq!(
+            format_current_dir(Path::new("/usr/lib"), home_path),
+            "/usr/lib"
+        );
+        assert_eq!(format_current_dir(Path::new("/"), home_path), "");
+    }
+
+    #[test]
+    fn test_fit_column_pos() {
+        let column = ColumnInfo {
+            starting_index: 21,
+            display_offset: 17,
+        };
+
+        // Shrinking scrolls the cursor back into view
+        assert_eq!(
+            fit_column_pos(53, 10, column).unwrap(),
+            ColumnInfo {
+                starting_index: 33,
+                display_offset: 5,
+            }
+        );
+
+        // Growing never scrolls the view past the last entry
+        assert_eq!(
+            fit_column_pos(53, 40, column).unwrap(),
+            ColumnInfo {
+                starting_index: 13,
+                display_offset: 25,
+            }
+        );
+
+        // Once every entry fits, the view starts at the top
+        assert_eq!(
+            fit_column_pos(30, 40, column).unwrap(),
+            ColumnInfo {
+                starting_index: 0,
+                display_offset: 29,
+            }
+        );
+
+        // A column too short to show anything still keeps the cursor on its entry
+        let result_column = fit_column_pos(53, 0, column).unwrap();
+        assert_eq!(
+            result_column.starting_index + result_column.display_offset,
+            38
+        );
+    }
 }
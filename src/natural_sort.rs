@@ -1,3 +1,5 @@
+use crate::unicode_fold::fold_str;
+
 use std::cmp::Ordering;
 
 fn is_digit(b: u8) -> bool {
@@ -79,7 +81,19 @@ pub fn cmp_natural(str1: &str, str2: &str) -> Ordering {
         // both of the chunks actually numerical. Thus, these chunks are the ones which will
         // finally determine the order of the strings, so we only need to compare them.
         // return s1[lo1..hi1].cmp(&s2[lo2..hi2]);
-        return str1.to_ascii_lowercase().cmp(&str2.to_ascii_lowercase());
+        return fold_str(str1, true, false).cmp(&fold_str(str2, true, false));
+    }
+}
+
+// Like cmp_natural, but can treat letters with diacritics like their base letters (so "école"
+// sorts next to "ecole" instead of after "zebra"). Names which are otherwise equal fall back to
+// cmp_natural, so that the order is still deterministic.
+pub fn cmp_natural_with(str1: &str, str2: &str, ignore_diacritics: bool) -> Ordering {
+    if ignore_diacritics {
+        cmp_natural(&fold_str(str1, true, true), &fold_str(str2, true, true))
+            .then_with(|| cmp_natural(str1, str2))
+    } else {
+        cmp_natural(str1, str2)
     }
 }
 
@@ -100,6 +114,23 @@ mod tests {
 
         assert_eq!(cmp_natural(".gitignore", ".gitignore"), Ordering::Equal);
 
-        assert_eq!(cmp_natural("class_schedule", "Electron_Background"), Ordering::Less);
+        assert_eq!(
+            cmp_natural("class_schedule", "Electron_Background"),
+            Ordering::Less
+        );
+
+        assert_eq!(cmp_natural("ärger", "Ärger2"), Ordering::Less);
+    }
+
+    #[test]
+    fn cmp_natural_with_works() {
+        assert_eq!(cmp_natural_with("école", "zebra", false), Ordering::Greater);
+        assert_eq!(cmp_natural_with("école", "zebra", true), Ordering::Less);
+
+        assert_eq!(
+            cmp_natural_with("résumé10", "resume9", true),
+            Ordering::Greater
+        );
+        assert_eq!(cmp_natural_with("resume", "résumé", true), Ordering::Less);
     }
 }
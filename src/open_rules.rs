@@ -0,0 +1,110 @@
+// Matches files against the `open` rules declared in rolfrc, like "open *.pdf zathura $f".
+
+use std::path::Path;
+
+pub struct OpenRules {
+    rules: Vec<OpenRule>,
+}
+
+struct OpenRule {
+    // The pattern is split on '*', so that each piece must appear in order
+    pattern_pieces: Vec<String>,
+    command: String,
+}
+
+impl OpenRules {
+    pub fn new() -> Self {
+        OpenRules { rules: vec![] }
+    }
+
+    // NOTE(Chris): Later rules take precedence, so that rules added by rolfrc can be overridden
+    // by rules added afterwards.
+    pub fn add(&mut self, pattern: &str, command: &str) {
+        self.rules.push(OpenRule {
+            pattern_pieces: pattern
+                .to_lowercase()
+                .split('*')
+                .map(String::from)
+                .collect(),
+            command: command.to_string(),
+        });
+    }
+
+    /// Returns the command of the last rule whose pattern matches the path's file name
+    pub fn find_command(&self, path: &Path) -> Option<&str> {
+        let file_name = path.file_name()?.to_str()?.to_lowercase();
+
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| matches_pieces(&rule.pattern_pieces, &file_name))
+            .map(|rule| rule.command.as_str())
+    }
+}
+
+// Checks a name against a pattern which was split on '*'. The first piece must be a prefix, the
+// last piece must be a suffix, and the pieces in between must appear in order.
+fn matches_pieces(pieces: &[String], name: &str) -> bool {
+    if let [only_piece] = pieces {
+        return only_piece == name;
+    }
+
+    let (first, rest) = match pieces.split_first() {
+        Some(split) => split,
+        None => return false,
+    };
+    let (last, middle) = match rest.split_last() {
+        Some(split) => split,
+        None => return false,
+    };
+
+    if name.len() < first.len() + last.len()
+        || !name.starts_with(first.as_str())
+        || !name.ends_with(last.as_str())
+    {
+        return false;
+    }
+
+    let mut remaining = &name[first.len()..name.len() - last.len()];
+
+    for piece in middle {
+        match remaining.find(piece.as_str()) {
+            Some(index) => remaining = &remaining[index + piece.len()..],
+            None => return false,
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_command() {
+        let mut open_rules = OpenRules::new();
+        open_rules.add("*.pdf", "zathura $f");
+        open_rules.add("*.tar*", "atool -l $f");
+        open_rules.add("Makefile", "make -f $f");
+        open_rules.add("*.PDF", "evince $f");
+
+        assert_eq!(
+            open_rules.find_command(Path::new("/tmp/paper.pdf")),
+            Some("evince $f")
+        );
+        assert_eq!(
+            open_rules.find_command(Path::new("a.tar.gz")),
+            Some("atool -l $f")
+        );
+        assert_eq!(
+            open_rules.find_command(Path::new("makefile")),
+            Some("make -f $f")
+        );
+        assert_eq!(open_rules.find_command(Path::new("a.pd")), None);
+        assert_eq!(
+            open_rules.find_command(Path::new(".tar")),
+            Some("atool -l $f")
+        );
+    }
+}
@@ -17,7 +17,8 @@ pub use self::macos::*;
 #[cfg(windows)]
 pub use self::windows::*;
 
-use std::{env::{self, VarError}, path::{PathBuf, Path}};
+use std::{borrow::Cow, env::{self, VarError}, path::{PathBuf, Path}, time::SystemTime};
+use std::{collections::HashSet, fs, io};
 
 pub struct ExtraPermissions {
     pub mode: String, // The mode string "drwxr-xr-x"
@@ -25,10 +26,66 @@ pub struct ExtraPermissions {
     pub group_name: Option<String>,
     pub hard_link_count: Option<u64>,
     pub size: Option<u64>,
-    pub modify_date_time: Option<String>
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    // Not every file system records when a file was created
+    pub created: Option<SystemTime>,
 }
 
-#[derive(Debug, Clone, Copy)]
+// The user running rolf, which is looked up once at startup
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub uid: u32,
+    // The primary group followed by any supplementary groups
+    pub gids: Vec<u32>,
+}
+
+impl CurrentUser {
+    pub fn is_user(&self, uid: u32) -> bool {
+        self.uid == uid
+    }
+
+    pub fn is_in_group(&self, gid: u32) -> bool {
+        self.gids.contains(&gid)
+    }
+}
+
+// The space on the file system holding some path, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskSpace {
+    pub total: u64,
+    pub free: u64,
+    // Less than free when some space is reserved for root
+    pub available: u64,
+}
+
+impl DiskSpace {
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    /// The percentage of the space that's used, rounded up like df's "Use%". The reserved space
+    /// isn't counted, since nobody but root can use it.
+    pub fn used_percent(&self) -> u64 {
+        let usable = self.used() + self.available;
+
+        if usable == 0 {
+            return 0;
+        }
+
+        (self.used() * 100).div_ceil(usable)
+    }
+}
+
+// A phone or camera that the desktop has mounted where we can browse it, like a gvfs mount of an
+// MTP device on Linux
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct WindowPixels {
     pub width: u16,
     pub height: u16,
@@ -36,8 +93,9 @@ pub struct WindowPixels {
 
 fn env_or_dir<K: AsRef<Path>>(env_var: &str, alt_env_base: &str, alt_join_path: K) -> PathBuf {
     match env::var(env_var) {
-        Ok(data_dir) => PathBuf::from(data_dir),
-        Err(VarError::NotPresent) => {
+        // NOTE(Chris): The XDG spec says that empty and relative paths should be ignored
+        Ok(data_dir) if Path::new(&data_dir).is_absolute() => PathBuf::from(data_dir),
+        Ok(_) | Err(VarError::NotPresent) => {
             let mut result = PathBuf::from(env::var(alt_env_base).unwrap());
             result.push(alt_join_path);
             result
@@ -45,3 +103,178 @@ fn env_or_dir<K: AsRef<Path>>(env_var: &str, alt_env_base: &str, alt_join_path:
         Err(_) => panic!("Unable to read data directory"),
     }
 }
+
+/// Whether the file system dir is on tells apart names which only differ in case. This is found by
+/// looking up one of dir's entries with the case of its name swapped, so a directory with no such
+/// entries gets the system's usual answer (case-insensitive on macOS and Windows).
+pub fn is_case_sensitive(dir: &Path) -> bool {
+    let names: HashSet<String> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+            .collect(),
+        Err(_) => HashSet::new(),
+    };
+
+    // NOTE(Chris): If both "a" and "A" are in the directory, it has to be case-sensitive
+    let probe_name = names.iter().find_map(|name| {
+        let swapped = swap_case(name);
+        (swapped != *name).then_some(swapped)
+    });
+
+    match probe_name {
+        Some(swapped) if names.contains(&swapped) => true,
+        Some(swapped) => match dir.join(swapped).symlink_metadata() {
+            Ok(_) => false,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => true,
+            Err(_) => !cfg!(any(windows, target_os = "macos")),
+        },
+        None => !cfg!(any(windows, target_os = "macos")),
+    }
+}
+
+/// Whether two paths name the same file, counting names which only differ in case as the same
+/// when they're on a case-insensitive file system.
+pub fn is_same_path(path1: &Path, path2: &Path) -> bool {
+    if path1 == path2 {
+        return true;
+    }
+
+    if path1.to_string_lossy().to_lowercase() != path2.to_string_lossy().to_lowercase() {
+        return false;
+    }
+
+    path2.parent().is_some_and(|dir| !is_case_sensitive(dir))
+}
+
+fn swap_case(name: &str) -> String {
+    name.chars()
+        .map(|ch| {
+            if ch.is_lowercase() {
+                ch.to_uppercase().collect::<String>()
+            } else {
+                ch.to_lowercase().collect::<String>()
+            }
+        })
+        .collect()
+}
+
+// The most UTF-16 units a path can have in Win32 calls without the \\?\ prefix (MAX_PATH, less the
+// nul at the end)
+const MAX_PATH_LEN: usize = 259;
+
+/// Turns an absolute Windows path that's too long for Win32 calls into its extended-length form,
+/// like \\?\C:\... or \\?\UNC\server\share\..., which can be up to about 32,000 characters.
+/// Shorter (or relative) paths are left as they are.
+// NOTE(Chris): std already does this for the paths it's given, so this is only needed for the
+// paths we pass to Win32 ourselves
+#[cfg_attr(not(windows), allow(dead_code))]
+fn extended_length_path(path: &str) -> Cow<'_, str> {
+    if path.encode_utf16().count() <= MAX_PATH_LEN || path.starts_with(r"\\?\") {
+        return Cow::Borrowed(path);
+    }
+
+    // NOTE(Chris): Extended-length paths are passed along without being normalized, so they
+    // can't use forward slashes
+    let path_with_backslashes = path.replace('/', "\\");
+
+    if let Some(unc_path) = path_with_backslashes.strip_prefix(r"\\") {
+        return Cow::Owned(format!(r"\\?\UNC\{}", unc_path));
+    }
+
+    let is_drive_absolute = matches!(
+        path.as_bytes(),
+        [drive, b':', b'\\' | b'/', ..] if drive.is_ascii_alphabetic()
+    );
+
+    if is_drive_absolute {
+        Cow::Owned(format!(r"\\?\{}", path_with_backslashes))
+    } else {
+        Cow::Borrowed(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_case_sensitive() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Notes.txt"), "").unwrap();
+
+        // NOTE(Chris): Whichever file system the temporary directory is on, the probe has to agree
+        // with what it does
+        let is_sensitive = dir.path().join("nOTES.TXT").symlink_metadata().is_err();
+        assert_eq!(is_case_sensitive(dir.path()), is_sensitive);
+
+        assert!(is_same_path(
+            &dir.path().join("Notes.txt"),
+            &dir.path().join("Notes.txt")
+        ));
+        assert_eq!(
+            is_same_path(&dir.path().join("Notes.txt"), &dir.path().join("notes.txt")),
+            !is_sensitive
+        );
+        assert!(!is_same_path(
+            &dir.path().join("Notes.txt"),
+            &dir.path().join("Other.txt")
+        ));
+
+        assert_eq!(swap_case("Notes.txt"), "nOTES.TXT");
+    }
+
+    #[test]
+    fn test_extended_length_path() {
+        let long_name = "a".repeat(300);
+
+        assert_eq!(extended_length_path(r"C:\Users"), r"C:\Users");
+        assert_eq!(
+            extended_length_path(&format!(r"C:\Users/{}", long_name)),
+            format!(r"\\?\C:\Users\{}", long_name)
+        );
+        assert_eq!(
+            extended_length_path(&format!(r"\\server\share\{}", long_name)),
+            format!(r"\\?\UNC\server\share\{}", long_name)
+        );
+
+        // Already extended, or relative
+        let extended = format!(r"\\?\C:\{}", long_name);
+        assert_eq!(extended_length_path(&extended), extended);
+        assert_eq!(extended_length_path(&long_name), long_name);
+    }
+
+    #[test]
+    fn test_current_user() {
+        let current_user = CurrentUser {
+            uid: 1000,
+            gids: vec![1000, 27],
+        };
+
+        assert!(current_user.is_user(1000));
+        assert!(!current_user.is_user(0));
+
+        assert!(current_user.is_in_group(27));
+        assert!(!current_user.is_in_group(0));
+    }
+
+    #[test]
+    fn test_disk_space_used_percent() {
+        let disk_space = DiskSpace {
+            total: 1000,
+            free: 300,
+            available: 250,
+        };
+
+        assert_eq!(disk_space.used(), 700);
+        // NOTE(Chris): 700 / 950 is 73.7%
+        assert_eq!(disk_space.used_percent(), 74);
+
+        let empty = DiskSpace {
+            total: 0,
+            free: 0,
+            available: 0,
+        };
+
+        assert_eq!(empty.used_percent(), 0);
+    }
+}
@@ -1,7 +1,395 @@
-use std::path::PathBuf;
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 
-use super::env_or_dir;
+use super::{env_or_dir, read_xattrs, Device, ACL_ACCESS_XATTR, ACL_DEFAULT_XATTR, SELINUX_XATTR};
+use crate::unix_users;
 
 pub fn config_dir(project_name: &str) -> PathBuf {
     env_or_dir("XDG_CONFIG_HOME", "HOME", ".config").join(project_name)
 }
+
+pub fn data_dir(project_name: &str) -> PathBuf {
+    env_or_dir("XDG_DATA_HOME", "HOME", ".local/share").join(project_name)
+}
+
+pub fn cache_dir(project_name: &str) -> PathBuf {
+    env_or_dir("XDG_CACHE_HOME", "HOME", ".cache").join(project_name)
+}
+
+// _IOW(0x94, 9, int) from linux/fs.h, which libc doesn't have yet
+const FICLONE: libc::c_ulong = 0x40049409;
+
+/// Makes the destination a copy-on-write clone of the source, which shares the source's data until
+/// either is changed. This only works within a file system which supports it, like Btrfs or XFS.
+/// The destination mustn't exist yet.
+pub fn clone_file(source: &Path, dest: &Path) -> io::Result<()> {
+    let source_file = fs::File::open(source)?;
+    let dest_file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest)?;
+
+    let result =
+        unsafe { libc::ioctl(dest_file.as_raw_fd(), FICLONE as _, source_file.as_raw_fd()) };
+
+    if result != 0 {
+        let err = io::Error::last_os_error();
+
+        drop(dest_file);
+        let _ = fs::remove_file(dest);
+
+        return Err(err);
+    }
+
+    Ok(())
+}
+
+/// Mounts the phones and cameras which gvfs knows about, then lists every one that's mounted.
+/// This runs gio (which comes with GLib), so it can take a moment.
+pub fn mount_devices() -> io::Result<Vec<Device>> {
+    let output = Command::new("gio")
+        .args(["mount", "--list", "--detail"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("unable to run gio: {}", err)))?;
+
+    // NOTE(Chris): Devices which are already mounted just fail to mount again, so errors are
+    // ignored here
+    for activation_root in device_activation_roots(&String::from_utf8_lossy(&output.stdout)) {
+        let _ = Command::new("gio")
+            .args(["mount", &activation_root])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+
+    // NOTE(Chris): gvfs only makes its directory once something is mounted
+    match list_gvfs_devices(&gvfs_dir()) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(vec![]),
+        result => result,
+    }
+}
+
+// Where gvfs's FUSE daemon shows its mounts
+fn gvfs_dir() -> PathBuf {
+    match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) if Path::new(&runtime_dir).is_absolute() => {
+            PathBuf::from(runtime_dir).join("gvfs")
+        }
+        _ => PathBuf::from(format!("/run/user/{}/gvfs", unsafe { libc::getuid() })),
+    }
+}
+
+const DEVICE_SCHEMES: [&str; 2] = ["mtp", "gphoto2"];
+
+// The URIs (like "mtp://Google_Pixel_6_1A2B/") of the phones and cameras listed by
+// "gio mount --list --detail"
+fn device_activation_roots(gio_output: &str) -> Vec<String> {
+    gio_output
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("activation_root="))
+        .filter(|uri| {
+            DEVICE_SCHEMES
+                .iter()
+                .any(|scheme| uri.starts_with(&format!("{}://", scheme)))
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+// The phones and cameras in the gvfs directory, whose mounts are named like
+// "mtp:host=Google_Pixel_6_1A2B"
+fn list_gvfs_devices(gvfs_dir: &Path) -> io::Result<Vec<Device>> {
+    let mut devices = vec![];
+
+    for entry in fs::read_dir(gvfs_dir)? {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let file_name = match file_name.to_str() {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+
+        let is_device = DEVICE_SCHEMES
+            .iter()
+            .any(|scheme| file_name.starts_with(&format!("{}:", scheme)));
+
+        if is_device {
+            let name = file_name
+                .split_once("host=")
+                .map_or(file_name, |(_, host)| host)
+                .to_string();
+
+            devices.push(Device {
+                name,
+                path: entry.path(),
+            });
+        }
+    }
+
+    devices.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(devices)
+}
+
+// Used when the system opener (xdg-open and friends) can't open a file
+pub fn default_fallback_opener() -> Option<&'static str> {
+    Some("mimeopen -n")
+}
+
+/// Describes a file's ACL the way getfacl does, with the owners and SELinux security context
+/// first. A file without an ACL gets the one its mode stands for.
+pub fn read_acl(path: &Path, metadata: &fs::Metadata) -> io::Result<Vec<String>> {
+    let xattrs = read_xattrs(path)?;
+    let xattr = |xattr_name: &str| {
+        xattrs
+            .iter()
+            .find(|(name, _value)| name == xattr_name)
+            .map(|(_name, value)| value.as_slice())
+    };
+
+    let mut lines = vec![
+        format!("# file: {}", path.display()),
+        format!("# owner: {}", user_name(metadata.uid())),
+        format!("# group: {}", group_name(metadata.gid())),
+    ];
+
+    if let Some(context) = xattr(SELINUX_XATTR) {
+        let context = context.strip_suffix(&[0]).unwrap_or(context);
+        lines.push(format!("# context: {}", String::from_utf8_lossy(context)));
+    }
+
+    let access_entries = match xattr(ACL_ACCESS_XATTR) {
+        Some(value) => parse_posix_acl(value).ok_or_else(invalid_acl)?,
+        None => mode_acl(metadata.mode()),
+    };
+    lines.extend(
+        access_entries
+            .iter()
+            .map(|entry| format_acl_entry(entry, "")),
+    );
+
+    if let Some(value) = xattr(ACL_DEFAULT_XATTR) {
+        let default_entries = parse_posix_acl(value).ok_or_else(invalid_acl)?;
+        lines.extend(
+            default_entries
+                .iter()
+                .map(|entry| format_acl_entry(entry, "default:")),
+        );
+    }
+
+    Ok(lines)
+}
+
+// The tags of POSIX ACL entries, from linux/posix_acl.h
+const ACL_USER_OBJ: u16 = 0x01;
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP_OBJ: u16 = 0x04;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_OTHER: u16 = 0x20;
+
+// The version of the xattr format, from linux/posix_acl_xattr.h
+const POSIX_ACL_XATTR_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct AclEntry {
+    tag: u16,
+    perms: u16,
+    // The user or group for ACL_USER and ACL_GROUP entries
+    id: u32,
+}
+
+// An ACL xattr is a little-endian version number, followed by 8-byte entries
+fn parse_posix_acl(value: &[u8]) -> Option<Vec<AclEntry>> {
+    let (version, entries) = value.split_first_chunk::<4>()?;
+
+    if u32::from_le_bytes(*version) != POSIX_ACL_XATTR_VERSION || entries.len() % 8 != 0 {
+        return None;
+    }
+
+    Some(
+        entries
+            .chunks_exact(8)
+            .map(|entry| AclEntry {
+                tag: u16::from_le_bytes([entry[0], entry[1]]),
+                perms: u16::from_le_bytes([entry[2], entry[3]]),
+                id: u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]),
+            })
+            .collect(),
+    )
+}
+
+// The ACL that a file's permission bits are the same as
+fn mode_acl(mode: u32) -> Vec<AclEntry> {
+    [(ACL_USER_OBJ, 6), (ACL_GROUP_OBJ, 3), (ACL_OTHER, 0)]
+        .into_iter()
+        .map(|(tag, shift)| AclEntry {
+            tag,
+            perms: ((mode >> shift) & 0o7) as u16,
+            id: 0,
+        })
+        .collect()
+}
+
+fn format_acl_entry(entry: &AclEntry, prefix: &str) -> String {
+    let qualified = match entry.tag {
+        ACL_USER_OBJ => "user::".to_string(),
+        ACL_USER => format!("user:{}:", user_name(entry.id)),
+        ACL_GROUP_OBJ => "group::".to_string(),
+        ACL_GROUP => format!("group:{}:", group_name(entry.id)),
+        ACL_MASK => "mask::".to_string(),
+        ACL_OTHER => "other::".to_string(),
+        tag => format!("unknown({:#x})::", tag),
+    };
+
+    let perms: String = [(0o4, 'r'), (0o2, 'w'), (0o1, 'x')]
+        .into_iter()
+        .map(|(bit, letter)| if entry.perms & bit != 0 { letter } else { '-' })
+        .collect();
+
+    format!("{}{}{}", prefix, qualified, perms)
+}
+
+// NOTE(Chris): Like getfacl, users and groups without names are shown by their ids
+fn user_name(uid: u32) -> String {
+    unix_users::get_unix_username(uid).unwrap_or_else(|| uid.to_string())
+}
+
+fn group_name(gid: u32) -> String {
+    unix_users::get_unix_groupname(gid).unwrap_or_else(|| gid.to_string())
+}
+
+fn invalid_acl() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "the ACL isn't in a format we know",
+    )
+}
+
+// TODO(Chris): Use GNOME's Sushi previewer when it's running
+pub fn quick_look_command(_path: &Path) -> io::Result<Command> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "quick look is only available on macOS and Windows",
+    ))
+}
+
+// NOTE(Chris): File managers don't agree on a way to select a file, so this only opens the
+// directory it's in
+pub fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command
+        .arg(path.parent().unwrap_or(path))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_posix_acl() {
+        let mut value = 2u32.to_le_bytes().to_vec();
+        for (tag, perms, id) in [
+            (ACL_USER_OBJ, 6u16, u32::MAX),
+            (ACL_USER, 5, 4_000_000),
+            (ACL_GROUP_OBJ, 4, u32::MAX),
+            (ACL_MASK, 5, u32::MAX),
+            (ACL_OTHER, 0, u32::MAX),
+        ] {
+            value.extend(tag.to_le_bytes());
+            value.extend(perms.to_le_bytes());
+            value.extend(id.to_le_bytes());
+        }
+
+        let lines: Vec<String> = parse_posix_acl(&value)
+            .unwrap()
+            .iter()
+            .map(|entry| format_acl_entry(entry, ""))
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "user::rw-",
+                "user:4000000:r-x",
+                "group::r--",
+                "mask::r-x",
+                "other::---"
+            ]
+        );
+
+        assert_eq!(parse_posix_acl(&1u32.to_le_bytes()), None);
+        assert_eq!(parse_posix_acl(&[2, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn test_mode_acl() {
+        let lines: Vec<String> = mode_acl(0o100754)
+            .iter()
+            .map(|entry| format_acl_entry(entry, "default:"))
+            .collect();
+
+        assert_eq!(
+            lines,
+            [
+                "default:user::rwx",
+                "default:group::r-x",
+                "default:other::r--"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_device_activation_roots() {
+        let gio_output = "\
+Volume(0): Pixel 6
+  Type: GProxyVolume (GProxyVolumeMonitorMTP)
+  activation_root=mtp://Google_Pixel_6_1A2B/
+  should_automount=1
+Volume(1): Backup
+  Type: GProxyVolume (GProxyVolumeMonitorUDisks2)
+  activation_root=file:///media/backup
+";
+
+        assert_eq!(
+            device_activation_roots(gio_output),
+            vec!["mtp://Google_Pixel_6_1A2B/".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_list_gvfs_devices() {
+        let gvfs_dir = tempfile::tempdir().unwrap();
+
+        fs::create_dir(gvfs_dir.path().join("mtp:host=Google_Pixel_6_1A2B")).unwrap();
+        fs::create_dir(gvfs_dir.path().join("gphoto2:host=Canon_EOS")).unwrap();
+        fs::create_dir(gvfs_dir.path().join("smb-share:server=nas,share=media")).unwrap();
+
+        assert_eq!(
+            list_gvfs_devices(gvfs_dir.path()).unwrap(),
+            vec![
+                Device {
+                    name: "Canon_EOS".to_string(),
+                    path: gvfs_dir.path().join("gphoto2:host=Canon_EOS"),
+                },
+                Device {
+                    name: "Google_Pixel_6_1A2B".to_string(),
+                    path: gvfs_dir.path().join("mtp:host=Google_Pixel_6_1A2B"),
+                },
+            ]
+        );
+    }
+}
@@ -1,8 +1,89 @@
 use std::env;
-use std::path::PathBuf;
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::Device;
 
 pub fn config_dir(project_name: &str) -> PathBuf {
     PathBuf::from(env::var("HOME").unwrap())
         .join("Library/Application Support")
         .join(project_name)
 }
+
+pub fn data_dir(project_name: &str) -> PathBuf {
+    config_dir(project_name)
+}
+
+pub fn cache_dir(project_name: &str) -> PathBuf {
+    PathBuf::from(env::var("HOME").unwrap())
+        .join("Library/Caches")
+        .join(project_name)
+}
+
+// From sys/clonefile.h, which libc doesn't have
+const CLONE_NOFOLLOW: u32 = 0x0001;
+
+/// Makes the destination a copy-on-write clone of the source, which shares the source's data until
+/// either is changed. This only works within an APFS volume. The destination mustn't exist yet.
+pub fn clone_file(source: &Path, dest: &Path) -> io::Result<()> {
+    let to_cstring = |path: &Path| {
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+    };
+
+    let source = to_cstring(source)?;
+    let dest = to_cstring(dest)?;
+
+    if unsafe { libc::clonefile(source.as_ptr(), dest.as_ptr(), CLONE_NOFOLLOW) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+// TODO(Chris): List phones and cameras, which only work through gvfs on Linux for now
+pub fn mount_devices() -> io::Result<Vec<Device>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "phones and cameras can only be browsed on Linux",
+    ))
+}
+
+// NOTE(Chris): `open` already handles every file that has an associated application
+pub fn default_fallback_opener() -> Option<&'static str> {
+    None
+}
+
+// Quick Look's preview panel, which stays open until it's closed
+pub fn quick_look_command(path: &Path) -> io::Result<Command> {
+    let mut command = Command::new("qlmanage");
+
+    command
+        .arg("-p")
+        .arg(path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    Ok(command)
+}
+
+// TODO(Chris): Show macOS's ACLs, which come from acl_get_link_np rather than an xattr
+pub fn read_acl(_path: &Path, _metadata: &fs::Metadata) -> io::Result<Vec<String>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "ACLs can only be shown on Linux",
+    ))
+}
+
+// Shows the file selected in a Finder window
+pub fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path).stdin(Stdio::null());
+
+    command
+}
@@ -1,16 +1,21 @@
 // This module implements functions that should work on both macOS and Linux
 
 use crate::WindowPixels;
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use std::io;
 
 use crate::strmode;
 use crate::unix_users;
-use std::fs::Metadata;
+use std::ffi::CString;
+use std::fs::{self, FileTimes, Metadata};
+use std::os::raw::{c_char, c_void};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::MetadataExt;
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::sync::Once;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::ExtraPermissions;
+use super::{CurrentUser, DiskSpace, ExtraPermissions};
 
 use libc::c_int;
 
@@ -21,24 +26,52 @@ pub fn get_strmode(metadata: &Metadata) -> String {
 }
 
 pub fn get_extra_perms(metadata: &Metadata) -> ExtraPermissions {
-    let naive = NaiveDateTime::from_timestamp(
-        metadata.mtime(),
-        27, // Apparently 27 leap seconds have passed since 1972
-    );
-
-    let date_time: DateTime<Local> =
-        DateTime::from_utc(naive, Local.offset_from_local_datetime(&naive).unwrap());
-
     ExtraPermissions {
         mode: get_strmode(metadata),
-        user_name: unix_users::get_unix_groupname(metadata.gid()),
-        group_name: unix_users::get_unix_username(metadata.uid()),
+        user_name: unix_users::get_unix_username(metadata.uid()),
+        group_name: unix_users::get_unix_groupname(metadata.gid()),
         hard_link_count: Some(metadata.nlink()),
         size: Some(metadata.size()),
-        modify_date_time: Some(date_time.format("%c").to_string()),
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        // NOTE(Chris): The standard library gets this from statx on Linux and from st_birthtime
+        // on macOS, failing if the file system doesn't record it
+        created: metadata.created().ok(),
     }
 }
 
+/// Formats a time the way the user's locale (from LC_TIME or LANG) writes dates and times.
+pub fn format_locale_time(time: SystemTime) -> String {
+    static SET_LOCALE: Once = Once::new();
+
+    // NOTE(Chris): Programs start in the "C" locale until they ask for the user's
+    SET_LOCALE.call_once(|| unsafe {
+        libc::setlocale(libc::LC_TIME, c"".as_ptr());
+    });
+
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => since_epoch.as_secs() as libc::time_t,
+        Err(err) => -(err.duration().as_secs() as libc::time_t),
+    };
+
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return String::new();
+    }
+
+    let mut buf = [0u8; 128];
+    let len = unsafe {
+        strftime(
+            buf.as_mut_ptr() as *mut c_char,
+            buf.len(),
+            c"%c".as_ptr(),
+            &tm,
+        )
+    };
+
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
 // A possibly-safe wrapper around an ioctl call with TIOCGWINSZ.
 // Gets the width and height of the terminal in pixels.
 pub fn get_win_pixels() -> std::result::Result<WindowPixels, io::Error> {
@@ -83,14 +116,311 @@ pub fn get_file_id(metadata: &Metadata) -> u64 {
     metadata.ino()
 }
 
+// The device that the file is on, which together with its file id (the inode) tells it apart
+pub fn get_device_id(metadata: &Metadata) -> u64 {
+    metadata.dev()
+}
+
+// Whether the file is a regular file which anyone can execute
+pub fn is_executable(_path: &Path, metadata: &Metadata) -> bool {
+    metadata.is_file() && metadata.mode() & 0o111 != 0
+}
+
+// Whether a symlink couldn't be resolved because of where it points (in a loop), rather than
+// because of an error reading it
+pub fn is_unresolvable_link_error(err: &io::Error) -> bool {
+    // NOTE(Chris): The ErrorKind for this (FilesystemLoop) requires the unstable io_error_more
+    // feature: https://github.com/rust-lang/rust/issues/86442
+    err.raw_os_error() == Some(libc::ELOOP)
+}
+
+// Returns the uid and gid of the file's owner
+pub fn get_owner_ids(metadata: &Metadata) -> Option<(u32, u32)> {
+    Some((metadata.uid(), metadata.gid()))
+}
+
+pub fn get_current_user() -> Option<CurrentUser> {
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+
+    let mut gids = vec![gid];
+
+    unsafe {
+        // NOTE(Chris): Passing a size of 0 just returns the number of supplementary groups
+        let group_count = libc::getgroups(0, std::ptr::null_mut());
+
+        if group_count > 0 {
+            let mut groups = vec![0; group_count as usize];
+            let group_count = libc::getgroups(group_count, groups.as_mut_ptr());
+
+            if group_count > 0 {
+                groups.truncate(group_count as usize);
+                gids.extend(groups.into_iter().filter(|&group| group != gid));
+            }
+        }
+    }
+
+    Some(CurrentUser { uid, gids })
+}
+
+// Gets the size of the file system that the path is on, and how much of it is free
+pub fn get_disk_space(path: &Path) -> io::Result<DiskSpace> {
+    let path = path_to_cstring(path)?;
+
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // NOTE(Chris): Block counts are in units of f_frsize, not f_bsize
+    let block_size = stat.f_frsize as u64;
+
+    Ok(DiskSpace {
+        total: stat.f_blocks as u64 * block_size,
+        free: stat.f_bfree as u64 * block_size,
+        available: stat.f_bavail as u64 * block_size,
+    })
+}
+
+// Whether the current user can create and remove files in the directory. This also catches
+// read-only file systems, which the permission bits don't show.
+pub fn is_writable_dir(path: &Path) -> bool {
+    match path_to_cstring(path) {
+        Ok(path) => unsafe { libc::access(path.as_ptr(), libc::W_OK) == 0 },
+        Err(_) => false,
+    }
+}
+
+// The permission bits that the attrs command edits, from the owner's read bit to everyone else's
+// execute bit
+pub const ATTRIBUTE_LETTERS: &str = "rwxrwxrwx";
+
+pub fn get_attributes(metadata: &Metadata) -> u32 {
+    metadata.permissions().mode() & 0o777
+}
+
+// Replaces the permission bits, keeping the others (like setuid and the sticky bit)
+pub fn set_attributes(path: &Path, metadata: &Metadata, attributes: u32) -> io::Result<()> {
+    let mode = (metadata.permissions().mode() & !0o777) | (attributes & 0o777);
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+// Sets a file's (or directory's) access and modification times
+pub fn set_file_times(path: &Path, times: FileTimes) -> io::Result<()> {
+    // NOTE(Chris): Only the owner can set the times, and they don't need write access to do so
+    fs::File::open(path)?.set_times(times)
+}
+
+/// Copies every extended attribute of the source to the destination, without following symlinks.
+/// Attributes which the destination's file system doesn't support (or which we aren't allowed to
+/// set, like most in the "security" namespace) are skipped.
+pub fn copy_xattrs(source: &Path, dest: &Path) -> io::Result<()> {
+    let source = path_to_cstring(source)?;
+    let dest = path_to_cstring(dest)?;
+
+    for name in xattr_names(&source)? {
+        let value = read_xattr_value(|buf, size| unsafe { get_xattr(&source, &name, buf, size) })?;
+
+        if unsafe { set_xattr(&dest, &name, &value) } != 0 {
+            let err = io::Error::last_os_error();
+
+            match err.kind() {
+                io::ErrorKind::Unsupported | io::ErrorKind::PermissionDenied => (),
+                _ => return Err(err),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads every extended attribute of a file (without following symlinks), as names and values.
+pub fn read_xattrs(path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let path = path_to_cstring(path)?;
+
+    xattr_names(&path)?
+        .into_iter()
+        .map(|name| {
+            let value =
+                read_xattr_value(|buf, size| unsafe { get_xattr(&path, &name, buf, size) })?;
+
+            Ok((name.to_string_lossy().into_owned(), value))
+        })
+        .collect()
+}
+
+// The extended attributes where Linux keeps a file's ACLs and its SELinux security context
+pub const ACL_ACCESS_XATTR: &str = "system.posix_acl_access";
+pub const ACL_DEFAULT_XATTR: &str = "system.posix_acl_default";
+pub const SELINUX_XATTR: &str = "security.selinux";
+
+/// The character which ls -l puts after the mode: + for a file with an ACL, . for one with only
+/// an SELinux security context, and @ (like on macOS) for one with any other extended attributes.
+pub fn mode_suffix(path: &Path) -> Option<char> {
+    let path = path_to_cstring(path).ok()?;
+    let names = xattr_names(&path).ok()?;
+
+    let has_xattr = |xattr_name: &str| {
+        names
+            .iter()
+            .any(|name| name.as_bytes() == xattr_name.as_bytes())
+    };
+
+    if has_xattr(ACL_ACCESS_XATTR) || has_xattr(ACL_DEFAULT_XATTR) {
+        Some('+')
+    } else if has_xattr(SELINUX_XATTR) {
+        Some('.')
+    } else if !names.is_empty() {
+        Some('@')
+    } else {
+        None
+    }
+}
+
+/// Removes an extended attribute from a file, returning whether it had one by that name.
+pub fn remove_xattr(path: &Path, name: &str) -> io::Result<bool> {
+    let path = path_to_cstring(path)?;
+    let name =
+        CString::new(name).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    if unsafe { remove_xattr_raw(&path, &name) } == 0 {
+        return Ok(true);
+    }
+
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(NO_XATTR) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
+
+// The names of a file's extended attributes, where a file system without them has none
+fn xattr_names(path: &CString) -> io::Result<Vec<CString>> {
+    let names = match read_xattr_value(|buf, size| unsafe { list_xattrs(path, buf, size) }) {
+        Ok(names) => names,
+        Err(err) if err.kind() == io::ErrorKind::Unsupported => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    Ok(names
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| CString::new(name).expect("xattr names are separated by nul bytes"))
+        .collect())
+}
+
+fn path_to_cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+}
+
+// Calls an xattr function which fills a buffer, first asking it how big the buffer needs to be
+fn read_xattr_value(read: impl Fn(*mut c_void, usize) -> isize) -> io::Result<Vec<u8>> {
+    loop {
+        let size = read(std::ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buf = vec![0u8; size as usize];
+        let len = read(buf.as_mut_ptr() as *mut c_void, buf.len());
+
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Ok(buf);
+        }
+
+        // NOTE(Chris): The value grew between the two calls, so we try again
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ERANGE) {
+            return Err(err);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn list_xattrs(path: &CString, buf: *mut c_void, size: usize) -> isize {
+    libc::llistxattr(path.as_ptr(), buf as *mut c_char, size)
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn get_xattr(path: &CString, name: &CString, buf: *mut c_void, size: usize) -> isize {
+    libc::lgetxattr(path.as_ptr(), name.as_ptr(), buf, size)
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn set_xattr(path: &CString, name: &CString, value: &[u8]) -> c_int {
+    libc::lsetxattr(
+        path.as_ptr(),
+        name.as_ptr(),
+        value.as_ptr() as *const c_void,
+        value.len(),
+        0,
+    )
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn remove_xattr_raw(path: &CString, name: &CString) -> c_int {
+    libc::lremovexattr(path.as_ptr(), name.as_ptr())
+}
+
+// The error for an attribute that the file doesn't have
+#[cfg(target_os = "linux")]
+const NO_XATTR: c_int = libc::ENODATA;
+
+#[cfg(target_os = "macos")]
+unsafe fn list_xattrs(path: &CString, buf: *mut c_void, size: usize) -> isize {
+    libc::listxattr(
+        path.as_ptr(),
+        buf as *mut c_char,
+        size,
+        libc::XATTR_NOFOLLOW,
+    )
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn get_xattr(path: &CString, name: &CString, buf: *mut c_void, size: usize) -> isize {
+    libc::getxattr(
+        path.as_ptr(),
+        name.as_ptr(),
+        buf,
+        size,
+        0,
+        libc::XATTR_NOFOLLOW,
+    )
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn set_xattr(path: &CString, name: &CString, value: &[u8]) -> c_int {
+    libc::setxattr(
+        path.as_ptr(),
+        name.as_ptr(),
+        value.as_ptr() as *const c_void,
+        value.len(),
+        0,
+        libc::XATTR_NOFOLLOW,
+    )
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn remove_xattr_raw(path: &CString, name: &CString) -> c_int {
+    libc::removexattr(path.as_ptr(), name.as_ptr(), libc::XATTR_NOFOLLOW)
+}
+
+#[cfg(target_os = "macos")]
+const NO_XATTR: c_int = libc::ENOATTR;
 
 unsafe fn errno() -> i32 {
     let errno_location = errno_location();
-    (*errno_location) as i32
+    *errno_location
 }
 
 extern "C" {
     #[cfg_attr(target_os = "macos", link_name = "__error")]
     #[cfg_attr(target_os = "linux", link_name = "__errno_location")]
     fn errno_location() -> *mut c_int;
+
+    // NOTE(Chris): This version of libc doesn't have strftime
+    fn strftime(s: *mut c_char, max: usize, format: *const c_char, tm: *const libc::tm) -> usize;
 }
@@ -1,8 +1,9 @@
 use windows::Win32::Foundation::GetLastError;
 use windows::Win32::Foundation::HWND;
 use windows::Win32::Foundation::RECT;
-use windows::Win32::Foundation::{BOOL, FILETIME, SYSTEMTIME};
-use windows::Win32::System::Time::FileTimeToSystemTime;
+use windows::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+use windows::Win32::Storage::FileSystem::SetFileAttributesW;
+use windows::Win32::Storage::FileSystem::FILE_FLAGS_AND_ATTRIBUTES;
 use windows::Win32::UI::Input::KeyboardAndMouse::GetActiveWindow;
 use windows::Win32::UI::WindowsAndMessaging::GetClientRect;
 use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
@@ -10,12 +11,15 @@ use windows::Win32::UI::WindowsAndMessaging::GetForegroundWindow;
 use crate::WindowPixels;
 use std::io;
 
-use std::fs::Metadata;
-use std::mem::MaybeUninit;
-use std::os::windows::fs::MetadataExt;
-use std::path::PathBuf;
+use std::fs::{self, FileTimes, Metadata};
+use std::os::windows::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
 
-use super::ExtraPermissions;
+use chrono::{DateTime, Local};
+
+use super::{extended_length_path, CurrentUser, Device, DiskSpace, ExtraPermissions};
 
 pub fn get_extra_perms(metadata: &Metadata) -> ExtraPermissions {
     let mode = {
@@ -24,9 +28,11 @@ pub fn get_extra_perms(metadata: &Metadata) -> ExtraPermissions {
         let perms = metadata.permissions();
         let file_attributes = metadata.file_attributes();
 
-        if metadata.is_dir() {
+        // NOTE(Chris): Junctions (and symlinks to directories) are reparse points rather than
+        // directories to std, but they still have the directory attribute
+        if file_attributes & FILE_ATTRIBUTE_DIRECTORY != 0 {
             result.push_str("d-");
-        } else if metadata.is_file() {
+        } else {
             result.push_str("-a");
         }
 
@@ -51,8 +57,13 @@ pub fn get_extra_perms(metadata: &Metadata) -> ExtraPermissions {
             result.push('-');
         }
 
+        // NOTE(Chris): std counts junctions as symlinks, since both are "name surrogate" reparse
+        // points. Other reparse points, like OneDrive's placeholders, stand in for a file that
+        // may only be online.
         if metadata.is_symlink() {
             result.push('l');
+        } else if file_attributes & FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS != 0 {
+            result.push('o');
         } else {
             result.push('-');
         }
@@ -60,45 +71,15 @@ pub fn get_extra_perms(metadata: &Metadata) -> ExtraPermissions {
         result
     };
 
-    // Converted from Windows FILETIME struct automatically
-    let last_write_time = metadata.last_write_time();
-
-    // https://docs.microsoft.com/en-us/windows/win32/api/minwinbase/ns-minwinbase-filetime
-    // https://docs.microsoft.com/en-us/windows/win32/api/timezoneapi/nf-timezoneapi-filetimetosystemtime
-    // https://docs.microsoft.com/en-us/windows/win32/api/minwinbase/ns-minwinbase-systemtime
-    let file_time = unsafe {
-        let mut result: SYSTEMTIME = MaybeUninit::zeroed().assume_init();
-        let err =
-            FileTimeToSystemTime(&last_write_time as *const _ as *const FILETIME, &mut result);
-
-        if err == BOOL(0) {
-            panic!(
-                "Failed to convert file time to system time with error code: {}",
-                err.0
-            );
-        } else {
-            result
-        }
-    };
-
-    let modify_date_time = format!(
-        "{} {} {:2} {:>2}:{:0>2}:{:0>2} {}",
-        week_day(file_time.wDayOfWeek),
-        month(file_time.wMonth),
-        file_time.wDay,
-        file_time.wHour,
-        file_time.wMinute,
-        file_time.wSecond,
-        file_time.wYear,
-    );
-
     ExtraPermissions {
         mode,
         user_name: None,
         group_name: None,
         hard_link_count: None,
         size: None,
-        modify_date_time: Some(modify_date_time),
+        modified: metadata.modified().ok(),
+        accessed: metadata.accessed().ok(),
+        created: metadata.created().ok(),
     }
 }
 
@@ -149,35 +130,173 @@ pub fn get_home_name() -> String {
     std::env::var("USERPROFILE").unwrap()
 }
 
-fn week_day(day: u16) -> &'static str {
-    match day {
-        0 => "Sun",
-        1 => "Mon",
-        2 => "Tue",
-        3 => "Wed",
-        4 => "Thu",
-        5 => "Fri",
-        6 => "Sat",
-        _ => unreachable!(),
+// Windows has no execute permission, so programs are recognized by their extensions instead
+pub fn is_executable(path: &Path, metadata: &Metadata) -> bool {
+    const EXECUTABLE_EXTENSIONS: [&str; 5] = ["exe", "com", "bat", "cmd", "ps1"];
+
+    metadata.is_file()
+        && path.extension().is_some_and(|extension| {
+            EXECUTABLE_EXTENSIONS
+                .iter()
+                .any(|executable| extension.eq_ignore_ascii_case(executable))
+        })
+}
+
+// TODO(Chris): Compare owners on Windows using security identifiers
+pub fn get_owner_ids(_metadata: &Metadata) -> Option<(u32, u32)> {
+    None
+}
+
+pub fn get_current_user() -> Option<CurrentUser> {
+    None
+}
+
+// TODO(Chris): Check the directory's access control list. Its read-only attribute doesn't stop
+// files from being created in it, so it isn't checked.
+pub fn is_writable_dir(_path: &Path) -> bool {
+    true
+}
+
+// Gets the size of the volume that the path is on, and how much of it is free
+pub fn get_disk_space(path: &Path) -> io::Result<DiskSpace> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path isn't valid unicode"))?;
+    let path = extended_length_path(path);
+
+    let mut available = 0;
+    let mut total = 0;
+    let mut free = 0;
+
+    // NOTE(Chris): The space available to us can be less than the free space when disk quotas are
+    // in use
+    let is_ok =
+        unsafe { GetDiskFreeSpaceExW(&*path, &mut available, &mut total, &mut free) }.as_bool();
+    if !is_ok {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(DiskSpace {
+        total,
+        free,
+        available,
+    })
+}
+
+// The attributes that the attrs command edits: read-only and hidden
+pub const ATTRIBUTE_LETTERS: &str = "rh";
+
+// https://docs.microsoft.com/en-us/windows/win32/fileio/file-attribute-constants
+const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+// Set on cloud files (like OneDrive's) whose contents haven't been downloaded yet
+const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+
+pub fn get_attributes(metadata: &Metadata) -> u32 {
+    let file_attributes = metadata.file_attributes();
+
+    let mut attributes = 0;
+    if file_attributes & FILE_ATTRIBUTE_READONLY != 0 {
+        attributes |= 0b10;
+    }
+    if file_attributes & FILE_ATTRIBUTE_HIDDEN != 0 {
+        attributes |= 0b01;
     }
+
+    attributes
 }
 
-fn month(month_val: u16) -> &'static str {
-    match month_val {
-        1 => "Jan",
-        2 => "Feb",
-        3 => "Mar",
-        4 => "Apr",
-        5 => "May",
-        6 => "Jun",
-        7 => "Jul",
-        8 => "Aug",
-        9 => "Sep",
-        10 => "Oct",
-        11 => "Nov",
-        12 => "Dec",
-        _ => unreachable!(),
+// Whether a link couldn't be resolved because of where it points, like to a drive which isn't
+// there anymore, rather than because of an error reading it
+pub fn is_unresolvable_link_error(err: &io::Error) -> bool {
+    // https://docs.microsoft.com/en-us/windows/win32/debug/system-error-codes
+    matches!(
+        err.raw_os_error(),
+        // ERROR_NOT_READY, ERROR_BAD_NETPATH, ERROR_BAD_NET_NAME, ERROR_CANT_ACCESS_FILE,
+        // ERROR_CANT_RESOLVE_FILENAME, ERROR_INVALID_REPARSE_DATA
+        Some(21 | 53 | 67 | 1920 | 1921 | 4392)
+    )
+}
+
+// Replaces the read-only and hidden attributes, keeping the others
+pub fn set_attributes(path: &Path, metadata: &Metadata, attributes: u32) -> io::Result<()> {
+    let path = path
+        .to_str()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path isn't valid unicode"))?;
+    let path = extended_length_path(path);
+
+    let mut file_attributes =
+        metadata.file_attributes() & !(FILE_ATTRIBUTE_READONLY | FILE_ATTRIBUTE_HIDDEN);
+    if attributes & 0b10 != 0 {
+        file_attributes |= FILE_ATTRIBUTE_READONLY;
+    }
+    if attributes & 0b01 != 0 {
+        file_attributes |= FILE_ATTRIBUTE_HIDDEN;
+    }
+
+    // NOTE(Chris): FILE_ATTRIBUTE_NORMAL is only valid on its own, and stands for no attributes
+    if file_attributes == 0 {
+        file_attributes = FILE_ATTRIBUTE_NORMAL;
+    }
+
+    let is_ok =
+        unsafe { SetFileAttributesW(&*path, FILE_FLAGS_AND_ATTRIBUTES(file_attributes)) }.as_bool();
+    if !is_ok {
+        return Err(io::Error::last_os_error());
     }
+
+    Ok(())
+}
+
+// Sets a file's (or directory's) access and modification times
+pub fn set_file_times(path: &Path, times: FileTimes) -> io::Result<()> {
+    // NOTE(Chris): FILE_WRITE_ATTRIBUTES is all we need, and FILE_FLAG_BACKUP_SEMANTICS lets
+    // directories be opened too
+    fs::OpenOptions::new()
+        .access_mode(0x100)
+        .custom_flags(0x02000000)
+        .open(path)?
+        .set_times(times)
+}
+
+// TODO(Chris): Use the user's date format with GetDateFormatEx and GetTimeFormatEx
+pub fn format_locale_time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).format("%c").to_string()
+}
+
+// TODO(Chris): Clone files on ReFS with FSCTL_DUPLICATE_EXTENTS_TO_FILE
+pub fn clone_file(_source: &Path, _dest: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "cloning files isn't supported on Windows",
+    ))
+}
+
+// TODO(Chris): Copy alternate data streams, which are the closest thing Windows has
+pub fn copy_xattrs(_source: &Path, _dest: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+// NOTE(Chris): Windows files have no extended attributes (in the Unix sense) to show or remove
+pub fn read_xattrs(_path: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    Ok(vec![])
+}
+
+pub fn mode_suffix(_path: &Path) -> Option<char> {
+    None
+}
+
+pub fn remove_xattr(_path: &Path, _name: &str) -> io::Result<bool> {
+    Ok(false)
+}
+
+pub fn read_acl(_path: &Path, _metadata: &Metadata) -> io::Result<Vec<String>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "ACLs can only be shown on Linux",
+    ))
 }
 
 pub fn config_dir(project_name: &str) -> PathBuf {
@@ -185,3 +304,46 @@ pub fn config_dir(project_name: &str) -> PathBuf {
         .join("AppData\\Roaming")
         .join(project_name)
 }
+
+pub fn data_dir(project_name: &str) -> PathBuf {
+    config_dir(project_name)
+}
+
+// NOTE(Chris): Caches belong in Local rather than Roaming, so that they aren't synced between
+// machines
+pub fn cache_dir(project_name: &str) -> PathBuf {
+    PathBuf::from(std::env::var("USERPROFILE").unwrap())
+        .join("AppData\\Local")
+        .join(project_name)
+        .join("cache")
+}
+
+// TODO(Chris): List phones and cameras, which only work through gvfs on Linux for now
+pub fn mount_devices() -> io::Result<Vec<Device>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "phones and cameras can only be browsed on Linux",
+    ))
+}
+
+// NOTE(Chris): `start` already handles every file that has an associated application
+pub fn default_fallback_opener() -> Option<&'static str> {
+    None
+}
+
+// NOTE(Chris): PowerToys Peek can only be opened with its shortcut, so this shows the file in File
+// Explorer, where Peek (or the preview pane's handler) can preview it
+pub fn quick_look_command(path: &Path) -> io::Result<Command> {
+    Ok(reveal_command(path))
+}
+
+// Shows the file selected in a File Explorer window
+pub fn reveal_command(path: &Path) -> Command {
+    let mut select_arg = std::ffi::OsString::from("/select,");
+    select_arg.push(path);
+
+    let mut command = Command::new("explorer.exe");
+    command.arg(select_arg);
+
+    command
+}
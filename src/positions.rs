@@ -0,0 +1,177 @@
+// Remembers where the cursor was in recently visited directories across sessions, by keeping them
+// in the "positions" file in the data directory. Each line is a directory, the name of the file the
+// cursor was on, and the column's starting index and display offset, separated by tabs. The lines
+// go from least to most recently visited, and only the most recent POSITIONS_CAPACITY are kept.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const POSITIONS_CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedPosition {
+    pub file_name: String,
+    pub starting_index: u16,
+    pub display_offset: u16,
+}
+
+pub struct SavedPositions {
+    path: PathBuf,
+    // Read from the file the first time it's needed, so that starting up doesn't wait on it
+    positions: Option<Vec<(PathBuf, SavedPosition)>>,
+}
+
+impl SavedPositions {
+    pub fn new(path: PathBuf) -> Self {
+        SavedPositions {
+            path,
+            positions: None,
+        }
+    }
+
+    pub fn get(&mut self, dir: &Path) -> Option<&SavedPosition> {
+        self.load()
+            .iter()
+            .rev()
+            .find(|(saved_dir, _position)| saved_dir == dir)
+            .map(|(_dir, position)| position)
+    }
+
+    /// Remembers the position in dir as the most recent one, forgetting the least recent position
+    /// if there are too many.
+    pub fn insert(&mut self, dir: PathBuf, position: SavedPosition) {
+        let positions = self.load();
+
+        positions.retain(|(saved_dir, _position)| *saved_dir != dir);
+        positions.push((dir, position));
+
+        if positions.len() > POSITIONS_CAPACITY {
+            positions.drain(..positions.len() - POSITIONS_CAPACITY);
+        }
+    }
+
+    pub fn write(&self) -> io::Result<()> {
+        // NOTE(Chris): Nothing can have changed if the file was never read
+        let positions = match &self.positions {
+            Some(positions) => positions,
+            None => return Ok(()),
+        };
+
+        let mut contents = String::new();
+
+        for (dir, position) in positions {
+            // TODO(Chris): Store paths which aren't valid unicode, too
+            let dir = match dir.to_str() {
+                Some(dir) if !dir.contains(['\t', '\n']) => dir,
+                _ => continue,
+            };
+
+            if position.file_name.contains(['\t', '\n']) {
+                continue;
+            }
+
+            contents.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                dir, position.file_name, position.starting_index, position.display_offset
+            ));
+        }
+
+        if let Some(data_dir) = self.path.parent() {
+            fs::create_dir_all(data_dir)?;
+        }
+
+        fs::write(&self.path, contents)
+    }
+
+    fn load(&mut self) -> &mut Vec<(PathBuf, SavedPosition)> {
+        let path = &self.path;
+
+        // NOTE(Chris): A missing or unreadable file just means there's nothing to restore
+        self.positions.get_or_insert_with(|| {
+            fs::read_to_string(path)
+                .map(|contents| parse_positions(&contents))
+                .unwrap_or_default()
+        })
+    }
+}
+
+// Skips any lines which aren't in the right format, rather than losing every position
+fn parse_positions(contents: &str) -> Vec<(PathBuf, SavedPosition)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+
+            let dir = PathBuf::from(fields.next()?);
+            let position = SavedPosition {
+                file_name: fields.next()?.to_string(),
+                starting_index: fields.next()?.parse().ok()?,
+                display_offset: fields.next()?.parse().ok()?,
+            };
+
+            if fields.next().is_some() || !dir.is_absolute() {
+                return None;
+            }
+
+            Some((dir, position))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(file_name: &str, display_offset: u16) -> SavedPosition {
+        SavedPosition {
+            file_name: file_name.to_string(),
+            starting_index: 0,
+            display_offset,
+        }
+    }
+
+    #[test]
+    fn test_saved_positions() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let path = data_dir.path().join("rolf/positions");
+
+        let mut positions = SavedPositions::new(path.clone());
+        assert_eq!(positions.get(Path::new("/tmp")), None);
+
+        positions.insert(PathBuf::from("/tmp"), position("a", 1));
+        positions.insert(PathBuf::from("/home"), position("b", 2));
+        positions.insert(PathBuf::from("/tmp"), position("c", 3));
+        positions.write().unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "/home\tb\t0\t2\n/tmp\tc\t0\t3\n"
+        );
+
+        let mut positions = SavedPositions::new(path);
+        assert_eq!(positions.get(Path::new("/tmp")), Some(&position("c", 3)));
+        assert_eq!(positions.get(Path::new("/home")), Some(&position("b", 2)));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let mut positions = SavedPositions::new(data_dir.path().join("positions"));
+
+        for index in 0..=POSITIONS_CAPACITY {
+            positions.insert(PathBuf::from(format!("/dir{}", index)), position("a", 0));
+        }
+
+        assert_eq!(positions.get(Path::new("/dir0")), None);
+        assert!(positions.get(Path::new("/dir1")).is_some());
+    }
+
+    #[test]
+    fn test_parse_positions() {
+        assert_eq!(
+            parse_positions("/tmp\ta\t0\t1\nrelative\ta\t0\t1\n/tmp\tb\tx\t1\n/tmp\tc\t0\n"),
+            vec![(PathBuf::from("/tmp"), position("a", 1))]
+        );
+    }
+}
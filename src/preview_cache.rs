@@ -0,0 +1,151 @@
+// Keeps recently generated previews (like decoded images and highlighted text), so that going back
+// to a file shows its preview right away. The previews are kept within a memory budget, past which
+// the least recently used ones are dropped.
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+
+pub struct PreviewCache<K, V> {
+    // The most bytes the kept previews may take up
+    budget: usize,
+    used: usize,
+    entries: HashMap<K, (V, usize)>,
+    // The keys of every entry, from least to most recently used
+    recency: VecDeque<K>,
+    pub stats: PreviewCacheStats,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PreviewCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl<K: Hash + Eq + Clone, V> PreviewCache<K, V> {
+    pub fn new(budget: usize) -> Self {
+        PreviewCache {
+            budget,
+            used: 0,
+            entries: HashMap::new(),
+            recency: VecDeque::new(),
+            stats: PreviewCacheStats::default(),
+        }
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        if self.entries.contains_key(key) {
+            self.stats.hits += 1;
+            self.touch(key);
+        } else {
+            self.stats.misses += 1;
+        }
+
+        self.entries.get(key).map(|(value, _size)| value)
+    }
+
+    /// Keeps a preview which takes up size bytes, dropping the least recently used previews until
+    /// it fits. A preview bigger than the whole budget isn't kept at all.
+    pub fn insert(&mut self, key: K, value: V, size: usize) {
+        if size > self.budget {
+            return;
+        }
+
+        if let Some((_value, old_size)) = self.entries.remove(&key) {
+            self.used -= old_size;
+            self.recency.retain(|recent_key| recent_key != &key);
+        }
+
+        while self.used + size > self.budget {
+            let oldest_key = self
+                .recency
+                .pop_front()
+                .expect("The cache is only over budget when it has entries");
+            let (_value, oldest_size) = self
+                .entries
+                .remove(&oldest_key)
+                .expect("Every recent key has an entry");
+
+            self.used -= oldest_size;
+            self.stats.evictions += 1;
+        }
+
+        self.used += size;
+        self.entries.insert(key.clone(), (value, size));
+        self.recency.push_back(key);
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn touch(&mut self, key: &K) {
+        if let Some(index) = self.recency.iter().position(|recent_key| recent_key == key) {
+            let key = self
+                .recency
+                .remove(index)
+                .expect("The index was just found");
+            self.recency.push_back(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_eviction() {
+        let mut cache = PreviewCache::new(10);
+
+        cache.insert("a", 'a', 4);
+        cache.insert("b", 'b', 4);
+        assert_eq!(cache.used(), 8);
+
+        // Using "a" makes "b" the least recently used
+        assert_eq!(cache.get(&"a"), Some(&'a'));
+
+        cache.insert("c", 'c', 4);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(&'a'));
+        assert_eq!(cache.get(&"c"), Some(&'c'));
+        assert_eq!(cache.used(), 8);
+        assert_eq!(cache.len(), 2);
+
+        // Replacing an entry doesn't count its old size twice
+        cache.insert("c", 'C', 6);
+        assert_eq!(cache.get(&"c"), Some(&'C'));
+        assert_eq!(cache.used(), 10);
+
+        // Too big to keep at all
+        cache.insert("d", 'd', 11);
+        assert_eq!(cache.get(&"d"), None);
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(
+            cache.stats,
+            PreviewCacheStats {
+                hits: 4,
+                misses: 2,
+                evictions: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_zero_budget() {
+        let mut cache = PreviewCache::new(0);
+
+        cache.insert("a", 'a', 1);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.used(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+// A fixed-size pool of threads for generating previews. Every job belongs to a generation, and
+// starting a new preview (or cancelling previews) moves on to the next generation, so jobs from
+// earlier generations can notice that they're stale and stop early instead of running to
+// completion.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+pub struct PreviewPool {
+    job_tx: Sender<Job>,
+    generation: Arc<AtomicUsize>,
+}
+
+/// Lets a job check whether the preview it's generating is still wanted
+#[derive(Clone)]
+pub struct PreviewTicket {
+    generation: Arc<AtomicUsize>,
+    id: usize,
+}
+
+impl PreviewTicket {
+    pub fn is_current(&self) -> bool {
+        self.generation.load(Ordering::Acquire) == self.id
+    }
+}
+
+impl PreviewPool {
+    pub fn new(thread_count: usize) -> PreviewPool {
+        let (job_tx, job_rx) = mpsc::channel::<Job>();
+        let job_rx = Arc::new(Mutex::new(job_rx));
+
+        for _ in 0..thread_count {
+            let job_rx = Arc::clone(&job_rx);
+
+            thread::spawn(move || loop {
+                // NOTE(Chris): The lock is released as soon as a job has been received, so other
+                // workers can pick up jobs while this one runs.
+                let job = match job_rx.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    // The pool has been dropped
+                    Err(_) => break,
+                };
+
+                // NOTE(Chris): A job that panics only loses its own preview, rather than taking
+                // its worker down with it
+                let _ = panic::catch_unwind(AssertUnwindSafe(job));
+            });
+        }
+
+        PreviewPool {
+            job_tx,
+            generation: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Queues a job in a new generation, making every previously queued job stale. Jobs which are
+    /// already stale by the time a worker gets to them are skipped entirely.
+    pub fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce(PreviewTicket) + Send + 'static,
+    {
+        let ticket = PreviewTicket {
+            generation: Arc::clone(&self.generation),
+            id: self.generation.fetch_add(1, Ordering::AcqRel) + 1,
+        };
+
+        self.job_tx
+            .send(Box::new(move || {
+                if ticket.is_current() {
+                    f(ticket);
+                }
+            }))
+            .expect("Unable to send to preview thread");
+    }
+
+    /// Makes every queued or running job stale.
+    pub fn cancel_all(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_jobs_are_skipped() {
+        let pool = PreviewPool::new(1);
+        let (done_tx, done_rx) = mpsc::channel();
+        let (block_tx, block_rx) = mpsc::channel::<()>();
+        let (started_tx, started_rx) = mpsc::channel();
+
+        // Occupy the only worker so the following jobs stay queued. It has to start before they're
+        // spawned, since spawning them makes it stale.
+        pool.spawn(move |_| {
+            started_tx.send(()).unwrap();
+            block_rx.recv().unwrap();
+        });
+        started_rx.recv().unwrap();
+
+        for i in 0..3 {
+            let done_tx = done_tx.clone();
+            pool.spawn(move |_| done_tx.send(i).unwrap());
+        }
+
+        block_tx.send(()).unwrap();
+
+        assert_eq!(done_rx.recv().unwrap(), 2);
+        drop(done_tx);
+        drop(pool);
+        assert!(done_rx.recv().is_err());
+    }
+
+    #[test]
+    fn test_panicking_job_keeps_worker() {
+        let pool = PreviewPool::new(1);
+        let (done_tx, done_rx) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel();
+
+        // NOTE(Chris): Each job has to start before the next is spawned, since spawning makes the
+        // earlier ones stale
+        for _ in 0..3 {
+            let started_tx = started_tx.clone();
+            pool.spawn(move |_| {
+                started_tx.send(()).unwrap();
+                panic!("preview failed");
+            });
+            started_rx.recv().unwrap();
+        }
+
+        pool.spawn(move |_| done_tx.send(()).unwrap());
+
+        assert!(done_rx.recv().is_ok());
+    }
+
+    #[test]
+    fn test_cancel_all() {
+        let pool = PreviewPool::new(1);
+        let (ticket_tx, ticket_rx) = mpsc::channel();
+
+        pool.spawn(move |ticket| ticket_tx.send(ticket).unwrap());
+
+        let ticket = ticket_rx.recv().unwrap();
+        assert!(ticket.is_current());
+
+        pool.cancel_all();
+        assert!(!ticket.is_current());
+    }
+}
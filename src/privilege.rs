@@ -0,0 +1,86 @@
+// Retries file operations through a privilege-escalation helper (like sudo, doas, or pkexec) when
+// rolf itself isn't allowed to perform them.
+
+use crate::prompt::{Prompt, PromptHandle};
+
+use std::ffi::OsStr;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Asks whether to retry a failed operation with the helper, then runs `args` through it. Returns
+/// Ok(false) if the user declined, or the helper's error output if it failed.
+pub fn retry_privileged(
+    prompter: &PromptHandle,
+    helper: &str,
+    args: &[&OsStr],
+) -> Result<bool, String> {
+    let answer = prompter
+        .ask(Prompt::new(format!("Permission denied. Retry with {} (y/n)? ", helper)).single_key());
+    if answer.as_deref() != Some("y") {
+        return Ok(false);
+    }
+
+    // NOTE(Chris): sudo would otherwise ask for the password on the terminal, fighting with us for
+    // input, so we ask for it ourselves and pass it along with -S. Other helpers are expected to
+    // authenticate on their own (e.g. pkexec's graphical agent).
+    let password = if helper == "sudo" && !has_cached_credentials() {
+        match prompter.ask(Prompt::new("Password: ").masked()) {
+            Some(password) => Some(password),
+            None => return Ok(false),
+        }
+    } else {
+        None
+    };
+
+    run_privileged(helper, password.as_deref(), args)?;
+
+    Ok(true)
+}
+
+fn has_cached_credentials() -> bool {
+    Command::new("sudo")
+        .args(["-n", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn run_privileged(helper: &str, password: Option<&str>, args: &[&OsStr]) -> Result<(), String> {
+    let mut command = Command::new(helper);
+
+    if password.is_some() {
+        command.args(["-S", "-p", ""]);
+    }
+
+    let mut child = command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("Unable to run {}: {}", helper, err))?;
+
+    if let Some(password) = password {
+        let mut stdin = child.stdin.take().expect("Failed to open helper stdin");
+
+        // NOTE(Chris): If this fails, the helper will report the missing password itself
+        let _ = writeln!(stdin, "{}", password);
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("Unable to run {}: {}", helper, err))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+
+        match stderr.lines().rev().find(|line| !line.trim().is_empty()) {
+            Some(line) => Err(line.to_string()),
+            None => Err(format!("{} failed ({})", helper, output.status)),
+        }
+    }
+}
@@ -0,0 +1,242 @@
+// Prompts which command threads use to ask the user for input. A command thread builds a Prompt
+// and waits on PromptHandle::ask() while the main thread handles the actual line editing.
+
+use crate::line_edit;
+use crate::{CommandRequest, InputEvent};
+
+use std::sync::mpsc::{Receiver, Sender};
+
+type Validator = dyn Fn(&str) -> Result<(), String> + Send;
+type Completer = dyn Fn(&str) -> Vec<String> + Send;
+
+pub struct Prompt {
+    pub label: String,
+    pub initial_text: String,
+    // Whether the input should be hidden (e.g. for passwords)
+    pub masked: bool,
+    // Whether the prompt is answered by a single key press (e.g. y/n)
+    pub single_key: bool,
+    // Whether the input is edited in the current row of the listing, rather than at the bottom
+    pub inline: bool,
+    // Where the cursor starts, as a byte index into the initial text (the end if there's none)
+    pub initial_cursor: Option<usize>,
+    validator: Option<Box<Validator>>,
+    completer: Option<Box<Completer>>,
+    // The message from the last failed validation, shown until the next key press
+    error: Option<String>,
+}
+
+impl Prompt {
+    pub fn new<S: Into<String>>(label: S) -> Self {
+        Prompt {
+            label: label.into(),
+            initial_text: String::new(),
+            masked: false,
+            single_key: false,
+            inline: false,
+            initial_cursor: None,
+            validator: None,
+            completer: None,
+            error: None,
+        }
+    }
+
+    /// Builds a prompt which only accepts one of the choices, like "Template (a, b): " for the
+    /// noun "Template".
+    pub fn choice(noun: &str, choices: Vec<String>, screen_width: u16) -> Self {
+        // NOTE(Chris): The choices are listed in the prompt when they leave room for typing, and
+        // can always be completed with tab
+        let listed_choices = choices.join(", ");
+        let label = if line_edit::str_width(&listed_choices) < screen_width as usize / 2 {
+            format!("{} ({}): ", noun, listed_choices)
+        } else {
+            format!("{}: ", noun)
+        };
+
+        let completer_choices = choices.clone();
+        let noun = noun.to_lowercase();
+
+        Prompt::new(label)
+            .completer(move |input| {
+                completer_choices
+                    .iter()
+                    .filter(|choice| choice.starts_with(input))
+                    .cloned()
+                    .collect()
+            })
+            .validator(move |input| {
+                if choices.iter().any(|choice| choice == input) {
+                    Ok(())
+                } else {
+                    Err(format!("No {} named {}", noun, input))
+                }
+            })
+    }
+
+    pub fn initial_text<S: Into<String>>(mut self, initial_text: S) -> Self {
+        self.initial_text = initial_text.into();
+        self
+    }
+
+    pub fn masked(mut self) -> Self {
+        self.masked = true;
+        self
+    }
+
+    pub fn single_key(mut self) -> Self {
+        self.single_key = true;
+        self
+    }
+
+    pub fn inline(mut self) -> Self {
+        self.inline = true;
+        self
+    }
+
+    pub fn initial_cursor(mut self, initial_cursor: usize) -> Self {
+        self.initial_cursor = Some(initial_cursor);
+        self
+    }
+
+    /// The validator returns an error message for input which shouldn't be accepted yet.
+    pub fn validator(
+        mut self,
+        validator: impl Fn(&str) -> Result<(), String> + Send + 'static,
+    ) -> Self {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /// The completer returns every possible completion of the current input.
+    pub fn completer(mut self, completer: impl Fn(&str) -> Vec<String> + Send + 'static) -> Self {
+        self.completer = Some(Box::new(completer));
+        self
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn clear_error(&mut self) {
+        self.error = None;
+    }
+
+    /// Returns whether the input can be accepted, remembering the error message if it can't.
+    pub fn validate(&mut self, input: &str) -> bool {
+        self.error = match &self.validator {
+            Some(validator) => validator(input).err(),
+            None => None,
+        };
+
+        self.error.is_none()
+    }
+
+    /// Completes the input as far as every possible completion agrees, if there are any.
+    pub fn complete(&self, input: &str) -> Option<String> {
+        let completions = (self.completer.as_ref()?)(input);
+
+        longest_common_prefix(&completions)
+    }
+}
+
+impl std::fmt::Debug for Prompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Prompt({:?})", self.label)
+    }
+}
+
+fn longest_common_prefix(strings: &[String]) -> Option<String> {
+    let (first, rest) = strings.split_first()?;
+
+    let mut prefix_len = first.len();
+
+    for string in rest {
+        prefix_len = first
+            .char_indices()
+            .zip(string.chars())
+            .find(|((_, first_ch), ch)| first_ch != ch)
+            .map_or(prefix_len.min(string.len()), |((index, _), _)| {
+                index.min(prefix_len)
+            });
+    }
+
+    Some(first[..prefix_len].to_string())
+}
+
+// Held by a command thread to ask the main thread for input
+pub struct PromptHandle {
+    to_main_tx: Sender<InputEvent>,
+    from_main_rx: Receiver<String>,
+}
+
+impl PromptHandle {
+    pub fn new(to_main_tx: Sender<InputEvent>, from_main_rx: Receiver<String>) -> Self {
+        PromptHandle {
+            to_main_tx,
+            from_main_rx,
+        }
+    }
+
+    /// Shows the prompt and waits for the user's answer. Returns None if the prompt was cancelled
+    /// or left empty.
+    pub fn ask(&self, prompt: Prompt) -> Option<String> {
+        self.to_main_tx
+            .send(InputEvent::CommandRequest(CommandRequest::Ask(prompt)))
+            .expect("Failed to send to main thread");
+
+        match self.from_main_rx.recv() {
+            Ok(input) if !input.is_empty() => Some(input),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut prompt = Prompt::new("Name: ").validator(|input| {
+            if input.contains('/') {
+                Err("no slashes".to_string())
+            } else {
+                Ok(())
+            }
+        });
+
+        assert!(!prompt.validate("a/b"));
+        assert_eq!(prompt.error(), Some("no slashes"));
+
+        assert!(prompt.validate("ab"));
+        assert_eq!(prompt.error(), None);
+    }
+
+    #[test]
+    fn test_complete() {
+        let prompt = Prompt::new("Path: ").completer(|input| {
+            ["config.json", "config.jsonc", "rolfrc"]
+                .iter()
+                .filter(|name| name.starts_with(input))
+                .map(|name| name.to_string())
+                .collect()
+        });
+
+        assert_eq!(prompt.complete("co"), Some("config.json".to_string()));
+        assert_eq!(prompt.complete("r"), Some("rolfrc".to_string()));
+        assert_eq!(prompt.complete("x"), None);
+
+        assert_eq!(Prompt::new("Name: ").complete("co"), None);
+    }
+
+    #[test]
+    fn test_longest_common_prefix() {
+        let strings = vec!["abcd".to_string(), "abxy".to_string(), "abc".to_string()];
+        assert_eq!(longest_common_prefix(&strings), Some("ab".to_string()));
+
+        let strings = vec!["abc".to_string(), "ab".to_string()];
+        assert_eq!(longest_common_prefix(&strings), Some("ab".to_string()));
+
+        assert_eq!(longest_common_prefix(&[]), None);
+    }
+}
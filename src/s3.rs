@@ -0,0 +1,474 @@
+// Browses S3 buckets, with `cd s3://bucket/prefix` (or `cd s3://` to list every bucket). Prefixes
+// ending in a slash are shown as directories, the way the AWS console shows them. Credentials and
+// the region come from wherever the AWS CLI reads them: the AWS_* environment variables,
+// ~/.aws/config and ~/.aws/credentials (with AWS_PROFILE), or the instance's role.
+
+use crate::vfs::{
+    DirEntry, DirEntryInfo, FileSystem, Metadata, MountedMetadata, RecordedFileType, S_IFDIR,
+    S_IFREG,
+};
+
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::{ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::{CommonPrefix, Object};
+use aws_sdk_s3::Client;
+use tokio::runtime::Runtime;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// NOTE(Chris): S3 needs a region to send requests to, even to find out where a bucket is
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// The path that the list of buckets is shown at
+pub fn root() -> PathBuf {
+    PathBuf::from("s3://")
+}
+
+/// Finds the directory for a URL like s3://bucket/prefix, where the bucket and prefix are optional
+pub fn parse_url(url: &str) -> Option<PathBuf> {
+    let rest = url.strip_prefix("s3://")?;
+
+    let mut dir = root();
+
+    for component in Path::new(rest).components() {
+        match component {
+            Component::Normal(name) => dir.push(name),
+            Component::RootDir | Component::CurDir => (),
+            _ => return None,
+        }
+    }
+
+    Some(dir)
+}
+
+pub struct S3FileSystem {
+    runtime: Runtime,
+    // The client for the region from the environment, which finds out where each bucket is
+    client: Client,
+    // NOTE(Chris): A bucket can only be read from its own region, so each one gets its own client
+    bucket_clients: Mutex<HashMap<String, Client>>,
+}
+
+/// Reads the AWS configuration, returning the file system along with the directory for the URL.
+/// Nothing is sent to S3 until something is listed.
+pub fn connect(url: &str) -> io::Result<(S3FileSystem, PathBuf)> {
+    let start_dir = parse_url(url)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "invalid S3 URL"))?;
+
+    // NOTE(Chris): Requests are made from the preview threads as well as the main one. They can
+    // all wait on a single-threaded runtime at once, taking turns to drive it.
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+
+    let sdk_config = runtime.block_on(aws_config::load_defaults(BehaviorVersion::latest()));
+    let mut config = aws_sdk_s3::config::Builder::from(&sdk_config);
+    if sdk_config.region().is_none() {
+        config.set_region(Some(Region::new(DEFAULT_REGION)));
+    }
+
+    let file_system = S3FileSystem {
+        runtime,
+        client: Client::from_conf(config.build()),
+        bucket_clients: Mutex::new(HashMap::new()),
+    };
+
+    Ok((file_system, start_dir))
+}
+
+fn to_io_error<E, R>(err: SdkError<E, R>) -> io::Error
+where
+    E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    R: std::fmt::Debug,
+{
+    let kind = match &err {
+        SdkError::TimeoutError(_) => io::ErrorKind::TimedOut,
+        SdkError::DispatchFailure(_) => io::ErrorKind::ConnectionAborted,
+        _ => match err.code() {
+            Some("NoSuchBucket" | "NoSuchKey" | "NotFound") => io::ErrorKind::NotFound,
+            Some("AccessDenied" | "Forbidden" | "AllAccessDisabled") => {
+                io::ErrorKind::PermissionDenied
+            }
+            Some("InvalidAccessKeyId" | "SignatureDoesNotMatch" | "ExpiredToken") => {
+                io::ErrorKind::PermissionDenied
+            }
+            _ => io::ErrorKind::Other,
+        },
+    };
+
+    // NOTE(Chris): Without a message from S3, the cause at the bottom of the chain (like a failed
+    // DNS lookup) says the most
+    let message = match err.message() {
+        Some(message) => message.to_string(),
+        None => {
+            let mut cause: &dyn std::error::Error = &err;
+            while let Some(source) = cause.source() {
+                cause = source;
+            }
+
+            cause.to_string()
+        }
+    };
+
+    io::Error::new(kind, message)
+}
+
+// A bucket and the key of an object (or a prefix) in it, as in s3://bucket/key
+struct Location {
+    bucket: String,
+    key: String,
+}
+
+// The bucket and key that a path is for, or None for the list of buckets
+fn location(path: &Path) -> io::Result<Option<Location>> {
+    let inner_path = path
+        .strip_prefix(root())
+        .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "the path isn't in S3"))?;
+
+    let mut names = inner_path.iter().map(|name| {
+        name.to_str()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "keys must be unicode"))
+    });
+
+    let bucket = match names.next() {
+        Some(bucket) => bucket?.to_string(),
+        None => return Ok(None),
+    };
+    let key = names.collect::<io::Result<Vec<&str>>>()?.join("/");
+
+    Ok(Some(Location { bucket, key }))
+}
+
+// Directories don't exist in S3, so every one is given the same mode
+fn dir_entry_info(path: PathBuf, modified: Option<SystemTime>) -> DirEntryInfo {
+    DirEntryInfo {
+        metadata: Metadata::Mounted(MountedMetadata {
+            len: 0,
+            modified,
+            mode: S_IFDIR | 0o755,
+            file_id: path_id(&path),
+        }),
+        dir_entry: DirEntry::new(path),
+        file_type: RecordedFileType::Directory,
+    }
+}
+
+// NOTE(Chris): Objects don't have inode numbers, but a path is just as good for finding one again
+fn path_id(path: &Path) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+
+    hasher.finish()
+}
+
+fn to_system_time(date_time: Option<&DateTime>) -> Option<SystemTime> {
+    date_time.and_then(|date_time| SystemTime::try_from(*date_time).ok())
+}
+
+// The entries for a page of a listing with the "/" delimiter, where the prefixes are shown as
+// directories and the objects as files
+fn listing_entries(
+    dir: &Path,
+    prefix: &str,
+    common_prefixes: &[CommonPrefix],
+    objects: &[Object],
+) -> Vec<DirEntryInfo> {
+    // NOTE(Chris): The folders that the console makes are empty objects with the prefix as their
+    // key, which are left out. So are names that are empty because of a doubled slash, since
+    // there's no path for them.
+    let name_in_dir = |key: &str| {
+        key.strip_prefix(prefix)
+            .map(|name| name.trim_end_matches('/').to_string())
+            .filter(|name| !name.is_empty() && !name.contains('/'))
+    };
+
+    let dirs = common_prefixes
+        .iter()
+        .filter_map(|common_prefix| name_in_dir(common_prefix.prefix()?))
+        .map(|name| dir_entry_info(dir.join(name), None));
+
+    let files = objects.iter().filter_map(|object| {
+        let key = object.key()?;
+        if key.ends_with('/') {
+            return None;
+        }
+
+        let path = dir.join(name_in_dir(key)?);
+
+        Some(DirEntryInfo {
+            metadata: Metadata::Mounted(MountedMetadata {
+                len: object.size().unwrap_or(0).max(0) as u64,
+                modified: to_system_time(object.last_modified()),
+                mode: S_IFREG | 0o644,
+                file_id: path_id(&path),
+            }),
+            dir_entry: DirEntry::new(path),
+            file_type: RecordedFileType::File,
+        })
+    });
+
+    dirs.chain(files).collect()
+}
+
+impl S3FileSystem {
+    // The client for the bucket's region, which is found with the first request to the bucket
+    fn client_for(&self, bucket: &str) -> Client {
+        if let Some(client) = self.bucket_clients.lock().unwrap().get(bucket) {
+            return client.clone();
+        }
+
+        // NOTE(Chris): S3 says where a bucket is even when it's asked in the wrong region
+        let region = match self
+            .runtime
+            .block_on(self.client.head_bucket().bucket(bucket).send())
+        {
+            Ok(output) => output.bucket_region().map(str::to_string),
+            Err(err) => err
+                .raw_response()
+                .and_then(|response| response.headers().get("x-amz-bucket-region"))
+                .map(str::to_string),
+        };
+
+        let client = match region {
+            Some(region) => {
+                let config = self
+                    .client
+                    .config()
+                    .to_builder()
+                    .region(Region::new(region))
+                    .build();
+
+                Client::from_conf(config)
+            }
+            // The request will fail with a better error than we could give
+            None => self.client.clone(),
+        };
+
+        self.bucket_clients
+            .lock()
+            .unwrap()
+            .insert(bucket.to_string(), client.clone());
+
+        client
+    }
+
+    /// Whether there's a bucket or a prefix at the path, with an error if S3 couldn't be asked
+    pub fn check_dir(&self, path: &Path) -> io::Result<bool> {
+        let Location { bucket, key } = match location(path)? {
+            Some(location) => location,
+            None => return Ok(true),
+        };
+
+        let client = self.client_for(&bucket);
+
+        let request = client.list_objects_v2().bucket(&bucket).max_keys(1);
+        let request = if key.is_empty() {
+            request
+        } else {
+            request.prefix(key + "/")
+        };
+
+        let output = self.runtime.block_on(request.send()).map_err(to_io_error)?;
+
+        // NOTE(Chris): A bucket is there even when it's empty, but a prefix only is if something
+        // starts with it
+        Ok(path.parent() == Some(&root())
+            || !output.contents().is_empty()
+            || !output.common_prefixes().is_empty())
+    }
+
+    fn read_buckets(&self, dir: &Path) -> io::Result<Vec<DirEntryInfo>> {
+        let output = self
+            .runtime
+            .block_on(self.client.list_buckets().send())
+            .map_err(to_io_error)?;
+
+        Ok(output
+            .buckets()
+            .iter()
+            .filter_map(|bucket| {
+                let path = dir.join(bucket.name()?);
+
+                Some(dir_entry_info(path, to_system_time(bucket.creation_date())))
+            })
+            .collect())
+    }
+}
+
+impl FileSystem for S3FileSystem {
+    fn read_dir(
+        &self,
+        dir: &Path,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> io::Result<Vec<DirEntryInfo>> {
+        let Location { bucket, key } = match location(dir)? {
+            Some(location) => location,
+            None => return self.read_buckets(dir),
+        };
+
+        let prefix = if key.is_empty() { key } else { key + "/" };
+        let client = self.client_for(&bucket);
+
+        let mut entries = vec![];
+        let mut continuation_token = None;
+
+        // NOTE(Chris): Each request lists at most 1000 keys, so a big listing takes a few of them
+        loop {
+            if is_cancelled() {
+                return Err(io::Error::from(io::ErrorKind::Interrupted));
+            }
+
+            let output = self
+                .runtime
+                .block_on(
+                    client
+                        .list_objects_v2()
+                        .bucket(&bucket)
+                        .prefix(&prefix)
+                        .delimiter("/")
+                        .set_continuation_token(continuation_token)
+                        .send(),
+                )
+                .map_err(to_io_error)?;
+
+            entries.extend(listing_entries(
+                dir,
+                &prefix,
+                output.common_prefixes(),
+                output.contents(),
+            ));
+
+            continuation_token = output.next_continuation_token().map(str::to_string);
+            if continuation_token.is_none() {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.check_dir(path).unwrap_or(false)
+    }
+
+    fn read_file(&self, path: &Path, w: &mut dyn Write, limit: Option<u64>) -> io::Result<()> {
+        let Location { bucket, key } = location(path)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+
+        if limit == Some(0) {
+            return Ok(());
+        }
+
+        let client = self.client_for(&bucket);
+
+        self.runtime.block_on(async {
+            let output = client
+                .get_object()
+                .bucket(&bucket)
+                .key(&key)
+                .set_range(limit.map(|limit| format!("bytes=0-{}", limit - 1)))
+                .send()
+                .await;
+
+            let mut body = match output {
+                Ok(output) => output.body,
+                // NOTE(Chris): Asking for the start of an empty object is an error, since there's
+                // no first byte
+                Err(err) if limit.is_some() && err.code() == Some("InvalidRange") => return Ok(()),
+                Err(err) => return Err(to_io_error(err)),
+            };
+
+            while let Some(bytes) = body.try_next().await.map_err(io::Error::other)? {
+                w.write_all(&bytes)?;
+            }
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url("s3://"), Some(root()));
+        assert_eq!(
+            parse_url("s3://my-bucket/logs/2024/"),
+            Some(PathBuf::from("s3://my-bucket/logs/2024"))
+        );
+        assert_eq!(parse_url("s3://my-bucket/../other"), None);
+        assert_eq!(parse_url("/home/chris"), None);
+    }
+
+    #[test]
+    fn test_location() {
+        assert!(location(&root()).unwrap().is_none());
+
+        let Location { bucket, key } = location(Path::new("s3://my-bucket/logs/2024/app.log"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(bucket, "my-bucket");
+        assert_eq!(key, "logs/2024/app.log");
+
+        let Location { bucket, key } = location(Path::new("s3://my-bucket")).unwrap().unwrap();
+        assert_eq!(bucket, "my-bucket");
+        assert_eq!(key, "");
+
+        assert!(location(Path::new("/home/chris")).is_err());
+    }
+
+    #[test]
+    fn test_listing_entries() {
+        let dir = PathBuf::from("s3://my-bucket/logs");
+
+        let common_prefixes = [
+            CommonPrefix::builder().prefix("logs/2024/").build(),
+            CommonPrefix::builder().prefix("logs//").build(),
+        ];
+        let objects = [
+            // The folder itself, as the console makes it
+            Object::builder().key("logs/").size(0).build(),
+            Object::builder()
+                .key("logs/app.log")
+                .size(2048)
+                .last_modified(DateTime::from_secs(1_700_000_000))
+                .build(),
+        ];
+
+        let entries = listing_entries(&dir, "logs/", &common_prefixes, &objects);
+
+        let summary: Vec<(PathBuf, RecordedFileType, u64)> = entries
+            .iter()
+            .map(|entry_info| {
+                (
+                    entry_info.dir_entry.path(),
+                    entry_info.file_type,
+                    entry_info.metadata.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (dir.join("2024"), RecordedFileType::Directory, 0),
+                (dir.join("app.log"), RecordedFileType::File, 2048),
+            ]
+        );
+
+        assert_eq!(entries[1].metadata.extra_perms().mode, "-rw-r--r--");
+        assert_eq!(
+            entries[1].metadata.modified().unwrap(),
+            SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)
+        );
+    }
+}
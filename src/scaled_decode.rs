@@ -0,0 +1,169 @@
+// Decodes images at a reduced size when they're much bigger than the preview they'll be shown in,
+// so that a huge photo isn't decoded (and held in memory) at its full size just to be shrunk
+// right afterward. The results are still at least as big as the preview, so they're fitted to it
+// exactly the same way as a fully-decoded image.
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+
+use image::{DynamicImage, RgbaImage};
+
+/// Decodes a jpg using its DCT scaling, which can skip straight to a half, a quarter, or an eighth
+/// of the full size.
+// NOTE(Chris): jpgs are decoded by jpeg.rs instead when the turbojpeg feature is on
+#[cfg_attr(feature = "turbojpeg", allow(dead_code))]
+pub fn decode_jpg(bytes: &[u8], max_width: u32, max_height: u32) -> io::Result<DynamicImage> {
+    let mut decoder = image::jpeg::JpegDecoder::new(bytes).map_err(to_io_error)?;
+
+    decoder
+        .scale(clamp_u16(max_width), clamp_u16(max_height))
+        .map_err(to_io_error)?;
+
+    DynamicImage::from_decoder(decoder).map_err(to_io_error)
+}
+
+/// Decodes a png one row at a time, averaging each block of pixels into one as it goes, so only
+/// the shrunken image is ever kept in memory.
+pub fn decode_png(path: &Path, max_width: u32, max_height: u32) -> io::Result<DynamicImage> {
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(path)?));
+    // NOTE(Chris): This leaves us with 8-bit grayscale, grayscale with alpha, RGB, or RGBA
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+
+    let (info, mut reader) = decoder.read_info().map_err(to_io_error)?;
+
+    let divisor = scale_divisor((info.width, info.height), (max_width, max_height));
+
+    // NOTE(Chris): The rows of an interlaced png come in several passes, so they can't be
+    // averaged as they come in
+    if divisor == 1 || reader.info().interlaced {
+        return image::io::Reader::open(path)?.decode().map_err(to_io_error);
+    }
+
+    let samples = info.color_type.samples();
+
+    let out_width = info.width.div_ceil(divisor);
+    let out_height = info.height.div_ceil(divisor);
+
+    let mut image = RgbaImage::new(out_width, out_height);
+    // The running RGBA totals for the row of blocks being read
+    let mut sums = vec![0u32; out_width as usize * 4];
+
+    for y in 0..info.height {
+        let row = reader
+            .next_row()
+            .map_err(to_io_error)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "truncated png"))?;
+
+        for (x, pixel) in row.chunks_exact(samples).enumerate() {
+            let rgba = match *pixel {
+                [gray] => [gray, gray, gray, 255],
+                [gray, alpha] => [gray, gray, gray, alpha],
+                [red, green, blue] => [red, green, blue, 255],
+                [red, green, blue, alpha] => [red, green, blue, alpha],
+                _ => unreachable!("expanded pngs have 1 to 4 samples per pixel"),
+            };
+
+            let block_x = x / divisor as usize;
+            for (sum, sample) in sums[block_x * 4..block_x * 4 + 4].iter_mut().zip(rgba) {
+                *sum += u32::from(sample);
+            }
+        }
+
+        let is_last_row_of_block = (y + 1) % divisor == 0 || y + 1 == info.height;
+        if !is_last_row_of_block {
+            continue;
+        }
+
+        let block_y = y / divisor;
+        let block_height = y + 1 - block_y * divisor;
+
+        for (block_x, block_sums) in sums.chunks_exact_mut(4).enumerate() {
+            let block_width = divisor.min(info.width - block_x as u32 * divisor);
+            let count = block_width * block_height;
+
+            let pixel = image.get_pixel_mut(block_x as u32, block_y);
+            for (channel, sum) in pixel.0.iter_mut().zip(block_sums.iter_mut()) {
+                *channel = (*sum / count) as u8;
+                *sum = 0;
+            }
+        }
+    }
+
+    Ok(DynamicImage::ImageRgba8(image))
+}
+
+// The biggest whole number an image can be divided by while still being at least as big as it
+// will be when it's fitted into the given size
+fn scale_divisor((width, height): (u32, u32), (max_width, max_height): (u32, u32)) -> u32 {
+    // NOTE(Chris): A fitted image is limited by only one of its sides, so only one side needs to
+    // be big enough
+    (width / max_width.max(1))
+        .max(height / max_height.max(1))
+        .max(1)
+}
+
+#[cfg_attr(feature = "turbojpeg", allow(dead_code))]
+fn clamp_u16(value: u32) -> u16 {
+    value.try_into().unwrap_or(u16::MAX)
+}
+
+fn to_io_error(err: impl std::error::Error + Send + Sync + 'static) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use image::GenericImageView;
+
+    #[test]
+    fn test_scale_divisor() {
+        assert_eq!(scale_divisor((6000, 4000), (750, 500)), 8);
+        assert_eq!(scale_divisor((6000, 4000), (700, 500)), 8);
+        // A wide preview is limited by its height instead
+        assert_eq!(scale_divisor((6000, 4000), (3000, 1000)), 4);
+        // Images smaller than the preview are never scaled up
+        assert_eq!(scale_divisor((300, 200), (750, 500)), 1);
+    }
+
+    #[test]
+    fn test_decode_png() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stripes.png");
+
+        // Columns alternate between black and white, with a red last row
+        let mut image = RgbaImage::from_fn(9, 5, |x, _y| {
+            let value = if x % 2 == 0 { 0 } else { 255 };
+            image::Rgba([value, value, value, 255])
+        });
+        for x in 0..9 {
+            image.put_pixel(x, 4, image::Rgba([255, 0, 0, 255]));
+        }
+        image.save(&path).unwrap();
+
+        let decoded = decode_png(&path, 4, 2).unwrap();
+
+        assert_eq!(decoded.dimensions(), (5, 3));
+        assert_eq!(decoded.get_pixel(0, 0), image::Rgba([127, 127, 127, 255]));
+        // The last column and row are made from what's left over
+        assert_eq!(decoded.get_pixel(4, 0), image::Rgba([0, 0, 0, 255]));
+        assert_eq!(decoded.get_pixel(0, 2), image::Rgba([255, 0, 0, 255]));
+
+        // A png which is already small enough is decoded as is
+        assert_eq!(decode_png(&path, 9, 5).unwrap().dimensions(), (9, 5));
+    }
+
+    #[test]
+    fn test_decode_jpg() {
+        let mut bytes = vec![];
+        image::jpeg::JpegEncoder::new(&mut bytes)
+            .encode(&[128; 64 * 32 * 3], 64, 32, image::ColorType::Rgb8)
+            .unwrap();
+
+        assert_eq!(decode_jpg(&bytes, 8, 4).unwrap().dimensions(), (8, 4));
+        assert_eq!(decode_jpg(&bytes, 20, 10).unwrap().dimensions(), (32, 16));
+        assert_eq!(decode_jpg(&bytes, 640, 320).unwrap().dimensions(), (64, 32));
+    }
+}
@@ -0,0 +1,159 @@
+// Matching file names against search terms, following lf's ignorecase, smartcase, ignoredia, and
+// anchorfind options.
+
+use crate::unicode_fold::{fold_str, push_folded_char};
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MatchOptions {
+    pub ignore_case: bool,
+    // Only ignore case when the search term is all lowercase (requires ignore_case)
+    pub smart_case: bool,
+    // Only match at the start of file names
+    pub anchored: bool,
+    // Treat letters with diacritics (like "é") as their base letters
+    pub ignore_diacritics: bool,
+}
+
+impl MatchOptions {
+    fn should_ignore_case(&self, search_term: &str) -> bool {
+        self.ignore_case && !(self.smart_case && search_term.chars().any(char::is_uppercase))
+    }
+}
+
+/// Finds where the search term first appears in a file name, as a range of bytes.
+pub fn find_match_range(
+    file_name: &str,
+    search_term: &str,
+    options: MatchOptions,
+) -> Option<Range<usize>> {
+    if search_term.is_empty() {
+        return None;
+    }
+
+    let ignore_case = options.should_ignore_case(search_term);
+
+    let search_term = fold_str(search_term, ignore_case, options.ignore_diacritics);
+
+    // NOTE(Chris): Each character of the file name is folded on its own, so that the match can be
+    // mapped back to a range in the original file name
+    let mut folded_ch = String::new();
+
+    for (start, _) in file_name.char_indices() {
+        if options.anchored && start > 0 {
+            break;
+        }
+
+        let mut term_chars = search_term.chars().peekable();
+        let mut end = start;
+
+        for (offset, ch) in file_name[start..].char_indices() {
+            folded_ch.clear();
+            push_folded_char(&mut folded_ch, ch, ignore_case, options.ignore_diacritics);
+
+            // NOTE(Chris): Once the whole term has matched, we keep going only to include any
+            // dropped combining marks, which belong to the last matched letter
+            if term_chars.peek().is_none() && !folded_ch.is_empty() {
+                break;
+            }
+
+            let is_matching = folded_ch
+                .chars()
+                .all(|folded| term_chars.next() == Some(folded));
+
+            if !is_matching {
+                break;
+            }
+
+            end = start + offset + ch.len_utf8();
+        }
+
+        if term_chars.peek().is_none() {
+            return Some(start..end);
+        }
+    }
+
+    None
+}
+
+pub fn is_match(file_name: &str, search_term: &str, options: MatchOptions) -> bool {
+    find_match_range(file_name, search_term, options).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IGNORE_CASE: MatchOptions = MatchOptions {
+        ignore_case: true,
+        smart_case: false,
+        anchored: false,
+        ignore_diacritics: false,
+    };
+
+    #[test]
+    fn test_find_match_range() {
+        assert_eq!(
+            find_match_range("Cargo.toml", "go.T", IGNORE_CASE),
+            Some(3..7)
+        );
+        assert_eq!(
+            find_match_range("Cargo.toml", "cargo", IGNORE_CASE),
+            Some(0..5)
+        );
+        assert_eq!(
+            find_match_range("Ünïcode.txt", "ïc", IGNORE_CASE),
+            Some(3..6)
+        );
+        assert_eq!(find_match_range("Cargo.toml", "lock", IGNORE_CASE), None);
+        assert_eq!(find_match_range("Cargo.toml", "", IGNORE_CASE), None);
+    }
+
+    #[test]
+    fn test_case_options() {
+        let match_case = MatchOptions {
+            ignore_case: false,
+            ..IGNORE_CASE
+        };
+        assert!(!is_match("Cargo.toml", "cargo", match_case));
+        assert!(is_match("Cargo.toml", "Cargo", match_case));
+
+        let smart_case = MatchOptions {
+            smart_case: true,
+            ..IGNORE_CASE
+        };
+        assert!(is_match("Cargo.toml", "cargo", smart_case));
+        assert!(is_match("Cargo.toml", "Cargo", smart_case));
+        assert!(!is_match("cargo.toml", "Cargo", smart_case));
+    }
+
+    #[test]
+    fn test_ignore_diacritics() {
+        let ignore_diacritics = MatchOptions {
+            ignore_diacritics: true,
+            ..IGNORE_CASE
+        };
+
+        assert!(!is_match("Résumé.pdf", "resume", IGNORE_CASE));
+        assert_eq!(
+            find_match_range("Résumé.pdf", "resume", ignore_diacritics),
+            Some(0..8)
+        );
+        assert_eq!(
+            find_match_range("Re\u{0301}sume\u{0301}.pdf", "resume", ignore_diacritics),
+            Some(0..10)
+        );
+    }
+
+    #[test]
+    fn test_anchored() {
+        let anchored = MatchOptions {
+            anchored: true,
+            ..IGNORE_CASE
+        };
+
+        assert_eq!(find_match_range("Cargo.toml", "car", anchored), Some(0..3));
+        assert_eq!(find_match_range("Cargo.toml", "toml", anchored), None);
+    }
+}
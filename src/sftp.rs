@@ -0,0 +1,345 @@
+// Browses servers over SFTP, with `cd sftp://user@host:port/path`. Like ssh, the server's key has to
+// be in ~/.ssh/known_hosts already, and logging in tries ssh-agent before asking for a password.
+
+use crate::vfs::{DirEntry, DirEntryInfo, FileSystem, Metadata, MountedMetadata, RecordedFileType};
+
+use ssh2::{CheckResult, ErrorCode, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_PORT: u16 = 22;
+
+// Status codes from the SFTP spec, since ssh2 turns most of them into an ErrorKind::Other
+const FX_EOF: i32 = 1;
+const FX_NO_SUCH_FILE: i32 = 2;
+const FX_PERMISSION_DENIED: i32 = 3;
+const FX_BAD_MESSAGE: i32 = 5;
+const FX_NO_CONNECTION: i32 = 6;
+const FX_CONNECTION_LOST: i32 = 7;
+const FX_OP_UNSUPPORTED: i32 = 8;
+const FX_INVALID_HANDLE: i32 = 9;
+const FX_NO_SUCH_PATH: i32 = 10;
+const FX_FILE_ALREADY_EXISTS: i32 = 11;
+const FX_WRITE_PROTECT: i32 = 12;
+const FX_NO_MEDIA: i32 = 13;
+const FX_INVALID_FILENAME: i32 = 20;
+
+// From libssh2, for when the connection itself goes wrong
+const ERROR_SOCKET_SEND: i32 = -7;
+const ERROR_TIMEOUT: i32 = -9;
+const ERROR_SOCKET_DISCONNECT: i32 = -13;
+const ERROR_SOCKET_RECV: i32 = -43;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Address {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    // Either absolute, or in the user's home directory when it starts with /~ (or is empty)
+    pub path: String,
+}
+
+impl Address {
+    /// Parses a URL like sftp://user@host:port/path, where only the host is required
+    pub fn parse(url: &str) -> Option<Address> {
+        let rest = url.strip_prefix("sftp://")?;
+
+        let (authority, path) = match rest.find('/') {
+            Some(slash_index) => (&rest[..slash_index], &rest[slash_index..]),
+            None => (rest, ""),
+        };
+
+        let (user, host_port) = match authority.rsplit_once('@') {
+            Some((user, host_port)) => (user.to_string(), host_port),
+            None => (whoami::username(), authority),
+        };
+
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().ok()?),
+            None => (host_port, DEFAULT_PORT),
+        };
+
+        if host.is_empty() || user.is_empty() {
+            return None;
+        }
+
+        Some(Address {
+            user,
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    /// The path that the server's root directory is shown at
+    pub fn root(&self) -> PathBuf {
+        PathBuf::from(format!("sftp://{}@{}:{}", self.user, self.host, self.port))
+    }
+}
+
+pub struct SftpFileSystem {
+    root: PathBuf,
+    sftp: Sftp,
+}
+
+/// Logs into the server, asking for a password with ask_password if there's no key for it in
+/// ssh-agent. Returns the file system along with the full path to the address's directory.
+pub fn connect(
+    address: &Address,
+    ask_password: impl FnOnce(&str) -> Option<String>,
+) -> io::Result<(SftpFileSystem, PathBuf)> {
+    let tcp = TcpStream::connect((address.host.as_str(), address.port))?;
+
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+
+    check_host_key(&session, address)?;
+
+    if session.userauth_agent(&address.user).is_err() {
+        let label = format!("Password for {}@{}: ", address.user, address.host);
+
+        let password = ask_password(&label)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Interrupted, "cancelled"))?;
+
+        session.userauth_password(&address.user, &password)?;
+    }
+
+    let sftp = session.sftp()?;
+
+    // NOTE(Chris): Like with curl, a path starting with /~ is in the home directory
+    let home_path = match address.path.as_str() {
+        "" => Some(""),
+        path => path.strip_prefix("/~"),
+    };
+    let start_path = match home_path {
+        Some(home_path) => sftp.realpath(&Path::new(".").join(home_path.trim_start_matches('/'))),
+        None => sftp.realpath(Path::new(&address.path)),
+    }
+    .map_err(to_io_error)?;
+
+    let root = address.root();
+    let start_dir = root.join(start_path.strip_prefix("/").unwrap_or(&start_path));
+
+    Ok((SftpFileSystem { root, sftp }, start_dir))
+}
+
+// NOTE(Chris): Unknown servers aren't trusted here, since there's no good way to show a key's
+// fingerprint for checking. Connecting with ssh first adds the key to known_hosts.
+fn check_host_key(session: &Session, address: &Address) -> io::Result<()> {
+    let mut known_hosts = session.known_hosts()?;
+
+    let known_hosts_path = PathBuf::from(crate::os_abstract::get_home_name())
+        .join(".ssh")
+        .join("known_hosts");
+    // NOTE(Chris): A missing known_hosts is the same as an empty one
+    let _ = known_hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH);
+
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| io::Error::other("the server didn't send a host key"))?;
+
+    match known_hosts.check_port(&address.host, address.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => Err(io::Error::other(format!(
+            "{} isn't in known_hosts yet (connect with ssh first)",
+            address.host
+        ))),
+        CheckResult::Mismatch => Err(io::Error::other(format!(
+            "the host key for {} doesn't match the one in known_hosts",
+            address.host
+        ))),
+        CheckResult::Failure => Err(io::Error::other("unable to check the host key")),
+    }
+}
+
+fn to_io_error(err: ssh2::Error) -> io::Error {
+    let kind = match err.code() {
+        ErrorCode::SFTP(FX_EOF) => io::ErrorKind::UnexpectedEof,
+        ErrorCode::SFTP(FX_NO_SUCH_FILE | FX_NO_SUCH_PATH | FX_NO_MEDIA) => io::ErrorKind::NotFound,
+        ErrorCode::SFTP(FX_PERMISSION_DENIED | FX_WRITE_PROTECT) => io::ErrorKind::PermissionDenied,
+        ErrorCode::SFTP(FX_BAD_MESSAGE) => io::ErrorKind::InvalidData,
+        ErrorCode::SFTP(FX_NO_CONNECTION) => io::ErrorKind::NotConnected,
+        ErrorCode::SFTP(FX_CONNECTION_LOST)
+        | ErrorCode::Session(ERROR_SOCKET_SEND | ERROR_SOCKET_DISCONNECT | ERROR_SOCKET_RECV) => {
+            io::ErrorKind::ConnectionAborted
+        }
+        ErrorCode::SFTP(FX_OP_UNSUPPORTED) => io::ErrorKind::Unsupported,
+        ErrorCode::SFTP(FX_INVALID_HANDLE | FX_INVALID_FILENAME) => io::ErrorKind::InvalidInput,
+        ErrorCode::SFTP(FX_FILE_ALREADY_EXISTS) => io::ErrorKind::AlreadyExists,
+        ErrorCode::Session(ERROR_TIMEOUT) => io::ErrorKind::TimedOut,
+        _ => return err.into(),
+    };
+
+    io::Error::new(kind, err.message())
+}
+
+impl SftpFileSystem {
+    // The path on the server, like /home/user for sftp://user@host:22/home/user
+    fn remote_path(&self, path: &Path) -> PathBuf {
+        Path::new("/").join(path.strip_prefix(&self.root).unwrap_or(path))
+    }
+
+    fn file_type(&self, remote_path: &Path, stat: &ssh2::FileStat) -> RecordedFileType {
+        let file_type = stat.file_type();
+
+        if file_type.is_file() {
+            RecordedFileType::File
+        } else if file_type.is_dir() {
+            RecordedFileType::Directory
+        } else if file_type.is_symlink() {
+            match self.sftp.stat(remote_path).map_err(to_io_error) {
+                Ok(target_stat) if target_stat.is_file() => RecordedFileType::FileSymlink,
+                Ok(target_stat) if target_stat.is_dir() => RecordedFileType::DirectorySymlink,
+                Ok(_) => RecordedFileType::Other,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    RecordedFileType::InvalidSymlink
+                }
+                Err(_) => RecordedFileType::Unknown,
+            }
+        } else {
+            RecordedFileType::Other
+        }
+    }
+}
+
+impl FileSystem for SftpFileSystem {
+    fn read_dir(
+        &self,
+        dir: &Path,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> io::Result<Vec<DirEntryInfo>> {
+        let entries = self
+            .sftp
+            .readdir(self.remote_path(dir))
+            .map_err(to_io_error)?
+            .into_iter()
+            // NOTE(Chris): Finding out where a symlink goes takes another round trip to the
+            // server, so this is the slow part
+            .take_while(|_| !is_cancelled())
+            .map(|(remote_path, stat)| {
+                let path = self
+                    .root
+                    .join(remote_path.strip_prefix("/").unwrap_or(&remote_path));
+
+                // NOTE(Chris): Servers don't give out inode numbers, but a path is just as good
+                // for finding a file again
+                let mut hasher = DefaultHasher::new();
+                remote_path.hash(&mut hasher);
+
+                DirEntryInfo {
+                    file_type: self.file_type(&remote_path, &stat),
+                    dir_entry: DirEntry::new(path),
+                    metadata: Metadata::Mounted(MountedMetadata {
+                        len: stat.size.unwrap_or(0),
+                        modified: stat
+                            .mtime
+                            .map(|mtime| SystemTime::UNIX_EPOCH + Duration::from_secs(mtime)),
+                        mode: stat.perm.unwrap_or(0),
+                        file_id: hasher.finish(),
+                    }),
+                }
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.sftp
+            .stat(&self.remote_path(path))
+            .is_ok_and(|stat| stat.is_dir())
+    }
+
+    fn read_file(&self, path: &Path, w: &mut dyn Write, limit: Option<u64>) -> io::Result<()> {
+        let file = self
+            .sftp
+            .open(self.remote_path(path))
+            .map_err(to_io_error)?;
+
+        io::copy(&mut file.take(limit.unwrap_or(u64::MAX)), w)?;
+
+        Ok(())
+    }
+
+    // NOTE(Chris): The file is written in place rather than created again, so that it keeps its
+    // permissions
+    fn write_file(&self, path: &Path, r: &mut dyn Read) -> io::Result<()> {
+        let mut file = self
+            .sftp
+            .open_mode(
+                self.remote_path(path),
+                OpenFlags::WRITE | OpenFlags::TRUNCATE,
+                0o644,
+                OpenType::File,
+            )
+            .map_err(to_io_error)?;
+
+        io::copy(r, &mut file)?;
+
+        Ok(())
+    }
+
+    fn is_writable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_io_error() {
+        let kind = |code| to_io_error(ssh2::Error::new(code, "failed")).kind();
+
+        assert_eq!(
+            kind(ErrorCode::SFTP(FX_NO_SUCH_FILE)),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            kind(ErrorCode::SFTP(FX_PERMISSION_DENIED)),
+            io::ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            kind(ErrorCode::SFTP(FX_CONNECTION_LOST)),
+            io::ErrorKind::ConnectionAborted
+        );
+        assert_eq!(
+            kind(ErrorCode::Session(ERROR_SOCKET_DISCONNECT)),
+            io::ErrorKind::ConnectionAborted
+        );
+        assert_eq!(kind(ErrorCode::SFTP(4)), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            Address::parse("sftp://chris@example.com:2222/var/log"),
+            Some(Address {
+                user: "chris".to_string(),
+                host: "example.com".to_string(),
+                port: 2222,
+                path: "/var/log".to_string(),
+            })
+        );
+
+        let address = Address::parse("sftp://example.com").unwrap();
+        assert_eq!(address.user, whoami::username());
+        assert_eq!(address.port, DEFAULT_PORT);
+        assert_eq!(address.path, "");
+        assert_eq!(
+            address.root(),
+            PathBuf::from(format!("sftp://{}@example.com:22", whoami::username()))
+        );
+
+        assert_eq!(Address::parse("sftp://"), None);
+        assert_eq!(Address::parse("sftp://example.com:ssh/"), None);
+        assert_eq!(Address::parse("/home/chris"), None);
+    }
+}
@@ -98,7 +98,7 @@ pub fn strmode(mode: u32) -> String {
         flags[9] = 't';
     }
 
-    return flags.iter().collect();
+    flags.iter().collect()
 }
 
 #[test]
@@ -166,6 +166,53 @@ pub fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
     None
 }
 
+// Finds the orientation value in a JPEG's Exif data. Missing or corrupt Exif data just means
+// there's no orientation, since it shouldn't stop the image itself from being shown.
+pub fn find_orientation(bytes: &[u8]) -> Option<u32> {
+    // Find the location of the Exif header
+    let exif_header = b"Exif\x00\x00";
+    let exif_header_index = find_bytes(bytes, exif_header)?;
+
+    // This assumes that the beginning of the TIFF section
+    // comes right after the Exif header
+    let tiff_bytes = &bytes[exif_header_index + exif_header.len()..];
+
+    let byte_order = match tiff_bytes.get(0..=1)? {
+        b"II" => Endian::LittleEndian,
+        b"MM" => Endian::BigEndian,
+        _ => return None,
+    };
+
+    // Confirm that this is a TIFF section, which has 42 after the byte order
+    if usizeify(tiff_bytes.get(2..=3)?, byte_order) != 42 {
+        return None;
+    }
+
+    // From the beginning of the TIFF section
+    let first_ifd_offset = usizeify(tiff_bytes.get(4..=7)?, byte_order);
+
+    let num_ifd_entries = usizeify(
+        tiff_bytes.get(first_ifd_offset..first_ifd_offset.checked_add(2)?)?,
+        byte_order,
+    );
+
+    let first_ifd_entry_offset = first_ifd_offset + 2;
+
+    (0..num_ifd_entries)
+        .map_while(|entry_index| {
+            let entry_offset = first_ifd_entry_offset + (12 * entry_index);
+            let entry_bytes = tiff_bytes.get(entry_offset..entry_offset + 12)?;
+
+            Some(IFDEntry::from_slice(entry_bytes, byte_order))
+        })
+        .find(|entry| {
+            entry.tag == EntryTag::Orientation
+                && entry.field_type == EntryType::Short
+                && entry.count == 1
+        })
+        .map(|entry| entry.value_offset)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -268,4 +315,23 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_find_orientation() {
+        // An Exif header, a big-endian TIFF header, then an IFD with just an orientation of 6
+        let bytes = b"\xff\xd8\xff\xe1\x00\x22Exif\x00\x00\
+            MM\x00\x2a\x00\x00\x00\x08\
+            \x00\x01\x01\x12\x00\x03\x00\x00\x00\x01\x00\x06\x00\x00";
+
+        assert_eq!(find_orientation(bytes), Some(6));
+
+        // A truncated entry is ignored rather than read past the end
+        assert_eq!(find_orientation(&bytes[..bytes.len() - 4]), None);
+        assert_eq!(
+            find_orientation(b"\xff\xd8Exif\x00\x00MM\x00\x2a\xff\xff\xff\xff"),
+            None
+        );
+        assert_eq!(find_orientation(b"\xff\xd8Exif\x00\x00XX"), None);
+        assert_eq!(find_orientation(b"\xff\xd8\xff\xe0"), None);
+    }
 }
@@ -0,0 +1,152 @@
+// Formats the dates on the info line, following the time-format option.
+
+use crate::config::{Clock, Config, TimeFormat};
+use crate::os_abstract;
+
+use chrono::{DateTime, Local};
+
+use std::time::{Duration, SystemTime};
+
+pub fn format_time(time: SystemTime, config: &Config, now: SystemTime) -> String {
+    // NOTE(Chris): These are spelled out rather than using %c, so that they're the same width
+    // whatever the locale
+    let format = match (config.time_format, config.clock) {
+        // Like ctime(3), e.g. "Sun Jul  8 00:34:60 2001"
+        (TimeFormat::Ctime, Clock::TwentyFourHour) => "%a %b %e %H:%M:%S %Y",
+        (TimeFormat::Ctime, Clock::TwelveHour) => "%a %b %e %I:%M:%S %p %Y",
+        (TimeFormat::Iso, Clock::TwentyFourHour) => "%Y-%m-%d %H:%M:%S%:z",
+        (TimeFormat::Iso, Clock::TwelveHour) => "%Y-%m-%d %I:%M:%S %p%:z",
+        (TimeFormat::Custom, _) => &config.date_format,
+        (TimeFormat::Locale, _) => return os_abstract::format_locale_time(time),
+        (TimeFormat::Relative, _) => return format_relative_time(time, now),
+    };
+
+    DateTime::<Local>::from(time).format(format).to_string()
+}
+
+/// How long until a relative time (like "3 min ago") would be shown differently.
+pub fn until_relative_time_changes(time: SystemTime, now: SystemTime) -> Duration {
+    let (secs, _) = distance(time, now);
+    let (unit, _, _) = relative_unit(secs);
+
+    Duration::from_secs(unit - secs % unit)
+}
+
+// Like "3 h ago", "2 days ago", or "in 5 min" (for files with times in the future)
+fn format_relative_time(time: SystemTime, now: SystemTime) -> String {
+    let (secs, is_future) = distance(time, now);
+    let (unit, singular, plural) = relative_unit(secs);
+
+    let count = secs / unit;
+    let amount = format!("{} {}", count, if count == 1 { singular } else { plural });
+
+    if is_future {
+        format!("in {}", amount)
+    } else {
+        format!("{} ago", amount)
+    }
+}
+
+// The number of whole seconds between the time and now, and whether the time is after now
+fn distance(time: SystemTime, now: SystemTime) -> (u64, bool) {
+    match now.duration_since(time) {
+        Ok(age) => (age.as_secs(), false),
+        Err(err) => (err.duration().as_secs(), true),
+    }
+}
+
+// The biggest unit that fits in the number of seconds, as its length in seconds and its singular
+// and plural names
+fn relative_unit(secs: u64) -> (u64, &'static str, &'static str) {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    const MONTH: u64 = 30 * DAY;
+    const YEAR: u64 = 365 * DAY;
+
+    if secs >= YEAR {
+        (YEAR, "year", "years")
+    } else if secs >= MONTH {
+        (MONTH, "month", "months")
+    } else if secs >= DAY {
+        (DAY, "day", "days")
+    } else if secs >= HOUR {
+        (HOUR, "h", "h")
+    } else if secs >= MINUTE {
+        (MINUTE, "min", "min")
+    } else {
+        (1, "s", "s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_relative_time() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let config = Config {
+            time_format: TimeFormat::Relative,
+            ..Config::default()
+        };
+        let ago = |secs| format_time(now - Duration::from_secs(secs), &config, now);
+
+        assert_eq!(ago(5), "5 s ago");
+        assert_eq!(ago(3 * 60 * 60 + 59), "3 h ago");
+        assert_eq!(ago(24 * 60 * 60), "1 day ago");
+        assert_eq!(ago(2 * 24 * 60 * 60), "2 days ago");
+        assert_eq!(ago(400 * 24 * 60 * 60), "1 year ago");
+
+        assert_eq!(
+            format_time(now + Duration::from_secs(300), &config, now),
+            "in 5 min"
+        );
+    }
+
+    #[test]
+    fn test_format_time() {
+        let time =
+            DateTime::<Local>::from(SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000));
+        let now = SystemTime::now();
+
+        let format = |time_format, clock| {
+            let config = Config {
+                time_format,
+                clock,
+                date_format: "%d.%m.%Y %H:%M".to_string(),
+                ..Config::default()
+            };
+
+            format_time(time.into(), &config, now)
+        };
+
+        // NOTE(Chris): The times are compared with chrono's, since they depend on the time zone
+        assert_eq!(
+            format(TimeFormat::Ctime, Clock::TwentyFourHour),
+            time.format("%a %b %e %H:%M:%S %Y").to_string()
+        );
+        assert_eq!(
+            format(TimeFormat::Ctime, Clock::TwelveHour),
+            time.format("%a %b %e %I:%M:%S %p %Y").to_string()
+        );
+        assert_eq!(
+            format(TimeFormat::Custom, Clock::TwelveHour),
+            time.format("%d.%m.%Y %H:%M").to_string()
+        );
+    }
+
+    #[test]
+    fn test_until_relative_time_changes() {
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+
+        assert_eq!(
+            until_relative_time_changes(now - Duration::from_secs(5), now),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            until_relative_time_changes(now - Duration::from_secs(3 * 60 + 20), now),
+            Duration::from_secs(40)
+        );
+    }
+}
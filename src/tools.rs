@@ -0,0 +1,117 @@
+// The external programs rolf can make use of, which are looked up once at startup. Any of them
+// can be pointed at a different binary (or turned off) with the "tools" config option.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use which::which;
+
+pub struct Tool {
+    pub name: &'static str,
+    // What rolf (or a typical rolf setup) uses the tool for, as shown by --print-config and help
+    pub purpose: &'static str,
+}
+
+pub const TOOLS: [Tool; 10] = [
+    Tool {
+        name: "highlight",
+        purpose: "Colors text previews",
+    },
+    Tool {
+        name: "bat",
+        purpose: "Colors text previews when highlight isn't installed",
+    },
+    Tool {
+        name: "ffmpeg",
+        purpose: "Previews videos",
+    },
+    Tool {
+        name: "ffprobe",
+        purpose: "Finds the middle of videos for their previews",
+    },
+    Tool {
+        name: "pdftoppm",
+        purpose: "Renders PDFs, e.g. in open rules",
+    },
+    Tool {
+        name: "chafa",
+        purpose: "Draws images as text, e.g. in dir-previews",
+    },
+    Tool {
+        name: "ueberzugpp",
+        purpose: "Draws images in terminals without an image protocol",
+    },
+    Tool {
+        name: "7z",
+        purpose: "Lists and extracts archives, e.g. in open rules",
+    },
+    Tool {
+        name: "rg",
+        purpose: "Searches file contents, e.g. in rolfrc commands",
+    },
+    Tool {
+        name: "fzf",
+        purpose: "Picks files interactively, e.g. in rolfrc commands",
+    },
+];
+
+pub fn is_known_tool(name: &str) -> bool {
+    TOOLS.iter().any(|tool| tool.name == name)
+}
+
+// Where each available tool was found
+#[derive(Debug, Default)]
+pub struct Tools {
+    paths: HashMap<&'static str, PathBuf>,
+}
+
+impl Tools {
+    /// Finds every tool in PATH, unless the config overrides where it is. An override can be a
+    /// path or another name to look up (like "batcat"), and an empty one turns the tool off.
+    pub fn detect(overrides: &HashMap<String, String>) -> Self {
+        let paths = TOOLS
+            .iter()
+            .filter_map(|tool| {
+                let binary = overrides.get(tool.name).map_or(tool.name, String::as_str);
+
+                if binary.is_empty() {
+                    return None;
+                }
+
+                // NOTE(Chris): A tool which can't be found is just unavailable, like it would be
+                // if it weren't installed
+                which(binary).ok().map(|path| (tool.name, path))
+            })
+            .collect();
+
+        Tools { paths }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Path> {
+        self.paths.get(name).map(PathBuf::as_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let overrides = HashMap::from([
+            ("highlight".to_string(), "sh".to_string()),
+            ("bat".to_string(), String::new()),
+            ("rg".to_string(), "/no/such/rg".to_string()),
+        ]);
+
+        let tools = Tools::detect(&overrides);
+
+        assert_eq!(tools.get("highlight"), which("sh").ok().as_deref());
+        assert_eq!(tools.get("bat"), None);
+        assert_eq!(tools.get("rg"), None);
+        assert_eq!(tools.get("no-such-tool"), None);
+
+        assert!(is_known_tool("ffprobe"));
+        assert!(!is_known_tool("no-such-tool"));
+    }
+}
@@ -0,0 +1,119 @@
+// Shortens file names which don't fit in their column, following the truncate option. Cutting
+// from the middle or the start keeps extensions visible, unlike lf's default of cutting the end.
+
+use crate::config::Truncation;
+use crate::line_edit::{char_width, str_width};
+
+use std::path::Path;
+
+/// Shortens the name to fit in the given number of terminal columns, marking where it was cut.
+/// The end is marked with "~" like lf, and the other styles with "…" (or "~" in plain mode, which
+/// sticks to ASCII).
+pub fn truncate_name(name: &str, width: usize, truncation: Truncation, plain: bool) -> String {
+    if str_width(name) <= width {
+        return name.to_string();
+    }
+
+    if width == 0 {
+        return String::new();
+    }
+
+    let marker = if truncation == Truncation::End || plain {
+        '~'
+    } else {
+        '…'
+    };
+
+    // NOTE(Chris): The marker is one column wide either way
+    let kept_width = width - 1;
+
+    match truncation {
+        Truncation::End => format!("{}{}", take_start(name, kept_width), marker),
+        Truncation::Start => format!("{}{}", marker, take_end(name, kept_width)),
+        Truncation::Middle => {
+            let extension_width = Path::new(name)
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .map_or(0, |extension| str_width(extension) + 1);
+
+            // NOTE(Chris): The end gets at least the extension (with its dot), as long as a
+            // character of the start still fits
+            let end_width = (kept_width / 2)
+                .max(extension_width)
+                .min(kept_width.saturating_sub(1));
+
+            format!(
+                "{}{}{}",
+                take_start(name, kept_width - end_width),
+                marker,
+                take_end(name, end_width)
+            )
+        }
+    }
+}
+
+// The longest start of the string that fits in the width
+fn take_start(string: &str, width: usize) -> &str {
+    let mut used_width = 0;
+
+    for (index, ch) in string.char_indices() {
+        used_width += char_width(ch);
+
+        if used_width > width {
+            return &string[..index];
+        }
+    }
+
+    string
+}
+
+// The longest end of the string that fits in the width
+fn take_end(string: &str, width: usize) -> &str {
+    let mut used_width = 0;
+
+    for (index, ch) in string.char_indices().rev() {
+        used_width += char_width(ch);
+
+        if used_width > width {
+            return &string[index + ch.len_utf8()..];
+        }
+    }
+
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_name() {
+        let name = "a-very-long-file-name.tar.gz";
+
+        assert_eq!(truncate_name(name, 40, Truncation::Middle, false), name);
+        assert_eq!(
+            truncate_name(name, 10, Truncation::End, false),
+            "a-very-lo~"
+        );
+        assert_eq!(
+            truncate_name(name, 10, Truncation::Start, false),
+            "…me.tar.gz"
+        );
+        assert_eq!(
+            truncate_name(name, 10, Truncation::Middle, false),
+            "a-ver…r.gz"
+        );
+        assert_eq!(
+            truncate_name(name, 10, Truncation::Middle, true),
+            "a-ver~r.gz"
+        );
+        assert_eq!(truncate_name(name, 0, Truncation::Middle, false), "");
+        assert_eq!(truncate_name(name, 1, Truncation::Middle, false), "…");
+
+        // NOTE(Chris): A double-width character that doesn't fit is left out entirely
+        assert_eq!(
+            truncate_name("日本語.txt", 6, Truncation::Middle, false),
+            "….txt"
+        );
+    }
+}
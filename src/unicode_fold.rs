@@ -0,0 +1,120 @@
+// Folding of file names for comparisons which should ignore case and (optionally) diacritics.
+//
+// NOTE(Chris): Stripping diacritics properly means decomposing to NFKD and dropping the combining
+// marks, which needs the Unicode decomposition tables. Instead, we map the precomposed Latin
+// letters (which cover almost every accented file name in practice) to their base letters and drop
+// any combining marks, which handles names that are already decomposed (like those from macOS).
+
+pub fn fold_str(s: &str, ignore_case: bool, ignore_diacritics: bool) -> String {
+    let mut result = String::with_capacity(s.len());
+
+    for ch in s.chars() {
+        push_folded_char(&mut result, ch, ignore_case, ignore_diacritics);
+    }
+
+    result
+}
+
+/// Pushes the folded form of a character, which may be empty (for a dropped combining mark) or
+/// more than one character long (for some lowercase forms, like İ's).
+pub fn push_folded_char(result: &mut String, ch: char, ignore_case: bool, ignore_diacritics: bool) {
+    if ignore_diacritics && is_combining_mark(ch) {
+        return;
+    }
+
+    if ignore_case {
+        for lower in ch.to_lowercase() {
+            if ignore_diacritics && is_combining_mark(lower) {
+                continue;
+            }
+
+            result.push(if ignore_diacritics {
+                base_letter(lower)
+            } else {
+                lower
+            });
+        }
+    } else if ignore_diacritics {
+        result.push(base_letter(ch));
+    } else {
+        result.push(ch);
+    }
+}
+
+fn is_combining_mark(ch: char) -> bool {
+    matches!(ch, '\u{0300}'..='\u{036f}' | '\u{1ab0}'..='\u{1aff}' | '\u{1dc0}'..='\u{1dff}')
+}
+
+fn base_letter(ch: char) -> char {
+    match ch {
+        'À'..='Å' => 'A',
+        'Ç' => 'C',
+        'È'..='Ë' => 'E',
+        'Ì'..='Ï' => 'I',
+        'Ñ' => 'N',
+        'Ò'..='Ö' | 'Ø' => 'O',
+        'Ù'..='Ü' => 'U',
+        'Ý' => 'Y',
+        'à'..='å' => 'a',
+        'ç' => 'c',
+        'è'..='ë' => 'e',
+        'ì'..='ï' => 'i',
+        'ñ' => 'n',
+        'ò'..='ö' | 'ø' => 'o',
+        'ù'..='ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        // Latin Extended-A alternates between uppercase and lowercase forms of each letter
+        '\u{0100}'..='\u{017f}' => {
+            let is_lowercase = ch.is_lowercase();
+            let base = match ch {
+                'Ā'..='ą' => 'a',
+                'Ć'..='č' => 'c',
+                'Ď'..='đ' => 'd',
+                'Ē'..='ě' => 'e',
+                'Ĝ'..='ģ' => 'g',
+                'Ĥ'..='ħ' => 'h',
+                'Ĩ'..='ı' => 'i',
+                'Ĵ'..='ĵ' => 'j',
+                'Ķ'..='ĸ' => 'k',
+                'Ĺ'..='ł' => 'l',
+                'Ń'..='ŋ' => 'n',
+                'Ō'..='ő' => 'o',
+                'Ŕ'..='ř' => 'r',
+                'Ś'..='š' => 's',
+                'Ţ'..='ŧ' => 't',
+                'Ũ'..='ų' => 'u',
+                'Ŵ'..='ŵ' => 'w',
+                'Ŷ'..='Ÿ' => 'y',
+                'Ź'..='ž' => 'z',
+                _ => return ch,
+            };
+
+            if is_lowercase {
+                base
+            } else {
+                base.to_ascii_uppercase()
+            }
+        }
+        _ => ch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_str() {
+        assert_eq!(fold_str("Résumé.PDF", true, false), "résumé.pdf");
+        assert_eq!(fold_str("Résumé.PDF", false, true), "Resume.PDF");
+        assert_eq!(fold_str("Résumé.PDF", true, true), "resume.pdf");
+        assert_eq!(fold_str("Łódź Żółć", true, true), "lodz zolc");
+
+        // Decomposed names, like the ones macOS creates
+        assert_eq!(fold_str("Re\u{0301}sume\u{0301}", true, true), "resume");
+        assert_eq!(
+            fold_str("Re\u{0301}sume\u{0301}", true, false),
+            "re\u{0301}sume\u{0301}"
+        );
+    }
+}
@@ -0,0 +1,624 @@
+// The file systems that directories are listed from. Most directories are read straight from the
+// disk, but a file system can also be mounted at a path (like a zip file, or a server at
+// sftp://host), in which case everything under that path is read through it instead. Since the
+// rest of rolf works on local files, a mounted file is copied to a temporary directory to be
+// previewed or opened.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::os_abstract::{self, ExtraPermissions};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordedFileType {
+    File,
+    Directory,
+    FileSymlink,
+    DirectorySymlink,
+    InvalidSymlink,
+    Unknown,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirEntryInfo {
+    pub dir_entry: DirEntry,
+    pub metadata: Metadata,
+    pub file_type: RecordedFileType,
+}
+
+// An entry in a directory of any file system, like std::fs::DirEntry
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+}
+
+impl DirEntry {
+    pub fn new(path: PathBuf) -> DirEntry {
+        DirEntry { path }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    pub fn file_name(&self) -> OsString {
+        self.path.file_name().unwrap_or_default().to_os_string()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Metadata {
+    Local(fs::Metadata),
+    Mounted(MountedMetadata),
+}
+
+// What a mounted file system knows about a file, which is a lot less than the disk does
+#[derive(Debug, Clone)]
+pub struct MountedMetadata {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    // The file's type and permissions, like st_mode
+    pub mode: u32,
+    // Tells the file apart from the others on its file system, like an inode number
+    pub file_id: u64,
+}
+
+// NOTE(Chris): These are the st_mode bits from sys/stat.h, which are the same everywhere (and are
+// used by zip, tar, and SFTP), but libc doesn't have them on Windows
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+pub const S_IFLNK: u32 = 0o120000;
+
+impl Metadata {
+    pub fn len(&self) -> u64 {
+        match self {
+            Metadata::Local(metadata) => metadata.len(),
+            Metadata::Mounted(metadata) => metadata.len,
+        }
+    }
+
+    pub fn modified(&self) -> io::Result<SystemTime> {
+        match self {
+            Metadata::Local(metadata) => metadata.modified(),
+            Metadata::Mounted(metadata) => metadata
+                .modified
+                .ok_or_else(|| io::Error::other("the modification time isn't known")),
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        match self {
+            Metadata::Local(metadata) => metadata.is_file(),
+            Metadata::Mounted(metadata) => metadata.mode & S_IFMT == S_IFREG,
+        }
+    }
+
+    pub fn file_id(&self) -> u64 {
+        match self {
+            Metadata::Local(metadata) => os_abstract::get_file_id(metadata),
+            Metadata::Mounted(metadata) => metadata.file_id,
+        }
+    }
+
+    /// The metadata from the disk, which is None for a mounted file
+    pub fn local(&self) -> Option<&fs::Metadata> {
+        match self {
+            Metadata::Local(metadata) => Some(metadata),
+            Metadata::Mounted(_) => None,
+        }
+    }
+
+    pub fn extra_perms(&self) -> ExtraPermissions {
+        match self {
+            Metadata::Local(metadata) => os_abstract::get_extra_perms(metadata),
+            Metadata::Mounted(metadata) => ExtraPermissions {
+                mode: mode_string(metadata.mode),
+                user_name: None,
+                group_name: None,
+                hard_link_count: None,
+                size: Some(metadata.len),
+                modified: metadata.modified,
+                accessed: None,
+                created: None,
+            },
+        }
+    }
+
+    pub fn owner_ids(&self) -> Option<(u32, u32)> {
+        self.local().and_then(os_abstract::get_owner_ids)
+    }
+
+    pub fn is_executable(&self, path: &Path) -> bool {
+        match self {
+            Metadata::Local(metadata) => os_abstract::is_executable(path, metadata),
+            Metadata::Mounted(metadata) => self.is_file() && metadata.mode & 0o111 != 0,
+        }
+    }
+}
+
+// Formats a mode like ls -l does, as in "drwxr-xr-x"
+pub fn mode_string(mode: u32) -> String {
+    let file_type = match mode & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFLNK => 'l',
+        _ => '-',
+    };
+
+    let permissions = (0..9).rev().map(|bit| {
+        if mode & (1 << bit) == 0 {
+            '-'
+        } else {
+            ['x', 'w', 'r'][bit % 3]
+        }
+    });
+
+    std::iter::once(file_type).chain(permissions).collect()
+}
+
+pub trait FileSystem: Send + Sync {
+    /// Lists the entries in a directory, in no particular order. Gives up with an Interrupted error
+    /// once is_cancelled returns true.
+    fn read_dir(
+        &self,
+        dir: &Path,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> io::Result<Vec<DirEntryInfo>>;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Writes a file's contents to w, stopping after limit bytes if there is one
+    fn read_file(&self, path: &Path, w: &mut dyn Write, limit: Option<u64>) -> io::Result<()>;
+
+    /// Replaces a file's contents with whatever r reads
+    fn write_file(&self, _path: &Path, _r: &mut dyn Read) -> io::Result<()> {
+        Err(read_only_error())
+    }
+
+    /// Whether files can be saved back with write_file
+    fn is_writable(&self) -> bool {
+        false
+    }
+
+    /// Whether paths on this file system are also paths on the disk, so that other programs (like
+    /// git) can read them
+    fn is_local(&self) -> bool {
+        false
+    }
+}
+
+pub fn read_only_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        "this file system is read-only",
+    )
+}
+
+// The disk, which every path outside of a mount is read from
+pub struct Local;
+
+impl FileSystem for Local {
+    fn read_dir(
+        &self,
+        dir: &Path,
+        is_cancelled: &dyn Fn() -> bool,
+    ) -> io::Result<Vec<DirEntryInfo>> {
+        let entries = fs::read_dir(dir)?
+            .take_while(|_| !is_cancelled())
+            .filter_map(|entry| {
+                // NOTE(Chris): An entry can fail to read if it's removed while the directory is
+                // being read, in which case it's left out like below
+                let entry_path = entry.ok()?.path();
+                let metadata = match fs::symlink_metadata(&entry_path) {
+                    Ok(metadata) => metadata,
+                    // TODO(Chris): Handles error in this case in more detail
+                    Err(_) => return None,
+                };
+
+                let file_type = {
+                    let curr_file_type = metadata.file_type();
+
+                    if curr_file_type.is_file() {
+                        RecordedFileType::File
+                    } else if curr_file_type.is_dir() {
+                        RecordedFileType::Directory
+                    } else if curr_file_type.is_symlink() {
+                        match fs::canonicalize(&entry_path) {
+                            // NOTE(Chris): A link's target can still be unreadable, like a OneDrive
+                            // file which can't be downloaded while offline
+                            Ok(canonical_path) => match fs::metadata(canonical_path) {
+                                Ok(canonical_metadata) if canonical_metadata.is_file() => {
+                                    RecordedFileType::FileSymlink
+                                }
+                                Ok(canonical_metadata) if canonical_metadata.is_dir() => {
+                                    RecordedFileType::DirectorySymlink
+                                }
+                                Ok(_) => RecordedFileType::Other,
+                                Err(_) => RecordedFileType::Unknown,
+                            },
+                            Err(err) => match err.kind() {
+                                io::ErrorKind::NotFound => RecordedFileType::InvalidSymlink,
+                                io::ErrorKind::PermissionDenied => RecordedFileType::Unknown,
+                                // NOTE(Chris): This includes symlinks in a loop, and (on Windows)
+                                // junctions to drives which aren't there anymore
+                                _ if os_abstract::is_unresolvable_link_error(&err) => {
+                                    RecordedFileType::InvalidSymlink
+                                }
+                                _ => RecordedFileType::Unknown,
+                            },
+                        }
+                    } else {
+                        RecordedFileType::Other
+                    }
+                };
+
+                Some(DirEntryInfo {
+                    dir_entry: DirEntry::new(entry_path),
+                    metadata: Metadata::Local(metadata),
+                    file_type,
+                })
+            })
+            .collect();
+
+        Ok(entries)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn read_file(&self, path: &Path, w: &mut dyn Write, limit: Option<u64>) -> io::Result<()> {
+        let file = fs::File::open(path)?;
+
+        io::copy(&mut file.take(limit.unwrap_or(u64::MAX)), w)?;
+
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, r: &mut dyn Read) -> io::Result<()> {
+        let mut file = fs::File::create(path)?;
+
+        io::copy(r, &mut file)?;
+
+        Ok(())
+    }
+
+    fn is_writable(&self) -> bool {
+        true
+    }
+
+    fn is_local(&self) -> bool {
+        true
+    }
+}
+
+/// A file system mounted at root, which every path under root is read through
+#[derive(Clone)]
+pub struct Mount {
+    root: PathBuf,
+    file_system: Arc<dyn FileSystem>,
+    // The directory listed above root, like the directory an archive is in. A server has nothing
+    // above it.
+    root_parent: Option<PathBuf>,
+    // The local directory that shell commands run in while the mount is open
+    working_dir: PathBuf,
+    // Where files are copied to, to be previewed or opened. It's removed once every clone of the
+    // mount (including those held by preview threads) is dropped.
+    local_copies: Arc<TempDir>,
+}
+
+impl Mount {
+    pub fn new(
+        root: PathBuf,
+        file_system: Arc<dyn FileSystem>,
+        root_parent: Option<PathBuf>,
+        working_dir: PathBuf,
+    ) -> io::Result<Mount> {
+        let local_copies = tempfile::Builder::new().prefix("rolf-").tempdir()?;
+
+        Ok(Mount {
+            root,
+            file_system,
+            root_parent,
+            working_dir,
+            local_copies: Arc::new(local_copies),
+        })
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    pub fn file_system(&self) -> &dyn FileSystem {
+        &*self.file_system
+    }
+
+    pub fn working_dir(&self) -> &Path {
+        &self.working_dir
+    }
+
+    /// The directory above path, which may be outside of the mount
+    pub fn parent(&self, path: &Path) -> Option<PathBuf> {
+        match path.parent() {
+            _ if path == self.root => self.root_parent.clone(),
+            // NOTE(Chris): The root is spelled the way it was given, since the parent of
+            // s3://bucket would otherwise come out as s3:
+            Some(parent) if parent == self.root => Some(self.root.clone()),
+            parent => parent.map(Path::to_path_buf),
+        }
+    }
+
+    /// Copies a file to the disk, or reuses an earlier copy. With a limit, only that many bytes
+    /// are copied (which is enough for a text preview, without downloading the whole file).
+    pub fn local_copy(&self, path: &Path, limit: Option<u64>) -> io::Result<PathBuf> {
+        let kind = if limit.is_some() { "head" } else { "full" };
+        let local_path = self.local_copy_path(kind, path)?;
+
+        if local_path.exists() {
+            return Ok(local_path);
+        }
+
+        self.copy_to(path, &local_path, limit)?;
+
+        Ok(local_path)
+    }
+
+    /// Copies a file to the disk again, so that it's up to date (e.g. before editing it)
+    pub fn download(&self, path: &Path) -> io::Result<PathBuf> {
+        let local_path = self.local_copy_path("full", path)?;
+
+        self.copy_to(path, &local_path, None)?;
+
+        Ok(local_path)
+    }
+
+    fn local_copy_path(&self, kind: &str, path: &Path) -> io::Result<PathBuf> {
+        let inner_path = path
+            .strip_prefix(&self.root)
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "the file isn't in the mount"))?;
+
+        // NOTE(Chris): Paths come from the file system, so one with .. in it (from a server, say)
+        // could otherwise put the copy outside of the temporary directory
+        if !inner_path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} leaves the mount", path.display()),
+            ));
+        }
+
+        Ok(self.local_copies.path().join(kind).join(inner_path))
+    }
+
+    // NOTE(Chris): The copy is written next to where it goes and then renamed, so that a preview
+    // thread never sees a half-written copy
+    fn copy_to(&self, path: &Path, local_path: &Path, limit: Option<u64>) -> io::Result<()> {
+        let local_dir = local_path.parent().unwrap_or(self.local_copies.path());
+        fs::create_dir_all(local_dir)?;
+
+        let mut temp_file = NamedTempFile::new_in(local_dir)?;
+        self.file_system
+            .read_file(path, temp_file.as_file_mut(), limit)?;
+        temp_file.persist(local_path)?;
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Mount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Mount({:?})", self.root)
+    }
+}
+
+/// Copies a file (or a directory and everything in it) out of a file system to dest on the disk,
+/// which mustn't exist yet. Returns how many files were copied.
+pub fn extract(
+    file_system: &dyn FileSystem,
+    entry_info: &DirEntryInfo,
+    dest: &Path,
+) -> io::Result<usize> {
+    let path = entry_info.dir_entry.path();
+
+    let count = match entry_info.file_type {
+        RecordedFileType::Directory | RecordedFileType::DirectorySymlink => {
+            fs::create_dir(dest)?;
+
+            let mut count = 0;
+            for child_info in file_system.read_dir(&path, &|| false)? {
+                count += extract(
+                    file_system,
+                    &child_info,
+                    &dest.join(child_info.dir_entry.file_name()),
+                )?;
+            }
+
+            count
+        }
+        RecordedFileType::File | RecordedFileType::FileSymlink => {
+            let mut file = fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(dest)?;
+            file_system.read_file(&path, &mut file, None)?;
+
+            1
+        }
+        // NOTE(Chris): Anything else (like a symlink in an archive) is left out
+        _ => return Ok(0),
+    };
+
+    // NOTE(Chris): This keeps scripts executable
+    #[cfg(unix)]
+    if let Metadata::Mounted(metadata) = &entry_info.metadata {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::set_permissions(dest, fs::Permissions::from_mode(metadata.mode & 0o7777))?;
+    }
+
+    Ok(count)
+}
+
+/// The file system that path is read through
+pub fn file_system_for<'a>(mount: Option<&'a Mount>, path: &Path) -> &'a dyn FileSystem {
+    match mount {
+        Some(mount) if mount.contains(path) => mount.file_system(),
+        _ => &Local,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    // A file system held in memory, mapping each file's path within it to its contents
+    struct MemoryFileSystem {
+        root: PathBuf,
+        files: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    }
+
+    impl FileSystem for MemoryFileSystem {
+        fn read_dir(
+            &self,
+            dir: &Path,
+            _is_cancelled: &dyn Fn() -> bool,
+        ) -> io::Result<Vec<DirEntryInfo>> {
+            let dir = dir.strip_prefix(&self.root).unwrap();
+
+            Ok(self
+                .files
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(path, _)| path.parent() == Some(dir))
+                .map(|(path, contents)| DirEntryInfo {
+                    dir_entry: DirEntry::new(self.root.join(path)),
+                    metadata: Metadata::Mounted(MountedMetadata {
+                        len: contents.len() as u64,
+                        modified: None,
+                        mode: S_IFREG | 0o644,
+                        file_id: 0,
+                    }),
+                    file_type: RecordedFileType::File,
+                })
+                .collect())
+        }
+
+        fn is_dir(&self, path: &Path) -> bool {
+            path == self.root
+        }
+
+        fn read_file(&self, path: &Path, w: &mut dyn Write, limit: Option<u64>) -> io::Result<()> {
+            let path = path.strip_prefix(&self.root).unwrap();
+            let files = self.files.lock().unwrap();
+            let contents = files.get(path).ok_or(io::ErrorKind::NotFound)?;
+            let len = limit.map_or(contents.len(), |limit| contents.len().min(limit as usize));
+
+            w.write_all(&contents[..len])
+        }
+
+        fn write_file(&self, path: &Path, r: &mut dyn Read) -> io::Result<()> {
+            let path = path.strip_prefix(&self.root).unwrap();
+            let mut contents = vec![];
+            r.read_to_end(&mut contents)?;
+
+            self.files
+                .lock()
+                .unwrap()
+                .insert(path.to_path_buf(), contents);
+
+            Ok(())
+        }
+
+        fn is_writable(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_mount() {
+        let root = PathBuf::from("mem://host");
+        let file_system = Arc::new(MemoryFileSystem {
+            root: root.clone(),
+            files: Mutex::new(HashMap::from([(
+                PathBuf::from("notes.txt"),
+                b"hello".to_vec(),
+            )])),
+        });
+        let mount =
+            Mount::new(root.clone(), file_system.clone(), None, PathBuf::from("/")).unwrap();
+
+        let notes_path = root.join("notes.txt");
+
+        assert!(mount.contains(&notes_path));
+        assert!(!mount.contains(Path::new("/home")));
+        assert_eq!(mount.parent(&root), None);
+        assert_eq!(mount.parent(&notes_path), Some(root.clone()));
+
+        // NOTE(Chris): The parent keeps the root's slashes, rather than being "mem:"
+        let bare_mount = Mount::new(
+            PathBuf::from("mem://"),
+            file_system.clone(),
+            None,
+            PathBuf::from("/"),
+        )
+        .unwrap();
+        assert_eq!(bare_mount.parent(&root).unwrap().to_str(), Some("mem://"));
+        assert!(!file_system_for(Some(&mount), &notes_path).is_local());
+        assert!(file_system_for(Some(&mount), Path::new("/home")).is_local());
+
+        let entries = mount.file_system().read_dir(&root, &|| false).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].dir_entry.file_name(), "notes.txt");
+        assert_eq!(entries[0].metadata.extra_perms().mode, "-rw-r--r--");
+
+        let head = mount.local_copy(&notes_path, Some(2)).unwrap();
+        assert_eq!(fs::read_to_string(&head).unwrap(), "he");
+
+        // Earlier copies are reused, while downloads are always fresh
+        file_system
+            .write_file(&notes_path, &mut "goodbye".as_bytes())
+            .unwrap();
+        let full = mount.local_copy(&notes_path, None).unwrap();
+        assert_eq!(fs::read_to_string(&full).unwrap(), "goodbye");
+        assert_eq!(
+            fs::read_to_string(mount.local_copy(&notes_path, Some(2)).unwrap()).unwrap(),
+            "he"
+        );
+
+        file_system
+            .write_file(&notes_path, &mut "hi again".as_bytes())
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(mount.download(&notes_path).unwrap()).unwrap(),
+            "hi again"
+        );
+
+        assert_eq!(
+            mount
+                .local_copy(&root.join("../../escaped.txt"), None)
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(mode_string(S_IFDIR | 0o755), "drwxr-xr-x");
+        assert_eq!(mode_string(S_IFREG | 0o640), "-rw-r-----");
+        assert_eq!(mode_string(S_IFLNK | 0o777), "lrwxrwxrwx");
+    }
+}